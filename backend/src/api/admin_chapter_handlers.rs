@@ -0,0 +1,340 @@
+use crate::api::extractor::RequirePermission;
+use crate::app::chapter_management::{
+    self, ChapterManager, MAX_CHAPTER_PAGES, UploadedPage,
+};
+use crate::builder::startup::AppState;
+use crate::common::error::{ChapterError, UploadError};
+use crate::common::permissions::SeriesEdit;
+use crate::common::upload::{self, TempUploadFile};
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum_core::__private::tracing::error;
+use axum_core::response::{IntoResponse, Response};
+use axum_extra::extract::Multipart;
+use serde::Deserialize;
+
+fn chapter_manager(state: &AppState) -> ChapterManager {
+    ChapterManager {
+        db_service: state.db_service.clone(),
+        storage_client: state.storage_client.clone(),
+        encoding_pool: state.encoding_pool.clone(),
+    }
+}
+
+/// Admin endpoint to create a chapter from uploaded images or a ZIP/CBZ archive.
+/// Multipart fields: `chapter_number`, optional `title`,
+/// and either repeated `pages` files (kept in upload order) or one `archive` file.
+pub async fn upload_chapter_handler(
+    admin: RequirePermission<SeriesEdit>,
+    Path(series_id): Path<i32>,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Response {
+    println!(
+        "->> {:<12} - upload_chapter_handler - user: {}, series_id: {}",
+        "HANDLER", admin.0.username, series_id
+    );
+
+    let mut chapter_number: Option<f32> = None;
+    let mut title: Option<String> = None;
+    let mut pages: Vec<UploadedPage> = Vec::new();
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                return ChapterError::BadRequest(format!(
+                    "Invalid multipart body: {}",
+                    e
+                ))
+                .into_response();
+            }
+        };
+
+        let field_name = field.name().unwrap_or_default().to_string();
+        let file_name = field.file_name().unwrap_or("unknown_file").to_string();
+
+        match field_name.as_str() {
+            "chapter_number" => {
+                let value = match field.text().await {
+                    Ok(value) => value,
+                    Err(e) => return UploadError::from(e).into_response(),
+                };
+                match value.trim().parse::<f32>() {
+                    Ok(number) if number.is_finite() && number >= 0.0 => {
+                        chapter_number = Some(number)
+                    }
+                    _ => {
+                        return ChapterError::BadRequest(
+                            "Invalid chapter number".to_string(),
+                        )
+                        .into_response();
+                    }
+                }
+            }
+            "title" => {
+                let value = match field.text().await {
+                    Ok(value) => value.trim().to_string(),
+                    Err(e) => return UploadError::from(e).into_response(),
+                };
+                title = (!value.is_empty()).then_some(value);
+            }
+            "pages" => {
+                if pages.len() >= MAX_CHAPTER_PAGES {
+                    return ChapterError::BadRequest(format!(
+                        "A chapter can have at most {} pages",
+                        MAX_CHAPTER_PAGES
+                    ))
+                    .into_response();
+                }
+                match upload::read_image_field(
+                    field,
+                    upload::CHAPTER_PAGE_MAX_BYTES,
+                )
+                .await
+                {
+                    Ok((bytes, format)) => pages.push(UploadedPage {
+                        file_name,
+                        bytes,
+                        content_type: Some(format.to_mime_type().to_string()),
+                    }),
+                    Err(e) => return e.into_response(),
+                }
+            }
+            "archive" => {
+                // Spooled to disk, only the extracted pages are kept in memory
+                let archive = match TempUploadFile::from_field(
+                    field,
+                    upload::CHAPTER_ARCHIVE_MAX_BYTES,
+                )
+                .await
+                {
+                    Ok(archive) => archive,
+                    Err(e) => return e.into_response(),
+                };
+
+                let extracted = tokio::task::spawn_blocking(move || {
+                    let file = std::fs::File::open(archive.path())
+                        .map_err(|e| ChapterError::Internal(e.into()))?;
+                    chapter_management::extract_archive_pages(
+                        std::io::BufReader::new(file),
+                    )
+                })
+                .await;
+
+                match extracted {
+                    Ok(Ok(archive_pages)) => pages.extend(archive_pages),
+                    Ok(Err(e)) => return e.into_response(),
+                    Err(e) => {
+                        return ChapterError::Internal(e.into())
+                            .into_response();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let Some(chapter_number) = chapter_number else {
+        return ChapterError::BadRequest(
+            "Missing chapter_number field".to_string(),
+        )
+        .into_response();
+    };
+
+    let series = match state.db_service.get_series_by_id(series_id).await {
+        Ok(Some(series)) => series,
+        Ok(None) => {
+            return ChapterError::NotFound("Series not found").into_response();
+        }
+        Err(e) => {
+            error!("Failed to get series {}: {:#?}", series_id, e);
+            return ChapterError::Internal(e).into_response();
+        }
+    };
+
+    match chapter_manager(&state)
+        .create_chapter(&series, chapter_number, title.as_deref(), pages)
+        .await
+    {
+        // Pages are encoded in the background, the chapter becomes available afterwards
+        Ok(chapter_id) => (
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({"status": "success", "message": "Chapter is being processed", "chapterId": chapter_id})),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpdateChapterRequest {
+    chapter_number: Option<f32>,
+    title: Option<String>,
+}
+
+pub async fn update_chapter_handler(
+    admin: RequirePermission<SeriesEdit>,
+    Path(chapter_id): Path<i32>,
+    State(state): State<AppState>,
+    Json(payload): Json<UpdateChapterRequest>,
+) -> Response {
+    println!(
+        "->> {:<12} - update_chapter_handler - user: {}, chapter_id: {}",
+        "HANDLER", admin.0.username, chapter_id
+    );
+
+    if payload
+        .chapter_number
+        .is_some_and(|number| !number.is_finite() || number < 0.0)
+    {
+        return ChapterError::BadRequest("Invalid chapter number".to_string())
+            .into_response();
+    }
+
+    match chapter_manager(&state)
+        .update_details(
+            chapter_id,
+            payload.chapter_number,
+            payload.title.as_deref(),
+        )
+        .await
+    {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(serde_json::json!({"status": "success", "message": "Chapter updated"})),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+// Stored images of a chapter, with the page numbers used by the page endpoints
+pub async fn get_chapter_pages_handler(
+    admin: RequirePermission<SeriesEdit>,
+    Path(chapter_id): Path<i32>,
+    State(state): State<AppState>,
+) -> Response {
+    println!(
+        "->> {:<12} - get_chapter_pages_handler - user: {}",
+        "HANDLER", admin.0.username
+    );
+
+    match state.db_service.get_chapter_images(chapter_id).await {
+        Ok(images) => (StatusCode::OK, Json(images)).into_response(),
+        Err(e) => {
+            error!("Failed to get chapter pages: {:#?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not retrieve chapter pages"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReorderPagesRequest {
+    // Current page numbers, in the new reading order
+    order: Vec<i32>,
+}
+
+pub async fn reorder_chapter_pages_handler(
+    admin: RequirePermission<SeriesEdit>,
+    Path(chapter_id): Path<i32>,
+    State(state): State<AppState>,
+    Json(payload): Json<ReorderPagesRequest>,
+) -> Response {
+    println!(
+        "->> {:<12} - reorder_chapter_pages_handler - user: {}, chapter_id: {}",
+        "HANDLER", admin.0.username, chapter_id
+    );
+
+    match chapter_manager(&state)
+        .reorder_pages(chapter_id, &payload.order)
+        .await
+    {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(serde_json::json!({"status": "success", "message": "Pages reordered"})),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn replace_chapter_page_handler(
+    admin: RequirePermission<SeriesEdit>,
+    Path((chapter_id, page_number)): Path<(i32, i32)>,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Response {
+    println!(
+        "->> {:<12} - replace_chapter_page_handler - user: {}, chapter_id: {}, page: {}",
+        "HANDLER", admin.0.username, chapter_id, page_number
+    );
+
+    let page = match multipart.next_field().await {
+        Ok(Some(field)) => {
+            let file_name =
+                field.file_name().unwrap_or("unknown_file").to_string();
+
+            match upload::read_image_field(
+                field,
+                upload::CHAPTER_PAGE_MAX_BYTES,
+            )
+            .await
+            {
+                Ok((bytes, format)) => UploadedPage {
+                    file_name,
+                    bytes,
+                    content_type: Some(format.to_mime_type().to_string()),
+                },
+                Err(e) => return e.into_response(),
+            }
+        }
+        _ => {
+            return ChapterError::BadRequest(
+                "No page image file found".to_string(),
+            )
+            .into_response();
+        }
+    };
+
+    match chapter_manager(&state)
+        .replace_page(chapter_id, page_number, page)
+        .await
+    {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(serde_json::json!({"status": "success", "message": "Page replaced"})),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn delete_chapter_page_handler(
+    admin: RequirePermission<SeriesEdit>,
+    Path((chapter_id, page_number)): Path<(i32, i32)>,
+    State(state): State<AppState>,
+) -> Response {
+    println!(
+        "->> {:<12} - delete_chapter_page_handler - user: {}, chapter_id: {}, page: {}",
+        "HANDLER", admin.0.username, chapter_id, page_number
+    );
+
+    match chapter_manager(&state)
+        .delete_page(chapter_id, page_number)
+        .await
+    {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(serde_json::json!({"status": "success", "message": "Page deleted"})),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
@@ -1,6 +1,14 @@
-use crate::api::extractor::AdminUser;
+use crate::api::extractor::RequirePermission;
+use crate::app::coordinator;
 use crate::builder::startup::AppState;
+use crate::common::error::UploadError;
+use crate::common::permissions::{
+    CommentsModerate, SeriesDelete, SeriesEdit, SystemManage, UsersView,
+};
+use crate::common::upload::{self, ImageFieldStream, TempUploadFile};
 use crate::database::{NewSeriesData, Series, UpdateSeriesData};
+use crate::encoding::image_encoding;
+use crate::task_workers::orphan_gc_worker::{self, OrphanGcConfig};
 use crate::task_workers::repair_chapter_worker;
 use crate::task_workers::series_check_worker::SeriesCheckJob;
 use axum::Json;
@@ -26,7 +34,7 @@ pub struct CreateSeriesRequest {
 
 // Admin endpoint to create new series
 pub async fn create_new_series_handler(
-    admin: AdminUser,
+    admin: RequirePermission<SeriesEdit>,
     State(state): State<AppState>,
     Json(payload): Json<CreateSeriesRequest>,
 ) -> Response {
@@ -39,6 +47,9 @@ pub async fn create_new_series_handler(
 
     // Random time to check target website
     let check_interval_minutes = rand::rng().random_range(90..=120);
+    let cover_image_url = state
+        .storage_client
+        .to_stored_value(&payload.cover_image_url);
 
     let new_series_data = NewSeriesData {
         title: &payload.title,
@@ -46,7 +57,7 @@ pub async fn create_new_series_handler(
         authors: payload.authors.as_ref(),
         category_ids: Some(&payload.category_ids),
         description: &payload.description,
-        cover_image_url: &payload.cover_image_url,
+        cover_image_url: &cover_image_url,
         source_url: &payload.source_url,
         check_interval_minutes,
     };
@@ -116,7 +127,7 @@ pub struct UpdateSeriesRequest {
 
 pub async fn update_existing_series_handler(
     Path(series_id): Path<i32>,
-    admin: AdminUser,
+    admin: RequirePermission<SeriesEdit>,
     State(state): State<AppState>,
     Json(payload): Json<UpdateSeriesRequest>,
 ) -> Response {
@@ -127,12 +138,17 @@ pub async fn update_existing_series_handler(
         "HANDLER", admin.0.username, series_id
     );
 
+    let cover_image_url = payload
+        .cover_image_url
+        .as_deref()
+        .map(|url| state.storage_client.to_stored_value(url));
+
     let update_series_data = UpdateSeriesData {
         title: payload.title.as_deref(),
         original_title: payload.original_title.as_deref(),
         authors: payload.authors.as_ref(),
         description: payload.description.as_deref(),
-        cover_image_url: payload.cover_image_url.as_deref(),
+        cover_image_url: cover_image_url.as_deref(),
         source_url: payload.source_url.as_deref(),
         check_interval_minutes: None,
         category_ids: payload.category_ids.as_deref(),
@@ -167,70 +183,110 @@ pub struct UploadResponse {
 }
 
 pub async fn upload_series_cover_image_handler(
+    admin: RequirePermission<SeriesEdit>,
     State(state): State<AppState>,
     mut multipart: Multipart,
 ) -> Response {
-    if let Ok(Some(field)) = multipart.next_field().await {
-        let file_name = field.file_name().unwrap_or("unknown_file").to_string();
-        let content_type = field
-            .content_type()
-            .unwrap_or("application/octet-stream")
-            .to_string();
-
-        let file_data = match field.bytes().await {
-            Ok(bytes) => bytes.to_vec(),
-            Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({"status": "error", "message": format!("Failed to read file bytes: {}", e)}))
-                ).into_response();
-            }
-        };
-
-        let file_extension = std::path::Path::new(&file_name)
-            .extension()
-            .and_then(std::ffi::OsStr::to_str)
-            .unwrap_or("jpg");
+    println!(
+        "->> {:<12} - upload_series_cover_image_handler - user: {}",
+        "HANDLER", admin.0.username
+    );
 
-        let unique_image_key =
-            format!("cover-manga/{}.{}", Uuid::new_v4(), file_extension);
+    let field = match multipart.next_field().await {
+        Ok(Some(field)) => field,
+        Ok(None) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"status": "error", "message": "No cover image file found"})),
+            )
+                .into_response();
+        }
+        Err(e) => return UploadError::from(e).into_response(),
+    };
 
-        match state
-            .storage_client
-            .upload_image_file(file_data, &unique_image_key, &content_type)
+    let upload =
+        match ImageFieldStream::open(field, upload::COVER_UPLOAD_MAX_BYTES)
             .await
         {
-            Ok(key) => {
-                // Construct the public URL
-                let public_url = format!(
-                    "{}/{}",
-                    state.storage_client.domain_cdn_url(),
-                    &key
-                );
-
-                (
-                    StatusCode::OK,
-                    Json(UploadResponse {
-                        status: "success".to_string(),
-                        url: public_url,
-                    }),
-                )
-                    .into_response()
-            }
-            Err(e) => {
-                eprintln!("Failed to upload cover image: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({"status": "error", "message": "Failed to upload cover image to storage"}))
-                ).into_response()
-            }
+            Ok(upload) => upload,
+            Err(e) => return e.into_response(),
+        };
+
+    let unique_image_key = format!(
+        "cover-manga/{}.{}",
+        Uuid::new_v4(),
+        upload::image_extension(upload.format)
+    );
+    let content_type = upload.content_type();
+
+    // Spooled to disk, the responsive variants are generated from the file after upload
+    let cover_file =
+        match TempUploadFile::from_chunks(upload.into_chunks()).await {
+            Ok(cover_file) => cover_file,
+            Err(e) => return e.into_response(),
+        };
+    let chunks = match cover_file.chunks().await {
+        Ok(chunks) => chunks,
+        Err(e) => return UploadError::Storage(e).into_response(),
+    };
+
+    let key = match state
+        .storage_client
+        .upload_stream(
+            &unique_image_key,
+            chunks,
+            content_type,
+            Some(admin.0.id),
+        )
+        .await
+    {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("Failed to upload cover image: {:#}", e);
+            return UploadError::from(e).into_response();
         }
-    } else {
-        (
-        StatusCode::BAD_REQUEST,
-        Json(serde_json::json!({"status": "error", "message": "No cover image file found"}))
-    ).into_response()
+    };
+
+    // The cover itself is usable without variants, so failures are only logged
+    if let Err(e) = store_cover_variants(&state, &key, &cover_file).await {
+        eprintln!("Failed to generate variants for cover {}: {}", key, e);
     }
+
+    let public_url = state.storage_client.public_url(&key);
+
+    (
+        StatusCode::OK,
+        Json(UploadResponse {
+            status: "success".to_string(),
+            url: public_url,
+        }),
+    )
+        .into_response()
+}
+
+// Encode, upload and record the responsive variants of an uploaded cover
+async fn store_cover_variants(
+    state: &AppState,
+    cover_key: &str,
+    cover_file: &TempUploadFile,
+) -> anyhow::Result<()> {
+    let image_bytes = tokio::fs::read(cover_file.path()).await?;
+    let variants = state
+        .encoding_pool
+        .run(move || {
+            image_encoding::convert_image_bytes_to_variants(&image_bytes)
+        })
+        .await?;
+
+    let uploaded = coordinator::upload_image_variants(
+        &state.storage_client,
+        cover_key,
+        variants,
+    )
+    .await?;
+
+    coordinator::record_image_variants(&state.db_service, cover_key, &uploaded)
+        .await
 }
 
 #[derive(Deserialize)]
@@ -271,7 +327,7 @@ pub struct PaginatedResponse<T: Serialize> {
 }
 
 pub async fn get_all_series_handler(
-    admin: AdminUser,
+    admin: RequirePermission<SeriesEdit>,
     State(state): State<AppState>,
     Query(pagination): Query<PaginationParams>,
 ) -> Response {
@@ -294,7 +350,7 @@ pub async fn get_all_series_handler(
                     title: s.title,
                     original_title: s.original_title,
                     description: s.description,
-                    cover_image_url: s.cover_image_url,
+                    cover_image_url: state.storage_client.public_url(&s.cover_image_url),
                     source_url: s.current_source_url,
                     authors: serde_json::from_value(s.authors).unwrap_or_else(|_| vec![]),
                     last_updated: s.updated_at.format("%Y-%m-%d %H:%M:%S").to_string(),
@@ -320,7 +376,7 @@ pub async fn get_all_series_handler(
 }
 
 pub async fn get_all_users_handler(
-    admin: AdminUser,
+    admin: RequirePermission<UsersView>,
     State(state): State<AppState>,
     Query(pagination): Query<PaginationParams>,
 ) -> Response {
@@ -364,7 +420,7 @@ pub struct RepairChapterRequest {
 }
 
 pub async fn repair_chapter_handler(
-    admin: AdminUser,
+    admin: RequirePermission<SeriesEdit>,
     Path(series_id): Path<i32>,
     State(state): State<AppState>,
     Json(payload): Json<RepairChapterRequest>,
@@ -400,7 +456,7 @@ pub async fn repair_chapter_handler(
 }
 
 pub async fn delete_series_handler(
-    admin: AdminUser,
+    admin: RequirePermission<SeriesDelete>,
     Path(series_id): Path<i32>,
     State(state): State<AppState>,
 ) -> Response {
@@ -409,12 +465,20 @@ pub async fn delete_series_handler(
         "HANDLER", admin.0.username, series_id
     );
 
-    // Send series_id to deletion worker with channel
-    match state.db_service.mark_series_for_deletion(series_id).await {
+    // The deletion worker picks the series up once the undo window has passed
+    match state
+        .db_service
+        .mark_series_for_deletion(series_id, state.series_deletion_delay)
+        .await
+    {
         Ok(row_affected) if row_affected > 0 => {
             (
                 StatusCode::ACCEPTED,
-                Json(serde_json::json!({"status": "success", "message": "Series has been scheduled for deletion."})),
+                Json(serde_json::json!({
+                    "status": "success",
+                    "message": "Series has been scheduled for deletion.",
+                    "delaySeconds": state.series_deletion_delay.as_secs(),
+                })),
             )
                 .into_response()
         }
@@ -425,11 +489,144 @@ pub async fn delete_series_handler(
             )
                 .into_response()
         }
-        Err(_) => {
-            // This error accours if the worker crashes and the channel is closed
+        Err(e) => {
+            error!("Failed to schedule deletion of series {}: {}", series_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not schedule deletion."})),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Admin endpoint to undo a deletion during its window
+pub async fn cancel_series_deletion_handler(
+    admin: RequirePermission<SeriesDelete>,
+    Path(series_id): Path<i32>,
+    State(state): State<AppState>,
+) -> Response {
+    println!(
+        "->> {:<12} - CANCEL DELETE - user: {}, series_id: {}",
+        "HANDLER", admin.0.username, series_id
+    );
+
+    match state.db_service.cancel_series_deletion(series_id).await {
+        Ok(row_affected) if row_affected > 0 => {
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({"status": "success", "message": "Series deletion has been cancelled."})),
+            )
+                .into_response()
+        }
+        Ok(_) => {
+            (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({"status": "error", "message": "Series is not pending deletion or its deletion has already started."})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("Failed to cancel deletion of series {}: {}", series_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not cancel deletion."})),
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn get_pending_series_deletions_handler(
+    admin: RequirePermission<SeriesDelete>,
+    State(state): State<AppState>,
+) -> Response {
+    println!(
+        "->> {:<12} - get_pending_series_deletions_handler - user: {}",
+        "HANDLER", admin.0.username
+    );
+
+    match state.db_service.get_pending_series_deletions().await {
+        Ok(deletions) => (StatusCode::OK, Json(deletions)).into_response(),
+        Err(e) => {
+            error!("Failed to list pending series deletions: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not list pending deletions."})),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Admin endpoint to move a series' chapter images to the archive and hide it
+pub async fn archive_series_handler(
+    admin: RequirePermission<SeriesDelete>,
+    Path(series_id): Path<i32>,
+    State(state): State<AppState>,
+) -> Response {
+    println!(
+        "->> {:<12} - SCHEDULE ARCHIVE - user: {}, series_id: {}",
+        "HANDLER", admin.0.username, series_id
+    );
+
+    match state.db_service.mark_series_for_archive(series_id).await {
+        Ok(row_affected) if row_affected > 0 => {
+            (
+                StatusCode::ACCEPTED,
+                Json(serde_json::json!({"status": "success", "message": "Series has been scheduled for archiving."})),
+            )
+                .into_response()
+        }
+        Ok(_) => {
+            (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({"status": "error", "message": "Series not found, already archived, or busy with a check or deletion."})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("Failed to schedule archive of series {}: {}", series_id, e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"status": "error", "message": "Could not schedule deletion. The deletion service may be down."})),
+                Json(serde_json::json!({"status": "error", "message": "Could not schedule archiving."})),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Admin endpoint to bring an archived series back
+pub async fn restore_series_handler(
+    admin: RequirePermission<SeriesDelete>,
+    Path(series_id): Path<i32>,
+    State(state): State<AppState>,
+) -> Response {
+    println!(
+        "->> {:<12} - SCHEDULE RESTORE - user: {}, series_id: {}",
+        "HANDLER", admin.0.username, series_id
+    );
+
+    match state.db_service.mark_series_for_restore(series_id).await {
+        Ok(row_affected) if row_affected > 0 => {
+            (
+                StatusCode::ACCEPTED,
+                Json(serde_json::json!({"status": "success", "message": "Series has been scheduled for restore."})),
+            )
+                .into_response()
+        }
+        Ok(_) => {
+            (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({"status": "error", "message": "Series not found or not archived."})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("Failed to schedule restore of series {}: {}", series_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not schedule restore."})),
             )
                 .into_response()
         }
@@ -442,7 +639,7 @@ pub struct CreateCategoryTagRequest {
 }
 
 pub async fn create_category_tag_handler(
-    admin: AdminUser,
+    admin: RequirePermission<SeriesEdit>,
     State(state): State<AppState>,
     Json(payload): Json<CreateCategoryTagRequest>,
 ) -> Response {
@@ -478,7 +675,7 @@ pub async fn create_category_tag_handler(
 }
 
 pub async fn delete_category_tag_handler(
-    admin: AdminUser,
+    admin: RequirePermission<SeriesEdit>,
     State(state): State<AppState>,
     Path(category_id): Path<i32>,
 ) -> Response {
@@ -506,8 +703,41 @@ pub async fn delete_category_tag_handler(
     }
 }
 
+// Moderation: hide a comment and its replies
+pub async fn remove_comment_handler(
+    moderator: RequirePermission<CommentsModerate>,
+    State(state): State<AppState>,
+    Path(comment_id): Path<i64>,
+) -> Response {
+    println!(
+        "->> {:<12} - remove_comment_handler - user: {}, comment_id: {}",
+        "HANDLER", moderator.0.username, comment_id
+    );
+
+    match state.db_service.remove_comment(comment_id).await {
+        Ok(row_affected) if row_affected > 0 => (
+            StatusCode::OK,
+            Json(serde_json::json!({"status": "success", "message": "Comment has been removed."})),
+        )
+            .into_response(),
+        Ok(_) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"status": "error", "message": "Comment not found."})),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to remove comment {}: {}", comment_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not remove comment."})),
+            )
+                .into_response()
+        }
+    }
+}
+
 pub async fn get_list_category_tags_handler(
-    admin: AdminUser,
+    admin: RequirePermission<SeriesEdit>,
     State(state): State<AppState>,
 ) -> Response {
     println!(
@@ -530,7 +760,7 @@ pub async fn get_list_category_tags_handler(
 }
 
 pub async fn get_series_category_tags_handler(
-    admin: AdminUser,
+    admin: RequirePermission<SeriesEdit>,
     State(state): State<AppState>,
     Path(series_id): Path<i32>,
 ) -> Response {
@@ -550,3 +780,188 @@ pub async fn get_series_category_tags_handler(
         ).into_response(),
     }
 }
+
+// Queue wait and encode time of the shared image encoding pool
+pub async fn get_encoding_metrics_handler(
+    admin: RequirePermission<SystemManage>,
+    State(state): State<AppState>,
+) -> Response {
+    println!(
+        "->> {:<12} - get_encoding_metrics_handler - user: {}",
+        "HANDLER", admin.0.username
+    );
+
+    (StatusCode::OK, Json(state.encoding_pool.snapshot())).into_response()
+}
+
+// Per-page failure reasons of an incomplete chapter
+pub async fn get_chapter_image_failures_handler(
+    admin: RequirePermission<SeriesEdit>,
+    State(state): State<AppState>,
+    Path(chapter_id): Path<i32>,
+) -> Response {
+    println!(
+        "->> {:<12} - get_chapter_image_failures_handler - user: {}",
+        "HANDLER", admin.0.username
+    );
+
+    match state
+        .db_service
+        .get_chapter_image_failures(chapter_id)
+        .await
+    {
+        Ok(failures) => (StatusCode::OK, Json(failures)).into_response(),
+        Err(e) => {
+            error!("Failed to get chapter image failures: {:#?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not retrieve chapter image failures"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Dry-run report of unreferenced storage objects, nothing is deleted
+pub async fn get_orphaned_objects_report_handler(
+    admin: RequirePermission<SystemManage>,
+    State(state): State<AppState>,
+) -> Response {
+    println!(
+        "->> {:<12} - get_orphaned_objects_report_handler - user: {}",
+        "HANDLER", admin.0.username
+    );
+
+    match orphan_gc_worker::collect_orphaned_objects(
+        &state.db_service,
+        &state.storage_client,
+        OrphanGcConfig::from_env().as_dry_run(),
+    )
+    .await
+    {
+        Ok(report) => (StatusCode::OK, Json(report)).into_response(),
+        Err(e) => {
+            error!("Failed to collect orphaned objects: {:#?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not scan storage for orphaned objects"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StorageUsageParams {
+    limit: Option<i64>,
+    days: Option<i32>,
+}
+
+fn storage_usage_error(e: anyhow::Error) -> Response {
+    error!("Failed to get storage usage: {:#?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({"status": "error", "message": "Could not retrieve storage usage"})),
+    )
+        .into_response()
+}
+
+// Current storage usage, in total and per key prefix
+pub async fn get_storage_usage_handler(
+    admin: RequirePermission<SystemManage>,
+    State(state): State<AppState>,
+) -> Response {
+    println!(
+        "->> {:<12} - get_storage_usage_handler - user: {}",
+        "HANDLER", admin.0.username
+    );
+
+    match state.db_service.get_storage_usage_by_prefix().await {
+        Ok(by_prefix) => {
+            let total_objects: i64 =
+                by_prefix.iter().map(|usage| usage.object_count).sum();
+            let total_bytes: i64 =
+                by_prefix.iter().map(|usage| usage.total_bytes).sum();
+
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "totalObjects": total_objects,
+                    "totalBytes": total_bytes,
+                    "byPrefix": by_prefix,
+                })),
+            )
+                .into_response()
+        }
+        Err(e) => storage_usage_error(e),
+    }
+}
+
+// Series using the most storage, to decide what to archive
+pub async fn get_series_storage_usage_handler(
+    admin: RequirePermission<SystemManage>,
+    State(state): State<AppState>,
+    Query(params): Query<StorageUsageParams>,
+) -> Response {
+    println!(
+        "->> {:<12} - get_series_storage_usage_handler - user: {}",
+        "HANDLER", admin.0.username
+    );
+
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    match state.db_service.get_series_storage_usage(limit).await {
+        Ok(usage) => (StatusCode::OK, Json(usage)).into_response(),
+        Err(e) => storage_usage_error(e),
+    }
+}
+
+pub async fn get_chapter_storage_usage_handler(
+    admin: RequirePermission<SystemManage>,
+    State(state): State<AppState>,
+    Path(series_id): Path<i32>,
+) -> Response {
+    println!(
+        "->> {:<12} - get_chapter_storage_usage_handler - user: {}, series_id: {}",
+        "HANDLER", admin.0.username, series_id
+    );
+
+    match state.db_service.get_chapter_storage_usage(series_id).await {
+        Ok(usage) => (StatusCode::OK, Json(usage)).into_response(),
+        Err(e) => storage_usage_error(e),
+    }
+}
+
+pub async fn get_user_storage_usage_handler(
+    admin: RequirePermission<SystemManage>,
+    State(state): State<AppState>,
+    Query(params): Query<StorageUsageParams>,
+) -> Response {
+    println!(
+        "->> {:<12} - get_user_storage_usage_handler - user: {}",
+        "HANDLER", admin.0.username
+    );
+
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    match state.db_service.get_user_storage_usage(limit).await {
+        Ok(usage) => (StatusCode::OK, Json(usage)).into_response(),
+        Err(e) => storage_usage_error(e),
+    }
+}
+
+// Daily totals, `days` back from today
+pub async fn get_storage_usage_history_handler(
+    admin: RequirePermission<SystemManage>,
+    State(state): State<AppState>,
+    Query(params): Query<StorageUsageParams>,
+) -> Response {
+    println!(
+        "->> {:<12} - get_storage_usage_history_handler - user: {}",
+        "HANDLER", admin.0.username
+    );
+
+    let days = params.days.unwrap_or(30).clamp(1, 365);
+    match state.db_service.get_storage_usage_history(days).await {
+        Ok(history) => (StatusCode::OK, Json(history)).into_response(),
+        Err(e) => storage_usage_error(e),
+    }
+}
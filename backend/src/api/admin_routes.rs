@@ -1,30 +1,104 @@
 use axum::Router;
-use axum::routing::{delete, get, patch, post};
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{delete, get, patch, post, put};
 
+use crate::api::admin_chapter_handlers::{
+    delete_chapter_page_handler, get_chapter_pages_handler,
+    reorder_chapter_pages_handler, replace_chapter_page_handler,
+    update_chapter_handler, upload_chapter_handler,
+};
 use crate::api::admin_handlers::{
+    archive_series_handler, cancel_series_deletion_handler,
     create_category_tag_handler, create_new_series_handler,
     delete_category_tag_handler, delete_series_handler, get_all_series_handler,
-    get_all_users_handler, get_list_category_tags_handler,
-    get_series_category_tags_handler, repair_chapter_handler,
+    get_all_users_handler, get_chapter_image_failures_handler,
+    get_chapter_storage_usage_handler, get_encoding_metrics_handler,
+    get_list_category_tags_handler, get_orphaned_objects_report_handler,
+    get_pending_series_deletions_handler, get_series_category_tags_handler,
+    get_series_storage_usage_handler, get_storage_usage_handler,
+    get_storage_usage_history_handler, get_user_storage_usage_handler,
+    remove_comment_handler, repair_chapter_handler, restore_series_handler,
     update_existing_series_handler, upload_series_cover_image_handler,
 };
+use crate::api::admin_user_handlers::{
+    ban_user_handler, delete_user_handler, force_password_reset_handler,
+    lift_user_suspension_handler, suspend_user_handler,
+    update_user_role_handler,
+};
 use crate::builder::startup::AppState;
+use crate::common::upload::{
+    CHAPTER_ARCHIVE_MAX_BYTES, CHAPTER_PAGE_MAX_BYTES, COVER_UPLOAD_MAX_BYTES,
+    multipart_body_limit,
+};
+
+// Chapter uploads carry every page (or a whole archive) in one request
+const CHAPTER_UPLOAD_BODY_LIMIT: usize =
+    multipart_body_limit(CHAPTER_ARCHIVE_MAX_BYTES, 1);
+const PAGE_UPLOAD_BODY_LIMIT: usize =
+    multipart_body_limit(CHAPTER_PAGE_MAX_BYTES, 1);
+const COVER_UPLOAD_BODY_LIMIT: usize =
+    multipart_body_limit(COVER_UPLOAD_MAX_BYTES, 1);
 
 pub fn admin_routes() -> Router<AppState> {
     Router::new()
         // User management routes
         .route("/users/list", get(get_all_users_handler))
+        .route("/users/{id}", delete(delete_user_handler))
+        .route("/users/{id}/role", put(update_user_role_handler))
+        .route("/users/{id}/suspend", post(suspend_user_handler))
+        .route("/users/{id}/ban", post(ban_user_handler))
+        .route(
+            "/users/{id}/suspension",
+            delete(lift_user_suspension_handler),
+        )
+        .route(
+            "/users/{id}/password-reset",
+            post(force_password_reset_handler),
+        )
         // Series management routes
         .route("/series/add", post(create_new_series_handler))
         .route("/series/delete/{id}", delete(delete_series_handler))
+        .route(
+            "/series/delete/{id}/cancel",
+            post(cancel_series_deletion_handler),
+        )
+        .route(
+            "/series/deletions",
+            get(get_pending_series_deletions_handler),
+        )
         .route("/series/repair/chapter/{id}", post(repair_chapter_handler))
+        .route("/series/archive/{id}", post(archive_series_handler))
+        .route("/series/restore/{id}", post(restore_series_handler))
         .route("/series/list", get(get_all_series_handler))
         .route("/series/update/{id}", patch(update_existing_series_handler))
         .route("/series/tags/{id}", get(get_series_category_tags_handler))
+        .route(
+            "/series/chapter/{id}/failures",
+            get(get_chapter_image_failures_handler),
+        )
+        // Manual chapter management routes
+        .route(
+            "/series/{id}/chapters/upload",
+            post(upload_chapter_handler)
+                .layer(DefaultBodyLimit::max(CHAPTER_UPLOAD_BODY_LIMIT)),
+        )
+        .route("/chapters/{id}", patch(update_chapter_handler))
+        .route("/chapters/{id}/pages", get(get_chapter_pages_handler))
+        .route(
+            "/chapters/{id}/pages/order",
+            put(reorder_chapter_pages_handler),
+        )
+        .route(
+            "/chapters/{id}/pages/{page}",
+            put(replace_chapter_page_handler)
+                .layer(DefaultBodyLimit::max(PAGE_UPLOAD_BODY_LIMIT))
+                .delete(delete_chapter_page_handler),
+        )
         // Image upload routes
         .route(
             "/series/cover/upload/image",
-            post(upload_series_cover_image_handler),
+            post(upload_series_cover_image_handler)
+                .layer(DefaultBodyLimit::max(COVER_UPLOAD_BODY_LIMIT)),
         )
         // Category Tag management routes
         .route("/category/tag/add", post(create_category_tag_handler))
@@ -33,4 +107,24 @@ pub fn admin_routes() -> Router<AppState> {
             delete(delete_category_tag_handler),
         )
         .route("/category/tag/list", get(get_list_category_tags_handler))
+        // Moderation routes
+        .route("/comments/{id}", delete(remove_comment_handler))
+        // Metrics routes
+        .route("/metrics/encoding", get(get_encoding_metrics_handler))
+        // Storage maintenance routes
+        .route("/storage/orphans", get(get_orphaned_objects_report_handler))
+        .route("/storage/usage", get(get_storage_usage_handler))
+        .route(
+            "/storage/usage/series",
+            get(get_series_storage_usage_handler),
+        )
+        .route(
+            "/storage/usage/series/{id}/chapters",
+            get(get_chapter_storage_usage_handler),
+        )
+        .route("/storage/usage/users", get(get_user_storage_usage_handler))
+        .route(
+            "/storage/usage/history",
+            get(get_storage_usage_history_handler),
+        )
 }
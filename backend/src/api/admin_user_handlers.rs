@@ -0,0 +1,428 @@
+use crate::api::auth_handlers::send_password_reset;
+use crate::api::extractor::{AuthenticatedUser, RequirePermission};
+use crate::builder::startup::AppState;
+use crate::common::permissions::{Permission, UsersBan, UsersManage};
+use crate::database::UserAuthState;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum_core::__private::tracing::error;
+use axum_core::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+// Longer reasons are cut, they are shown to the user on login
+const MAX_REASON_CHARS: usize = 500;
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(serde_json::json!({"status": "error", "message": message})),
+    )
+        .into_response()
+}
+
+fn success_response(message: &str) -> Response {
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({"status": "success", "message": message})),
+    )
+        .into_response()
+}
+
+fn normalize_reason(reason: Option<String>) -> Option<String> {
+    reason
+        .map(|reason| reason.trim().chars().take(MAX_REASON_CHARS).collect())
+        .filter(|reason: &String| !reason.is_empty())
+}
+
+// Staff can not act on their own account
+fn ensure_not_own_account(
+    actor: &AuthenticatedUser,
+    user_id: i32,
+) -> Result<(), (StatusCode, &'static str)> {
+    if user_id == actor.id {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "You can not change your own account.",
+        ));
+    }
+
+    Ok(())
+}
+
+// Only user managers can act on other staff (anyone with a permission)
+fn ensure_can_change_target(
+    actor: &AuthenticatedUser,
+    target: &UserAuthState,
+) -> Result<(), (StatusCode, &'static str)> {
+    if !target.permissions.is_empty()
+        && !actor.has_permission(UsersManage::NAME)
+    {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only user managers can change staff accounts.",
+        ));
+    }
+
+    Ok(())
+}
+
+// The account an action targets, see `ensure_not_own_account`
+// and `ensure_can_change_target`.
+async fn load_target_user(
+    state: &AppState,
+    actor: &AuthenticatedUser,
+    user_id: i32,
+) -> Result<UserAuthState, Response> {
+    ensure_not_own_account(actor, user_id)
+        .map_err(|(status, message)| error_response(status, message))?;
+
+    let target = match state.db_service.get_user_auth_state(user_id).await {
+        Ok(Some(target)) => target,
+        Ok(None) => {
+            return Err(error_response(
+                StatusCode::NOT_FOUND,
+                "User not found.",
+            ));
+        }
+        Err(e) => {
+            error!("Failed to get user {}: {:#}", user_id, e);
+            return Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not retrieve user.",
+            ));
+        }
+    };
+
+    ensure_can_change_target(actor, &target)
+        .map_err(|(status, message)| error_response(status, message))?;
+
+    Ok(target)
+}
+
+// Ends every session of the user, a banned or suspended user is logged out everywhere
+async fn end_user_sessions(state: &AppState, user_id: i32, reason: &str) {
+    if let Err(e) = state
+        .db_service
+        .revoke_all_user_sessions(user_id, None, reason)
+        .await
+    {
+        error!("Failed to revoke sessions of user {}: {:#}", user_id, e);
+    }
+    state.user_cache.invalidate(user_id);
+}
+
+#[derive(Deserialize)]
+pub struct UpdateUserRoleRequest {
+    role: String,
+}
+
+pub async fn update_user_role_handler(
+    admin: RequirePermission<UsersManage>,
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+    Json(payload): Json<UpdateUserRoleRequest>,
+) -> Response {
+    println!(
+        "->> {:<12} - update_user_role_handler - user: {}, target: {}, role: {}",
+        "HANDLER", admin.0.username, user_id, payload.role
+    );
+
+    if let Err(response) = load_target_user(&state, &admin.0, user_id).await {
+        return response;
+    }
+
+    let role_id = match state
+        .db_service
+        .get_role_id_by_name(&payload.role)
+        .await
+    {
+        Ok(Some(role_id)) => role_id,
+        Ok(None) => {
+            return error_response(StatusCode::BAD_REQUEST, "Unknown role.");
+        }
+        Err(e) => {
+            error!("Failed to get role {}: {:#}", payload.role, e);
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not change role.",
+            );
+        }
+    };
+
+    match state.db_service.update_user_role(user_id, role_id).await {
+        Ok(true) => {
+            state.user_cache.invalidate(user_id);
+            success_response("Role has been changed.")
+        }
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "User not found."),
+        Err(e) => {
+            error!("Failed to change role of user {}: {:#}", user_id, e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not change role.",
+            )
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuspendUserRequest {
+    until: DateTime<Utc>,
+    reason: Option<String>,
+}
+
+pub async fn suspend_user_handler(
+    moderator: RequirePermission<UsersBan>,
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+    Json(payload): Json<SuspendUserRequest>,
+) -> Response {
+    println!(
+        "->> {:<12} - suspend_user_handler - user: {}, target: {}, until: {}",
+        "HANDLER", moderator.0.username, user_id, payload.until
+    );
+
+    if payload.until <= Utc::now() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "Suspension must end in the future.",
+        );
+    }
+
+    if let Err(response) = load_target_user(&state, &moderator.0, user_id).await
+    {
+        return response;
+    }
+
+    let reason = normalize_reason(payload.reason);
+    match state
+        .db_service
+        .suspend_user(user_id, payload.until, reason.as_deref())
+        .await
+    {
+        Ok(true) => {
+            end_user_sessions(&state, user_id, "suspended").await;
+            success_response("User has been suspended.")
+        }
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "User not found."),
+        Err(e) => {
+            error!("Failed to suspend user {}: {:#}", user_id, e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not suspend user.",
+            )
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BanUserRequest {
+    reason: Option<String>,
+}
+
+pub async fn ban_user_handler(
+    moderator: RequirePermission<UsersBan>,
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+    Json(payload): Json<BanUserRequest>,
+) -> Response {
+    println!(
+        "->> {:<12} - ban_user_handler - user: {}, target: {}",
+        "HANDLER", moderator.0.username, user_id
+    );
+
+    if let Err(response) = load_target_user(&state, &moderator.0, user_id).await
+    {
+        return response;
+    }
+
+    let reason = normalize_reason(payload.reason);
+    match state.db_service.ban_user(user_id, reason.as_deref()).await {
+        Ok(true) => {
+            end_user_sessions(&state, user_id, "banned").await;
+            success_response("User has been banned.")
+        }
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "User not found."),
+        Err(e) => {
+            error!("Failed to ban user {}: {:#}", user_id, e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not ban user.",
+            )
+        }
+    }
+}
+
+// Lifts a ban as well as a suspension
+pub async fn lift_user_suspension_handler(
+    moderator: RequirePermission<UsersBan>,
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+) -> Response {
+    println!(
+        "->> {:<12} - lift_user_suspension_handler - user: {}, target: {}",
+        "HANDLER", moderator.0.username, user_id
+    );
+
+    if let Err(response) = load_target_user(&state, &moderator.0, user_id).await
+    {
+        return response;
+    }
+
+    match state.db_service.lift_user_suspension(user_id).await {
+        Ok(true) => {
+            state.user_cache.invalidate(user_id);
+            success_response("Suspension has been lifted.")
+        }
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "User not found."),
+        Err(e) => {
+            error!("Failed to lift suspension of user {}: {:#}", user_id, e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not lift suspension.",
+            )
+        }
+    }
+}
+
+// Logs the user out and refuses password logins until the emailed reset link is used
+pub async fn force_password_reset_handler(
+    admin: RequirePermission<UsersManage>,
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+) -> Response {
+    println!(
+        "->> {:<12} - force_password_reset_handler - user: {}, target: {}",
+        "HANDLER", admin.0.username, user_id
+    );
+
+    if let Err(response) = load_target_user(&state, &admin.0, user_id).await {
+        return response;
+    }
+
+    let user = match state.db_service.get_user_by_id(user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return error_response(StatusCode::NOT_FOUND, "User not found.");
+        }
+        Err(e) => {
+            error!("Failed to get user {}: {:#}", user_id, e);
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not force password reset.",
+            );
+        }
+    };
+
+    if let Err(e) = state.db_service.require_password_reset(user_id).await {
+        error!("Failed to require password reset of {}: {:#}", user_id, e);
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Could not force password reset.",
+        );
+    }
+
+    end_user_sessions(&state, user_id, "password reset required").await;
+
+    if send_password_reset(&state, user.id, &user.email, &user.username)
+        .await
+        .is_err()
+    {
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Password reset required, but the reset link could not be created.",
+        );
+    }
+
+    success_response("Password reset link has been sent to the user.")
+}
+
+pub async fn delete_user_handler(
+    admin: RequirePermission<UsersManage>,
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+) -> Response {
+    println!(
+        "->> {:<12} - delete_user_handler - user: {}, target: {}",
+        "HANDLER", admin.0.username, user_id
+    );
+
+    if let Err(response) = load_target_user(&state, &admin.0, user_id).await {
+        return response;
+    }
+
+    match state.db_service.delete_user(user_id).await {
+        Ok(true) => {
+            state.user_cache.invalidate(user_id);
+            success_response("User has been deleted.")
+        }
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "User not found."),
+        Err(e) => {
+            error!("Failed to delete user {}: {:#}", user_id, e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not delete user.",
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::permissions::UsersView;
+
+    fn actor(permissions: &[&str]) -> AuthenticatedUser {
+        AuthenticatedUser {
+            id: 1,
+            username: "staff".to_string(),
+            role_name: "moderator".to_string(),
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+            session_id: 1,
+            email_verified: true,
+            two_factor_enabled: true,
+        }
+    }
+
+    fn target(permissions: &[&str]) -> UserAuthState {
+        UserAuthState {
+            id: 2,
+            username: "target".to_string(),
+            role_name: "user".to_string(),
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+            email_verified: true,
+            two_factor_enabled: false,
+            token_version: 0,
+            is_active: true,
+            suspended_until: None,
+            suspension_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_staff_can_not_act_on_own_account() {
+        let moderator = actor(&[UsersBan::NAME, UsersManage::NAME]);
+
+        let (status, _) = ensure_not_own_account(&moderator, 1).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(ensure_not_own_account(&moderator, 2).is_ok());
+    }
+
+    #[test]
+    fn test_staff_targets_require_user_manager() {
+        let moderator = actor(&[UsersBan::NAME]);
+        let manager = actor(&[UsersBan::NAME, UsersManage::NAME]);
+
+        // Regular users are fair game for anyone allowed to call the endpoint
+        assert!(ensure_can_change_target(&moderator, &target(&[])).is_ok());
+
+        let staff = target(&[UsersView::NAME]);
+        let (status, _) =
+            ensure_can_change_target(&moderator, &staff).unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(ensure_can_change_target(&manager, &staff).is_ok());
+    }
+}
@@ -1,16 +1,26 @@
-use crate::api::extractor::AuthenticatedUser;
+use crate::api::extractor::{
+    AuthenticatedUser, ClientInfo, ensure_account_not_suspended,
+};
 use crate::builder::startup::AppState;
-use crate::common::email_service::send_password_reset_email;
+use crate::common::email_service::{
+    send_password_reset_email, send_verification_email,
+};
 use crate::common::error::AuthError;
-use crate::common::hashing::{hash_password, verify_password};
+use crate::common::hashing::{
+    generate_secure_token, hash_password, hash_secure_token, verify_password,
+};
 use crate::common::jwt::{
-    RefreshClaims, create_access_jwt, create_refresh_jwt,
+    REFRESH_TOKEN_TTL_DAYS, SESSION_MAX_LIFETIME_DAYS, create_access_jwt, jwks,
 };
-use crate::database::DatabaseService;
+use crate::common::totp;
+use crate::database::moderation::AccountStatus;
+use crate::database::sessions::RefreshTokenRotation;
+use crate::database::{DatabaseService, Users};
 use axum::Json;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum_core::__private::tracing::error;
+use axum_core::response::{IntoResponse, Response};
 use axum_extra::extract::CookieJar;
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use chrono::{Duration, Utc};
@@ -22,6 +32,19 @@ pub struct GenericMessageResponse {
     message: String,
 }
 
+// Verification links are valid for a day
+const EMAIL_VERIFICATION_TTL_HOURS: i64 = 24;
+// Resend throttling per user
+const VERIFICATION_RESEND_INTERVAL_SECS: i64 = 60;
+const MAX_VERIFICATION_EMAILS_PER_HOUR: i64 = 5;
+
+// The second login step has to follow the password within a few minutes
+const TWO_FACTOR_CHALLENGE_TTL_MINUTES: i64 = 5;
+// Social logins hand the challenge to the browser in this cookie, never in the URL
+const TWO_FACTOR_CHALLENGE_COOKIE: &str = "two-factor-challenge";
+const TWO_FACTOR_CHALLENGE_COOKIE_PATH: &str = "/api/auth/login/2fa";
+const MAX_TWO_FACTOR_ATTEMPTS: i32 = 5;
+
 // Helper function to get role name string from role id
 async fn get_role_name(
     db_service: &DatabaseService,
@@ -40,12 +63,196 @@ async fn get_role_name(
         })
 }
 
+// Starts a server-side session, returns its id and first refresh token
+async fn create_session(
+    db_service: &DatabaseService,
+    user_id: i32,
+    client: &ClientInfo,
+) -> Result<(i64, String), AuthError> {
+    let refresh_token = generate_secure_token();
+    let expires_at = Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS);
+    let max_expires_at = Utc::now() + Duration::days(SESSION_MAX_LIFETIME_DAYS);
+
+    let session_id = db_service
+        .create_user_session(
+            user_id,
+            &hash_secure_token(&refresh_token),
+            expires_at,
+            max_expires_at,
+            client.user_agent.as_deref(),
+            client.ip_address.as_deref(),
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to create session for user {}: {}", user_id, e);
+            AuthError::TokenCreation
+        })?;
+
+    Ok((session_id, refresh_token))
+}
+
+fn build_refresh_cookie(refresh_token: String) -> Cookie<'static> {
+    Cookie::build(("refresh-token", refresh_token))
+        .path("/")
+        .http_only(true)
+        .secure(false) // Only send via HTTPS (disable for local development)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::days(REFRESH_TOKEN_TTL_DAYS))
+        .build()
+}
+
+/// Failed logins of an account are counted under this key.
+pub fn account_failure_key(user_id: i32) -> String {
+    format!("user:{}", user_id)
+}
+
+// Failed logins with a name that matches no account are counted under the
+// name, it gets the same delays and lockout as a real account
+fn identifier_failure_key(identifier: &str) -> String {
+    format!("identifier:{}", identifier.trim().to_lowercase())
+}
+
+/// Rejects logins to an account (or unknown name) locked by repeated failures.
+pub async fn ensure_login_not_locked(
+    db_service: &DatabaseService,
+    failure_key: &str,
+) -> Result<(), AuthError> {
+    if let Some(locked_until) =
+        db_service.get_login_locked_until(failure_key).await?
+    {
+        let remaining = (locked_until - Utc::now()).num_seconds().max(1);
+        return Err(AuthError::TooManyRequests {
+            retry_after_secs: remaining as u64,
+        });
+    }
+
+    Ok(())
+}
+
+// Rejects banned and suspended accounts, and accounts whose password staff flagged.
+// A forced reset blocks every login, a linked provider must not get around it:
+// the flagged account may be taken over, and the provider login would let
+// the holder set a new password from the profile.
+fn check_account_status(status: &AccountStatus) -> Result<(), AuthError> {
+    ensure_account_not_suspended(
+        status.is_active,
+        status.suspended_until,
+        status.suspension_reason.as_deref(),
+    )?;
+
+    if status.password_reset_required {
+        return Err(AuthError::PasswordResetRequired);
+    }
+
+    Ok(())
+}
+
+async fn ensure_account_usable(
+    db_service: &DatabaseService,
+    user_id: i32,
+) -> Result<(), AuthError> {
+    let status = db_service
+        .get_account_status(user_id)
+        .await?
+        .ok_or(AuthError::WrongCredentials)?;
+
+    check_account_status(&status)
+}
+
+// Counts a failed password or second factor, locks the account once it adds up.
+// Returns whether the full lockout was reached.
+async fn record_login_failure(
+    state: &AppState,
+    failure_key: &str,
+) -> Result<bool, AuthError> {
+    let policy = state.login_lockout;
+    let failures = state
+        .db_service
+        .record_failed_login(failure_key, policy.lockout)
+        .await?;
+
+    if let Some(lock) = policy.lock_duration(failures.max(0) as u32) {
+        let locked_until = Utc::now()
+            + Duration::from_std(lock).unwrap_or(Duration::minutes(15));
+        state
+            .db_service
+            .lock_login(failure_key, locked_until)
+            .await?;
+
+        if failures as u32 >= policy.lockout_after_failures {
+            error!(
+                "[AUTH HANDLER] Login for {} locked after {} failed attempts",
+                failure_key, failures
+            );
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+// Addresses that failed too many logins lately get 429 before any account is looked at
+fn ensure_client_not_throttled(
+    state: &AppState,
+    client: &ClientInfo,
+) -> Result<(), AuthError> {
+    let key = client.ip_address.as_deref().unwrap_or("unknown");
+    state
+        .login_failure_limiter
+        .peek(key)
+        .map_err(|retry_after_secs| AuthError::TooManyRequests {
+            retry_after_secs,
+        })
+}
+
+// Counts a failed login against the client address, see `ensure_client_not_throttled`
+fn record_client_login_failure(state: &AppState, client: &ClientInfo) {
+    let key = client.ip_address.as_deref().unwrap_or("unknown");
+    let _ = state.login_failure_limiter.check(key);
+}
+
+/// Emails a new verification link to the user's current address.
+/// A failed send is only logged, the user can ask for another link.
+pub async fn send_email_verification(
+    state: &AppState,
+    user_id: i32,
+    email: &str,
+    username: &str,
+) -> Result<(), AuthError> {
+    let token = generate_secure_token();
+    let expires_at = Utc::now() + Duration::hours(EMAIL_VERIFICATION_TTL_HOURS);
+
+    state
+        .db_service
+        .create_email_verification_token(
+            user_id,
+            &hash_secure_token(&token),
+            email,
+            expires_at,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to create verification token: {}", e);
+            AuthError::InternalServerError
+        })?;
+
+    if let Err(e) =
+        send_verification_email(&state.mailer, email, username, &token).await
+    {
+        error!("[AUTH HANDLER] Failed to send verification email: {:?}", e);
+    }
+
+    Ok(())
+}
+
 // Struct for Responses
 #[derive(Serialize)]
 pub struct UserData {
     id: i32,
     username: String,
     role: String,
+    #[serde(rename = "emailVerified")]
+    email_verified: bool,
 }
 
 #[derive(Serialize)]
@@ -71,14 +278,192 @@ impl LoginRequest {
     }
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorChallengeResponse {
+    message: String,
+    two_factor_required: bool,
+    challenge_token: String,
+}
+
+/// Starts a session for a fully authenticated user and sets its cookies.
+pub async fn complete_login(
+    jar: CookieJar,
+    db_service: &DatabaseService,
+    user: Users,
+    client: &ClientInfo,
+) -> Result<(CookieJar, Json<LoginResponse>), AuthError> {
+    ensure_account_usable(db_service, user.id).await?;
+    db_service
+        .reset_failed_logins(&account_failure_key(user.id))
+        .await?;
+
+    let role_name = get_role_name(db_service, user.role_id).await?;
+
+    let (session_id, refresh_token) =
+        create_session(db_service, user.id, client).await?;
+    let access_token = create_access_jwt(
+        user.id,
+        role_name.clone(),
+        session_id,
+        user.token_version,
+    )?;
+
+    // Set cookie
+    let access_cookie = Cookie::build(("token", access_token))
+        .path("/")
+        .http_only(true)
+        .secure(false) // Only send via HTTPS (disable for local development)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(30 * 60))
+        .build();
+
+    // Set cookie
+    let refresh_cookie = build_refresh_cookie(refresh_token);
+
+    // Add both to the jar
+    let new_jar = jar.add(access_cookie).add(refresh_cookie);
+
+    let response = LoginResponse {
+        message: "Login Successfull".to_string(),
+        user: UserData {
+            id: user.id,
+            username: user.username,
+            role: role_name,
+            email_verified: user.email_verified,
+        },
+    };
+
+    Ok((new_jar, Json(response)))
+}
+
+/// Token for the second login step of a user with 2FA enabled.
+pub async fn create_two_factor_challenge(
+    db_service: &DatabaseService,
+    user_id: i32,
+) -> Result<String, AuthError> {
+    let challenge_token = generate_secure_token();
+    db_service
+        .create_two_factor_challenge(
+            user_id,
+            &hash_secure_token(&challenge_token),
+            Utc::now() + Duration::minutes(TWO_FACTOR_CHALLENGE_TTL_MINUTES),
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to create two-factor challenge: {}", e);
+            AuthError::InternalServerError
+        })?;
+
+    Ok(challenge_token)
+}
+
+/// Cookie carrying a challenge token to `login_two_factor_handler`,
+/// only sent to that route and unreadable by scripts.
+pub fn two_factor_challenge_cookie(challenge_token: String) -> Cookie<'static> {
+    Cookie::build((TWO_FACTOR_CHALLENGE_COOKIE, challenge_token))
+        .path(TWO_FACTOR_CHALLENGE_COOKIE_PATH)
+        .http_only(true)
+        .secure(false) // Only send via HTTPS (disable for local development)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::minutes(TWO_FACTOR_CHALLENGE_TTL_MINUTES))
+        .build()
+}
+
+/// Checks a TOTP code or an unused recovery code of a user with 2FA enabled.
+/// Either is consumed on success, a code can not be used twice.
+pub async fn verify_second_factor(
+    db_service: &DatabaseService,
+    user_id: i32,
+    code: &str,
+) -> Result<bool, AuthError> {
+    let Some(user_totp) = db_service
+        .get_user_totp(user_id)
+        .await?
+        .filter(|user_totp| user_totp.enabled)
+    else {
+        return Ok(false);
+    };
+
+    if let Some(step) = totp::verify_code(
+        &user_totp.secret,
+        code,
+        Utc::now().timestamp(),
+        user_totp.last_used_step,
+    ) {
+        return Ok(db_service.record_totp_step(user_id, step).await?);
+    }
+
+    let recovery_code = totp::normalize_recovery_code(code);
+    if recovery_code.is_empty() {
+        return Ok(false);
+    }
+
+    Ok(db_service
+        .use_recovery_code(user_id, &hash_secure_token(&recovery_code))
+        .await?)
+}
+
+/// `verify_second_factor` for requests of a logged-in user (disabling 2FA,
+/// new recovery codes). Wrong codes count as failed logins like at the login
+/// challenge, the session that keeps guessing is revoked at the lockout.
+pub async fn verify_session_second_factor(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    code: &str,
+) -> Result<(), AuthError> {
+    let failure_key = account_failure_key(user.id);
+    ensure_login_not_locked(&state.db_service, &failure_key).await?;
+
+    if verify_second_factor(&state.db_service, user.id, code).await? {
+        return Ok(());
+    }
+
+    if record_login_failure(state, &failure_key).await? {
+        end_guessing_session(state, user).await;
+    }
+    Err(AuthError::InvalidTwoFactorCode)
+}
+
+/// Counts a wrong password sent by a logged-in user (disabling 2FA)
+/// like a failed login, see `verify_session_second_factor`.
+pub async fn record_session_password_failure(
+    state: &AppState,
+    user: &AuthenticatedUser,
+) -> Result<(), AuthError> {
+    if record_login_failure(state, &account_failure_key(user.id)).await? {
+        end_guessing_session(state, user).await;
+    }
+    Ok(())
+}
+
+async fn end_guessing_session(state: &AppState, user: &AuthenticatedUser) {
+    if let Err(e) = state
+        .db_service
+        .revoke_user_session(
+            user.id,
+            user.session_id,
+            "too many failed attempts",
+        )
+        .await
+    {
+        error!("Failed to revoke session {}: {}", user.session_id, e);
+    }
+    state.user_cache.invalidate_session(user.session_id);
+}
+
 // Accepts a `CookieJar` and return modified `CookieJar`
-// with the token set as a cookie
+// with the token set as a cookie.
+// With 2FA enabled no cookies are set, the client gets a challenge token
+// for `login_two_factor_handler` instead.
 pub async fn login_handler(
     jar: CookieJar,
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(payload): Json<LoginRequest>,
-) -> Result<(CookieJar, Json<LoginResponse>), AuthError> {
+) -> Result<Response, AuthError> {
     payload.validate_input()?;
+    ensure_client_not_throttled(&state, &client)?;
 
     let db_service = &state.db_service;
 
@@ -88,8 +473,20 @@ pub async fn login_handler(
         .map_err(|e| {
             eprintln!("Database error on user lookup: {}", e);
             AuthError::InternalServerError
-        })?
-        .ok_or(AuthError::WrongCredentials)?;
+        })?;
+
+    // Unknown names answer exactly like accounts, failures and lockout included
+    let failure_key = match &user {
+        Some(user) => account_failure_key(user.id),
+        None => identifier_failure_key(&payload.identifier),
+    };
+    ensure_login_not_locked(db_service, &failure_key).await?;
+
+    let Some(user) = user else {
+        record_login_failure(&state, &failure_key).await?;
+        record_client_login_failure(&state, &client);
+        return Err(AuthError::WrongCredentials);
+    };
 
     let is_password_valid = verify_password(
         &payload.password,
@@ -101,51 +498,95 @@ pub async fn login_handler(
     })?;
 
     if !is_password_valid {
+        record_login_failure(&state, &failure_key).await?;
+        record_client_login_failure(&state, &client);
         return Err(AuthError::WrongCredentials);
     }
 
-    let role_name = get_role_name(db_service, user.role_id).await?;
+    ensure_account_usable(db_service, user.id).await?;
 
-    let access_token =
-        create_access_jwt(user.username.clone(), role_name.clone())?;
-    let refresh_token = create_refresh_jwt(user.username.clone())?;
+    if db_service.is_two_factor_enabled(user.id).await? {
+        let challenge_token =
+            create_two_factor_challenge(db_service, user.id).await?;
 
-    // Set cookie
-    let access_cookie = Cookie::build(("token", access_token))
-        .path("/")
-        .http_only(true)
-        .secure(false) // Only send via HTTPS (disable for local development)
-        .same_site(SameSite::Lax)
-        .max_age(time::Duration::seconds(30 * 60))
-        .build();
+        let response = TwoFactorChallengeResponse {
+            message: "Two-factor authentication required".to_string(),
+            two_factor_required: true,
+            challenge_token,
+        };
+        return Ok((StatusCode::OK, Json(response)).into_response());
+    }
 
-    // Set cookie
-    let refresh_cookie = Cookie::build(("refresh-token", refresh_token))
-        .path("/")
-        .http_only(true)
-        .secure(false) // Only send via HTTPS (disable for local development)
-        .same_site(SameSite::Lax)
-        .max_age(time::Duration::days(7))
-        .build();
+    Ok(complete_login(jar, db_service, user, &client)
+        .await?
+        .into_response())
+}
 
-    // Add both to the jar
-    let new_jar = jar.add(access_cookie).add(refresh_cookie);
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginTwoFactorRequest {
+    // Empty after a social login, the challenge is in a cookie then
+    #[serde(default)]
+    challenge_token: String,
+    code: String,
+}
 
-    let response = LoginResponse {
-        message: "Login Successfull".to_string(),
-        user: UserData {
-            id: user.id,
-            username: user.username,
-            role: role_name,
-        },
+// Second login step, accepts a TOTP code or a recovery code
+pub async fn login_two_factor_handler(
+    jar: CookieJar,
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<LoginTwoFactorRequest>,
+) -> Result<(CookieJar, Json<LoginResponse>), AuthError> {
+    let challenge_token = match payload.challenge_token.is_empty() {
+        true => jar
+            .get(TWO_FACTOR_CHALLENGE_COOKIE)
+            .map(|cookie| cookie.value().to_string())
+            .unwrap_or_default(),
+        false => payload.challenge_token,
     };
+    if challenge_token.is_empty() || payload.code.is_empty() {
+        return Err(AuthError::MissingCredentials);
+    }
+    ensure_client_not_throttled(&state, &client)?;
 
-    Ok((new_jar, Json(response)))
+    let db_service = &state.db_service;
+    let challenge_hash = hash_secure_token(&challenge_token);
+
+    // Expired or out of attempts, the password has to be entered again
+    let user_id = db_service
+        .attempt_two_factor_challenge(&challenge_hash, MAX_TWO_FACTOR_ATTEMPTS)
+        .await?
+        .ok_or(AuthError::InvalidToken)?;
+
+    // New challenges do not bypass the lockout, codes count as failed logins too
+    let failure_key = account_failure_key(user_id);
+    ensure_login_not_locked(db_service, &failure_key).await?;
+
+    if !verify_second_factor(db_service, user_id, &payload.code).await? {
+        record_login_failure(&state, &failure_key).await?;
+        record_client_login_failure(&state, &client);
+        return Err(AuthError::InvalidTwoFactorCode);
+    }
+
+    db_service
+        .delete_two_factor_challenge(&challenge_hash)
+        .await?;
+    let jar = jar.remove(
+        Cookie::build(TWO_FACTOR_CHALLENGE_COOKIE)
+            .path(TWO_FACTOR_CHALLENGE_COOKIE_PATH),
+    );
+
+    let user = db_service
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or(AuthError::InvalidToken)?;
+
+    complete_login(jar, db_service, user, &client).await
 }
 
-pub async fn logout_handler(
-    jar: CookieJar,
-) -> Result<(CookieJar, Json<GenericMessageResponse>), AuthError> {
+/// Expires the access and refresh token cookies.
+pub fn clear_auth_cookies(jar: CookieJar) -> CookieJar {
     fn create_expired_cookie(name: &'static str) -> Cookie<'static> {
         Cookie::build(name)
             .path("/")
@@ -156,12 +597,34 @@ pub async fn logout_handler(
             .build()
     }
 
-    let clear_access_token_cookie = create_expired_cookie("token");
-    let clear_refresh_token_cookie = create_expired_cookie("refresh-token");
+    jar.add(create_expired_cookie("token"))
+        .add(create_expired_cookie("refresh-token"))
+}
 
-    let new_jar = jar
-        .add(clear_access_token_cookie)
-        .add(clear_refresh_token_cookie);
+pub async fn logout_handler(
+    jar: CookieJar,
+    State(state): State<AppState>,
+) -> Result<(CookieJar, Json<GenericMessageResponse>), AuthError> {
+    // Revoke the session server-side, a copied refresh token stops working too.
+    // The cookies are cleared even when that fails, the session still expires on its own.
+    if let Some(refresh_cookie) = jar.get("refresh-token") {
+        match state
+            .db_service
+            .revoke_session_by_refresh_token(
+                &hash_secure_token(refresh_cookie.value()),
+                "logout",
+            )
+            .await
+        {
+            Ok(Some(session_id)) => {
+                state.user_cache.invalidate_session(session_id)
+            }
+            Ok(None) => {}
+            Err(e) => error!("Failed to revoke session on logout: {}", e),
+        }
+    }
+
+    let new_jar = clear_auth_cookies(jar);
 
     let response_body = GenericMessageResponse {
         message: "Logged out successfully".to_string(),
@@ -173,19 +636,61 @@ pub async fn logout_handler(
 pub async fn refresh_token_handler(
     jar: CookieJar,
     State(state): State<AppState>,
-    claims: RefreshClaims,
 ) -> Result<(CookieJar, Json<GenericMessageResponse>), AuthError> {
+    let refresh_token = jar
+        .get("refresh-token")
+        .map(|cookie| cookie.value().to_string())
+        .ok_or(AuthError::InvalidToken)?;
+
+    // Every refresh rotates the token, the presented one can not be used again
+    let new_refresh_token = generate_secure_token();
+    let rotation = state
+        .db_service
+        .rotate_refresh_token(
+            &hash_secure_token(&refresh_token),
+            &hash_secure_token(&new_refresh_token),
+            Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS),
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to rotate refresh token: {}", e);
+            AuthError::InternalServerError
+        })?;
+
+    let (user_id, session_id) = match rotation {
+        RefreshTokenRotation::Rotated {
+            user_id,
+            session_id,
+        } => (user_id, session_id),
+        RefreshTokenRotation::Reused {
+            user_id,
+            session_id,
+        } => {
+            error!(
+                "[AUTH HANDLER] Refresh token reuse for user {}, session {} revoked",
+                user_id, session_id
+            );
+            state.user_cache.invalidate_session(session_id);
+            return Err(AuthError::InvalidRefreshToken);
+        }
+        RefreshTokenRotation::Invalid => {
+            return Err(AuthError::InvalidRefreshToken);
+        }
+    };
+
     let user = state
         .db_service
-        .get_user_by_identifier(&claims.sub)
+        .get_user_by_id(user_id)
         .await
-        .map_err(|_| AuthError::InvalidToken)?
+        .map_err(|_| AuthError::InternalServerError)?
         .ok_or(AuthError::InvalidRefreshToken)?;
 
+    ensure_account_usable(&state.db_service, user.id).await?;
+
     let role_name = get_role_name(&state.db_service, user.role_id).await?;
 
     let new_access_token =
-        create_access_jwt(claims.sub.clone(), role_name.clone())?;
+        create_access_jwt(user.id, role_name, session_id, user.token_version)?;
 
     let new_access_cookie = Cookie::build(("token", new_access_token))
         .path("/")
@@ -195,7 +700,9 @@ pub async fn refresh_token_handler(
         .max_age(time::Duration::minutes(15))
         .build();
 
-    let new_jar = jar.add(new_access_cookie);
+    let new_jar = jar
+        .add(new_access_cookie)
+        .add(build_refresh_cookie(new_refresh_token));
     let response_body = GenericMessageResponse {
         message: "Token refreshed successfully".to_string(),
     };
@@ -203,6 +710,11 @@ pub async fn refresh_token_handler(
     Ok((new_jar, Json(response_body)))
 }
 
+// Public keys of the access tokens, for services verifying them without a shared secret
+pub async fn jwks_handler() -> Json<serde_json::Value> {
+    Json(jwks())
+}
+
 #[derive(Serialize)]
 pub struct UserResponse {
     user: UserData,
@@ -210,7 +722,6 @@ pub struct UserResponse {
 
 /// Protected handler. `Claims` acts as a guard.
 pub async fn protected_handler(
-    State(state): State<AppState>,
     user: AuthenticatedUser,
 ) -> Result<(StatusCode, Json<UserResponse>), AuthError> {
     println!(
@@ -218,12 +729,11 @@ pub async fn protected_handler(
         user.username
     );
 
-    let role_name = get_role_name(&state.db_service, user.role_id).await?;
-
     let user_data = UserData {
         id: user.id,
         username: user.username,
-        role: role_name,
+        role: user.role_name,
+        email_verified: user.email_verified,
     };
 
     let response = UserResponse { user: user_data };
@@ -252,47 +762,62 @@ impl ResetPasswordRequest {
     }
 }
 
+/// Stores a new password reset token for the user and emails the link.
+/// A failure to send is only logged.
+pub async fn send_password_reset(
+    state: &AppState,
+    user_id: i32,
+    email: &str,
+    username: &str,
+) -> Result<(), AuthError> {
+    let unique_reset_token = Uuid::new_v4().to_string();
+    let expired_at = Utc::now() + Duration::hours(1);
+
+    // Store reset token in the database
+    state
+        .db_service
+        .create_password_reset_token(user_id, &unique_reset_token, expired_at)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+    // Send the password reset email
+    if let Err(e) = send_password_reset_email(
+        &state.mailer,
+        email,
+        username,
+        &unique_reset_token,
+    )
+    .await
+    {
+        // Error log if sending email fails
+        error!(
+            "[AUTH HANDLER] Failed to send password reset email: {:?}",
+            e
+        );
+    }
+
+    Ok(())
+}
+
 // Handler for the password reset request
 // Finds a user by email, generates a token, and in a real app, sends an email.
 pub async fn forgot_password_handler(
     State(state): State<AppState>,
     Json(payload): Json<ForgotPasswordRequest>,
 ) -> Result<Json<GenericMessageResponse>, AuthError> {
-    // Find user by email
+    // Find user by email.
+    // Throttled accounts get the same response, the limit does not reveal them.
     if let Ok(Some(user)) = state
         .db_service
         .get_user_by_identifier(&payload.email)
         .await
+        && state
+            .password_reset_limiter
+            .check(&user.id.to_string())
+            .is_ok()
     {
-        let unique_reset_token = Uuid::new_v4().to_string();
-        let expired_at = Utc::now() + Duration::hours(1);
-
-        // Store reset token in the database
-        state
-            .db_service
-            .create_password_reset_token(
-                user.id,
-                &unique_reset_token,
-                expired_at,
-            )
-            .await
-            .map_err(|_| AuthError::InternalServerError)?;
-
-        // Send the password reset email
-        if let Err(e) = send_password_reset_email(
-            &state.mailer,
-            &user.email,
-            &user.username,
-            &unique_reset_token,
-        )
-        .await
-        {
-            // Error log if sending email fails
-            error!(
-                "[AUTH HANDLER] Failed to send password reset email: {:?}",
-                e
-            );
-        }
+        send_password_reset(&state, user.id, &user.email, &user.username)
+            .await?;
     }
 
     let response = GenericMessageResponse {
@@ -339,6 +864,14 @@ pub async fn reset_password_handler(
         .delete_password_reset_token(&payload.token)
         .await?;
 
+    // Whoever knew the old password may still hold a session
+    db_service
+        .revoke_all_user_sessions(user_id, None, "password reset")
+        .await?;
+    // Access tokens of those sessions too, without waiting for them to expire
+    db_service.bump_user_token_version(user_id).await?;
+    state.user_cache.invalidate(user_id);
+
     let response = GenericMessageResponse {
         message: "Password reset successful".to_string(),
     };
@@ -346,6 +879,96 @@ pub async fn reset_password_handler(
     Ok(Json(response))
 }
 
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+// Handler for the link in the verification email
+pub async fn verify_email_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<VerifyEmailRequest>,
+) -> Result<Json<GenericMessageResponse>, AuthError> {
+    if payload.token.is_empty() {
+        return Err(AuthError::MissingCredentials);
+    }
+
+    let user_id = state
+        .db_service
+        .verify_email_with_token(&hash_secure_token(&payload.token))
+        .await?
+        .ok_or(AuthError::InvalidToken)?;
+    state.user_cache.invalidate(user_id);
+
+    let response = GenericMessageResponse {
+        message: "Email verified successfully".to_string(),
+    };
+
+    Ok(Json(response))
+}
+
+/// Throttles verification emails per user, shared by resending and email changes.
+pub async fn ensure_verification_email_allowed(
+    db_service: &DatabaseService,
+    user_id: i32,
+) -> Result<(), AuthError> {
+    let (last_sent_at, sent_last_hour) = db_service
+        .get_email_verification_send_stats(user_id)
+        .await?;
+
+    if sent_last_hour >= MAX_VERIFICATION_EMAILS_PER_HOUR {
+        return Err(AuthError::TooManyRequests {
+            retry_after_secs: 60 * 60,
+        });
+    }
+    if let Some(last_sent_at) = last_sent_at {
+        let elapsed = (Utc::now() - last_sent_at).num_seconds();
+        if elapsed < VERIFICATION_RESEND_INTERVAL_SECS {
+            return Err(AuthError::TooManyRequests {
+                retry_after_secs: (VERIFICATION_RESEND_INTERVAL_SECS - elapsed)
+                    as u64,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// Sends another verification link, throttled per user
+pub async fn resend_verification_email_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<GenericMessageResponse>, AuthError> {
+    if user.email_verified {
+        let response = GenericMessageResponse {
+            message: "Email already verified".to_string(),
+        };
+        return Ok(Json(response));
+    }
+
+    ensure_verification_email_allowed(&state.db_service, user.id).await?;
+
+    let account = state
+        .db_service
+        .get_user_by_id(user.id)
+        .await?
+        .ok_or(AuthError::InvalidToken)?;
+
+    send_email_verification(
+        &state,
+        account.id,
+        &account.email,
+        &account.username,
+    )
+    .await?;
+
+    let response = GenericMessageResponse {
+        message: "Verification email sent".to_string(),
+    };
+
+    Ok(Json(response))
+}
+
 #[derive(Deserialize)]
 pub struct RegisterPayload {
     username: String,
@@ -414,7 +1037,7 @@ pub async fn register_new_user_handler(
         })?;
 
     // Create a new user in the database
-    let _new_user = db_service
+    let new_user_id = db_service
         .create_user(
             &payload.username,
             &payload.email,
@@ -424,9 +1047,19 @@ pub async fn register_new_user_handler(
         .await
         .map_err(|_| AuthError::InternalServerError)?;
 
+    // The account can log in right away, commenting, rating and uploading wait for verification
+    send_email_verification(
+        &state,
+        new_user_id,
+        &payload.email,
+        &payload.username,
+    )
+    .await?;
+
     // Return success response
     let response = GenericMessageResponse {
-        message: "User registered successfully".to_string(),
+        message: "User registered successfully. Check your email to verify your account"
+            .to_string(),
     };
 
     Ok((StatusCode::CREATED, Json(response)))
@@ -491,3 +1124,44 @@ pub async fn realtime_check_username_handler(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_keys_of_accounts_and_unknown_names() {
+        assert_eq!(account_failure_key(42), "user:42");
+        assert_eq!(
+            identifier_failure_key("  Someone@Example.com "),
+            identifier_failure_key("someone@example.com")
+        );
+        // A name that looks like an account key can not collide with one
+        assert_ne!(identifier_failure_key("user:42"), account_failure_key(42));
+    }
+
+    #[test]
+    fn test_forced_password_reset_blocks_every_login() {
+        let mut status = AccountStatus {
+            is_active: true,
+            suspended_until: None,
+            suspension_reason: None,
+            password_reset_required: false,
+        };
+        assert!(check_account_status(&status).is_ok());
+
+        // Password, 2FA and provider logins all finish through `complete_login`
+        status.password_reset_required = true;
+        assert!(matches!(
+            check_account_status(&status),
+            Err(AuthError::PasswordResetRequired)
+        ));
+
+        // A ban is reported before the reset
+        status.is_active = false;
+        assert!(matches!(
+            check_account_status(&status),
+            Err(AuthError::AccountBanned { .. })
+        ));
+    }
+}
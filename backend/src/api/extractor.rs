@@ -1,16 +1,134 @@
 use crate::builder::startup::AppState;
 use crate::common::error::AuthError;
 use crate::common::jwt::Claims;
-use crate::database::Users;
-use axum::extract::FromRequestParts;
+use crate::common::permissions::Permission;
+use crate::database::UserAuthState;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::header;
 use axum::http::request::Parts;
 use axum_core::__private::tracing::error;
+use chrono::{DateTime, Utc};
 use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+
+// Behind a reverse proxy the peer address is the proxy's,
+// the client address is the last one it appended to `X-Forwarded-For`
+static TRUST_FORWARDED_FOR: LazyLock<bool> = LazyLock::new(|| {
+    std::env::var("TRUST_FORWARDED_FOR")
+        .is_ok_and(|value| matches!(value.as_str(), "1" | "true"))
+});
+
+/// Users with any permission (admins, moderators) are rejected by `RequirePermission`
+/// without two-factor authentication, they can still log in to enrol.
+pub static REQUIRE_ADMIN_TWO_FACTOR: LazyLock<bool> = LazyLock::new(|| {
+    std::env::var("REQUIRE_ADMIN_TWO_FACTOR")
+        .is_ok_and(|value| matches!(value.as_str(), "1" | "true"))
+});
 
 pub struct AuthenticatedUser {
     pub id: i32,
     pub username: String,
-    pub role_id: i32,
+    pub role_name: String,
+    pub permissions: Vec<String>,
+    pub session_id: i64,
+    pub email_verified: bool,
+    pub two_factor_enabled: bool,
+}
+
+/// Rejects banned accounts and accounts suspended until a later date.
+pub fn ensure_account_not_suspended(
+    is_active: bool,
+    suspended_until: Option<DateTime<Utc>>,
+    reason: Option<&str>,
+) -> Result<(), AuthError> {
+    if !is_active {
+        return Err(AuthError::AccountBanned {
+            reason: reason.map(str::to_string),
+        });
+    }
+
+    if let Some(until) = suspended_until
+        && until > Utc::now()
+    {
+        return Err(AuthError::AccountSuspended {
+            until,
+            reason: reason.map(str::to_string),
+        });
+    }
+
+    Ok(())
+}
+
+// The user of a token, from the user cache when enabled.
+// Tokens issued before the user's token version was bumped are rejected.
+async fn load_token_user(
+    state: &AppState,
+    claims: &Claims,
+) -> Result<UserAuthState, AuthError> {
+    let user_id = claims.user_id()?;
+
+    let user = match state.user_cache.get(user_id) {
+        Some(user) => user,
+        None => {
+            let user = state
+                .db_service
+                .get_user_auth_state(user_id)
+                .await
+                .map_err(|e| {
+                    error!(
+                        "Failed to get user {} from token: {:?}",
+                        user_id, e
+                    );
+                    AuthError::InternalServerError
+                })?
+                .ok_or(AuthError::InvalidToken)?;
+            state.user_cache.insert(user.clone());
+            user
+        }
+    };
+
+    if user.token_version != claims.ver {
+        return Err(AuthError::InvalidToken);
+    }
+
+    ensure_account_not_suspended(
+        user.is_active,
+        user.suspended_until,
+        user.suspension_reason.as_deref(),
+    )?;
+
+    Ok(user)
+}
+
+// Access tokens stay valid until they expire, the session lets a revoked login end sooner.
+// With the user cache enabled an active session is checked in the database once per TTL,
+// revocations on this instance drop it from the cache right away.
+async fn ensure_session_active(
+    state: &AppState,
+    claims: &Claims,
+    user_id: i32,
+) -> Result<(), AuthError> {
+    if state.user_cache.is_session_active(claims.sid, user_id) {
+        return Ok(());
+    }
+
+    let is_active = state
+        .db_service
+        .touch_user_session(claims.sid, user_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to check session {}: {:?}", claims.sid, e);
+            AuthError::InternalServerError
+        })?;
+
+    if !is_active {
+        return Err(AuthError::InvalidToken);
+    }
+
+    state.user_cache.insert_session(claims.sid, user_id);
+    Ok(())
 }
 
 impl FromRequestParts<AppState> for AuthenticatedUser {
@@ -24,24 +142,67 @@ impl FromRequestParts<AppState> for AuthenticatedUser {
             .await
             .map_err(|_| AuthError::InvalidToken)?;
 
-        let user = state
-            .db_service
-            .get_user_by_identifier(&claims.sub)
-            .await
-            .map_err(|e| {
-                error!("Failed to get user identifier from token: {:?}", e);
-                AuthError::InternalServerError
-            })?
-            .ok_or(AuthError::InvalidToken)?;
+        let user = load_token_user(state, &claims).await?;
+
+        ensure_session_active(state, &claims, user.id).await?;
 
         Ok(AuthenticatedUser {
             id: user.id,
             username: user.username,
-            role_id: user.role_id,
+            role_name: user.role_name,
+            permissions: user.permissions,
+            session_id: claims.sid,
+            email_verified: user.email_verified,
+            two_factor_enabled: user.two_factor_enabled,
         })
     }
 }
 
+impl AuthenticatedUser {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|granted| granted == permission)
+    }
+
+    /// Logged in but not allowed is a 403, staff without two-factor
+    /// authentication is told to enrol when `require_two_factor` is set.
+    pub fn ensure_permission(
+        &self,
+        permission: &str,
+        require_two_factor: bool,
+    ) -> Result<(), AuthError> {
+        if !self.has_permission(permission) {
+            return Err(AuthError::Forbidden);
+        }
+
+        if require_two_factor && !self.two_factor_enabled {
+            return Err(AuthError::TwoFactorRequired);
+        }
+
+        Ok(())
+    }
+}
+
+/// A logged-in user with a verified email address,
+/// required to comment, rate and upload.
+pub struct VerifiedUser(pub AuthenticatedUser);
+
+impl FromRequestParts<AppState> for VerifiedUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let user = AuthenticatedUser::from_request_parts(parts, state).await?;
+
+        if !user.email_verified {
+            return Err(AuthError::EmailNotVerified);
+        }
+
+        Ok(VerifiedUser(user))
+    }
+}
+
 pub struct OptionalAuthenticatedUser(pub Option<AuthenticatedUser>);
 
 impl FromRequestParts<AppState> for OptionalAuthenticatedUser {
@@ -62,37 +223,152 @@ impl FromRequestParts<AppState> for OptionalAuthenticatedUser {
     }
 }
 
-pub struct AdminUser(pub Users);
+/// A logged-in user whose role grants the permission `P`,
+/// e.g. `RequirePermission<SeriesEdit>` guards the series management routes.
+pub struct RequirePermission<P: Permission>(
+    pub AuthenticatedUser,
+    PhantomData<P>,
+);
 
-impl FromRequestParts<AppState> for AdminUser {
+impl<P: Permission> FromRequestParts<AppState> for RequirePermission<P> {
     type Rejection = AuthError;
 
     async fn from_request_parts(
         parts: &mut Parts,
         state: &AppState,
     ) -> Result<Self, Self::Rejection> {
-        let claims = Claims::from_request_parts(parts, state)
-            .await
-            .map_err(|_| AuthError::InvalidToken)?;
+        let user = AuthenticatedUser::from_request_parts(parts, state).await?;
 
-        let user = state
-            .db_service
-            .get_user_by_identifier(&claims.sub)
-            .await
-            .map_err(|_| AuthError::InternalServerError)?
-            .ok_or(AuthError::InvalidToken)?;
+        user.ensure_permission(P::NAME, *REQUIRE_ADMIN_TWO_FACTOR)?;
 
-        let role_name = state
-            .db_service
-            .get_role_name_by_id(user.role_id)
-            .await
-            .map_err(|_| AuthError::InternalServerError)?
-            .ok_or(AuthError::InvalidToken)?;
+        Ok(RequirePermission(user, PhantomData))
+    }
+}
+
+/// User agent and address of the client, recorded with its sessions.
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let forwarded_for = TRUST_FORWARDED_FOR
+            .then(|| parts.headers.get("x-forwarded-for"))
+            .flatten()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty());
+
+        let ip_address = forwarded_for.or_else(|| {
+            parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        });
+
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(512).collect());
+
+        Ok(ClientInfo {
+            ip_address,
+            user_agent,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::permissions::{SeriesEdit, UsersManage};
+    use axum::http::StatusCode;
+    use axum_core::response::IntoResponse;
 
-        if role_name != "admin" {
-            return Err(AuthError::WrongCredentials);
+    fn user(
+        permissions: &[&str],
+        two_factor_enabled: bool,
+    ) -> AuthenticatedUser {
+        AuthenticatedUser {
+            id: 1,
+            username: "moderator".to_string(),
+            role_name: "moderator".to_string(),
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+            session_id: 1,
+            email_verified: true,
+            two_factor_enabled,
         }
+    }
+
+    #[test]
+    fn test_banned_and_suspended_accounts_are_rejected() {
+        assert!(ensure_account_not_suspended(true, None, None).is_ok());
+
+        let err = ensure_account_not_suspended(false, None, Some("spam"))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AuthError::AccountBanned { reason: Some(ref r) } if r == "spam"
+        ));
+
+        let until = Utc::now() + chrono::Duration::days(1);
+        let err =
+            ensure_account_not_suspended(true, Some(until), None).unwrap_err();
+        assert!(matches!(
+            err,
+            AuthError::AccountSuspended { until: u, reason: None } if u == until
+        ));
+        assert_eq!(err.into_response().status(), StatusCode::FORBIDDEN);
+
+        // A ban wins over a suspension, an ended suspension no longer counts
+        let err =
+            ensure_account_not_suspended(false, Some(until), None).unwrap_err();
+        assert!(matches!(err, AuthError::AccountBanned { .. }));
+        let ended = Utc::now() - chrono::Duration::minutes(1);
+        assert!(ensure_account_not_suspended(true, Some(ended), None).is_ok());
+    }
+
+    #[test]
+    fn test_missing_permission_is_forbidden() {
+        let moderator = user(&[SeriesEdit::NAME], false);
+
+        assert!(moderator.ensure_permission(SeriesEdit::NAME, false).is_ok());
+
+        let err = moderator
+            .ensure_permission(UsersManage::NAME, false)
+            .unwrap_err();
+        assert!(matches!(err, AuthError::Forbidden));
+        assert_eq!(err.into_response().status(), StatusCode::FORBIDDEN);
+
+        // Without the permission the two-factor requirement does not matter
+        let err = user(&[], true)
+            .ensure_permission(SeriesEdit::NAME, true)
+            .unwrap_err();
+        assert!(matches!(err, AuthError::Forbidden));
+    }
+
+    #[test]
+    fn test_permission_requires_two_factor_when_enforced() {
+        let err = user(&[SeriesEdit::NAME], false)
+            .ensure_permission(SeriesEdit::NAME, true)
+            .unwrap_err();
+        assert!(matches!(err, AuthError::TwoFactorRequired));
 
-        Ok(AdminUser(user))
+        assert!(
+            user(&[SeriesEdit::NAME], true)
+                .ensure_permission(SeriesEdit::NAME, true)
+                .is_ok()
+        );
     }
 }
@@ -0,0 +1,62 @@
+use crate::builder::startup::AppState;
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum_core::__private::tracing::error;
+use axum_core::response::{IntoResponse, Response};
+use chrono::Utc;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct SignedImageParams {
+    expires: i64,
+    signature: String,
+}
+
+// Serves a stored image behind an HMAC-signed, expiring URL
+pub async fn serve_signed_image_handler(
+    State(state): State<AppState>,
+    Path(object_key): Path<String>,
+    Query(params): Query<SignedImageParams>,
+) -> Response {
+    if !state.image_url_signer.verify(
+        &object_key,
+        params.expires,
+        &params.signature,
+    ) {
+        return (StatusCode::FORBIDDEN, "Invalid or expired image URL")
+            .into_response();
+    }
+
+    let chunks = match state.storage_client.get_object_stream(&object_key).await
+    {
+        Ok(chunks) => chunks,
+        Err(e) => {
+            error!("Failed to get signed image {}: {:#}", object_key, e);
+            return (StatusCode::NOT_FOUND, "Image not found").into_response();
+        }
+    };
+
+    let content_type = match object_key.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("avif") => "image/avif",
+        Some("webp") => "image/webp",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        _ => "image/jpeg",
+    };
+
+    // Caches must not keep the image past the expiry of its URL
+    let max_age = (params.expires - Utc::now().timestamp()).max(0);
+
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CACHE_CONTROL,
+                format!("private, max-age={}", max_age),
+            ),
+        ],
+        Body::from_stream(chunks),
+    )
+        .into_response()
+}
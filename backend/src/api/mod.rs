@@ -1,7 +1,12 @@
+mod admin_chapter_handlers;
 mod admin_handlers;
 mod admin_routes;
+mod admin_user_handlers;
 pub mod auth_handlers;
 pub mod extractor;
+mod image_handlers;
+mod oidc_handlers;
 pub mod routes;
 pub mod series_handlers;
+mod two_factor_handlers;
 pub mod user_handlers;
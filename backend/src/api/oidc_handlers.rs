@@ -0,0 +1,630 @@
+use crate::api::auth_handlers::{
+    complete_login, create_two_factor_challenge, send_email_verification,
+    two_factor_challenge_cookie,
+};
+use crate::api::extractor::{AuthenticatedUser, ClientInfo};
+use crate::builder::startup::AppState;
+use crate::common::error::AuthError;
+use crate::common::hashing::hash_secure_token;
+use crate::common::oidc::{
+    OidcProvider, ProviderIdentity, generate_oidc_token,
+};
+use crate::database::identities::OidcLoginState;
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Redirect;
+use axum_core::__private::tracing::error;
+use axum_core::response::{IntoResponse, Response};
+use axum_extra::extract::CookieJar;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use chrono::{Duration, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// The user has this long to log in at the provider
+const OIDC_LOGIN_STATE_TTL_MINUTES: i64 = 10;
+// Binds the callback to the browser that started the login
+const OIDC_STATE_COOKIE: &str = "oidc-state";
+const OIDC_STATE_COOKIE_PATH: &str = "/api/auth/oidc";
+
+/// Why a social login failed, passed to the frontend as `?error=<code>`.
+enum OidcError {
+    Denied,
+    InvalidState,
+    Provider(anyhow::Error),
+    EmailMissing,
+    EmailInUse,
+    AlreadyLinked,
+    PasswordResetRequired,
+    Internal,
+}
+
+impl OidcError {
+    fn code(&self) -> &'static str {
+        match self {
+            OidcError::Denied => "access_denied",
+            OidcError::InvalidState => "invalid_state",
+            OidcError::Provider(_) => "provider_error",
+            OidcError::EmailMissing => "email_missing",
+            OidcError::EmailInUse => "email_in_use",
+            OidcError::AlreadyLinked => "already_linked",
+            OidcError::PasswordResetRequired => "password_reset_required",
+            OidcError::Internal => "server_error",
+        }
+    }
+}
+
+impl From<AuthError> for OidcError {
+    fn from(e: AuthError) -> Self {
+        match e {
+            AuthError::PasswordResetRequired => {
+                OidcError::PasswordResetRequired
+            }
+            _ => OidcError::Internal,
+        }
+    }
+}
+
+impl From<anyhow::Error> for OidcError {
+    fn from(e: anyhow::Error) -> Self {
+        error!("[OIDC] Internal error: {:#}", e);
+        OidcError::Internal
+    }
+}
+
+fn unknown_provider_response() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({"status": "error", "message": "Unknown login provider"})),
+    )
+        .into_response()
+}
+
+fn state_cookie(value: String) -> Cookie<'static> {
+    Cookie::build((OIDC_STATE_COOKIE, value))
+        .path(OIDC_STATE_COOKIE_PATH)
+        .http_only(true)
+        .secure(false) // Only send via HTTPS (disable for local development)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::minutes(OIDC_LOGIN_STATE_TTL_MINUTES))
+        .build()
+}
+
+// Stores the request server-side and returns the provider's authorization URL
+async fn start_authorization(
+    state: &AppState,
+    provider: &OidcProvider,
+    link_user_id: Option<i32>,
+    jar: CookieJar,
+) -> Result<(CookieJar, String), OidcError> {
+    let state_token = generate_oidc_token();
+    let nonce = generate_oidc_token();
+    let code_verifier = generate_oidc_token();
+
+    state
+        .db_service
+        .create_oidc_login_state(
+            &hash_secure_token(&state_token),
+            &provider.name,
+            &code_verifier,
+            &nonce,
+            link_user_id,
+            Utc::now() + Duration::minutes(OIDC_LOGIN_STATE_TTL_MINUTES),
+        )
+        .await?;
+
+    let authorization_url = provider
+        .authorization_url(&state_token, &nonce, &code_verifier)
+        .await
+        .map_err(OidcError::Provider)?;
+
+    Ok((jar.add(state_cookie(state_token)), authorization_url))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcProviderResponse {
+    name: String,
+    display_name: String,
+}
+
+// Providers for the login buttons
+pub async fn get_oidc_providers_handler(
+    State(state): State<AppState>,
+) -> Json<Vec<OidcProviderResponse>> {
+    let providers = state
+        .oidc_providers
+        .iter()
+        .map(|provider| OidcProviderResponse {
+            name: provider.name.clone(),
+            display_name: provider.display_name.clone(),
+        })
+        .collect();
+
+    Json(providers)
+}
+
+// Browser navigation to the provider's login page
+pub async fn oidc_authorize_handler(
+    jar: CookieJar,
+    State(state): State<AppState>,
+    Path(provider_name): Path<String>,
+) -> Response {
+    let Some(provider) = state.oidc_providers.get(&provider_name) else {
+        return unknown_provider_response();
+    };
+
+    match start_authorization(&state, &provider, None, jar.clone()).await {
+        Ok((jar, authorization_url)) => {
+            (jar, Redirect::to(&authorization_url)).into_response()
+        }
+        Err(e) => error_redirect(&state, "/login", e, jar),
+    }
+}
+
+// Starts linking a provider to the logged-in user, the frontend navigates to the returned URL
+pub async fn oidc_link_handler(
+    jar: CookieJar,
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Path(provider_name): Path<String>,
+) -> Response {
+    let Some(provider) = state.oidc_providers.get(&provider_name) else {
+        return unknown_provider_response();
+    };
+
+    match start_authorization(&state, &provider, Some(user.id), jar).await {
+        Ok((jar, authorization_url)) => (
+            jar,
+            Json(serde_json::json!({"authorizationUrl": authorization_url})),
+        )
+            .into_response(),
+        Err(e) => {
+            if let OidcError::Provider(e) = &e {
+                error!(
+                    "[OIDC] Provider {} unavailable: {:#}",
+                    provider_name, e
+                );
+            }
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({"status": "error", "message": "Login provider unavailable"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OidcCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+// The provider sends the browser back here with the authorization code
+pub async fn oidc_callback_handler(
+    jar: CookieJar,
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Path(provider_name): Path<String>,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Response {
+    let Some(provider) = state.oidc_providers.get(&provider_name) else {
+        return unknown_provider_response();
+    };
+
+    let cookie_state = jar
+        .get(OIDC_STATE_COOKIE)
+        .map(|cookie| cookie.value().to_string());
+    let jar = jar
+        .remove(Cookie::build(OIDC_STATE_COOKIE).path(OIDC_STATE_COOKIE_PATH));
+
+    let login_state =
+        match take_login_state(&state, &provider, cookie_state, &query).await {
+            Ok(login_state) => login_state,
+            Err(e) => return error_redirect(&state, "/login", e, jar),
+        };
+    let error_path = callback_error_path(login_state.link_user_id);
+
+    let result = async {
+        let identity = provider
+            .exchange_code(
+                query.code.as_deref().unwrap_or_default(),
+                &login_state.code_verifier,
+                &login_state.nonce,
+            )
+            .await
+            .map_err(OidcError::Provider)?;
+
+        match login_state.link_user_id {
+            Some(user_id) => {
+                link_identity(&state, user_id, &provider.name, &identity).await
+            }
+            None => {
+                login_with_identity(
+                    &state,
+                    &provider.name,
+                    &identity,
+                    jar.clone(),
+                    &client,
+                )
+                .await
+            }
+        }
+    }
+    .await;
+
+    match result {
+        Ok(response) => response,
+        Err(e) => error_redirect(&state, error_path, e, jar),
+    }
+}
+
+// Failed links go back to the profile page they were started from
+fn callback_error_path(link_user_id: Option<i32>) -> &'static str {
+    match link_user_id {
+        Some(_) => "/profile",
+        None => "/login",
+    }
+}
+
+// The returned state of a callback, it must match the cookie of this browser
+fn check_callback_state<'a>(
+    cookie_state: Option<&str>,
+    query: &'a OidcCallbackQuery,
+) -> Result<&'a str, OidcError> {
+    if query.error.is_some() {
+        return Err(OidcError::Denied);
+    }
+
+    match (&query.state, cookie_state, &query.code) {
+        (Some(query_state), Some(cookie_state), Some(_))
+            if query_state == cookie_state =>
+        {
+            Ok(query_state)
+        }
+        _ => Err(OidcError::InvalidState),
+    }
+}
+
+// The state must match the cookie of this browser and a stored, unexpired request
+async fn take_login_state(
+    state: &AppState,
+    provider: &OidcProvider,
+    cookie_state: Option<String>,
+    query: &OidcCallbackQuery,
+) -> Result<OidcLoginState, OidcError> {
+    let query_state = check_callback_state(cookie_state.as_deref(), query)?;
+
+    state
+        .db_service
+        .take_oidc_login_state(&hash_secure_token(query_state), &provider.name)
+        .await?
+        .ok_or(OidcError::InvalidState)
+}
+
+fn error_redirect(
+    state: &AppState,
+    path: &str,
+    e: OidcError,
+    jar: CookieJar,
+) -> Response {
+    if let OidcError::Provider(e) = &e {
+        error!("[OIDC] Provider error: {:#}", e);
+    }
+
+    let url = state.oidc_providers.frontend_url(&format!(
+        "{}?error={}",
+        path,
+        e.code()
+    ));
+    (jar, Redirect::to(&url)).into_response()
+}
+
+// Logs in the linked user, a new provider account gets a new user
+async fn login_with_identity(
+    state: &AppState,
+    provider_name: &str,
+    identity: &ProviderIdentity,
+    jar: CookieJar,
+    client: &ClientInfo,
+) -> Result<Response, OidcError> {
+    let db_service = &state.db_service;
+
+    let user_id = match db_service
+        .get_user_id_by_identity(provider_name, &identity.subject)
+        .await?
+    {
+        Some(user_id) => user_id,
+        None => {
+            create_user_for_identity(state, provider_name, identity).await?
+        }
+    };
+
+    let user = db_service
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or(OidcError::Internal)?;
+
+    // The provider replaces the password, not the second factor.
+    // The challenge goes into a cookie, URLs end up in logs and the browser history.
+    if db_service.is_two_factor_enabled(user.id).await? {
+        let challenge_token =
+            create_two_factor_challenge(db_service, user.id).await?;
+        let url = state
+            .oidc_providers
+            .frontend_url("/login?twoFactorRequired=true");
+        let jar = jar.add(two_factor_challenge_cookie(challenge_token));
+        return Ok((jar, Redirect::to(&url)).into_response());
+    }
+
+    let (jar, _) = complete_login(jar, db_service, user, client).await?;
+
+    Ok((jar, Redirect::to(&state.oidc_providers.frontend_url("/")))
+        .into_response())
+}
+
+async fn create_user_for_identity(
+    state: &AppState,
+    provider_name: &str,
+    identity: &ProviderIdentity,
+) -> Result<i32, OidcError> {
+    let db_service = &state.db_service;
+    let email = identity.email.as_deref().ok_or(OidcError::EmailMissing)?;
+
+    // Existing accounts are only linked by their owner from the profile page,
+    // a provider is not trusted to prove ownership of a local account
+    if db_service.get_user_by_identifier(email).await?.is_some() {
+        return Err(OidcError::EmailInUse);
+    }
+
+    let role_id = db_service
+        .get_role_id_by_name("user")
+        .await?
+        .ok_or(OidcError::Internal)?;
+
+    for username in username_candidates(identity) {
+        if db_service
+            .get_user_by_identifier(&username)
+            .await?
+            .is_some()
+        {
+            continue;
+        }
+
+        if let Some(user_id) = db_service
+            .create_user_with_identity(
+                &username,
+                email,
+                identity.email_verified,
+                role_id,
+                provider_name,
+                &identity.subject,
+            )
+            .await?
+        {
+            if !identity.email_verified {
+                send_email_verification(state, user_id, email, &username)
+                    .await?;
+            }
+            return Ok(user_id);
+        }
+    }
+
+    Err(OidcError::Internal)
+}
+
+// The provider's username (or email name), then variants with a random suffix
+fn username_candidates(identity: &ProviderIdentity) -> Vec<String> {
+    let source = identity
+        .preferred_username
+        .as_deref()
+        .or_else(|| identity.email.as_deref()?.split('@').next())
+        .unwrap_or_default();
+
+    let mut base: String = source
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .take(24)
+        .collect();
+    if base.len() < 4 {
+        base = format!("user_{}", base);
+    }
+
+    let mut rng = rand::rng();
+    std::iter::once(base.clone())
+        .chain(
+            (0..4)
+                .map(|_| format!("{}_{}", base, rng.random_range(1000..10000))),
+        )
+        .collect()
+}
+
+async fn link_identity(
+    state: &AppState,
+    user_id: i32,
+    provider_name: &str,
+    identity: &ProviderIdentity,
+) -> Result<Response, OidcError> {
+    let is_linked = state
+        .db_service
+        .link_user_identity(
+            user_id,
+            provider_name,
+            &identity.subject,
+            identity.email.as_deref(),
+        )
+        .await?;
+    if !is_linked {
+        return Err(OidcError::AlreadyLinked);
+    }
+
+    let url = state
+        .oidc_providers
+        .frontend_url(&format!("/profile?linked={}", provider_name));
+    Ok(Redirect::to(&url).into_response())
+}
+
+// Providers linked to the current user
+pub async fn get_user_identities_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Response {
+    match state.db_service.get_user_identities(user.id).await {
+        Ok(identities) => (StatusCode::OK, Json(identities)).into_response(),
+        Err(e) => {
+            error!("DB error listing identities: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not list linked accounts"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn unlink_user_identity_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Path(provider_name): Path<String>,
+) -> Response {
+    match state
+        .db_service
+        .unlink_user_identity(user.id, &provider_name)
+        .await
+    {
+        Ok(Some(true)) => (
+            StatusCode::OK,
+            Json(serde_json::json!({"status": "success", "message": "Account unlinked"})),
+        )
+            .into_response(),
+        Ok(Some(false)) => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({"status": "error", "message": "Set a password before unlinking your last login provider"})),
+        )
+            .into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"status": "error", "message": "Account not linked"})),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("DB error unlinking identity: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not unlink account"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn callback_query(
+        code: Option<&str>,
+        state: Option<&str>,
+        error: Option<&str>,
+    ) -> OidcCallbackQuery {
+        OidcCallbackQuery {
+            code: code.map(String::from),
+            state: state.map(String::from),
+            error: error.map(String::from),
+        }
+    }
+
+    fn identity(
+        preferred_username: Option<&str>,
+        email: Option<&str>,
+    ) -> ProviderIdentity {
+        ProviderIdentity {
+            subject: "subject-1".to_string(),
+            email: email.map(String::from),
+            email_verified: true,
+            preferred_username: preferred_username.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_callback_state_must_match_the_browser() {
+        let query = callback_query(Some("code"), Some("state-1"), None);
+        assert_eq!(
+            check_callback_state(Some("state-1"), &query).ok(),
+            Some("state-1")
+        );
+
+        // Started in another browser, or the cookie expired
+        let code =
+            |result: Result<&str, OidcError>| result.err().map(|e| e.code());
+        assert_eq!(
+            code(check_callback_state(Some("state-2"), &query)),
+            Some("invalid_state")
+        );
+        assert_eq!(
+            code(check_callback_state(None, &query)),
+            Some("invalid_state")
+        );
+
+        let no_code = callback_query(None, Some("state-1"), None);
+        assert_eq!(
+            code(check_callback_state(Some("state-1"), &no_code)),
+            Some("invalid_state")
+        );
+
+        // Cancelled at the provider
+        let denied =
+            callback_query(None, Some("state-1"), Some("access_denied"));
+        assert_eq!(
+            code(check_callback_state(Some("state-1"), &denied)),
+            Some("access_denied")
+        );
+    }
+
+    #[test]
+    fn test_forced_password_reset_is_reported_to_the_frontend() {
+        let error = OidcError::from(AuthError::PasswordResetRequired);
+        assert_eq!(error.code(), "password_reset_required");
+        assert_eq!(
+            OidcError::from(AuthError::InternalServerError).code(),
+            "server_error"
+        );
+    }
+
+    #[test]
+    fn test_link_errors_return_to_the_profile() {
+        assert_eq!(callback_error_path(Some(42)), "/profile");
+        assert_eq!(callback_error_path(None), "/login");
+    }
+
+    #[test]
+    fn test_username_candidates_for_new_users() {
+        let candidates =
+            username_candidates(&identity(Some("Jane Doe!"), None));
+        assert_eq!(candidates[0], "JaneDoe");
+        assert_eq!(candidates.len(), 5);
+        assert!(
+            candidates[1..]
+                .iter()
+                .all(|candidate| candidate.starts_with("JaneDoe_"))
+        );
+
+        // The email name is used without a username, short names are padded
+        let from_email =
+            username_candidates(&identity(None, Some("reader@example.com")));
+        assert_eq!(from_email[0], "reader");
+        let short = username_candidates(&identity(Some("ab"), None));
+        assert_eq!(short[0], "user_ab");
+    }
+
+    #[test]
+    fn test_two_factor_challenge_cookie() {
+        let cookie = two_factor_challenge_cookie("challenge".to_string());
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.path(), Some("/api/auth/login/2fa"));
+        assert!(cookie.max_age().is_some());
+    }
+}
@@ -1,41 +1,112 @@
 use crate::api::admin_routes::admin_routes;
 use crate::api::auth_handlers::{
-    forgot_password_handler, login_handler, logout_handler, protected_handler,
+    forgot_password_handler, jwks_handler, login_handler,
+    login_two_factor_handler, logout_handler, protected_handler,
     realtime_check_username_handler, refresh_token_handler,
-    register_new_user_handler, reset_password_handler,
+    register_new_user_handler, resend_verification_email_handler,
+    reset_password_handler, verify_email_handler,
+};
+use crate::api::image_handlers::serve_signed_image_handler;
+use crate::api::oidc_handlers::{
+    get_oidc_providers_handler, get_user_identities_handler,
+    oidc_authorize_handler, oidc_callback_handler, oidc_link_handler,
+    unlink_user_identity_handler,
 };
 use crate::api::series_handlers::{
-    browse_series_handler, fetch_chapter_details_handler,
-    fetch_most_viewed_series_handler, fetch_new_series_handler,
-    fetch_series_details_by_id_handler, fetch_updated_series_chapter_handler,
-    get_all_categories_handler, get_chapter_comment_handler,
-    get_series_comment_handler, post_chapter_comment_handler,
-    post_series_comment_handler, rate_series_handler,
-    record_series_view_handler, update_existing_comment_handler,
-    upload_comment_attachments_handler, vote_on_comment_handler,
+    browse_series_handler, export_series_chapters_handler,
+    fetch_chapter_details_handler, fetch_most_viewed_series_handler,
+    fetch_new_series_handler, fetch_series_details_by_id_handler,
+    fetch_updated_series_chapter_handler, get_all_categories_handler,
+    get_chapter_comment_handler, get_series_comment_handler,
+    post_chapter_comment_handler, post_series_comment_handler,
+    rate_series_handler, record_series_view_handler,
+    update_existing_comment_handler, upload_comment_attachments_handler,
+    vote_on_comment_handler,
+};
+use crate::api::two_factor_handlers::{
+    disable_two_factor_handler, enable_two_factor_handler,
+    get_two_factor_status_handler, regenerate_recovery_codes_handler,
+    setup_two_factor_handler,
 };
 use crate::api::user_handlers::{
     add_bookmark_series_handler, delete_bookmark_series_handler,
     get_bookmark_status_current_user_handler,
     get_user_bookmark_library_handler, get_user_profile_handler,
-    update_user_avatar_handler, update_user_password_setting_handler,
-    update_user_profile_handler,
+    get_user_sessions_handler, revoke_all_user_sessions_handler,
+    revoke_user_session_handler, update_user_avatar_handler,
+    update_user_password_setting_handler, update_user_profile_handler,
 };
 use crate::builder::startup::AppState;
+use crate::common::rate_limit::{
+    RateLimitPolicy, RateLimiter, enforce_rate_limit,
+};
+use crate::common::upload::{
+    AVATAR_UPLOAD_MAX_BYTES, COMMENT_ATTACHMENT_MAX_BYTES,
+    MAX_COMMENT_ATTACHMENTS, multipart_body_limit,
+};
 use axum::Router;
-use axum::routing::{get, patch, post};
+use axum::extract::DefaultBodyLimit;
+use axum::middleware;
+use axum::routing::{delete, get, patch, post};
+
+const AVATAR_UPLOAD_BODY_LIMIT: usize =
+    multipart_body_limit(AVATAR_UPLOAD_MAX_BYTES, 1);
+const COMMENT_BODY_LIMIT: usize =
+    multipart_body_limit(COMMENT_ATTACHMENT_MAX_BYTES, MAX_COMMENT_ATTACHMENTS);
+const COMMENT_ATTACHMENT_BODY_LIMIT: usize =
+    multipart_body_limit(COMMENT_ATTACHMENT_MAX_BYTES, 1);
+
+// Per client address, overridable with `RATE_LIMIT_AUTH` and `RATE_LIMIT_USERNAME_CHECK`
+const AUTH_RATE_LIMIT: RateLimitPolicy = RateLimitPolicy::new(10, 60);
+const USERNAME_CHECK_RATE_LIMIT: RateLimitPolicy = RateLimitPolicy::new(30, 60);
+
+/// Stored images, outside the global limit: a single chapter loads dozens of them.
+/// `limiter` is shared with the local storage route.
+pub fn image_routes(limiter: RateLimiter) -> Router<AppState> {
+    Router::new()
+        .route("/api/images/{*key}", get(serve_signed_image_handler))
+        .route_layer(middleware::from_fn_with_state(
+            limiter,
+            enforce_rate_limit,
+        ))
+}
 
 pub fn routes() -> Router<AppState> {
-    // Route for user auth api
-    let auth_api_routes = Router::new()
+    // Credential guessing and email sending, limited per client address
+    let auth_limited_routes = Router::new()
         .route("/login", post(login_handler))
+        .route("/login/2fa", post(login_two_factor_handler))
         .route("/register", post(register_new_user_handler))
+        .route("/forgot-password", post(forgot_password_handler))
+        .route("/reset-password", post(reset_password_handler))
+        .route("/verify-email", post(verify_email_handler))
+        .route(
+            "/verify-email/resend",
+            post(resend_verification_email_handler),
+        )
+        .route("/oidc/{provider}/authorize", get(oidc_authorize_handler))
+        .route("/oidc/{provider}/callback", get(oidc_callback_handler))
+        .route_layer(middleware::from_fn_with_state(
+            RateLimiter::from_env("AUTH", AUTH_RATE_LIMIT),
+            enforce_rate_limit,
+        ));
+
+    // Called while typing, but still a way to enumerate accounts
+    let username_check_routes = Router::new()
+        .route("/check-username", post(realtime_check_username_handler))
+        .route_layer(middleware::from_fn_with_state(
+            RateLimiter::from_env("USERNAME_CHECK", USERNAME_CHECK_RATE_LIMIT),
+            enforce_rate_limit,
+        ));
+
+    // Route for user auth api
+    let auth_api_routes = Router::new()
         .route("/refresh", post(refresh_token_handler))
         .route("/logout", post(logout_handler))
         .route("/user", post(protected_handler))
-        .route("/check-username", post(realtime_check_username_handler))
-        .route("/forgot-password", post(forgot_password_handler))
-        .route("/reset-password", post(reset_password_handler));
+        .route("/oidc/providers", get(get_oidc_providers_handler))
+        .merge(auth_limited_routes)
+        .merge(username_check_routes);
 
     // Router user related api
     let user_api = Router::new()
@@ -48,7 +119,31 @@ pub fn routes() -> Router<AppState> {
             "/user/profile/password",
             patch(update_user_password_setting_handler),
         )
-        .route("/user/profile/avatar", post(update_user_avatar_handler));
+        .route(
+            "/user/profile/avatar",
+            post(update_user_avatar_handler)
+                .layer(DefaultBodyLimit::max(AVATAR_UPLOAD_BODY_LIMIT)),
+        )
+        .route("/user/sessions", get(get_user_sessions_handler))
+        .route("/user/sessions/{id}", delete(revoke_user_session_handler))
+        .route(
+            "/user/sessions/revoke-all",
+            post(revoke_all_user_sessions_handler),
+        )
+        .route("/user/2fa", get(get_two_factor_status_handler))
+        .route("/user/2fa/setup", post(setup_two_factor_handler))
+        .route("/user/2fa/enable", post(enable_two_factor_handler))
+        .route("/user/2fa/disable", post(disable_two_factor_handler))
+        .route(
+            "/user/2fa/recovery-codes",
+            post(regenerate_recovery_codes_handler),
+        )
+        .route("/user/identities", get(get_user_identities_handler))
+        .route(
+            "/user/identities/{provider}",
+            delete(unlink_user_identity_handler),
+        )
+        .route("/user/identities/{provider}/link", post(oidc_link_handler));
 
     // Route for public api
     let public_series_api_routes = Router::new()
@@ -68,6 +163,7 @@ pub fn routes() -> Router<AppState> {
             "/series/{id}/chapter/{chapter_number}",
             get(fetch_chapter_details_handler),
         )
+        .route("/series/{id}/export", get(export_series_chapters_handler))
         .route("/series/{id}/rate", post(rate_series_handler))
         .route("/series/{id}/views-count", post(record_series_view_handler))
         .route(
@@ -84,11 +180,15 @@ pub fn routes() -> Router<AppState> {
     let comments_api = Router::new()
         .route(
             "/series/{id}/comments",
-            get(get_series_comment_handler).post(post_series_comment_handler),
+            get(get_series_comment_handler)
+                .post(post_series_comment_handler)
+                .layer(DefaultBodyLimit::max(COMMENT_BODY_LIMIT)),
         )
         .route(
             "/series/chapter/{id}/comments",
-            get(get_chapter_comment_handler).post(post_chapter_comment_handler),
+            get(get_chapter_comment_handler)
+                .post(post_chapter_comment_handler)
+                .layer(DefaultBodyLimit::max(COMMENT_BODY_LIMIT)),
         )
         .route(
             "/comments/{id}/edit",
@@ -97,7 +197,8 @@ pub fn routes() -> Router<AppState> {
         .route("/comments/{id}/vote", post(vote_on_comment_handler))
         .route(
             "/comments/attachments/upload",
-            post(upload_comment_attachments_handler),
+            post(upload_comment_attachments_handler)
+                .layer(DefaultBodyLimit::max(COMMENT_ATTACHMENT_BODY_LIMIT)),
         );
 
     // Merge same prefix "/api" routes into one
@@ -106,6 +207,7 @@ pub fn routes() -> Router<AppState> {
 
     // Combine routers under prefix "/api"
     Router::new()
+        .route("/.well-known/jwks.json", get(jwks_handler))
         .nest("/api/admin", admin_routes())
         .nest("/api/auth", auth_api_routes)
         .nest("/api", general_api)
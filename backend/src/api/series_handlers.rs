@@ -1,19 +1,141 @@
-use crate::api::extractor::{AuthenticatedUser, OptionalAuthenticatedUser};
+use crate::api::extractor::{
+    AuthenticatedUser, OptionalAuthenticatedUser, VerifiedUser,
+};
+use crate::app::chapter_export::{self, ExportBook, ExportFormat};
 use crate::builder::startup::AppState;
+use crate::common::error::UploadError;
+use crate::common::upload;
 use crate::database::{
-    CategoryTag, Comment, CommentEntityType, Series, SeriesChapter,
-    SeriesOrderBy, VotePayload,
+    CategoryTag, Comment, CommentEntityType, ImageVariantSet, PaginatedResult,
+    Series, SeriesChapter, SeriesOrderBy, VotePayload,
 };
+use crate::encoding::image_encoding;
 use axum::Json;
+use axum::body::Body;
 use axum::extract::{Path, Query, State};
+use axum::http::header;
 use axum_core::__private::tracing::error;
 use axum_core::response::{IntoResponse, Response};
 use axum_extra::extract::Multipart;
+use image::ImageFormat;
 use reqwest::StatusCode;
 use serde::de::{Deserializer, Error};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
+/// Srcset-ready URLs of a full-size image and its responsive width variants.
+/// `srcset` and `placeholderUrl` are empty for images stored before variants existed.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponsiveImage {
+    url: String,
+    srcset: Option<String>,
+    width: Option<i32>,
+    height: Option<i32>,
+    placeholder_url: Option<String>,
+}
+
+impl ResponsiveImage {
+    fn new(
+        url: String,
+        variant_set: Option<&ImageVariantSet>,
+        object_url: impl Fn(&str) -> String,
+    ) -> Self {
+        let Some(set) = variant_set else {
+            return Self {
+                url,
+                srcset: None,
+                width: None,
+                height: None,
+                placeholder_url: None,
+            };
+        };
+
+        // Smallest to largest, the full-size image is the last candidate
+        let srcset = set
+            .variants
+            .iter()
+            .map(|v| format!("{} {}w", object_url(&v.object_key), v.width))
+            .chain(std::iter::once(format!("{} {}w", url, set.width)))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Self {
+            srcset: Some(srcset),
+            width: Some(set.width),
+            height: Some(set.height),
+            placeholder_url: set
+                .placeholder_key
+                .as_ref()
+                .map(|key| object_url(key)),
+            url,
+        }
+    }
+}
+
+/// Series list item extended with its responsive cover image.
+#[derive(Serialize)]
+pub struct SeriesWithCover<T> {
+    #[serde(flatten)]
+    series: T,
+    cover_image: ResponsiveImage,
+}
+
+// Look up the cover variants of every listed series in a single query.
+// The stored cover value is replaced with its public URL.
+async fn attach_cover_variants<T>(
+    state: &AppState,
+    mut items: Vec<T>,
+    cover_image_url: fn(&mut T) -> &mut String,
+) -> Vec<SeriesWithCover<T>> {
+    let storage = &state.storage_client;
+    let cover_keys: Vec<String> = items
+        .iter_mut()
+        .filter_map(|item| storage.object_key(cover_image_url(item)))
+        .collect();
+
+    let variant_sets = state
+        .db_service
+        .get_image_variant_sets(&cover_keys)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Failed to get cover variants: {}", e);
+            HashMap::new()
+        });
+
+    items
+        .into_iter()
+        .map(|mut series| {
+            let cover = cover_image_url(&mut series);
+            let variant_set = storage
+                .object_key(cover)
+                .and_then(|key| variant_sets.get(&key));
+            *cover = storage.public_url(cover);
+            let url = cover.clone();
+
+            SeriesWithCover {
+                cover_image: ResponsiveImage::new(url, variant_set, |key| {
+                    storage.public_url(key)
+                }),
+                series,
+            }
+        })
+        .collect()
+}
+
+async fn attach_cover_variants_paginated<T>(
+    state: &AppState,
+    result: PaginatedResult<T>,
+    cover_image_url: fn(&mut T) -> &mut String,
+) -> PaginatedResult<SeriesWithCover<T>> {
+    PaginatedResult {
+        items: attach_cover_variants(state, result.items, cover_image_url)
+            .await,
+        total_items: result.total_items,
+    }
+}
+
 #[derive(Deserialize)]
 pub struct MostViewedParams {
     #[serde(default = "default_period")]
@@ -48,7 +170,13 @@ pub async fn fetch_most_viewed_series_handler(
         .fetch_most_viewed_series(period_str, params.limit)
         .await
     {
-        Ok(series) => (StatusCode::OK, Json(series)).into_response(),
+        Ok(series) => {
+            let series = attach_cover_variants(&state, series, |s| {
+                &mut s.cover_image_url
+            })
+            .await;
+            (StatusCode::OK, Json(series)).into_response()
+        }
         Err(e) => {
             eprintln!("Error fetching most viewed series: {}", e);
             (
@@ -75,9 +203,10 @@ pub async fn fetch_series_details_by_id_handler(
 ) -> Response {
     let db = &state.db_service;
 
-    let series = match db.get_series_by_id(id).await {
-        Ok(Some(s)) => s,
-        Ok(None) => {
+    let mut series = match db.get_series_by_id(id).await {
+        // Archived series are hidden like missing ones
+        Ok(Some(s)) if !s.processing_status.is_hidden() => s,
+        Ok(_) => {
             return (
                 StatusCode::NOT_FOUND,
                 Json(serde_json::json!({"status": "error", "message": "Series not found."})),
@@ -95,6 +224,8 @@ pub async fn fetch_series_details_by_id_handler(
     };
 
     let series_id = series.id;
+    series.cover_image_url =
+        state.storage_client.public_url(&series.cover_image_url);
 
     // Fetch authors, chapters, and categories tag in parallel
     let (authors_result, chapters_result, categories_result) = tokio::join!(
@@ -121,7 +252,13 @@ pub async fn fetch_new_series_handler(
         .get_public_series_paginated(1, 20, SeriesOrderBy::CreatedAt)
         .await
     {
-        Ok(series) => (StatusCode::OK, Json(series)).into_response(),
+        Ok(series) => {
+            let series = attach_cover_variants(&state, series, |s| {
+                &mut s.cover_image_url
+            })
+            .await;
+            (StatusCode::OK, Json(series)).into_response()
+        }
         Err(e) => {
             eprintln!("Error fetching new series: {}", e);
             (
@@ -139,6 +276,7 @@ pub struct PaginationParams {
     #[serde(default = "default_pagesize")]
     page_size: u32,
     #[serde(default)]
+    #[allow(dead_code)]
     search: Option<String>,
 }
 
@@ -162,6 +300,12 @@ pub async fn fetch_updated_series_chapter_handler(
         .await
     {
         Ok(paginated_result) => {
+            let paginated_result = attach_cover_variants_paginated(
+                &state,
+                paginated_result,
+                |s| &mut s.cover_image_url,
+            )
+            .await;
             (StatusCode::OK, Json(paginated_result)).into_response()
         }
         Err(e) => {
@@ -183,6 +327,8 @@ pub struct ChapterDetailsResponse {
     chapter_id: i32,
     chapter_number: f32,
     pages: Vec<String>,
+    // Same order as `pages`, with srcset-ready variant URLs
+    page_images: Vec<ResponsiveImage>,
     all_chapters: Vec<SeriesChapter>,
     prev_chapter_number: Option<f32>,
     next_chapter_number: Option<f32>,
@@ -198,7 +344,6 @@ pub async fn fetch_chapter_details_handler(
     );
 
     let db = &state.db_service;
-    let base_url = state.storage_client.domain_cdn_url();
 
     let (series_result, all_chapters_result, images_result) = tokio::join!(
         db.get_series_by_id(series_id),
@@ -208,7 +353,7 @@ pub async fn fetch_chapter_details_handler(
 
     // Get series title
     let series = match series_result {
-        Ok(Some(s)) => s,
+        Ok(Some(s)) if !s.processing_status.is_hidden() => s,
         _ => return (StatusCode::NOT_FOUND, Json(serde_json::json!({"status": "error", "message": "Series not found."})),
         ).into_response(),
     };
@@ -225,10 +370,62 @@ pub async fn fetch_chapter_details_handler(
         }
     };
 
-    let pages = object_keys
+    let variant_sets = db
+        .get_image_variant_sets(&object_keys)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Error fetching chapter image variants: {}", e);
+            HashMap::new()
+        });
+
+    // Page URLs may be signed and expiring, depending on the deployment
+    let url_keys: Vec<&String> = object_keys
+        .iter()
+        .chain(variant_sets.values().flat_map(|set| {
+            set.variants
+                .iter()
+                .map(|variant| &variant.object_key)
+                .chain(set.placeholder_key.as_ref())
+        }))
+        .collect();
+    let url_values = match futures::future::try_join_all(url_keys.iter().map(
+        |key| {
+            state
+                .image_url_signer
+                .object_url(&state.storage_client, key)
+        },
+    ))
+    .await
+    {
+        Ok(url_values) => url_values,
+        Err(e) => {
+            error!("Error building chapter image URLs: {:#}", e);
+            return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({"status": "error", "message": "Could not retrieve chapter images."})),
+                ).into_response();
+        }
+    };
+    let object_urls: HashMap<&str, String> = url_keys
         .into_iter()
-        .map(|key| format!("{}/{}", base_url, key))
+        .map(String::as_str)
+        .zip(url_values)
         .collect();
+    let object_url =
+        |key: &str| object_urls.get(key).cloned().unwrap_or_default();
+
+    let (pages, page_images) = object_keys
+        .iter()
+        .map(|key| {
+            let url = object_url(key);
+            let image = ResponsiveImage::new(
+                url.clone(),
+                variant_sets.get(key),
+                object_url,
+            );
+            (url, image)
+        })
+        .unzip();
 
     // Get all chapters for the series and find current, next and previous chapters
     let all_chapters = match all_chapters_result {
@@ -281,6 +478,7 @@ pub async fn fetch_chapter_details_handler(
         chapter_id: current_chapter.id,
         chapter_number,
         pages,
+        page_images,
         all_chapters,
         prev_chapter_number,
         next_chapter_number,
@@ -289,6 +487,142 @@ pub async fn fetch_chapter_details_handler(
     (StatusCode::OK, Json(response_data)).into_response()
 }
 
+#[derive(Deserialize)]
+pub struct ExportChaptersParams {
+    format: ExportFormat,
+    // Chapter range, `to` defaults to a single chapter export
+    from: f32,
+    to: Option<f32>,
+}
+
+// Download one chapter or a range of chapters as CBZ, EPUB or PDF for offline reading
+pub async fn export_series_chapters_handler(
+    user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Path(series_id): Path<i32>,
+    Query(params): Query<ExportChaptersParams>,
+) -> Response {
+    println!(
+        "->> {:<12} - export_series_chapters - user: {}, series_id: {}",
+        "HANDLER", user.username, series_id
+    );
+
+    let to_chapter = params.to.unwrap_or(params.from);
+    if to_chapter < params.from {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"status": "error", "message": "Invalid chapter range."})),
+        )
+            .into_response();
+    }
+
+    if params.format.needs_avif_decoding()
+        && !image_encoding::avif_decoding_enabled()
+    {
+        let message = format!(
+            "{} export is not available on this server.",
+            params.format.file_extension().to_uppercase()
+        );
+        return (
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({"status": "error", "message": message})),
+        )
+            .into_response();
+    }
+
+    let db = &state.db_service;
+
+    let (series_result, chapters_result) = tokio::join!(
+        db.get_series_by_id(series_id),
+        db.get_chapter_pages_in_range(series_id, params.from, to_chapter),
+    );
+
+    let series = match series_result {
+        Ok(Some(s)) if !s.processing_status.is_hidden() => s,
+        _ => return (StatusCode::NOT_FOUND, Json(serde_json::json!({"status": "error", "message": "Series not found."})),
+        ).into_response(),
+    };
+
+    let chapters = match chapters_result {
+        Ok(chapters) if chapters.is_empty() => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({"status": "error", "message": "No available chapters in this range."})),
+            )
+                .into_response();
+        }
+        Ok(chapters) => chapters,
+        Err(e) => {
+            error!("Error fetching chapters for export: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not retrieve chapters."})),
+            )
+                .into_response();
+        }
+    };
+
+    let max_chapters = state.export_limiter.max_chapters();
+    if chapters.len() > max_chapters {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"status": "error", "message": format!("At most {} chapters can be exported at once.", max_chapters)})),
+        )
+            .into_response();
+    }
+
+    let Some(permit) = state.export_limiter.try_acquire(user.id) else {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, "30")],
+            Json(serde_json::json!({"status": "error", "message": "Too many exports in progress, please try again later."})),
+        )
+            .into_response();
+    };
+
+    let (authors, categories) = tokio::join!(
+        db.get_authors_by_series_id(series_id),
+        db.get_category_tag_by_series_id(series_id),
+    );
+
+    let book = ExportBook {
+        series_title: series.title,
+        description: series.description,
+        authors: authors.unwrap_or_default(),
+        categories: categories
+            .unwrap_or_default()
+            .into_iter()
+            .map(|category| category.name)
+            .collect(),
+        chapters,
+    };
+    let file_name = book.file_name(params.format);
+
+    let stream = chapter_export::stream_export(
+        state.storage_client.clone(),
+        state.encoding_pool.clone(),
+        params.format,
+        book,
+        permit,
+    );
+
+    (
+        StatusCode::OK,
+        [
+            (
+                header::CONTENT_TYPE,
+                params.format.content_type().to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
 pub async fn record_series_view_handler(
     State(state): State<AppState>,
     Path(series_id): Path<i32>,
@@ -330,7 +664,7 @@ pub struct RateSeriesResponse {
 
 pub async fn rate_series_handler(
     State(state): State<AppState>,
-    user: AuthenticatedUser,
+    VerifiedUser(user): VerifiedUser,
     Path(series_id): Path<i32>,
     Json(payload): Json<RateSeriesPayload>,
 ) -> Response {
@@ -376,6 +710,25 @@ pub async fn rate_series_handler(
     }
 }
 
+// Attachments and avatars are stored as object keys, including in every reply
+fn resolve_comment_image_urls(state: &AppState, comments: &mut [Comment]) {
+    let storage = &state.storage_client;
+
+    let mut stack: Vec<&mut Comment> = comments.iter_mut().collect();
+    while let Some(comment) = stack.pop() {
+        if let Some(urls) = &mut comment.attachment_urls {
+            for url in urls.iter_mut() {
+                *url = storage.public_url(url);
+            }
+        }
+        if let Some(avatar_url) = &mut comment.user.avatar_url {
+            *avatar_url = storage.public_url(avatar_url);
+        }
+
+        stack.extend(comment.replies.iter_mut());
+    }
+}
+
 // Fetch series comments
 pub async fn get_series_comment_handler(
     State(state): State<AppState>,
@@ -389,21 +742,7 @@ pub async fn get_series_comment_handler(
         .await
     {
         Ok(mut comments) => {
-            let base_url = state.storage_client.domain_cdn_url();
-
-            let mut stack: Vec<&mut Comment> = comments.iter_mut().collect();
-            while let Some(comment) = stack.pop() {
-                if let Some(urls) = &mut comment.attachment_urls {
-                    // Iterate over each URL string mutably.
-                    for url in urls.iter_mut() {
-                        // Prepend the base_url to the existing URL string.
-                        *url = format!("{}/{}", base_url, url);
-                    }
-                }
-
-                stack.extend(comment.replies.iter_mut());
-            }
-
+            resolve_comment_image_urls(&state, &mut comments);
             (StatusCode::OK, Json(comments)).into_response()
         }
         Err(e) => {
@@ -432,17 +771,7 @@ pub async fn get_chapter_comment_handler(
         .await
     {
         Ok(mut comments) => {
-            let base_url = state.storage_client.domain_cdn_url();
-
-            let mut stack: Vec<&mut Comment> = comments.iter_mut().collect();
-            while let Some(comment) = stack.pop() {
-                if let Some(urls) = &mut comment.attachment_urls {
-                    for url in urls.iter_mut() {
-                        *url = format!("{}{}", base_url, url);
-                    }
-                }
-                stack.extend(comment.replies.iter_mut());
-            }
+            resolve_comment_image_urls(&state, &mut comments);
             (StatusCode::OK, Json(comments)).into_response()
         }
         Err(e) => {
@@ -468,7 +797,7 @@ async fn new_comment_submission_handler(
 ) -> Response {
     let mut content_markdown = None;
     let mut parent_id: Option<i64> = None;
-    let mut attachment_data: Vec<(Vec<u8>, String, String)> = Vec::new();
+    let mut attachment_data: Vec<(Vec<u8>, ImageFormat)> = Vec::new();
 
     while let Ok(Some(field)) = multipart.next_field().await {
         if let Some(field_name) = field.name() {
@@ -482,20 +811,21 @@ async fn new_comment_submission_handler(
                     }
                 }
                 "images" => {
-                    let file_name = field.file_name().unwrap_or("").to_string();
-                    let content_type = field
-                        .content_type()
-                        .unwrap_or("application/octet-stream")
-                        .to_string();
-                    if let Ok(data) = field.bytes().await {
-                        if data.len() > 5 * 1024 * 1024 {
-                            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"message": "File size exceeds 5MB"}))).into_response();
+                    if attachment_data.len() >= upload::MAX_COMMENT_ATTACHMENTS
+                    {
+                        return UploadError::TooManyFiles {
+                            max_files: upload::MAX_COMMENT_ATTACHMENTS,
                         }
-                        attachment_data.push((
-                            data.to_vec(),
-                            file_name,
-                            content_type,
-                        ));
+                        .into_response();
+                    }
+                    match upload::read_image_field(
+                        field,
+                        upload::COMMENT_ATTACHMENT_MAX_BYTES,
+                    )
+                    .await
+                    {
+                        Ok(attachment) => attachment_data.push(attachment),
+                        Err(e) => return e.into_response(),
                     }
                 }
                 _ => (),
@@ -511,22 +841,22 @@ async fn new_comment_submission_handler(
 
     // Upload file if any
     let mut attachment_keys: Vec<String> = Vec::new();
-    for (file_data, file_name, content_type) in attachment_data {
-        let file_extension = std::path::Path::new(&file_name)
-            .extension()
-            .and_then(std::ffi::OsStr::to_str)
-            .unwrap_or("");
-
+    for (file_data, format) in attachment_data {
         let unique_key = format!(
             "comments/{}/{}.{}",
             user.id,
             Uuid::new_v4(),
-            file_extension
+            upload::image_extension(format)
         );
 
         if let Err(e) = state
             .storage_client
-            .upload_image_file(file_data, &unique_key, &content_type)
+            .upload_image_file(
+                file_data,
+                &unique_key,
+                format.to_mime_type(),
+                Some(user.id),
+            )
             .await
         {
             error!("Failed to upload comment attachment: {}", e);
@@ -570,12 +900,10 @@ async fn new_comment_submission_handler(
     {
         // If fetch is successful, return the full comment object
         Ok(Some(mut new_comment)) => {
-            if let Some(urls) = &mut new_comment.attachment_urls {
-                let base_url = state.storage_client.domain_cdn_url();
-                for url in urls.iter_mut() {
-                    *url = format!("{}{}", base_url, url);
-                }
-            }
+            resolve_comment_image_urls(
+                &state,
+                std::slice::from_mut(&mut new_comment),
+            );
             (StatusCode::OK, Json(new_comment)).into_response()
         },
         // Handle cases where the comment couldn't be fetched right after creation
@@ -590,7 +918,7 @@ async fn new_comment_submission_handler(
 // Post new comment to a specific series page
 pub async fn post_series_comment_handler(
     State(state): State<AppState>,
-    user: AuthenticatedUser,
+    VerifiedUser(user): VerifiedUser,
     Path(series_id): Path<i32>,
     multipart: Multipart,
 ) -> Response {
@@ -612,7 +940,7 @@ pub async fn post_series_comment_handler(
 // Post a new comment to a specific chapter
 pub async fn post_chapter_comment_handler(
     State(state): State<AppState>,
-    user: AuthenticatedUser,
+    VerifiedUser(user): VerifiedUser,
     Path(chapter_id): Path<i32>,
     multipart: Multipart,
 ) -> Response {
@@ -633,51 +961,35 @@ pub async fn post_chapter_comment_handler(
 
 pub async fn upload_comment_attachments_handler(
     State(state): State<AppState>,
-    user: AuthenticatedUser,
+    VerifiedUser(user): VerifiedUser,
     mut multipart: Multipart,
 ) -> Response {
     if let Ok(Some(field)) = multipart.next_field().await {
-        let content_type = field
-            .content_type()
-            .unwrap_or("application/octet-stream")
-            .to_string();
-        let file_name = field.file_name().unwrap_or("").to_string();
-
-        let file_data = match field.bytes().await {
-            Ok(bytes) => bytes.to_vec(),
-            Err(e) => {
-                return  (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({"message": format!("Failed to read file: {}", e)})),
-                    )
-                    .into_response();
-            }
+        let (file_data, format) = match upload::read_image_field(
+            field,
+            upload::COMMENT_ATTACHMENT_MAX_BYTES,
+        )
+        .await
+        {
+            Ok(upload) => upload,
+            Err(e) => return e.into_response(),
         };
 
-        const MAX_FILE_SIZE: usize = 5 * 1024 * 1024;
-        if file_data.len() > MAX_FILE_SIZE {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({"message": "File size cannot exceed 5MB"})),
-                )
-                .into_response();
-        }
-
-        let file_extension = std::path::Path::new(&file_name)
-            .extension()
-            .and_then(std::ffi::OsStr::to_str)
-            .unwrap_or("");
-
         let unique_image_key = format!(
             "comments/{}/{}.{}",
             user.id,
             Uuid::new_v4(),
-            file_extension
+            upload::image_extension(format)
         );
 
         match state
             .storage_client
-            .upload_image_file(file_data, &unique_image_key, &content_type)
+            .upload_image_file(
+                file_data,
+                &unique_image_key,
+                format.to_mime_type(),
+                Some(user.id),
+            )
             .await
         {
             Ok(url) => (StatusCode::OK, Json(serde_json::json!({"url": url})))
@@ -709,7 +1021,7 @@ pub struct UpdateCommentPayload {
 
 pub async fn update_existing_comment_handler(
     State(state): State<AppState>,
-    user: AuthenticatedUser,
+    VerifiedUser(user): VerifiedUser,
     Path(comment_id): Path<i64>,
     Json(payload): Json<UpdateCommentPayload>,
 ) -> Response {
@@ -724,7 +1036,11 @@ pub async fn update_existing_comment_handler(
                 .get_comment_by_id(comment_id, Some(user.id))
                 .await
             {
-                Ok(Some(updated_comment)) => {
+                Ok(Some(mut updated_comment)) => {
+                    resolve_comment_image_urls(
+                        &state,
+                        std::slice::from_mut(&mut updated_comment),
+                    );
                     (
                         StatusCode::OK,
                         Json(updated_comment),
@@ -861,6 +1177,12 @@ pub async fn browse_series_handler(
         .await
     {
         Ok(paginated_result) => {
+            let paginated_result = attach_cover_variants_paginated(
+                &state,
+                paginated_result,
+                |s| &mut s.cover_image_url,
+            )
+            .await;
             (StatusCode::OK, Json(paginated_result)).into_response()
         }
         Err(e) => {
@@ -0,0 +1,230 @@
+use crate::api::auth_handlers::{
+    account_failure_key, ensure_login_not_locked,
+    record_session_password_failure, verify_session_second_factor,
+};
+use crate::api::extractor::{AuthenticatedUser, REQUIRE_ADMIN_TWO_FACTOR};
+use crate::builder::startup::AppState;
+use crate::common::error::AuthError;
+use crate::common::hashing::{hash_secure_token, verify_password};
+use crate::common::totp;
+use axum::Json;
+use axum::extract::State;
+use axum_core::__private::tracing::error;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+// Name shown next to the account in authenticator apps
+static TOTP_ISSUER: LazyLock<String> = LazyLock::new(|| {
+    std::env::var("TOTP_ISSUER").unwrap_or_else(|_| "web-rs".to_string())
+});
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorStatusResponse {
+    enabled: bool,
+    recovery_codes_left: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorSetupResponse {
+    secret: String,
+    otpauth_uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodesResponse {
+    message: String,
+    recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct TwoFactorMessageResponse {
+    message: String,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorCodePayload {
+    code: String,
+}
+
+#[derive(Deserialize)]
+pub struct DisableTwoFactorPayload {
+    password: String,
+    code: String,
+}
+
+// New recovery codes, only their hashes are stored
+fn new_recovery_codes() -> (Vec<String>, Vec<String>) {
+    let codes = totp::generate_recovery_codes();
+    let hashes = codes
+        .iter()
+        .map(|code| hash_secure_token(&totp::normalize_recovery_code(code)))
+        .collect();
+    (codes, hashes)
+}
+
+pub async fn get_two_factor_status_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<TwoFactorStatusResponse>, AuthError> {
+    let enabled = state.db_service.is_two_factor_enabled(user.id).await?;
+    let recovery_codes_left = if enabled {
+        state
+            .db_service
+            .count_unused_recovery_codes(user.id)
+            .await?
+    } else {
+        0
+    };
+
+    Ok(Json(TwoFactorStatusResponse {
+        enabled,
+        recovery_codes_left,
+    }))
+}
+
+// Creates a new secret, 2FA is enabled once a code from it is confirmed
+pub async fn setup_two_factor_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<TwoFactorSetupResponse>, AuthError> {
+    let secret = totp::generate_secret();
+
+    let is_stored = state
+        .db_service
+        .start_totp_enrolment(user.id, &secret)
+        .await
+        .map_err(|e| {
+            error!("Failed to store totp secret: {}", e);
+            AuthError::InternalServerError
+        })?;
+    if !is_stored {
+        return Err(AuthError::InvalidCharacter(
+            "Two-factor authentication is already enabled.".to_string(),
+        ));
+    }
+
+    let otpauth_uri =
+        totp::provisioning_uri(&secret, &user.username, &TOTP_ISSUER);
+
+    Ok(Json(TwoFactorSetupResponse {
+        secret,
+        otpauth_uri,
+    }))
+}
+
+// Confirms the enrolment and returns the recovery codes, shown only once
+pub async fn enable_two_factor_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(payload): Json<TwoFactorCodePayload>,
+) -> Result<Json<RecoveryCodesResponse>, AuthError> {
+    let user_totp = state
+        .db_service
+        .get_user_totp(user.id)
+        .await?
+        .filter(|user_totp| !user_totp.enabled)
+        .ok_or_else(|| {
+            AuthError::InvalidCharacter(
+                "Start the two-factor setup first.".to_string(),
+            )
+        })?;
+
+    let step = totp::verify_code(
+        &user_totp.secret,
+        &payload.code,
+        Utc::now().timestamp(),
+        None,
+    )
+    .ok_or(AuthError::InvalidTwoFactorCode)?;
+
+    let (recovery_codes, recovery_code_hashes) = new_recovery_codes();
+    let is_enabled = state
+        .db_service
+        .enable_totp(user.id, step, &recovery_code_hashes)
+        .await?;
+    if !is_enabled {
+        return Err(AuthError::InvalidTwoFactorCode);
+    }
+
+    // Sessions from before were logged in with the password only
+    if let Err(e) = state
+        .db_service
+        .revoke_all_user_sessions(
+            user.id,
+            Some(user.session_id),
+            "two-factor enabled",
+        )
+        .await
+    {
+        error!("Failed to revoke sessions after enabling 2FA: {}", e);
+    }
+    state.user_cache.invalidate(user.id);
+
+    Ok(Json(RecoveryCodesResponse {
+        message: "Two-factor authentication enabled".to_string(),
+        recovery_codes,
+    }))
+}
+
+// Needs the password and a code, a stolen session alone can not turn 2FA off
+pub async fn disable_two_factor_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(payload): Json<DisableTwoFactorPayload>,
+) -> Result<Json<TwoFactorMessageResponse>, AuthError> {
+    if payload.password.is_empty() || payload.code.is_empty() {
+        return Err(AuthError::MissingCredentials);
+    }
+
+    let db_service = &state.db_service;
+
+    if *REQUIRE_ADMIN_TWO_FACTOR && !user.permissions.is_empty() {
+        return Err(AuthError::TwoFactorRequired);
+    }
+    ensure_login_not_locked(db_service, &account_failure_key(user.id)).await?;
+
+    let account = db_service
+        .get_user_by_id(user.id)
+        .await?
+        .ok_or(AuthError::InvalidToken)?;
+    let is_password_valid =
+        verify_password(&payload.password, &account.password_hash)
+            .map_err(|_| AuthError::WrongCredentials)?;
+    if !is_password_valid {
+        record_session_password_failure(&state, &user).await?;
+        return Err(AuthError::WrongCredentials);
+    }
+
+    verify_session_second_factor(&state, &user, &payload.code).await?;
+
+    db_service.disable_totp(user.id).await?;
+    state.user_cache.invalidate(user.id);
+
+    Ok(Json(TwoFactorMessageResponse {
+        message: "Two-factor authentication disabled".to_string(),
+    }))
+}
+
+// Replaces all recovery codes, the old ones stop working
+pub async fn regenerate_recovery_codes_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(payload): Json<TwoFactorCodePayload>,
+) -> Result<Json<RecoveryCodesResponse>, AuthError> {
+    verify_session_second_factor(&state, &user, &payload.code).await?;
+
+    let (recovery_codes, recovery_code_hashes) = new_recovery_codes();
+    state
+        .db_service
+        .replace_recovery_codes(user.id, &recovery_code_hashes)
+        .await?;
+
+    Ok(Json(RecoveryCodesResponse {
+        message: "Recovery codes regenerated".to_string(),
+        recovery_codes,
+    }))
+}
@@ -1,12 +1,19 @@
-use crate::api::extractor::AuthenticatedUser;
+use crate::api::auth_handlers::{
+    clear_auth_cookies, ensure_verification_email_allowed,
+    send_email_verification,
+};
+use crate::api::extractor::{AuthenticatedUser, VerifiedUser};
 use crate::builder::startup::AppState;
+use crate::common::error::AuthError;
 use crate::common::hashing::hash_password;
+use crate::common::upload;
+use crate::database::sessions::UserSession;
 use axum::Json;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
 use axum_core::__private::tracing::error;
 use axum_core::response::{IntoResponse, Response};
-use axum_extra::extract::Multipart;
+use axum_extra::extract::{CookieJar, Multipart};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -38,11 +45,7 @@ pub async fn get_user_profile_handler(
             if let Some(key) = &profile.avatar_url
                 && !key.is_empty()
             {
-                profile.avatar_url = Some(format!(
-                    "{}/{}",
-                    state.storage_client.domain_cdn_url(),
-                    key
-                ));
+                profile.avatar_url = Some(state.storage_client.public_url(key));
             }
             (StatusCode::OK, Json(profile)).into_response()
         }
@@ -65,16 +68,38 @@ pub async fn update_user_profile_handler(
     Json(payload): Json<UpdateProfilePayload>,
 ) -> Response {
     // Validate email uniqueness if its being changed
-    if let Some(ref email) = payload.email
-        && let Ok(Some(existing_user)) =
-            state.db_service.get_user_by_identifier(email).await
-        && existing_user.id != user.id
+    let mut email_changed = false;
+    if let Some(ref email) = payload.email {
+        match state.db_service.get_user_by_identifier(email).await {
+            Ok(Some(existing_user)) if existing_user.id != user.id => {
+                return (
+                    StatusCode::CONFLICT,
+                    Json(
+                        serde_json::json!({"message": "Email already in use"}),
+                    ),
+                )
+                    .into_response();
+            }
+            // Already the user's own address
+            Ok(Some(_)) => {}
+            Ok(None) => email_changed = true,
+            Err(e) => {
+                error!("DB error checking email availability: {}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({"status": "Failed to update profile"})),
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    // Every change sends a verification link, so changes share its throttle
+    if email_changed
+        && let Err(e) =
+            ensure_verification_email_allowed(&state.db_service, user.id).await
     {
-        return (
-            StatusCode::CONFLICT,
-            Json(serde_json::json!({"message": "Email already in use"})),
-        )
-            .into_response();
+        return e.into_response();
     }
 
     // Call db to perform partial update
@@ -87,11 +112,31 @@ pub async fn update_user_profile_handler(
         )
         .await
     {
-        Ok(_) => (
-            StatusCode::OK,
-            Json(serde_json::json!({"status": "Profile update successfully"})),
-        )
-            .into_response(),
+        Ok(_) => {
+            state.user_cache.invalidate(user.id);
+
+            // A changed address is unverified until the new link is used
+            if email_changed
+                && let Ok(Some(account)) =
+                    state.db_service.get_user_by_id(user.id).await
+                && !account.email_verified
+            {
+                // Failures are logged, the user can request another link
+                let _ = send_email_verification(
+                    &state,
+                    account.id,
+                    &account.email,
+                    &account.username,
+                )
+                .await;
+            }
+
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({"status": "Profile update successfully"})),
+            )
+                .into_response()
+        }
         Err(e) => {
             error!("DB error updating user profiles: {}", e);
             (
@@ -112,6 +157,19 @@ pub async fn update_user_password_setting_handler(
         return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"message": "Password must be at least 8 characters long."}))).into_response();
     }
 
+    // A forced reset goes through the emailed link, a session from
+    // an OIDC login must not set a password around it
+    match state.db_service.get_account_status(user.id).await {
+        Ok(Some(status)) if status.password_reset_required => {
+            return AuthError::PasswordResetRequired.into_response();
+        }
+        Ok(_) => {}
+        Err(e) => {
+            error!("DB error getting account status: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"message": "Could not update password."}))).into_response();
+        }
+    }
+
     let hashed_password = match hash_password(&payload.new_password) {
         Ok(hashed) => hashed,
         Err(_) => return (
@@ -126,16 +184,134 @@ pub async fn update_user_password_setting_handler(
         .update_user_password(user.id, &hashed_password)
         .await
     {
+        Ok(_) => {
+            // Other devices have to log in with the new password
+            if let Err(e) = state
+                .db_service
+                .revoke_all_user_sessions(
+                    user.id,
+                    Some(user.session_id),
+                    "password change",
+                )
+                .await
+            {
+                error!(
+                    "Failed to revoke sessions after password change: {}",
+                    e
+                );
+            }
+            state.user_cache.invalidate(user.id);
+
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({"message": "Password updated successfully"})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("DB error updating password: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"message": "Could not update password."}))).into_response()
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSessionResponse {
+    #[serde(flatten)]
+    session: UserSession,
+    current: bool,
+}
+
+// List the devices the current user is logged in on
+pub async fn get_user_sessions_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Response {
+    match state.db_service.get_active_user_sessions(user.id).await {
+        Ok(sessions) => {
+            let response: Vec<UserSessionResponse> = sessions
+                .into_iter()
+                .map(|session| UserSessionResponse {
+                    current: session.id == user.session_id,
+                    session,
+                })
+                .collect();
+
+            (StatusCode::OK, Json(response)).into_response()
+        }
+        Err(e) => {
+            error!("DB error listing sessions: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not list sessions"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Log out one device of the current user
+pub async fn revoke_user_session_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Path(session_id): Path<i64>,
+) -> Response {
+    match state
+        .db_service
+        .revoke_user_session(user.id, session_id, "revoked by user")
+        .await
+    {
+        Ok(rows) if rows > 0 => {
+            state.user_cache.invalidate_session(session_id);
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({"status": "success", "message": "Session revoked"})),
+            )
+                .into_response()
+        }
         Ok(_) => (
-            StatusCode::OK,
-            Json(
-                serde_json::json!({"message": "Password updated successfully"}),
-            ),
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"status": "error", "message": "Session not found"})),
         )
             .into_response(),
         Err(e) => {
-            error!("DB error updating password: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"message": "Could not update password."}))).into_response()
+            error!("DB error revoking session: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not revoke session"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Log out everywhere, including this device
+pub async fn revoke_all_user_sessions_handler(
+    jar: CookieJar,
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Response {
+    match state
+        .db_service
+        .revoke_all_user_sessions(user.id, None, "logged out everywhere")
+        .await
+    {
+        Ok(rows) => {
+            state.user_cache.invalidate(user.id);
+            (
+                clear_auth_cookies(jar),
+                Json(serde_json::json!({"status": "success", "revokedSessions": rows})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("DB error revoking all sessions: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"status": "error", "message": "Could not revoke sessions"})),
+            )
+                .into_response()
         }
     }
 }
@@ -143,49 +319,43 @@ pub async fn update_user_password_setting_handler(
 // Upload and update user avatar
 pub async fn update_user_avatar_handler(
     State(state): State<AppState>,
-    user: AuthenticatedUser,
+    VerifiedUser(user): VerifiedUser,
     mut multipart: Multipart,
 ) -> Response {
     if let Ok(Some(field)) = multipart.next_field().await {
-        let content_type = field
-            .content_type()
-            .unwrap_or("application/octet-stream")
-            .to_string();
-        let file_name = field.file_name().unwrap_or("unknown.jpg").to_string();
-
-        let file_data = match field.bytes().await {
-            Ok(bytes) => bytes.to_vec(),
-            Err(e) => return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"message": format!("Failed to read file: {}", e)})),
-            )
-                .into_response(),
+        let (file_data, format) = match upload::read_image_field(
+            field,
+            upload::AVATAR_UPLOAD_MAX_BYTES,
+        )
+        .await
+        {
+            Ok(upload) => upload,
+            Err(e) => return e.into_response(),
         };
 
-        let file_extension = std::path::Path::new(&file_name)
-            .extension()
-            .and_then(std::ffi::OsStr::to_str)
-            .unwrap_or("jpg");
-
         // Image avatar key (avatars/user_123_random-uuid.jpg)
         let unique_image_key = format!(
             "avatars/user_{}_{}.{}",
             user.id,
             Uuid::new_v4(),
-            file_extension
+            upload::image_extension(format)
         );
 
         // Upload to cloud storage
         return match state
             .storage_client
-            .upload_image_file(file_data, &unique_image_key, &content_type)
+            .upload_image_file(
+                file_data,
+                &unique_image_key,
+                format.to_mime_type(),
+                Some(user.id),
+            )
             .await
         {
             Ok(key) => {
                 match state.db_service.update_user_avatar(user.id, &key).await {
                     Ok(_) => {
-                        // Construct the public URL
-                        let public_url = format!("{}/{}", state.storage_client.domain_cdn_url(), key);
+                        let public_url = state.storage_client.public_url(&key);
 
                         (StatusCode::OK, Json(serde_json::json!({"status": "success", "url": public_url}))).into_response()
                     },
@@ -327,7 +497,9 @@ pub async fn get_user_bookmark_library_handler(
                     BookmarkSeriesResponse {
                         id: series.id,
                         title: series.title,
-                        cover_image_url: series.cover_image_url,
+                        cover_image_url: state
+                            .storage_client
+                            .public_url(&series.cover_image_url),
                         updated_at: series.updated_at,
                         latest_chapter: latest_chapter_info,
                     }
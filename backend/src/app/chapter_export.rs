@@ -0,0 +1,728 @@
+use anyhow::{Context, Result, anyhow};
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::database::ChapterPages;
+use crate::database::storage::StorageClient;
+use crate::encoding::encoding_pool::EncodingPool;
+use crate::encoding::image_encoding;
+use crate::encoding::pdf_writer::PdfWriter;
+
+/// Default number of exports allowed to run at the same time, server-wide.
+const DEFAULT_MAX_CONCURRENT_EXPORTS: usize = 2;
+
+/// Default maximum number of chapters in a single export.
+const DEFAULT_MAX_EXPORT_CHAPTERS: usize = 25;
+
+/// Pages downloaded ahead of the one being written.
+const PAGE_PREFETCH: usize = 4;
+
+/// Output formats for offline reading.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Cbz,
+    Epub,
+    Pdf,
+}
+
+impl ExportFormat {
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Cbz => "cbz",
+            ExportFormat::Epub => "epub",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+
+    /// PDF cannot embed AVIF, and EPUB needs a JPEG fallback for every page.
+    pub fn needs_avif_decoding(&self) -> bool {
+        matches!(self, ExportFormat::Epub | ExportFormat::Pdf)
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Cbz => "application/vnd.comicbook+zip",
+            ExportFormat::Epub => "application/epub+zip",
+            ExportFormat::Pdf => "application/pdf",
+        }
+    }
+}
+
+/// Series metadata and chapters packaged into one export file.
+pub struct ExportBook {
+    pub series_title: String,
+    pub description: String,
+    pub authors: Vec<String>,
+    pub categories: Vec<String>,
+    pub chapters: Vec<ChapterPages>,
+}
+
+impl ExportBook {
+    /// "Series - Chapter 3" or "Series - Chapters 3-7"
+    pub fn title(&self) -> String {
+        match (self.chapters.first(), self.chapters.last()) {
+            (Some(first), Some(last)) if self.chapters.len() > 1 => format!(
+                "{} - Chapters {}-{}",
+                self.series_title, first.chapter_number, last.chapter_number
+            ),
+            (Some(chapter), _) => format!(
+                "{} - Chapter {}",
+                self.series_title, chapter.chapter_number
+            ),
+            _ => self.series_title.clone(),
+        }
+    }
+
+    pub fn file_name(&self, format: ExportFormat) -> String {
+        let stem = slug::slugify(self.title());
+        format!("{}.{}", stem, format.file_extension())
+    }
+
+    fn page_count(&self) -> usize {
+        self.chapters.iter().map(|c| c.image_keys.len()).sum()
+    }
+
+    // Index of the first page of every chapter, with its display name
+    fn chapter_starts(&self) -> Vec<(usize, String)> {
+        let mut first_page = 0;
+        self.chapters
+            .iter()
+            .map(|chapter| {
+                let start = first_page;
+                first_page += chapter.image_keys.len();
+                let name = match &chapter.title {
+                    Some(title) => {
+                        format!("Chapter {}: {}", chapter.chapter_number, title)
+                    }
+                    None => format!("Chapter {}", chapter.chapter_number),
+                };
+                (start, name)
+            })
+            .collect()
+    }
+}
+
+/// Limits how many exports run at once, and to one export per user.
+/// Exports hold a download slot for their whole duration,
+/// so large requests are rejected instead of queueing up behind each other.
+#[derive(Clone)]
+pub struct ExportLimiter {
+    slots: Arc<Semaphore>,
+    active_users: Arc<Mutex<HashSet<i32>>>,
+    max_chapters: usize,
+}
+
+/// Held while an export streams, frees the slot when dropped.
+pub struct ExportPermit {
+    _slot: OwnedSemaphorePermit,
+    user_id: i32,
+    active_users: Arc<Mutex<HashSet<i32>>>,
+}
+
+impl Drop for ExportPermit {
+    fn drop(&mut self) {
+        if let Ok(mut active_users) = self.active_users.lock() {
+            active_users.remove(&self.user_id);
+        }
+    }
+}
+
+impl ExportLimiter {
+    /// Reads `EXPORT_MAX_CONCURRENT` and `EXPORT_MAX_CHAPTERS` from the environment.
+    pub fn new_from_env() -> Self {
+        let read_env = |name: &str, default: usize| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(default)
+        };
+
+        Self {
+            slots: Arc::new(Semaphore::new(read_env(
+                "EXPORT_MAX_CONCURRENT",
+                DEFAULT_MAX_CONCURRENT_EXPORTS,
+            ))),
+            active_users: Arc::new(Mutex::new(HashSet::new())),
+            max_chapters: read_env(
+                "EXPORT_MAX_CHAPTERS",
+                DEFAULT_MAX_EXPORT_CHAPTERS,
+            ),
+        }
+    }
+
+    pub fn max_chapters(&self) -> usize {
+        self.max_chapters
+    }
+
+    /// `None` when the user already has an export running or all slots are busy.
+    pub fn try_acquire(&self, user_id: i32) -> Option<ExportPermit> {
+        let mut active_users = self.active_users.lock().ok()?;
+        if active_users.contains(&user_id) {
+            return None;
+        }
+
+        let slot = self.slots.clone().try_acquire_owned().ok()?;
+        active_users.insert(user_id);
+
+        Some(ExportPermit {
+            _slot: slot,
+            user_id,
+            active_users: self.active_users.clone(),
+        })
+    }
+}
+
+/// Start packaging the book in the background and return the stream of file chunks.
+/// Pages are downloaded from storage while the file is written,
+/// a chunk is sent after every page so memory stays bounded to a few pages.
+pub fn stream_export(
+    storage_client: Arc<StorageClient>,
+    encoding_pool: EncodingPool,
+    format: ExportFormat,
+    book: ExportBook,
+    permit: ExportPermit,
+) -> impl Stream<Item = io::Result<Bytes>> {
+    let (sender, receiver) = async_channel::bounded(PAGE_PREFETCH);
+
+    tokio::spawn(async move {
+        // Keep the export slot until the last chunk is sent
+        let _permit = permit;
+        let mut sink = ChunkSink {
+            buffer: SharedBuffer::default(),
+            sender,
+        };
+
+        let result = match format {
+            ExportFormat::Cbz => {
+                write_cbz(&book, &storage_client, &mut sink).await
+            }
+            ExportFormat::Epub => {
+                write_epub(&book, &storage_client, &encoding_pool, &mut sink)
+                    .await
+            }
+            ExportFormat::Pdf => {
+                write_pdf(&book, &storage_client, &encoding_pool, &mut sink)
+                    .await
+            }
+        };
+
+        match result {
+            Ok(()) => println!(
+                "[EXPORT] Finished {} export '{}' ({} pages)",
+                format.file_extension(),
+                book.title(),
+                book.page_count()
+            ),
+            Err(e) => {
+                eprintln!("[EXPORT] Export '{}' failed: {:#}", book.title(), e);
+                // Abort the response so the client does not keep a truncated file
+                let _ = sink.sender.send(Err(io::Error::other(e))).await;
+            }
+        }
+    });
+
+    receiver
+}
+
+// Download page objects in order, a few ahead of the writer
+fn fetch_pages<'a>(
+    book: &'a ExportBook,
+    storage_client: &'a StorageClient,
+) -> impl Stream<Item = Result<Vec<u8>>> + 'a {
+    futures::stream::iter(
+        book.chapters.iter().flat_map(|c| c.image_keys.iter()),
+    )
+    .map(move |key| storage_client.get_object_bytes(key))
+    .buffered(PAGE_PREFETCH)
+}
+
+async fn write_cbz(
+    book: &ExportBook,
+    storage_client: &StorageClient,
+    sink: &mut ChunkSink,
+) -> Result<()> {
+    let mut zip = ZipWriter::new_stream(sink.buffer.clone());
+    // Pages are already compressed, deflating them again only costs CPU
+    let stored = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored);
+
+    zip.start_file("ComicInfo.xml", SimpleFileOptions::default())?;
+    zip.write_all(comic_info_xml(book).as_bytes())?;
+
+    let mut pages = std::pin::pin!(fetch_pages(book, storage_client));
+    let mut index = 0;
+    while let Some(page) = pages.next().await {
+        zip.start_file(format!("{:05}.avif", index + 1), stored)?;
+        zip.write_all(&page?)?;
+        sink.flush().await?;
+        index += 1;
+    }
+
+    zip.finish()?;
+    sink.flush().await
+}
+
+async fn write_epub(
+    book: &ExportBook,
+    storage_client: &StorageClient,
+    encoding_pool: &EncodingPool,
+    sink: &mut ChunkSink,
+) -> Result<()> {
+    // Not streamed: OCF forbids a data descriptor on the mimetype entry.
+    // The writer fills in sizes when an entry is finished, so only
+    // finished entries are sent to the client.
+    let mut zip = ZipWriter::new(sink.buffer.clone());
+    let deflated = SimpleFileOptions::default();
+    let stored = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored);
+
+    // The mimetype must be the first entry and uncompressed
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(EPUB_CONTAINER_XML.as_bytes())?;
+
+    let mut pages = std::pin::pin!(fetch_pages(book, storage_client));
+    let mut index = 0;
+    while let Some(page) = pages.next().await {
+        let page_number = index + 1;
+        let avif = page?;
+
+        // AVIF is not an EPUB core media type, readers without support
+        // show the JPEG fallback
+        let avif_for_jpeg = avif.clone();
+        let (jpeg, _, _) = encoding_pool
+            .run(move || image_encoding::convert_avif_to_jpeg(&avif_for_jpeg))
+            .await?;
+
+        zip.start_file(
+            format!("OEBPS/pages/{:05}.xhtml", page_number),
+            deflated,
+        )?;
+        zip.write_all(epub_page_xhtml(page_number).as_bytes())?;
+        zip.start_file(format!("OEBPS/images/{:05}.jpg", page_number), stored)?;
+        zip.write_all(&jpeg)?;
+
+        let open_entry_start = sink.buffer.position()?;
+        zip.start_file(
+            format!("OEBPS/images/{:05}.avif", page_number),
+            stored,
+        )?;
+        zip.write_all(&avif)?;
+        sink.flush_before(open_entry_start).await?;
+        index += 1;
+    }
+
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(epub_nav_xhtml(book).as_bytes())?;
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(epub_package_opf(book).as_bytes())?;
+
+    zip.finish()?;
+    sink.flush().await
+}
+
+// PDF cannot embed AVIF, every page is converted to JPEG on the encoding pool
+async fn write_pdf(
+    book: &ExportBook,
+    storage_client: &StorageClient,
+    encoding_pool: &EncodingPool,
+    sink: &mut ChunkSink,
+) -> Result<()> {
+    let mut pdf = PdfWriter::new(sink.buffer.clone())?;
+
+    let mut pages = std::pin::pin!(fetch_pages(book, storage_client));
+    while let Some(page) = pages.next().await {
+        let avif = page?;
+        let (jpeg, width, height) = encoding_pool
+            .run(move || image_encoding::convert_avif_to_jpeg(&avif))
+            .await?;
+        pdf.add_jpeg_page(&jpeg, width, height)?;
+        sink.flush().await?;
+    }
+
+    if pdf.page_count() != book.page_count() {
+        return Err(anyhow!("PDF export is missing pages"));
+    }
+
+    pdf.finish(&book.title())?;
+    sink.flush().await
+}
+
+/// ComicInfo.xml (Anansi schema v2) read by comic readers such as Komga and Kavita.
+pub fn comic_info_xml(book: &ExportBook) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<ComicInfo xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\">\n",
+    );
+    let mut element = |name: &str, value: &str| {
+        if !value.is_empty() {
+            xml.push_str(&format!(
+                "  <{}>{}</{}>\n",
+                name,
+                escape_xml(value),
+                name
+            ));
+        }
+    };
+
+    element("Title", &book.title());
+    element("Series", &book.series_title);
+    if let [chapter] = book.chapters.as_slice() {
+        element("Number", &chapter.chapter_number.to_string());
+    }
+    element("Summary", &book.description);
+    element("Writer", &book.authors.join(", "));
+    element("Genre", &book.categories.join(", "));
+    element("PageCount", &book.page_count().to_string());
+    element("Manga", "Yes");
+
+    // Bookmark the first page of every chapter
+    xml.push_str("  <Pages>\n");
+    for (first_page, name) in book.chapter_starts() {
+        xml.push_str(&format!(
+            "    <Page Image=\"{}\" Bookmark=\"{}\" />\n",
+            first_page,
+            escape_xml(&name)
+        ));
+    }
+    xml.push_str("  </Pages>\n</ComicInfo>\n");
+    xml
+}
+
+const EPUB_CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+fn epub_page_xhtml(page_number: usize) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+  <title>Page {page}</title>
+  <style>body {{ margin: 0; text-align: center; }} img {{ max-width: 100%; }}</style>
+</head>
+<body><img src="../images/{page:05}.avif" alt="Page {page}"/></body>
+</html>
+"#,
+        page = page_number
+    )
+}
+
+fn epub_nav_xhtml(book: &ExportBook) -> String {
+    let entries = book
+        .chapter_starts()
+        .into_iter()
+        .map(|(first_page, name)| {
+            format!(
+                "      <li><a href=\"pages/{:05}.xhtml\">{}</a></li>\n",
+                first_page + 1,
+                escape_xml(&name)
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head><title>{title}</title></head>
+<body>
+  <nav epub:type="toc">
+    <ol>
+{entries}    </ol>
+  </nav>
+</body>
+</html>
+"#,
+        title = escape_xml(&book.title()),
+        entries = entries
+    )
+}
+
+fn epub_package_opf(book: &ExportBook) -> String {
+    let mut metadata = format!(
+        "    <dc:identifier id=\"book-id\">urn:uuid:{}</dc:identifier>\n    <dc:title>{}</dc:title>\n    <dc:language>en</dc:language>\n    <meta property=\"dcterms:modified\">{}</meta>\n",
+        Uuid::new_v4(),
+        escape_xml(&book.title()),
+        chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+    );
+    for author in &book.authors {
+        metadata.push_str(&format!(
+            "    <dc:creator>{}</dc:creator>\n",
+            escape_xml(author)
+        ));
+    }
+    for category in &book.categories {
+        metadata.push_str(&format!(
+            "    <dc:subject>{}</dc:subject>\n",
+            escape_xml(category)
+        ));
+    }
+    if !book.description.is_empty() {
+        metadata.push_str(&format!(
+            "    <dc:description>{}</dc:description>\n",
+            escape_xml(&book.description)
+        ));
+    }
+
+    let mut manifest = String::from(
+        "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+    );
+    let mut spine = String::new();
+    for page_number in 1..=book.page_count() {
+        manifest.push_str(&format!(
+            "    <item id=\"img-{n:05}\" href=\"images/{n:05}.avif\" media-type=\"image/avif\" fallback=\"jpg-{n:05}\"/>\n    <item id=\"jpg-{n:05}\" href=\"images/{n:05}.jpg\" media-type=\"image/jpeg\"/>\n    <item id=\"page-{n:05}\" href=\"pages/{n:05}.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+            n = page_number
+        ));
+        spine.push_str(&format!(
+            "    <itemref idref=\"page-{:05}\"/>\n",
+            page_number
+        ));
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+{metadata}  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+"#
+    )
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Write target shared with the zip/PDF writer, drained after every page.
+/// Seeking is possible within the bytes that were not sent yet.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<BufferState>>);
+
+#[derive(Default)]
+struct BufferState {
+    // Bytes written but not sent yet
+    pending: Vec<u8>,
+    // Bytes sent before `pending`
+    sent: u64,
+    // Write position from the start of the file
+    position: u64,
+}
+
+impl SharedBuffer {
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, BufferState>> {
+        self.0
+            .lock()
+            .map_err(|_| io::Error::other("Export buffer poisoned"))
+    }
+
+    fn position(&self) -> io::Result<u64> {
+        Ok(self.lock()?.position)
+    }
+
+    // Remove and return the pending bytes before `offset`
+    fn take_before(&self, offset: u64) -> io::Result<Vec<u8>> {
+        let mut state = self.lock()?;
+        let end = (offset.saturating_sub(state.sent) as usize)
+            .min(state.pending.len());
+        let chunk: Vec<u8> = state.pending.drain(..end).collect();
+        state.sent += chunk.len() as u64;
+        Ok(chunk)
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.lock()?;
+        let start = (state.position - state.sent) as usize;
+        let overlap = state.pending.len().saturating_sub(start).min(buf.len());
+
+        state.pending[start..start + overlap].copy_from_slice(&buf[..overlap]);
+        state.pending.extend_from_slice(&buf[overlap..]);
+        state.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for SharedBuffer {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let mut state = self.lock()?;
+        let end = state.sent + state.pending.len() as u64;
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => end.checked_add_signed(delta),
+            SeekFrom::Current(delta) => {
+                state.position.checked_add_signed(delta)
+            }
+        };
+
+        match target {
+            Some(target) if target >= state.sent && target <= end => {
+                state.position = target;
+                Ok(target)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Cannot seek into data already sent",
+            )),
+        }
+    }
+}
+
+struct ChunkSink {
+    buffer: SharedBuffer,
+    sender: async_channel::Sender<io::Result<Bytes>>,
+}
+
+impl ChunkSink {
+    // Send everything written so far to the client
+    async fn flush(&mut self) -> Result<()> {
+        self.flush_before(u64::MAX).await
+    }
+
+    // Send the bytes before `offset`, the rest can still be rewritten
+    async fn flush_before(&mut self, offset: u64) -> Result<()> {
+        let chunk = self.buffer.take_before(offset)?;
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        self.sender
+            .send(Ok(Bytes::from(chunk)))
+            .await
+            .context("Client disconnected during export")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book() -> ExportBook {
+        ExportBook {
+            series_title: "Solo & Co".to_string(),
+            description: String::new(),
+            authors: vec!["A".to_string()],
+            categories: vec!["Action".to_string()],
+            chapters: vec![
+                ChapterPages {
+                    chapter_number: 1.0,
+                    title: None,
+                    image_keys: vec!["a".to_string(), "b".to_string()],
+                },
+                ChapterPages {
+                    chapter_number: 2.5,
+                    title: Some("<End>".to_string()),
+                    image_keys: vec!["c".to_string()],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_comic_info_bookmarks_chapters() {
+        let xml = comic_info_xml(&book());
+
+        assert!(xml.contains("<Series>Solo &amp; Co</Series>"));
+        assert!(xml.contains("<PageCount>3</PageCount>"));
+        assert!(xml.contains("<Page Image=\"0\" Bookmark=\"Chapter 1\" />"));
+        assert!(xml.contains(
+            "<Page Image=\"2\" Bookmark=\"Chapter 2.5: &lt;End&gt;\" />"
+        ));
+        // A range export has no single chapter number
+        assert!(!xml.contains("<Number>"));
+    }
+
+    #[test]
+    fn test_streamed_zip_is_readable() {
+        let buffer = SharedBuffer::default();
+        let mut zip = ZipWriter::new_stream(buffer.clone());
+        zip.start_file(
+            "00001.avif",
+            SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Stored),
+        )
+        .unwrap();
+        zip.write_all(b"page").unwrap();
+        zip.finish().unwrap();
+
+        let bytes = buffer.take_before(u64::MAX).unwrap();
+        let mut archive =
+            zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "00001.avif");
+    }
+
+    #[test]
+    fn test_epub_mimetype_has_no_data_descriptor() {
+        let buffer = SharedBuffer::default();
+        let mut zip = ZipWriter::new(buffer.clone());
+        let stored = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored);
+        zip.start_file("mimetype", stored).unwrap();
+        zip.write_all(b"application/epub+zip").unwrap();
+
+        // Finished entries are sent while the open one can still be rewritten
+        let open_entry_start = buffer.position().unwrap();
+        zip.start_file("OEBPS/images/00001.avif", stored).unwrap();
+        zip.write_all(b"page").unwrap();
+        let mut bytes = buffer.take_before(open_entry_start).unwrap();
+        assert_eq!(bytes.len() as u64, open_entry_start);
+
+        zip.finish().unwrap();
+        bytes.extend(buffer.take_before(u64::MAX).unwrap());
+
+        // Local header: no data descriptor flag, sizes known up front
+        let flags = u16::from_le_bytes([bytes[6], bytes[7]]);
+        assert_eq!(flags & (1 << 3), 0);
+        assert_eq!(u32::from_le_bytes(bytes[22..26].try_into().unwrap()), 20);
+        assert_eq!(&bytes[30..38], b"mimetype");
+        assert_eq!(&bytes[38..58], b"application/epub+zip");
+
+        let mut archive =
+            zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(
+            archive.by_index(1).unwrap().name(),
+            "OEBPS/images/00001.avif"
+        );
+    }
+
+    #[test]
+    fn test_epub_manifest_has_jpeg_fallback() {
+        let opf = epub_package_opf(&book());
+
+        assert!(opf.contains(
+            "href=\"images/00001.avif\" media-type=\"image/avif\" fallback=\"jpg-00001\""
+        ));
+        assert!(opf.contains(
+            "<item id=\"jpg-00001\" href=\"images/00001.jpg\" media-type=\"image/jpeg\"/>"
+        ));
+    }
+}
@@ -0,0 +1,882 @@
+use anyhow::Context;
+use std::cmp::Ordering;
+use std::io::{Read, Seek};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::app::coordinator::{self, UploadedVariants};
+use crate::common::error::ChapterError;
+use crate::common::upload::CHAPTER_PAGE_MAX_BYTES;
+use crate::database::storage::StorageClient;
+use crate::database::{ChapterStatus, DatabaseService, Series, SeriesChapter};
+use crate::encoding::encoding_pool::EncodingPool;
+use crate::encoding::image_encoding::{self, EncodedTile};
+use crate::encoding::image_validation::{self, ImageValidationRules};
+
+/// Maximum number of pages accepted for a single chapter.
+pub const MAX_CHAPTER_PAGES: usize = 500;
+
+/// Maximum uncompressed size of a single archive entry, guards against zip bombs.
+/// Same limit as a directly uploaded page.
+const MAX_ARCHIVE_ENTRY_BYTES: u64 = CHAPTER_PAGE_MAX_BYTES as u64;
+
+/// Source URL prefix of manually uploaded chapters.
+const MANUAL_SOURCE_PREFIX: &str = "manual://";
+
+/// Image extensions picked up from ZIP/CBZ archives.
+const ARCHIVE_IMAGE_EXTENSIONS: [&str; 5] =
+    ["jpg", "jpeg", "png", "gif", "webp"];
+
+/// A page image uploaded by an admin, before validation.
+pub struct UploadedPage {
+    pub file_name: String,
+    pub bytes: Vec<u8>,
+    pub content_type: Option<String>,
+}
+
+/// Context shared by the manual chapter operations.
+#[derive(Clone)]
+pub struct ChapterManager {
+    pub db_service: DatabaseService,
+    pub storage_client: Arc<StorageClient>,
+    pub encoding_pool: EncodingPool,
+}
+
+impl ChapterManager {
+    /// Create a chapter from uploaded pages, in the given order.
+    /// The page headers are checked first, so a single bad page rejects the whole upload.
+    /// Decoding, encoding and storage then continue in the background (status `Processing`),
+    /// a whole chapter takes longer to encode than the request timeout.
+    /// A manual chapter whose upload failed is replaced by the new upload.
+    pub async fn create_chapter(
+        &self,
+        series: &Series,
+        chapter_number: f32,
+        title: Option<&str>,
+        pages: Vec<UploadedPage>,
+    ) -> Result<i32, ChapterError> {
+        ensure_not_archived(series)?;
+        if pages.is_empty() {
+            return Err(ChapterError::BadRequest(
+                "No page images were uploaded".to_string(),
+            ));
+        }
+        if pages.len() > MAX_CHAPTER_PAGES {
+            return Err(ChapterError::BadRequest(format!(
+                "A chapter can have at most {} pages",
+                MAX_CHAPTER_PAGES
+            )));
+        }
+
+        let pages = validate_page_headers(pages)?;
+
+        if let Some(existing_id) = self
+            .db_service
+            .get_chapter_id_by_number(series.id, chapter_number)
+            .await?
+        {
+            self.remove_failed_upload(existing_id).await?;
+        }
+
+        // Same title format as scraped chapters when none is given
+        let default_title =
+            format!("{}-eng", chapter_number.to_string().replace('.', "-"));
+        // Manual chapters have no source, the unique placeholder keeps source_url unique
+        let chapter_id = self
+            .db_service
+            .add_new_chapter(
+                series.id,
+                chapter_number,
+                Some(title.unwrap_or(&default_title)),
+                &format!("{}{}", MANUAL_SOURCE_PREFIX, Uuid::new_v4()),
+            )
+            .await?;
+
+        let manager = self.clone();
+        let series = series.clone();
+        tokio::spawn(async move {
+            if let Err(e) = manager
+                .store_chapter_pages(&series, chapter_id, chapter_number, pages)
+                .await
+            {
+                eprintln!(
+                    "[CHAPTER] Failed to store chapter {} of '{}': {:#}",
+                    chapter_number, series.title, e
+                );
+                if let Err(status_err) = manager
+                    .db_service
+                    .update_chapter_status(
+                        chapter_id,
+                        ChapterStatus::Error,
+                        Some(&format!("Manual upload failed: {}", e)),
+                    )
+                    .await
+                {
+                    eprintln!(
+                        "[CHAPTER] Failed to mark chapter {} as failed: {:#}",
+                        chapter_id, status_err
+                    );
+                }
+            }
+        });
+
+        Ok(chapter_id)
+    }
+
+    // Encode, upload and record the pages of a newly created manual chapter
+    async fn store_chapter_pages(
+        &self,
+        series: &Series,
+        chapter_id: i32,
+        chapter_number: f32,
+        pages: Vec<UploadedPage>,
+    ) -> anyhow::Result<()> {
+        let encoded_pages = self.encode_pages(pages).await?;
+
+        let key_prefix =
+            coordinator::chapter_key_prefix(&series.title, chapter_number);
+        let stored_pages =
+            self.upload_pages(&key_prefix, 0, encoded_pages).await?;
+
+        let mut next_image_order = 1;
+        for (index, stored_tiles) in stored_pages.iter().enumerate() {
+            next_image_order = coordinator::record_page_tiles(
+                &self.db_service,
+                chapter_id,
+                (index + 1) as i32,
+                next_image_order,
+                stored_tiles,
+            )
+            .await;
+        }
+
+        self.db_service
+            .update_chapter_status(chapter_id, ChapterStatus::Available, None)
+            .await?;
+
+        if let Err(e) = self
+            .db_service
+            .update_series_new_content_timestamp(series.id)
+            .await
+        {
+            eprintln!(
+                "Non-critical error: Failed to update series timestamp: {}",
+                e
+            );
+        }
+
+        println!(
+            "[CHAPTER] Created chapter {} of '{}' with {} pages",
+            chapter_number,
+            series.title,
+            stored_pages.len()
+        );
+
+        Ok(())
+    }
+
+    /// Change the number and/or title of a chapter.
+    pub async fn update_details(
+        &self,
+        chapter_id: i32,
+        chapter_number: Option<f32>,
+        title: Option<&str>,
+    ) -> Result<(), ChapterError> {
+        let chapter = self
+            .db_service
+            .get_chapter_by_id(chapter_id)
+            .await?
+            .ok_or(ChapterError::NotFound("Chapter not found"))?;
+
+        if let Some(number) = chapter_number
+            && self
+                .db_service
+                .get_chapter_id_by_number(chapter.series_id, number)
+                .await?
+                .is_some_and(|existing_id| existing_id != chapter_id)
+        {
+            return Err(ChapterError::Conflict(format!(
+                "Chapter {} already exists",
+                number
+            )));
+        }
+
+        if let Some(number) = chapter_number
+            && number != chapter.chapter_number
+        {
+            self.move_chapter_objects(&chapter, number).await?;
+        }
+
+        self.db_service
+            .update_chapter_details(chapter_id, chapter_number, title)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Move pages into a new order. `page_order` must list every
+    /// current page number exactly once.
+    pub async fn reorder_pages(
+        &self,
+        chapter_id: i32,
+        page_order: &[i32],
+    ) -> Result<(), ChapterError> {
+        let mut current_pages = self.page_numbers(chapter_id).await?;
+        let mut requested_pages = page_order.to_vec();
+        current_pages.sort_unstable();
+        requested_pages.sort_unstable();
+
+        if current_pages != requested_pages {
+            return Err(ChapterError::BadRequest(
+                "Page order must contain every page of the chapter exactly once"
+                    .to_string(),
+            ));
+        }
+
+        self.db_service
+            .reorder_chapter_pages(chapter_id, page_order)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Replace the image of one page, keeping its position.
+    pub async fn replace_page(
+        &self,
+        chapter_id: i32,
+        page_number: i32,
+        page: UploadedPage,
+    ) -> Result<(), ChapterError> {
+        let chapter = self
+            .db_service
+            .get_chapter_by_id(chapter_id)
+            .await?
+            .ok_or(ChapterError::NotFound("Chapter not found"))?;
+
+        if !self.page_numbers(chapter_id).await?.contains(&page_number) {
+            return Err(ChapterError::NotFound("Page not found"));
+        }
+
+        let series = self
+            .db_service
+            .get_series_by_id(chapter.series_id)
+            .await?
+            .ok_or(ChapterError::NotFound("Series not found"))?;
+        ensure_not_archived(&series)?;
+
+        let encoded_pages = self.encode_pages(vec![page]).await?;
+        let key_prefix = coordinator::chapter_key_prefix(
+            &series.title,
+            chapter.chapter_number,
+        );
+        let stored_tiles = self
+            .upload_pages(
+                &key_prefix,
+                (page_number - 1) as usize,
+                encoded_pages,
+            )
+            .await?
+            .pop()
+            .unwrap_or_default();
+
+        let new_keys: Vec<String> =
+            stored_tiles.iter().map(|(key, _)| key.clone()).collect();
+        let old_keys = self
+            .db_service
+            .replace_chapter_page_images(chapter_id, page_number, &new_keys)
+            .await?;
+
+        for (key, variants) in &stored_tiles {
+            if let Err(e) = coordinator::record_image_variants(
+                &self.db_service,
+                key,
+                variants,
+            )
+            .await
+            {
+                eprintln!(
+                    "[CHAPTER] Failed to save variants for key {}: {}",
+                    key, e
+                );
+            }
+        }
+
+        self.delete_page_objects(old_keys).await;
+
+        Ok(())
+    }
+
+    /// Remove one page, the following pages move up.
+    pub async fn delete_page(
+        &self,
+        chapter_id: i32,
+        page_number: i32,
+    ) -> Result<(), ChapterError> {
+        let chapter = self
+            .db_service
+            .get_chapter_by_id(chapter_id)
+            .await?
+            .ok_or(ChapterError::NotFound("Chapter not found"))?;
+        if let Some(series) =
+            self.db_service.get_series_by_id(chapter.series_id).await?
+        {
+            ensure_not_archived(&series)?;
+        }
+
+        let old_keys = self
+            .db_service
+            .replace_chapter_page_images(chapter_id, page_number, &[])
+            .await?;
+
+        if old_keys.is_empty() {
+            return Err(ChapterError::NotFound("Page not found"));
+        }
+
+        self.delete_page_objects(old_keys).await;
+
+        // Readers only see available chapters, an empty one must not be listed
+        if self
+            .db_service
+            .get_chapter_images(chapter_id)
+            .await?
+            .is_empty()
+        {
+            self.db_service
+                .update_chapter_status(
+                    chapter_id,
+                    ChapterStatus::NoImagesFound,
+                    Some("All pages were deleted"),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // Only a manual chapter that failed to upload may be replaced,
+    // scraped and working chapters are a conflict.
+    async fn remove_failed_upload(
+        &self,
+        chapter_id: i32,
+    ) -> Result<(), ChapterError> {
+        let chapter = self
+            .db_service
+            .get_chapter_by_id(chapter_id)
+            .await?
+            .ok_or(ChapterError::NotFound("Chapter not found"))?;
+
+        let failed_upload =
+            chapter.source_url.starts_with(MANUAL_SOURCE_PREFIX)
+                && matches!(
+                    chapter.status,
+                    ChapterStatus::Error | ChapterStatus::NoImagesFound
+                );
+        if !failed_upload {
+            return Err(ChapterError::Conflict(format!(
+                "Chapter {} already exists",
+                chapter.chapter_number
+            )));
+        }
+
+        let image_keys: Vec<String> = self
+            .db_service
+            .get_chapter_images(chapter_id)
+            .await?
+            .into_iter()
+            .map(|image| image.image_url)
+            .collect();
+
+        self.db_service
+            .delete_chapter_and_images_for_chapter(
+                chapter.series_id,
+                chapter.chapter_number,
+            )
+            .await?;
+        self.delete_page_objects(image_keys).await;
+
+        Ok(())
+    }
+
+    // Page keys contain the chapter number. Left under the old number, they
+    // would be overwritten by a later scrape of that number.
+    async fn move_chapter_objects(
+        &self,
+        chapter: &SeriesChapter,
+        new_number: f32,
+    ) -> Result<(), ChapterError> {
+        let series = self
+            .db_service
+            .get_series_by_id(chapter.series_id)
+            .await?
+            .ok_or(ChapterError::NotFound("Series not found"))?;
+        ensure_not_archived(&series)?;
+
+        let image_keys: Vec<String> = self
+            .db_service
+            .get_chapter_images(chapter.id)
+            .await?
+            .into_iter()
+            .map(|image| image.image_url)
+            .collect();
+        let variant_sets =
+            self.db_service.get_image_variant_sets(&image_keys).await?;
+
+        let moves: Vec<(String, String)> = image_keys
+            .iter()
+            .map(String::as_str)
+            .chain(variant_sets.values().flat_map(|set| set.object_keys()))
+            .filter_map(|key| {
+                renumbered_object_key(key, chapter.chapter_number, new_number)
+                    .map(|new_key| (key.to_string(), new_key))
+            })
+            .collect();
+
+        if moves.is_empty() {
+            return Ok(());
+        }
+
+        self.storage_client
+            .move_objects(&moves)
+            .await
+            .context("Failed to move chapter pages")?;
+
+        if let Err(e) = self
+            .db_service
+            .move_chapter_image_keys(chapter.id, &moves)
+            .await
+        {
+            // Put the objects back where the database still expects them
+            let undo: Vec<(String, String)> =
+                moves.into_iter().map(|(from, to)| (to, from)).collect();
+            if let Err(undo_err) = self.storage_client.move_objects(&undo).await
+            {
+                eprintln!(
+                    "[CHAPTER] Failed to move pages of chapter {} back: {:#}",
+                    chapter.id, undo_err
+                );
+            }
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    async fn page_numbers(
+        &self,
+        chapter_id: i32,
+    ) -> Result<Vec<i32>, ChapterError> {
+        let mut page_numbers: Vec<i32> = self
+            .db_service
+            .get_chapter_images(chapter_id)
+            .await?
+            .iter()
+            .map(|image| image.page_number)
+            .collect();
+        page_numbers.dedup();
+
+        if page_numbers.is_empty() {
+            return Err(ChapterError::NotFound("Chapter has no pages"));
+        }
+
+        Ok(page_numbers)
+    }
+
+    // Validate and encode all pages on the shared pool, same pipeline as scraped pages
+    async fn encode_pages(
+        &self,
+        pages: Vec<UploadedPage>,
+    ) -> Result<Vec<Vec<EncodedTile>>, ChapterError> {
+        let max_tile_height = image_encoding::max_tile_height_from_env();
+        // Uploads have no site specific placeholder images
+        let validation_rules = Arc::new(ImageValidationRules::from_env(&[]));
+
+        let encode_tasks = pages.into_iter().map(|page| {
+            let encoding_pool = self.encoding_pool.clone();
+            let validation_rules = validation_rules.clone();
+            async move {
+                let file_name = page.file_name.clone();
+                let result = encoding_pool
+                    .run(move || {
+                        match image_validation::validate_image_bytes(
+                            &page.bytes,
+                            page.content_type.as_deref(),
+                            &validation_rules,
+                        ) {
+                            Ok(img) => {
+                                image_encoding::convert_image_to_avif_tiles(
+                                    &img,
+                                    max_tile_height,
+                                )
+                                .map(Ok)
+                            }
+                            Err(rejection) => Ok(Err(rejection)),
+                        }
+                    })
+                    .await;
+                (file_name, result)
+            }
+        });
+
+        let results = futures::future::join_all(encode_tasks).await;
+
+        let mut encoded_pages = Vec::with_capacity(results.len());
+        for (index, (file_name, result)) in results.into_iter().enumerate() {
+            match result {
+                Ok(Ok(tiles)) => encoded_pages.push(tiles),
+                Ok(Err(rejection)) => {
+                    return Err(ChapterError::BadRequest(format!(
+                        "Page {} ({}) was rejected: {}",
+                        index + 1,
+                        file_name,
+                        rejection
+                    )));
+                }
+                Err(e) => return Err(ChapterError::Internal(e)),
+            }
+        }
+
+        Ok(encoded_pages)
+    }
+
+    // Manual page keys carry a random suffix: chapters can be renumbered,
+    // and a replaced page must not be served from a stale CDN cache.
+    async fn upload_pages(
+        &self,
+        key_prefix: &str,
+        first_page_index: usize,
+        encoded_pages: Vec<Vec<EncodedTile>>,
+    ) -> anyhow::Result<Vec<Vec<(String, UploadedVariants)>>> {
+        let upload_tasks =
+            encoded_pages.into_iter().enumerate().map(|(index, tiles)| {
+                let page_key = format!(
+                    "{}/{:03}-{}",
+                    key_prefix,
+                    first_page_index + index,
+                    &Uuid::new_v4().simple().to_string()[..8]
+                );
+                async move {
+                    coordinator::upload_page_tiles(
+                        &self.storage_client,
+                        &page_key,
+                        tiles,
+                    )
+                    .await
+                }
+            });
+
+        let results = futures::future::join_all(upload_tasks).await;
+
+        let mut stored_pages = Vec::with_capacity(results.len());
+        let mut first_error = None;
+        for result in results {
+            match result {
+                Ok(stored_tiles) => stored_pages.push(stored_tiles),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        if let Some(e) = first_error {
+            // Without every page the upload fails, the stored pages would be orphaned
+            let uploaded_keys: Vec<String> = stored_pages
+                .iter()
+                .flat_map(|stored_tiles| coordinator::tile_keys(stored_tiles))
+                .collect();
+            if let Err(cleanup_err) = self
+                .storage_client
+                .delete_image_objects(&uploaded_keys)
+                .await
+            {
+                eprintln!(
+                    "[CHAPTER] Failed to remove uploaded pages: {}",
+                    cleanup_err
+                );
+            }
+            return Err(e.context("Failed to upload chapter pages"));
+        }
+
+        Ok(stored_pages)
+    }
+
+    // Storage cleanup is best effort, the page is already gone from the chapter
+    async fn delete_page_objects(&self, image_keys: Vec<String>) {
+        let mut object_keys = image_keys.clone();
+
+        match self.db_service.get_image_variant_sets(&image_keys).await {
+            Ok(variant_sets) => object_keys.extend(
+                variant_sets
+                    .values()
+                    .flat_map(|set| set.object_keys().map(str::to_string)),
+            ),
+            Err(e) => {
+                eprintln!("[CHAPTER] Failed to get image variant keys: {}", e)
+            }
+        }
+
+        if let Err(e) =
+            self.storage_client.delete_image_objects(&object_keys).await
+        {
+            eprintln!("[CHAPTER] Failed to delete page objects: {}", e);
+            return;
+        }
+
+        if let Err(e) =
+            self.db_service.delete_image_variant_sets(&image_keys).await
+        {
+            eprintln!(
+                "[CHAPTER] Failed to delete image variant records: {}",
+                e
+            );
+        }
+    }
+}
+
+// Cheap checks of every page before a chapter is accepted, the full decode
+// happens once when the page is encoded
+fn validate_page_headers(
+    pages: Vec<UploadedPage>,
+) -> Result<Vec<UploadedPage>, ChapterError> {
+    // Uploads have no site specific placeholder images
+    let validation_rules = ImageValidationRules::from_env(&[]);
+
+    for (index, page) in pages.iter().enumerate() {
+        if let Err(rejection) = image_validation::check_image_header(
+            &page.bytes,
+            page.content_type.as_deref(),
+            &validation_rules,
+        ) {
+            return Err(ChapterError::BadRequest(format!(
+                "Page {} ({}) was rejected: {}",
+                index + 1,
+                page.file_name,
+                rejection
+            )));
+        }
+    }
+
+    Ok(pages)
+}
+
+// `series/{slug}/ch-5/000.avif` becomes `series/{slug}/ch-6/000.avif`,
+// keys outside the chapter's folder are left alone
+fn renumbered_object_key(
+    key: &str,
+    old_number: f32,
+    new_number: f32,
+) -> Option<String> {
+    let old_segment =
+        format!("/{}/", coordinator::chapter_key_segment(old_number));
+    let new_segment =
+        format!("/{}/", coordinator::chapter_key_segment(new_number));
+
+    key.contains(&old_segment)
+        .then(|| key.replacen(&old_segment, &new_segment, 1))
+}
+
+// Pages of an archived series live under the archive prefix, edits would mix both
+fn ensure_not_archived(series: &Series) -> Result<(), ChapterError> {
+    if series.processing_status.is_archived() {
+        return Err(ChapterError::Conflict(
+            "Series is archived, restore it before editing chapters"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Read the page images of a ZIP/CBZ archive, sorted by file name in natural
+/// order (`2.jpg` before `10.jpg`). Folders, hidden files and non-images are skipped.
+pub fn extract_archive_pages<R: Read + Seek>(
+    archive: R,
+) -> Result<Vec<UploadedPage>, ChapterError> {
+    let invalid_archive = |e: zip::result::ZipError| {
+        ChapterError::BadRequest(format!("Invalid archive: {}", e))
+    };
+
+    let mut archive = zip::ZipArchive::new(archive).map_err(invalid_archive)?;
+
+    let mut pages = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(invalid_archive)?;
+
+        if !entry.is_file() || !is_archive_page(entry.name()) {
+            continue;
+        }
+
+        if pages.len() >= MAX_CHAPTER_PAGES {
+            return Err(ChapterError::BadRequest(format!(
+                "A chapter can have at most {} pages",
+                MAX_CHAPTER_PAGES
+            )));
+        }
+
+        if entry.size() > MAX_ARCHIVE_ENTRY_BYTES {
+            return Err(ChapterError::BadRequest(format!(
+                "Archive entry '{}' is too large",
+                entry.name()
+            )));
+        }
+
+        let file_name = entry.name().to_string();
+        let mut bytes = Vec::with_capacity(entry.size() as usize);
+        // The declared size can lie, so the read itself is capped as well
+        entry
+            .by_ref()
+            .take(MAX_ARCHIVE_ENTRY_BYTES + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|e| {
+                ChapterError::BadRequest(format!(
+                    "Failed to read '{}' from archive: {}",
+                    file_name, e
+                ))
+            })?;
+
+        if bytes.len() as u64 > MAX_ARCHIVE_ENTRY_BYTES {
+            return Err(ChapterError::BadRequest(format!(
+                "Archive entry '{}' is too large",
+                file_name
+            )));
+        }
+
+        pages.push(UploadedPage {
+            file_name,
+            bytes,
+            content_type: None,
+        });
+    }
+
+    pages.sort_by(|a, b| natural_cmp(&a.file_name, &b.file_name));
+
+    Ok(pages)
+}
+
+fn is_archive_page(path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+
+    // macOS resource forks and hidden files are not pages
+    if path.starts_with("__MACOSX/") || file_name.starts_with('.') {
+        return false;
+    }
+
+    file_name.rsplit_once('.').is_some_and(|(_, ext)| {
+        ARCHIVE_IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str())
+    })
+}
+
+/// Compare file names so that digit runs are ordered by their numeric value,
+/// ignoring letter case: `page2.png` < `Page10.png`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_char), Some(b_char))
+                if a_char.is_ascii_digit() && b_char.is_ascii_digit() =>
+            {
+                let take_number = |chars: &mut std::iter::Peekable<
+                    std::str::Chars,
+                >| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits.trim_start_matches('0').to_string()
+                };
+                let a_number = take_number(&mut a_chars);
+                let b_number = take_number(&mut b_chars);
+
+                // Without leading zeros, a longer digit run is a bigger number
+                let ordering = a_number
+                    .len()
+                    .cmp(&b_number.len())
+                    .then_with(|| a_number.cmp(&b_number));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(a_char), Some(b_char)) => {
+                let ordering = a_char
+                    .to_ascii_lowercase()
+                    .cmp(&b_char.to_ascii_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn test_natural_cmp_orders_numbers_by_value() {
+        let mut names =
+            vec!["10.jpg", "page-2.png", "2.jpg", "Page-10.png", "01.jpg"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec!["01.jpg", "2.jpg", "10.jpg", "page-2.png", "Page-10.png"]
+        );
+    }
+
+    #[test]
+    fn test_renumbered_object_key() {
+        assert_eq!(
+            renumbered_object_key("series/one-piece/ch-5/000.avif", 5.0, 6.5)
+                .as_deref(),
+            Some("series/one-piece/ch-6-5/000.avif")
+        );
+        // ch-5 must not match the ch-50 folder
+        assert_eq!(
+            renumbered_object_key("series/one-piece/ch-50/000.avif", 5.0, 6.0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_validate_page_headers_rejects_bad_page() {
+        let pages = vec![UploadedPage {
+            file_name: "001.jpg".to_string(),
+            bytes: b"<html>Not found</html>".to_vec(),
+            content_type: None,
+        }];
+
+        let err = validate_page_headers(pages).err().unwrap();
+        assert!(matches!(err, ChapterError::BadRequest(message)
+            if message.starts_with("Page 1 (001.jpg)")));
+    }
+
+    #[test]
+    fn test_extract_archive_pages_skips_non_pages() {
+        let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for name in [
+            "chapter/10.jpg",
+            "chapter/2.jpg",
+            "chapter/info.txt",
+            "__MACOSX/chapter/._2.jpg",
+            "chapter/.DS_Store",
+        ] {
+            archive
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            archive.write_all(b"data").unwrap();
+        }
+        let bytes = archive.finish().unwrap().into_inner();
+
+        let pages = extract_archive_pages(Cursor::new(bytes)).unwrap();
+        let names: Vec<&str> =
+            pages.iter().map(|page| page.file_name.as_str()).collect();
+        assert_eq!(names, vec!["chapter/2.jpg", "chapter/10.jpg"]);
+    }
+}
@@ -1,14 +1,16 @@
 use anyhow::Result;
 use reqwest::Client;
 use slug::slugify;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
-use tokio::task;
 
 use crate::common::utils::random_sleep_time;
 use crate::database::storage::StorageClient;
 use crate::database::{ChapterStatus, DatabaseService, Series};
-use crate::encoding::image_encoding;
+use crate::encoding::encoding_pool::EncodingPool;
+use crate::encoding::image_encoding::{self, EncodedTile, ResponsiveVariants};
+use crate::encoding::image_validation::{self, ImageValidationRules};
 use crate::scraping::model::SiteScrapingConfig;
 use crate::scraping::{fetcher, parser};
 
@@ -18,6 +20,7 @@ pub async fn process_series_chapters_from_list(
     chapters_to_process: &[parser::ChapterInfo],
     http_client: &Client,
     storage_client: Arc<StorageClient>,
+    encoding_pool: &EncodingPool,
     config: &SiteScrapingConfig,
     db_service: &DatabaseService,
 ) -> Result<Option<f32>> {
@@ -34,6 +37,7 @@ pub async fn process_series_chapters_from_list(
             chapter_info,
             http_client,
             storage_client.clone(),
+            encoding_pool,
             config,
             db_service,
         )
@@ -66,6 +70,7 @@ pub async fn process_single_chapter(
     chapter_info: &parser::ChapterInfo,
     http_client: &Client,
     storage_client: Arc<StorageClient>,
+    encoding_pool: &EncodingPool,
     config: &SiteScrapingConfig,
     db_service: &DatabaseService,
 ) -> Result<Option<f32>> {
@@ -114,83 +119,109 @@ pub async fn process_single_chapter(
         return Ok(None);
     }
 
+    // Limits concurrent downloads from the source site for this chapter.
+    // CPU-bound encoding is bounded separately by the shared encoding pool.
     let semaphore = Arc::new(Semaphore::new(2));
-    let series_slug = slugify(&series.title);
+    let chapter_key_prefix =
+        chapter_key_prefix(&series.title, chapter_info.number);
+    let max_tile_height = image_encoding::max_tile_height_from_env();
+    let validation_rules = Arc::new(ImageValidationRules::from_env(
+        &config.placeholder_image_hashes,
+    ));
     let mut processing_tasks = Vec::new();
 
+    // Failures of a previous attempt at this chapter are no longer relevant
+    if let Err(e) = db_service.clear_chapter_image_failures(chapter_id).await {
+        eprintln!(
+            "[COORDINATOR] Failed to clear old page failures for chapter {}: {}",
+            chapter_id, e
+        );
+    }
+
     // Process image
-    for (index, img_url) in image_urls.into_iter().enumerate() {
+    for (index, img_url) in image_urls.iter().cloned().enumerate() {
         let http_client = http_client.clone();
         let storage_client = storage_client.clone();
-        let series_slug = series_slug.clone();
+        let chapter_key_prefix = chapter_key_prefix.clone();
         let chapter_number_str = convert_chapter_number.clone();
         let permit_semaphore = Arc::clone(&semaphore);
+        let encoding_pool = encoding_pool.clone();
+        let validation_rules = validation_rules.clone();
 
         let task = tokio::spawn(async move {
             // This will wait until a permit is available from the semaphore
-            let _permit = permit_semaphore.acquire_owned().await.unwrap();
+            let permit = permit_semaphore.acquire_owned().await.unwrap();
 
             // Pause random delay before task
             //random_sleep_time(1, 2).await;
 
-            // The processing pipeline: fetch -> encode -> upload
-            let image_bytes = match fetcher::fetch_image_bytes(
-                &http_client,
-                &img_url,
-            )
-            .await
+            // The processing pipeline: fetch -> validate -> encode -> upload
+            let fetched = match fetcher::fetch_image(&http_client, &img_url)
+                .await
             {
-                Ok(bytes) => bytes,
+                Ok(fetched) => fetched,
                 Err(e) => {
                     eprintln!(
                         "[COORDINATOR-TASK][Ch:{}/Img:{}] Failed to fetch {}: {}",
                         chapter_number_str, index, img_url, e
                     );
-                    return (index, Err(anyhow::anyhow!("Fetch failed")));
+                    return Err(PageFailure::new("fetch_failed", e));
                 }
             };
 
-            let avif_bytes = match task::spawn_blocking(move || {
-                image_encoding::covert_image_bytes_to_avif(&image_bytes)
-            })
-            .await
-            {
-                Ok(Ok(bytes)) => bytes,
-                Ok(Err(e)) => {
-                    return (
-                        index,
-                        Err(anyhow::anyhow!("Encoding failed: {}", e)),
+            // Validation decodes the image, so it shares the encoding job.
+            // Very tall strips come back as several ordered tiles
+            let encode_result = encoding_pool
+                .run(move || {
+                    match image_validation::validate_image_bytes(
+                        &fetched.bytes,
+                        fetched.content_type.as_deref(),
+                        &validation_rules,
+                    ) {
+                        Ok(img) => image_encoding::convert_image_to_avif_tiles(
+                            &img,
+                            max_tile_height,
+                        )
+                        .map(Ok),
+                        Err(rejection) => Ok(Err(rejection)),
+                    }
+                })
+                .await;
+
+            // Holding the permit until the page is encoded keeps at most
+            // two downloaded pages of this chapter in memory
+            drop(permit);
+
+            let avif_tiles = match encode_result {
+                Ok(Ok(tiles)) => tiles,
+                Ok(Err(rejection)) => {
+                    eprintln!(
+                        "[COORDINATOR-TASK][Ch:{}/Img:{}] Rejected {}: {}",
+                        chapter_number_str, index, img_url, rejection
                     );
+                    return Err(PageFailure::new(rejection.code(), rejection));
                 }
                 Err(e) => {
-                    return (
-                        index,
-                        Err(anyhow::anyhow!("Encoding task panicked: {}", e)),
-                    );
+                    return Err(PageFailure::new("encode_failed", e));
                 }
             };
 
             // Define the key for the object in R2
             // domain/{series-name}/{chapter-number}/{image-number}.avif
-            let object_key = format!(
-                "series/{}/ch-{}/{:03}.avif",
-                series_slug, chapter_number_str, index
-            );
+            let page_key = format!("{}/{:03}", chapter_key_prefix, index);
 
-            // Upload to R2
-            if let Err(e) = storage_client
-                .upload_image_series_objects(
-                    &object_key,
-                    avif_bytes,
-                    "image/avif",
-                )
-                .await
-            {
-                eprintln!("[TASK] Failed to upload to R2: {}", e);
-                return (index, Err(anyhow::anyhow!("Upload failed")));
-            }
+            let stored_tiles =
+                match upload_page_tiles(&storage_client, &page_key, avif_tiles)
+                    .await
+                {
+                    Ok(stored_tiles) => stored_tiles,
+                    Err(e) => {
+                        eprintln!("[TASK] Failed to upload to R2: {}", e);
+                        return Err(PageFailure::new("upload_failed", e));
+                    }
+                };
 
-            (index, Ok(object_key))
+            Ok(stored_tiles)
         });
 
         processing_tasks.push(task);
@@ -198,16 +229,24 @@ pub async fn process_single_chapter(
 
     let task_results = futures::future::join_all(processing_tasks).await;
 
-    let mut successful_uploads: Vec<(usize, String)> = Vec::new();
-    for result in task_results {
+    let mut successful_uploads: Vec<(usize, Vec<(String, UploadedVariants)>)> =
+        Vec::new();
+    let mut page_failures: Vec<(usize, PageFailure)> = Vec::new();
+
+    // Tasks were spawned in page order, so the position is the page index
+    for (index, result) in task_results.into_iter().enumerate() {
         match result {
             // Task complete
-            Ok((index, Ok(object_key))) => {
-                successful_uploads.push((index, object_key));
+            Ok(Ok(stored_tiles)) => {
+                successful_uploads.push((index, stored_tiles));
             }
             // Task run but return error
-            Ok((_, Err(task_err))) => {
-                eprintln!("[COORDINATOR] Error processing task: {}", task_err);
+            Ok(Err(failure)) => {
+                eprintln!(
+                    "[COORDINATOR] Error processing page {}: {}",
+                    index, failure.detail
+                );
+                page_failures.push((index, failure));
             }
             // Task panicked
             Err(join_err) => {
@@ -215,33 +254,45 @@ pub async fn process_single_chapter(
                     "[COORDINATOR] Processing task panicked: {}",
                     join_err
                 );
+                page_failures
+                    .push((index, PageFailure::new("task_panicked", join_err)));
             }
         }
     }
 
-    // Sort the successful results by their original index to ensure correct page order
-    successful_uploads.sort_by_key(|(index, _)| *index);
-
-    // Perform the database writes sequentially and in the correct order
-    for (original_index, key_to_save) in &successful_uploads {
-        // Save CDN object key to the database if successful
-        if db_service
-            .add_chapter_images(
+    // Record why each missing page failed
+    for (index, failure) in &page_failures {
+        if let Err(e) = db_service
+            .add_chapter_image_failure(
                 chapter_id,
-                (*original_index + 1) as i32,
-                key_to_save,
+                (*index + 1) as i32,
+                &image_urls[*index],
+                failure.reason,
+                Some(&failure.detail),
             )
             .await
-            .is_err()
         {
             eprintln!(
-                "[COORDINATOR] Failed to save image record to DB for key: {}",
-                key_to_save
+                "[COORDINATOR] Failed to record failure of page {}: {}",
+                index, e
             );
-            // This specific DB write failed, but we continue with the others.
         }
     }
 
+    // Perform the database writes sequentially and in the correct order.
+    // Tiles get their own consecutive image_order so the reader keeps the page flow.
+    let mut next_image_order = 1;
+    for (original_index, stored_tiles) in &successful_uploads {
+        next_image_order = record_page_tiles(
+            db_service,
+            chapter_id,
+            (*original_index + 1) as i32,
+            next_image_order,
+            stored_tiles,
+        )
+        .await;
+    }
+
     let image_saved_count = successful_uploads.len();
 
     println!(
@@ -255,7 +306,11 @@ pub async fn process_single_chapter(
     ) {
         (true, true) => {
             db_service
-                .update_chapter_status(chapter_id, ChapterStatus::Available)
+                .update_chapter_status(
+                    chapter_id,
+                    ChapterStatus::Available,
+                    None,
+                )
                 .await?;
 
             if let Err(e) = db_service
@@ -271,17 +326,278 @@ pub async fn process_single_chapter(
         }
         // Partial/Incomplete chapter images
         (true, false) => {
+            let status_reason =
+                summarize_page_failures(&page_failures, total_image_found);
             db_service
-                .update_chapter_status(chapter_id, ChapterStatus::Error)
+                .update_chapter_status(
+                    chapter_id,
+                    ChapterStatus::Error,
+                    Some(&status_reason),
+                )
                 .await?;
             Ok(None)
         }
         // No images found
         (false, _) => {
             db_service
-                .update_chapter_status(chapter_id, ChapterStatus::NoImagesFound)
+                .update_chapter_status(
+                    chapter_id,
+                    ChapterStatus::NoImagesFound,
+                    None,
+                )
                 .await?;
             Ok(None)
         }
     }
 }
+
+/// Object key prefix shared by every page of a chapter: `series/{slug}/ch-{n}`
+pub fn chapter_key_prefix(series_title: &str, chapter_number: f32) -> String {
+    format!(
+        "series/{}/{}",
+        slugify(series_title),
+        chapter_key_segment(chapter_number)
+    )
+}
+
+/// The chapter part of a page key, `ch-12-5` for chapter 12.5.
+pub fn chapter_key_segment(chapter_number: f32) -> String {
+    format!("ch-{}", chapter_number.to_string().replace('.', "-"))
+}
+
+/// Upload every tile of one source page together with its variants.
+/// Keys are `{page_key}.avif`, or `{page_key}-{tile-number}.avif` for tiled pages.
+pub async fn upload_page_tiles(
+    storage_client: &StorageClient,
+    page_key: &str,
+    tiles: Vec<EncodedTile>,
+) -> Result<Vec<(String, UploadedVariants)>> {
+    let tile_count = tiles.len();
+    let mut stored_tiles = Vec::with_capacity(tile_count);
+
+    for (tile_index, tile) in tiles.into_iter().enumerate() {
+        let object_key = if tile_count > 1 {
+            format!("{}-{:02}.avif", page_key, tile_index)
+        } else {
+            format!("{}.avif", page_key)
+        };
+
+        // A page is stored completely or not at all
+        if let Err(e) = storage_client
+            .upload_image_series_objects(&object_key, tile.avif, "image/avif")
+            .await
+        {
+            discard_uploaded_objects(storage_client, &tile_keys(&stored_tiles))
+                .await;
+            return Err(e);
+        }
+
+        // Responsive variants are stored next to the full-size tile
+        match upload_image_variants(storage_client, &object_key, tile.variants)
+            .await
+        {
+            Ok(variants) => stored_tiles.push((object_key, variants)),
+            Err(e) => {
+                let mut uploaded_keys = tile_keys(&stored_tiles);
+                uploaded_keys.push(object_key);
+                discard_uploaded_objects(storage_client, &uploaded_keys).await;
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(stored_tiles)
+}
+
+/// Every object key of the given stored tiles, variants included.
+pub fn tile_keys(stored_tiles: &[(String, UploadedVariants)]) -> Vec<String> {
+    stored_tiles
+        .iter()
+        .flat_map(|(key, variants)| {
+            std::iter::once(key.clone()).chain(variants.object_keys())
+        })
+        .collect()
+}
+
+// Best effort removal of the objects uploaded before a later upload failed
+async fn discard_uploaded_objects(
+    storage_client: &StorageClient,
+    keys: &[String],
+) {
+    if let Err(e) = storage_client.delete_image_objects(keys).await {
+        eprintln!(
+            "[COORDINATOR] Failed to remove {} partially uploaded objects: {}",
+            keys.len(),
+            e
+        );
+    }
+}
+
+/// Save the uploaded tiles of one source page as chapter images, starting at
+/// `first_image_order`. Returns the image order following the last tile.
+pub async fn record_page_tiles(
+    db_service: &DatabaseService,
+    chapter_id: i32,
+    source_image_order: i32,
+    first_image_order: i32,
+    stored_tiles: &[(String, UploadedVariants)],
+) -> i32 {
+    let mut image_order = first_image_order;
+
+    for (tile_index, (key_to_save, variants)) in stored_tiles.iter().enumerate()
+    {
+        // Save CDN object key to the database if successful
+        if db_service
+            .add_chapter_images(
+                chapter_id,
+                image_order,
+                key_to_save,
+                source_image_order,
+                tile_index as i32,
+            )
+            .await
+            .is_err()
+        {
+            eprintln!(
+                "[COORDINATOR] Failed to save image record to DB for key: {}",
+                key_to_save
+            );
+            // This specific DB write failed, but we continue with the others.
+        }
+
+        if let Err(e) =
+            record_image_variants(db_service, key_to_save, variants).await
+        {
+            // Reader falls back to the full-size image
+            eprintln!(
+                "[COORDINATOR] Failed to save variants for key {}: {}",
+                key_to_save, e
+            );
+        }
+
+        image_order += 1;
+    }
+
+    image_order
+}
+
+/// Why a single source page could not be stored.
+struct PageFailure {
+    // Short code stored in `chapter_image_failures.reason`
+    reason: &'static str,
+    detail: String,
+}
+
+impl PageFailure {
+    fn new(reason: &'static str, detail: impl std::fmt::Display) -> Self {
+        Self {
+            reason,
+            detail: detail.to_string(),
+        }
+    }
+}
+
+// e.g. "3 of 40 pages failed: fetch_failed (1), truncated (2)"
+fn summarize_page_failures(
+    page_failures: &[(usize, PageFailure)],
+    total_pages: usize,
+) -> String {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for (_, failure) in page_failures {
+        *counts.entry(failure.reason).or_default() += 1;
+    }
+
+    let reasons = counts
+        .iter()
+        .map(|(reason, count)| format!("{} ({})", reason, count))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "{} of {} pages failed: {}",
+        page_failures.len(),
+        total_pages,
+        reasons
+    )
+}
+
+/// Object keys and dimensions of the uploaded responsive variants of an image.
+pub struct UploadedVariants {
+    pub width: i32,
+    pub height: i32,
+    pub placeholder_key: String,
+    // (width, object key)
+    pub sized: Vec<(i32, String)>,
+}
+
+impl UploadedVariants {
+    // Every stored object besides the full-size image
+    pub fn object_keys(&self) -> impl Iterator<Item = String> + '_ {
+        self.sized
+            .iter()
+            .map(|(_, key)| key.clone())
+            .chain(std::iter::once(self.placeholder_key.clone()))
+    }
+}
+
+// Upload the width variants and placeholder next to the full-size `source_key`
+pub async fn upload_image_variants(
+    storage_client: &StorageClient,
+    source_key: &str,
+    variants: ResponsiveVariants,
+) -> Result<UploadedVariants> {
+    let mut sized = Vec::with_capacity(variants.sized.len());
+
+    for (width, avif_bytes) in variants.sized {
+        let variant_key = image_encoding::variant_object_key(source_key, width);
+        if let Err(e) = storage_client
+            .upload_image_series_objects(&variant_key, avif_bytes, "image/avif")
+            .await
+        {
+            let uploaded_keys: Vec<String> =
+                sized.into_iter().map(|(_, key)| key).collect();
+            discard_uploaded_objects(storage_client, &uploaded_keys).await;
+            return Err(e);
+        }
+        sized.push((width as i32, variant_key));
+    }
+
+    let placeholder_key = image_encoding::placeholder_object_key(source_key);
+    if let Err(e) = storage_client
+        .upload_image_series_objects(
+            &placeholder_key,
+            variants.placeholder,
+            "image/avif",
+        )
+        .await
+    {
+        let uploaded_keys: Vec<String> =
+            sized.into_iter().map(|(_, key)| key).collect();
+        discard_uploaded_objects(storage_client, &uploaded_keys).await;
+        return Err(e);
+    }
+
+    Ok(UploadedVariants {
+        width: variants.width as i32,
+        height: variants.height as i32,
+        placeholder_key,
+        sized,
+    })
+}
+
+// Save the uploaded variant keys so handlers can build `srcset` URLs
+pub async fn record_image_variants(
+    db_service: &DatabaseService,
+    source_key: &str,
+    variants: &UploadedVariants,
+) -> Result<()> {
+    db_service
+        .upsert_image_variant_set(
+            source_key,
+            variants.width,
+            variants.height,
+            Some(&variants.placeholder_key),
+            &variants.sized,
+        )
+        .await
+}
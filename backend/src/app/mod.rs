@@ -1,2 +1,4 @@
+pub mod chapter_export;
+pub mod chapter_management;
 pub mod coordinator;
 pub mod orchestrator;
@@ -2,13 +2,13 @@ use crate::app::coordinator;
 use crate::common::utils::random_sleep_time;
 use crate::database::storage::StorageClient;
 use crate::database::{DatabaseService, Series};
+use crate::encoding::encoding_pool::EncodingPool;
 use crate::scraping::fetcher;
 use crate::scraping::model::SitesConfig;
 use crate::scraping::parser::{ChapterInfo, ChapterParser};
 use crate::task_workers::repair_chapter_worker::RepairChapterMsg;
 use anyhow::{Result, anyhow};
 use reqwest::Client;
-use std::env;
 use std::sync::Arc;
 use url::Url;
 
@@ -20,6 +20,7 @@ pub async fn run_series_check(
     db_service: &DatabaseService,
     sites_config: Arc<SitesConfig>,
     storage_client: Arc<StorageClient>,
+    encoding_pool: &EncodingPool,
 ) -> Result<()> {
     println!("[SERIES CHECK] Starting for series: '{}'", series.title);
 
@@ -142,6 +143,7 @@ pub async fn run_series_check(
             &chapters_to_scrape,
             &http_client,
             storage_client,
+            encoding_pool,
             site_config,
             db_service,
         )
@@ -170,6 +172,7 @@ pub async fn repair_specific_chapter_series(
     storage_client: Arc<StorageClient>,
     http_client: Client,
     sites_config: Arc<SitesConfig>,
+    encoding_pool: &EncodingPool,
 ) -> Result<()> {
     println!(
         "[REPAIR] Repairing chapter {} in series {}.",
@@ -183,6 +186,13 @@ pub async fn repair_specific_chapter_series(
             anyhow!("Series with ID {} not found.", msg.series_id)
         })?;
 
+    if series.processing_status.is_archived() {
+        return Err(anyhow!(
+            "Series {} is archived, restore it before repairing chapters.",
+            msg.series_id
+        ));
+    }
+
     let image_urls_to_delete = db_service
         .get_images_urls_for_chapter_series(msg.series_id, msg.chapter_number)
         .await?;
@@ -193,17 +203,29 @@ pub async fn repair_specific_chapter_series(
             image_urls_to_delete.len()
         );
 
-        let public_cdn_url = env::var("PUBLIC_CDN_URL")?;
         let key_to_delete: Vec<String> = image_urls_to_delete
-            .into_iter()
-            .filter_map(|url| {
-                url.strip_prefix(&format!("{}/", public_cdn_url))
-                    .map(String::from)
-            })
+            .iter()
+            .filter_map(|value| storage_client.object_key(value))
             .collect();
 
         storage_client.delete_image_objects(&key_to_delete).await?;
         println!("[REPAIR] Deleted images from storage");
+
+        // Variants are keyed by the stored object key of each page tile
+        let variant_sets = db_service
+            .get_image_variant_sets(&image_urls_to_delete)
+            .await?;
+        let variant_keys: Vec<String> = variant_sets
+            .values()
+            .flat_map(|set| set.object_keys())
+            .map(String::from)
+            .collect();
+
+        storage_client.delete_image_objects(&variant_keys).await?;
+        db_service
+            .delete_image_variant_sets(&image_urls_to_delete)
+            .await?;
+        println!("[REPAIR] Deleted image variants from storage");
     }
 
     db_service
@@ -232,6 +254,7 @@ pub async fn repair_specific_chapter_series(
         &chapter_info_to_scrape,
         &http_client,
         storage_client,
+        encoding_pool,
         site_config,
         db_service,
     )
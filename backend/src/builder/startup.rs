@@ -1,20 +1,35 @@
 use crate::api;
+use crate::app::chapter_export::ExportLimiter;
 use crate::builder::config_sites_watcher::config_sites_watcher;
+use crate::common::image_urls::ImageUrlSigner;
+use crate::common::jwt::{
+    JwtKeyRotationPolicy, reload_signing_keys, rotate_signing_keys,
+};
+use crate::common::oidc::OidcProviders;
+use crate::common::rate_limit::{
+    LoginLockoutPolicy, RateLimitPolicy, RateLimiter, enforce_rate_limit,
+};
+use crate::common::user_cache::UserCache;
 use crate::database::DatabaseService;
-use crate::database::storage::StorageClient;
+use crate::database::storage::{LOCAL_STORAGE_ROUTE, StorageClient};
+use crate::encoding::encoding_pool::EncodingPool;
 use crate::scraping::model::SitesConfig;
 use crate::task_workers::channels::{OnDemandChannels, setup_worker_channels};
+use crate::task_workers::delete_series_worker::series_deletion_delay_from_env;
 use arc_swap::ArcSwap;
 use axum::http::{HeaderValue, Method, header};
+use axum::middleware;
 use axum::{Router, serve};
 use lettre::AsyncSmtpTransport;
 use reqwest::Client;
 use std::env;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::signal;
 use tower::ServiceBuilder;
+use tower_http::services::ServeDir;
 use tower_http::timeout::TimeoutLayer;
 use tower_http::{compression::CompressionLayer, cors::CorsLayer};
 
@@ -25,12 +40,36 @@ pub type Mailer = AsyncSmtpTransport<lettre::Tokio1Executor>;
 pub struct AppState {
     pub db_service: DatabaseService,
     pub mailer: Mailer,
+    #[allow(dead_code)]
     pub http_client: Client,
+    #[allow(dead_code)]
     pub sites_config: Arc<ArcSwap<SitesConfig>>,
     pub storage_client: Arc<StorageClient>,
+    pub encoding_pool: EncodingPool,
+    pub export_limiter: ExportLimiter,
+    pub image_url_signer: ImageUrlSigner,
     pub worker_channels: OnDemandChannels,
+    pub series_deletion_delay: Duration,
+    pub login_lockout: LoginLockoutPolicy,
+    // Reset emails per account, on top of the per-address limit of the route
+    pub password_reset_limiter: RateLimiter,
+    // Failed logins per client address, see `LOGIN_FAILURE_RATE_LIMIT`
+    pub login_failure_limiter: RateLimiter,
+    pub oidc_providers: OidcProviders,
+    pub user_cache: UserCache,
 }
 
+// Requests per client address to the whole backend, the route groups add stricter limits
+const GLOBAL_RATE_LIMIT: RateLimitPolicy = RateLimitPolicy::new(600, 60);
+// Image requests per client address, counted apart from the API (`RATE_LIMIT_IMAGES`)
+const IMAGE_RATE_LIMIT: RateLimitPolicy = RateLimitPolicy::new(3000, 60);
+const PASSWORD_RESET_ACCOUNT_RATE_LIMIT: RateLimitPolicy =
+    RateLimitPolicy::new(3, 60 * 60);
+// Failed logins per client address (`RATE_LIMIT_LOGIN_FAILURES`). Below the account
+// lockout threshold, an address that keeps guessing is slowed down before it locks anyone out.
+const LOGIN_FAILURE_RATE_LIMIT: RateLimitPolicy =
+    RateLimitPolicy::new(5, 15 * 60);
+
 // Function to set up builder and server
 pub async fn run(
     listener: TcpListener,
@@ -38,10 +77,28 @@ pub async fn run(
     mailer: Mailer,
     http_client: Client,
 ) -> anyhow::Result<()> {
-    let storage_client_env = StorageClient::new_from_env().await?;
+    let db_service = DatabaseService::new(db_pool);
+
+    // Access tokens can only be issued once a signing key is loaded
+    rotate_signing_keys(&db_service, JwtKeyRotationPolicy::from_env()).await?;
+    reload_signing_keys(&db_service).await?;
+
+    let storage_client_env = StorageClient::new_from_env()
+        .await?
+        .with_usage_tracking(db_service.clone());
     let storage_client = Arc::new(storage_client_env);
 
-    let db_service = DatabaseService::new(db_pool);
+    // Older rows keep absolute URLs, only the configured CDN is ours to rewrite
+    if let Some(rewritten) = db_service
+        .migrate_stored_urls_to_object_keys(storage_client.domain_cdn_url())
+        .await?
+    {
+        println!(
+            "[STARTUP] Rewrote {} stored CDN URLs into object keys.",
+            rewritten
+        );
+    }
+    let image_url_signer = ImageUrlSigner::new_from_env(&storage_client)?;
 
     let config_path = "backend/config_sites.toml".to_string();
     let load_sites_config = Arc::new(SitesConfig::load(&config_path)?);
@@ -50,12 +107,16 @@ pub async fn run(
 
     tokio::spawn(config_sites_watcher(config_path, sites_config.clone()));
 
+    // Shared by scraping workers and upload handlers for CPU-bound encoding
+    let encoding_pool = EncodingPool::new_from_env();
+
     // Create channels
     let worker_channels = setup_worker_channels(
         db_service.clone(),
         storage_client.clone(),
         http_client.clone(),
         sites_config.clone(),
+        encoding_pool.clone(),
     );
 
     // Create AppState
@@ -65,7 +126,22 @@ pub async fn run(
         http_client,
         sites_config,
         storage_client,
+        encoding_pool,
+        export_limiter: ExportLimiter::new_from_env(),
+        image_url_signer,
         worker_channels,
+        series_deletion_delay: series_deletion_delay_from_env(),
+        login_lockout: LoginLockoutPolicy::from_env(),
+        password_reset_limiter: RateLimiter::from_env(
+            "PASSWORD_RESET_ACCOUNT",
+            PASSWORD_RESET_ACCOUNT_RATE_LIMIT,
+        ),
+        login_failure_limiter: RateLimiter::from_env(
+            "LOGIN_FAILURES",
+            LOGIN_FAILURE_RATE_LIMIT,
+        ),
+        oidc_providers: OidcProviders::new_from_env()?,
+        user_cache: UserCache::from_env(),
     };
 
     // CORS Configuration
@@ -92,27 +168,56 @@ pub async fn run(
             header::CONTENT_TYPE,
             header::COOKIE,
             header::ORIGIN,
-        ]);
+        ])
+        // Rate limited clients read when to retry
+        .expose_headers([header::RETRY_AFTER]);
 
     // Setup App router
     // Initialize the router and attach the authentication routes
-    let app = Router::new()
-        .merge(api::routes::routes())
+    let mut router = Router::new().merge(api::routes::routes()).layer(
+        middleware::from_fn_with_state(
+            RateLimiter::from_env("GLOBAL", GLOBAL_RATE_LIMIT),
+            enforce_rate_limit,
+        ),
+    );
+
+    // Images are added after the global limit, they have their own
+    let image_limiter = RateLimiter::from_env("IMAGES", IMAGE_RATE_LIMIT);
+    router = router.merge(api::routes::image_routes(image_limiter.clone()));
+
+    // Without a CDN in front, stored objects are served by the backend itself
+    if let Some(local_root) = app_state.storage_client.local_root() {
+        router = router.nest_service(
+            LOCAL_STORAGE_ROUTE,
+            ServiceBuilder::new()
+                .layer(middleware::from_fn_with_state(
+                    image_limiter,
+                    enforce_rate_limit,
+                ))
+                .service(ServeDir::new(local_root)),
+        );
+    }
+
+    // CORS is the outer layer, so 429 responses and preflights carry its headers
+    let app = router
         .layer(
             ServiceBuilder::new()
                 .layer(CompressionLayer::new())
-                // TODO: rate limiting
-                .layer(TimeoutLayer::new(Duration::from_secs(30)))
-                .layer(cors),
+                .layer(cors)
+                .layer(TimeoutLayer::new(Duration::from_secs(30))),
         )
         .with_state(app_state);
 
     println!("[STARTUP] Server started successfully!");
 
     // Run server
-    serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    // Peer addresses are recorded with login sessions
+    serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
 
     Ok(())
 }
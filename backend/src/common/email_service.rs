@@ -47,3 +47,42 @@ pub async fn send_password_reset_email(
 
     Ok(())
 }
+
+// Function to send the email address verification link
+pub async fn send_verification_email(
+    mailer: &Mailer,
+    recipient_email: &str,
+    recipient_username: &str,
+    token: &str,
+) -> anyhow::Result<()> {
+    let frontend_url =
+        env::var("FRONTEND_URL").expect("FRONTEND_URL must be set");
+    let app_email = env::var("SMTP_USERNAME").expect("APP_EMAIL must be set");
+
+    let verification_link =
+        format!("{}/verify-email?token={}", frontend_url, token);
+
+    let from_mailbox: Mailbox = format!("Your App Name <{}>", app_email)
+        .parse()
+        .context("Failed to parse sender email")?;
+    let to_mailbox: Mailbox = recipient_email
+        .parse()
+        .context("Failed to parse recipient email")?;
+
+    let email = Message::builder()
+        .from(from_mailbox)
+        .to(to_mailbox)
+        .subject("Verify your email address")
+        .body(format!(
+            "Hello {}, \n\nPlease confirm your email address by clicking the following link below:\n{}\n\nThe link is valid for 24 hours. If you did not create an account, please ignore this email.",
+            recipient_username,
+            verification_link
+        ))
+        .context("Failed to build email message")?;
+
+    mailer.send(email).await.context("Failed to send email")?;
+
+    println!("[EMAIL] Verification email sent to {}", recipient_email);
+
+    Ok(())
+}
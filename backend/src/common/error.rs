@@ -1,5 +1,9 @@
-use axum::{Json, http::StatusCode};
+use axum::Json;
+use axum::http::{StatusCode, header};
 use axum_core::response::{IntoResponse, Response};
+use axum_extra::extract::multipart::MultipartError;
+use chrono::{DateTime, Utc};
+use std::fmt;
 
 // Custom error type definition
 pub enum AuthError {
@@ -10,7 +14,24 @@ pub enum AuthError {
     TokenCreation,
     InvalidRefreshToken,
     InvalidCharacter(String),
-    UserAlreadyExists { field: String },
+    UserAlreadyExists {
+        field: String,
+    },
+    EmailNotVerified,
+    Forbidden,
+    InvalidTwoFactorCode,
+    TwoFactorRequired,
+    AccountBanned {
+        reason: Option<String>,
+    },
+    AccountSuspended {
+        until: DateTime<Utc>,
+        reason: Option<String>,
+    },
+    PasswordResetRequired,
+    TooManyRequests {
+        retry_after_secs: u64,
+    },
     InternalServerError,
 }
 
@@ -39,6 +60,43 @@ impl IntoResponse for AuthError {
             AuthError::InternalServerError => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
             }
+            AuthError::EmailNotVerified => {
+                (StatusCode::FORBIDDEN, "Email address not verified")
+            }
+            AuthError::Forbidden => {
+                (StatusCode::FORBIDDEN, "Insufficient permissions")
+            }
+            AuthError::InvalidTwoFactorCode => {
+                (StatusCode::UNAUTHORIZED, "Invalid two-factor code")
+            }
+            AuthError::TwoFactorRequired => {
+                (StatusCode::FORBIDDEN, "Two-factor authentication required")
+            }
+            AuthError::PasswordResetRequired => {
+                (StatusCode::FORBIDDEN, "Password reset required")
+            }
+            AuthError::AccountBanned { reason } => {
+                return (
+                    StatusCode::FORBIDDEN,
+                    Json(serde_json::json!({"message": "Account banned", "reason": reason})),
+                )
+                    .into_response();
+            }
+            AuthError::AccountSuspended { until, reason } => {
+                return (
+                    StatusCode::FORBIDDEN,
+                    Json(serde_json::json!({"message": "Account suspended", "suspendedUntil": until, "reason": reason})),
+                )
+                    .into_response();
+            }
+            AuthError::TooManyRequests { retry_after_secs } => {
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                    Json(serde_json::json!({"message": "Too many requests, try again later"})),
+                )
+                    .into_response();
+            }
             AuthError::UserAlreadyExists { field } => {
                 return (
                     StatusCode::CONFLICT,
@@ -66,3 +124,132 @@ impl From<anyhow::Error> for AuthError {
         AuthError::InternalServerError
     }
 }
+
+/// Errors of the manual chapter management endpoints
+#[derive(Debug)]
+pub enum ChapterError {
+    NotFound(&'static str),
+    BadRequest(String),
+    Conflict(String),
+    Internal(anyhow::Error),
+}
+
+impl fmt::Display for ChapterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChapterError::NotFound(message) => write!(f, "{}", message),
+            ChapterError::BadRequest(message)
+            | ChapterError::Conflict(message) => write!(f, "{}", message),
+            ChapterError::Internal(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for ChapterError {}
+
+impl IntoResponse for ChapterError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ChapterError::NotFound(message) => {
+                (StatusCode::NOT_FOUND, message.to_string())
+            }
+            ChapterError::BadRequest(message) => {
+                (StatusCode::BAD_REQUEST, message)
+            }
+            ChapterError::Conflict(message) => (StatusCode::CONFLICT, message),
+            ChapterError::Internal(e) => {
+                eprintln!("[CHAPTER] Internal error: {:#}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error".to_string(),
+                )
+            }
+        };
+
+        (
+            status,
+            Json(serde_json::json!({"status": "error", "message": message})),
+        )
+            .into_response()
+    }
+}
+
+impl From<anyhow::Error> for ChapterError {
+    fn from(e: anyhow::Error) -> Self {
+        ChapterError::Internal(e)
+    }
+}
+
+/// Errors while receiving an uploaded file
+#[derive(Debug)]
+pub enum UploadError {
+    TooLarge { max_bytes: usize },
+    UnsupportedType,
+    TooManyFiles { max_files: usize },
+    Multipart(MultipartError),
+    Storage(anyhow::Error),
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::TooLarge { max_bytes } => write!(
+                f,
+                "File is larger than the {} MB limit",
+                max_bytes / (1024 * 1024)
+            ),
+            UploadError::UnsupportedType => write!(
+                f,
+                "Unsupported file type, expected a JPEG, PNG, WebP, GIF or AVIF image"
+            ),
+            UploadError::TooManyFiles { max_files } => {
+                write!(f, "At most {} files can be uploaded", max_files)
+            }
+            UploadError::Multipart(e) => write!(f, "{}", e.body_text()),
+            UploadError::Storage(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+impl IntoResponse for UploadError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            UploadError::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            UploadError::UnsupportedType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            UploadError::TooManyFiles { .. } => StatusCode::BAD_REQUEST,
+            UploadError::Multipart(e) => e.status(),
+            UploadError::Storage(e) => {
+                eprintln!("[UPLOAD] Failed to store upload: {:#}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({"status": "error", "message": "Failed to store the uploaded file"})),
+                )
+                    .into_response();
+            }
+        };
+
+        (
+            status,
+            Json(serde_json::json!({"status": "error", "message": self.to_string()})),
+        )
+            .into_response()
+    }
+}
+
+impl From<MultipartError> for UploadError {
+    fn from(e: MultipartError) -> Self {
+        UploadError::Multipart(e)
+    }
+}
+
+/// Body stream errors pass through the storage backend unchanged
+impl From<anyhow::Error> for UploadError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<UploadError>() {
+            Ok(upload_error) => upload_error,
+            Err(e) => UploadError::Storage(e),
+        }
+    }
+}
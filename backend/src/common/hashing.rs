@@ -2,6 +2,8 @@ use anyhow::Result;
 use argon2::password_hash::SaltString;
 use argon2::password_hash::{Error as PwHashError, rand_core::OsRng};
 use argon2::{Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
 
 /// Configuration for Argon2 parameters
 struct ArgonConfig {
@@ -73,6 +75,23 @@ pub fn verify_password(
     }
 }
 
+/// Creates an opaque random token (refresh, verification), only its hash is stored.
+pub fn generate_secure_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+/// Hash a secure token is stored and looked up by.
+/// The token has 256 random bits, a fast hash is enough.
+pub fn hash_secure_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -93,4 +112,14 @@ mod tests {
         let is_valid = verify_password(password, &hash).unwrap();
         assert!(is_valid);
     }
+
+    #[test]
+    fn test_secure_token_hash() {
+        let token = generate_secure_token();
+
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, generate_secure_token());
+        assert_eq!(hash_secure_token(&token), hash_secure_token(&token));
+        assert_ne!(hash_secure_token(&token), token);
+    }
 }
@@ -0,0 +1,227 @@
+use crate::database::storage::StorageClient;
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// S3 presigned URLs cannot be valid for longer than a week
+const MAX_PRESIGNED_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How chapter page URLs are handed out to readers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageUrlMode {
+    /// Permanent CDN URLs.
+    Public,
+    /// Presigned URLs of a private bucket.
+    Presigned,
+    /// HMAC-signed URLs served by the `/api/images` proxy route.
+    Signed,
+}
+
+/// Builds chapter page URLs for the configured mode.
+/// The bucket should not be publicly readable in the expiring modes,
+/// otherwise the permanent URLs keep working.
+#[derive(Clone)]
+pub struct ImageUrlSigner {
+    mode: ImageUrlMode,
+    ttl: Duration,
+    secret: Arc<[u8]>,
+    proxy_base_url: String,
+}
+
+impl ImageUrlSigner {
+    /// Optional environment variables:
+    /// - `CHAPTER_IMAGE_URL_MODE`: `public` (default), `presigned` or `signed`.
+    /// - `CHAPTER_IMAGE_URL_TTL_SECONDS`: Lifetime of an expiring URL (default 3600).
+    /// - `IMAGE_URL_SIGNING_SECRET`: HMAC key, required for `signed`.
+    /// - `SIGNED_IMAGE_BASE_URL`: Base of the proxy route (default `/api/images`).
+    pub fn new_from_env(storage_client: &StorageClient) -> Result<Self> {
+        let mode = match env::var("CHAPTER_IMAGE_URL_MODE")
+            .unwrap_or_else(|_| "public".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "public" => ImageUrlMode::Public,
+            "presigned" => ImageUrlMode::Presigned,
+            "signed" => ImageUrlMode::Signed,
+            other => {
+                return Err(anyhow!(
+                    "Unknown CHAPTER_IMAGE_URL_MODE '{}', expected 'public', 'presigned' or 'signed'",
+                    other
+                ));
+            }
+        };
+
+        if mode == ImageUrlMode::Presigned
+            && !storage_client.supports_presigning()
+        {
+            return Err(anyhow!(
+                "CHAPTER_IMAGE_URL_MODE 'presigned' needs a storage backend that supports presigning"
+            ));
+        }
+
+        let secret = env::var("IMAGE_URL_SIGNING_SECRET").unwrap_or_default();
+        if mode == ImageUrlMode::Signed && secret.is_empty() {
+            return Err(anyhow!(
+                "IMAGE_URL_SIGNING_SECRET must be set for CHAPTER_IMAGE_URL_MODE 'signed'"
+            ));
+        }
+
+        let ttl_seconds = env::var("CHAPTER_IMAGE_URL_TTL_SECONDS")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(3600);
+
+        let proxy_base_url = env::var("SIGNED_IMAGE_BASE_URL")
+            .unwrap_or_else(|_| "/api/images".to_string());
+
+        Ok(Self::new(
+            mode,
+            Duration::from_secs(ttl_seconds),
+            secret.as_bytes(),
+            &proxy_base_url,
+        ))
+    }
+
+    pub fn new(
+        mode: ImageUrlMode,
+        ttl: Duration,
+        secret: &[u8],
+        proxy_base_url: &str,
+    ) -> Self {
+        Self {
+            mode,
+            ttl,
+            secret: Arc::from(secret),
+            proxy_base_url: proxy_base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// URL of a chapter page (or one of its variants) for the configured mode.
+    /// A failed presign is an error, the permanent URL would bypass the expiry.
+    pub async fn object_url(
+        &self,
+        storage_client: &StorageClient,
+        object_key: &str,
+    ) -> Result<String> {
+        match self.mode {
+            ImageUrlMode::Public => Ok(storage_client.public_url(object_key)),
+            ImageUrlMode::Presigned => {
+                let ttl = self.ttl.min(MAX_PRESIGNED_TTL);
+                storage_client
+                    .presigned_url(object_key, ttl)
+                    .await
+                    .with_context(|| {
+                        format!("Failed to presign {}", object_key)
+                    })
+            }
+            ImageUrlMode::Signed => {
+                let expires =
+                    Utc::now().timestamp() + self.ttl.as_secs() as i64;
+                Ok(format!(
+                    "{}/{}?expires={}&signature={}",
+                    self.proxy_base_url,
+                    object_key,
+                    expires,
+                    self.sign(object_key, expires)
+                ))
+            }
+        }
+    }
+
+    /// Checks a signed URL from the proxy route, `false` when forged or expired.
+    pub fn verify(
+        &self,
+        object_key: &str,
+        expires: i64,
+        signature: &str,
+    ) -> bool {
+        if self.mode != ImageUrlMode::Signed || expires < Utc::now().timestamp()
+        {
+            return false;
+        }
+
+        let Some(signature) = decode_hex(signature) else {
+            return false;
+        };
+
+        self.mac(object_key, expires)
+            .verify_slice(&signature)
+            .is_ok()
+    }
+
+    fn sign(&self, object_key: &str, expires: i64) -> String {
+        self.mac(object_key, expires)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn mac(&self, object_key: &str, expires: i64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret)
+            .expect("HMAC accepts keys of any size");
+        mac.update(format!("{}\n{}", object_key, expires).as_bytes());
+        mac
+    }
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) || !value.is_ascii() {
+        return None;
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&value[index..index + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::storage::local::TempStorageRoot;
+
+    #[test]
+    fn test_signed_url_verification() {
+        let signer = ImageUrlSigner::new(
+            ImageUrlMode::Signed,
+            Duration::from_secs(60),
+            b"secret",
+            "/api/images",
+        );
+        let key = "series/title/ch-1/001-abcd.avif";
+        let expires = Utc::now().timestamp() + 60;
+        let signature = signer.sign(key, expires);
+
+        assert!(signer.verify(key, expires, &signature));
+        assert!(!signer.verify("series/other.avif", expires, &signature));
+        assert!(!signer.verify(key, expires + 1, &signature));
+        assert!(!signer.verify(key, expires, "zz"));
+
+        let expired = Utc::now().timestamp() - 1;
+        assert!(!signer.verify(key, expired, &signer.sign(key, expired)));
+    }
+
+    #[tokio::test]
+    async fn test_failed_presign_is_an_error() {
+        let (_root, storage) = TempStorageRoot::client().await;
+        let signer = ImageUrlSigner::new(
+            ImageUrlMode::Presigned,
+            Duration::from_secs(60),
+            b"",
+            "/api/images",
+        );
+
+        // Local storage cannot presign, the permanent URL must not leak out
+        let key = "series/title/ch-1/001-abcd.avif";
+        assert!(signer.object_url(&storage, key).await.is_err());
+    }
+}
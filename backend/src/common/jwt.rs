@@ -1,49 +1,297 @@
 use crate::common::error::AuthError;
+use crate::database::DatabaseService;
+use crate::database::jwt_keys::JwtSigningKey;
+use anyhow::{Context, anyhow};
+use arc_swap::ArcSwap;
 use axum::{extract::FromRequestParts, http::request::Parts};
 use axum_extra::extract::CookieJar;
-use chrono::{Duration, Utc};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{
-    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode,
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode,
+    decode_header, encode,
 };
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{Ed25519KeyPair, KeyPair};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::env;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
 
-// Secret key for JWT signing and encryption
-static KEYS: LazyLock<Keys> = LazyLock::new(|| {
-    let secret_key =
-        env::var("JWT_SECRET_KEY").expect("JWT_SECRET_KEY must be set");
-    Keys::new(secret_key.as_bytes())
+const SIGNING_ALGORITHM: Algorithm = Algorithm::EdDSA;
+const SIGNING_ALGORITHM_NAME: &str = "EdDSA";
+
+// Keys currently known to this instance, replaced on every reload
+static KEY_RING: LazyLock<ArcSwap<KeyRing>> =
+    LazyLock::new(|| ArcSwap::from_pointee(KeyRing::default()));
+
+// `iss` claim, lets other services tell our tokens apart
+static ISSUER: LazyLock<String> = LazyLock::new(|| {
+    env::var("JWT_ISSUER").unwrap_or_else(|_| "web-rs".to_string())
 });
 
-pub struct Keys {
-    pub encoding: EncodingKey,
-    pub decoding: DecodingKey,
+// Encrypts the private keys at rest, see `KeyEncryptionKey::from_env`
+static KEY_ENCRYPTION_KEY: LazyLock<anyhow::Result<KeyEncryptionKey>> =
+    LazyLock::new(KeyEncryptionKey::from_env);
+
+/// AES-256-GCM key the signing keys are stored with, a database dump alone
+/// does not allow forging access tokens.
+struct KeyEncryptionKey(LessSafeKey);
+
+impl KeyEncryptionKey {
+    /// Reads `JWT_KEY_ENCRYPTION_KEY`: 32 random bytes, base64 encoded
+    /// (e.g. `openssl rand -base64 32`). Required, changing it makes the stored keys unreadable.
+    fn from_env() -> anyhow::Result<Self> {
+        let value = env::var("JWT_KEY_ENCRYPTION_KEY")
+            .context("JWT_KEY_ENCRYPTION_KEY must be set")?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(value.trim())
+            .context("JWT_KEY_ENCRYPTION_KEY is not valid base64")?;
+        Self::new(&bytes)
+    }
+
+    fn new(bytes: &[u8]) -> anyhow::Result<Self> {
+        let key = UnboundKey::new(&AES_256_GCM, bytes)
+            .map_err(|_| anyhow!("JWT_KEY_ENCRYPTION_KEY must be 32 bytes"))?;
+        Ok(Self(LessSafeKey::new(key)))
+    }
+
+    // Nonce followed by the ciphertext, the `kid` is authenticated with it
+    fn encrypt(&self, kid: &str, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("Failed to generate nonce"))?;
+
+        let mut sealed = plaintext.to_vec();
+        self.0
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(kid.as_bytes()),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("Failed to encrypt signing key"))?;
+
+        Ok([nonce.as_slice(), &sealed].concat())
+    }
+
+    fn decrypt(&self, kid: &str, stored: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if stored.len() < NONCE_LEN {
+            return Err(anyhow!("Stored signing key {} is too short", kid));
+        }
+        let (nonce, sealed) = stored.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| anyhow!("Invalid nonce of signing key {}", kid))?;
+
+        let mut sealed = sealed.to_vec();
+        let plaintext = self
+            .0
+            .open_in_place(nonce, Aad::from(kid.as_bytes()), &mut sealed)
+            .map_err(|_| {
+                anyhow!(
+                    "Failed to decrypt signing key {}, was JWT_KEY_ENCRYPTION_KEY changed?",
+                    kid
+                )
+            })?;
+
+        Ok(plaintext.to_vec())
+    }
+}
+
+fn key_encryption_key() -> anyhow::Result<&'static KeyEncryptionKey> {
+    KEY_ENCRYPTION_KEY
+        .as_ref()
+        .map_err(|e| anyhow!("Invalid key encryption key: {:#}", e))
 }
 
-impl Keys {
-    fn new(secret_key: &[u8]) -> Self {
+/// Signing key and verification keys by `kid`, built from the stored keys.
+#[derive(Default)]
+struct KeyRing {
+    signing: Option<(String, EncodingKey)>,
+    verification: HashMap<String, DecodingKey>,
+    jwks: Vec<serde_json::Value>,
+}
+
+impl KeyRing {
+    fn from_keys(keys: &[JwtSigningKey], now: DateTime<Utc>) -> Self {
+        let mut ring = KeyRing::default();
+
+        // Sorted by activation, the last active key signs
+        for key in keys.iter().filter(|key| key.expires_at > now) {
+            if key.activates_at <= now {
+                ring.signing = Some((
+                    key.kid.clone(),
+                    EncodingKey::from_ed_der(&key.private_key),
+                ));
+            }
+            ring.verification.insert(
+                key.kid.clone(),
+                DecodingKey::from_ed_der(&key.public_key),
+            );
+            ring.jwks.push(serde_json::json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "use": "sig",
+                "alg": key.algorithm,
+                "kid": key.kid,
+                "x": URL_SAFE_NO_PAD.encode(&key.public_key),
+            }));
+        }
+
+        ring
+    }
+
+    fn sign(&self, claims: &Claims) -> Result<String, AuthError> {
+        let (kid, encoding_key) =
+            self.signing.as_ref().ok_or(AuthError::TokenCreation)?;
+
+        let mut header = Header::new(SIGNING_ALGORITHM);
+        header.kid = Some(kid.clone());
+
+        encode(&header, claims, encoding_key)
+            .map_err(|_| AuthError::TokenCreation)
+    }
+
+    fn verify(&self, token: &str) -> Result<Claims, AuthError> {
+        let header =
+            decode_header(token).map_err(|_| AuthError::InvalidToken)?;
+        let decoding_key = header
+            .kid
+            .as_deref()
+            .and_then(|kid| self.verification.get(kid))
+            .ok_or(AuthError::InvalidToken)?;
+
+        let mut validation = Validation::new(SIGNING_ALGORITHM);
+        validation.set_issuer(&[ISSUER.as_str()]);
+
+        decode::<Claims>(token, decoding_key, &validation)
+            .map(|token_data| token_data.claims)
+            .map_err(|_| AuthError::InvalidToken)
+    }
+}
+
+/// When signing keys are replaced.
+#[derive(Debug, Clone, Copy)]
+pub struct JwtKeyRotationPolicy {
+    // How long a key signs before its successor takes over
+    rotation_interval: Duration,
+    // Time a new key is published before it signs, every instance reloads in between
+    publish_delay: Duration,
+    // A retired key keeps verifying this long, well beyond the access token lifetime
+    retirement_grace: Duration,
+}
+
+impl JwtKeyRotationPolicy {
+    /// Reads `JWT_KEY_ROTATION_DAYS` (default 30).
+    pub fn from_env() -> Self {
+        let rotation_days = env::var("JWT_KEY_ROTATION_DAYS")
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(30);
+
         Self {
-            encoding: EncodingKey::from_secret(secret_key),
-            decoding: DecodingKey::from_secret(secret_key),
+            rotation_interval: Duration::days(rotation_days),
+            publish_delay: Duration::hours(1),
+            retirement_grace: Duration::days(1),
         }
     }
 }
 
+// New Ed25519 key, identified by a hash of its public key
+fn generate_signing_key(
+    activates_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+) -> anyhow::Result<JwtSigningKey> {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map_err(|_| anyhow!("Failed to generate Ed25519 key"))?;
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+        .map_err(|_| anyhow!("Failed to parse generated Ed25519 key"))?;
+    let public_key = key_pair.public_key().as_ref().to_vec();
+    let kid = URL_SAFE_NO_PAD.encode(&Sha256::digest(&public_key)[..12]);
+
+    Ok(JwtSigningKey {
+        kid,
+        algorithm: SIGNING_ALGORITHM_NAME.to_string(),
+        private_key: pkcs8.as_ref().to_vec(),
+        public_key,
+        activates_at,
+        expires_at,
+    })
+}
+
+/// Creates the next signing key when the current one is due for rotation.
+/// Without any key (first start) the new key signs right away.
+pub async fn rotate_signing_keys(
+    db_service: &DatabaseService,
+    policy: JwtKeyRotationPolicy,
+) -> anyhow::Result<Option<String>> {
+    db_service
+        .rotate_jwt_signing_key(|latest_activation| {
+            let now = Utc::now();
+            let activates_at = match latest_activation {
+                None => now,
+                Some(latest) => {
+                    let next_activation = latest + policy.rotation_interval;
+                    if next_activation - policy.publish_delay > now {
+                        return Ok(None);
+                    }
+                    next_activation.max(now + policy.publish_delay)
+                }
+            };
+            let expires_at = activates_at
+                + policy.rotation_interval
+                + policy.retirement_grace;
+
+            let mut key = generate_signing_key(activates_at, expires_at)?;
+            key.private_key =
+                key_encryption_key()?.encrypt(&key.kid, &key.private_key)?;
+            Ok(Some(key))
+        })
+        .await
+        .context("Failed to rotate jwt signing key")
+}
+
+/// Loads the stored keys into this instance, keys activated since the last load start signing.
+pub async fn reload_signing_keys(
+    db_service: &DatabaseService,
+) -> anyhow::Result<()> {
+    let kek = key_encryption_key()?;
+    let mut keys = db_service.get_jwt_signing_keys().await?;
+    for key in &mut keys {
+        key.private_key = kek.decrypt(&key.kid, &key.private_key)?;
+    }
+
+    let ring = KeyRing::from_keys(&keys, Utc::now());
+    if ring.signing.is_none() {
+        return Err(anyhow!("No active jwt signing key"));
+    }
+
+    KEY_RING.store(Arc::new(ring));
+    Ok(())
+}
+
+/// Public keys for other services verifying our access tokens (RFC 7517).
+pub fn jwks() -> serde_json::Value {
+    serde_json::json!({ "keys": KEY_RING.load().jwks })
+}
+
 /// Claims is a struct that represents the claims in the JWT token.
-/// It contains the subject (user ID), expiration time, issued at time, and role.
+/// It contains the subject (numeric user ID), expiration time, issued at time, role,
+/// the user's token version and the server-side session the token was issued for.
+/// `role` is for services verifying the token through the JWKS, this backend
+/// checks permissions of the current role loaded with the user instead.
 /// The `Claims` struct is used to encode and decode the JWT tokens.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub role: String,
-    pub exp: usize,
-    pub iat: usize,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RefreshClaims {
-    pub sub: String,
+    pub ver: i32,
+    pub sid: i64,
+    pub iss: String,
     pub exp: usize,
     pub iat: usize,
 }
@@ -65,49 +313,25 @@ where
         let token_cookie = jar.get("token").ok_or(AuthError::InvalidToken)?;
         let token = token_cookie.value();
 
-        // Decode token with HS512 Algorithm
-        let mut validation = Validation::default();
-        validation.algorithms = vec![Algorithm::HS512];
-
-        let token_data = decode::<Claims>(token, &KEYS.decoding, &validation)
-            .map_err(|_| AuthError::InvalidToken)?;
-
-        Ok(token_data.claims)
+        // Verified with the key named by the token's `kid` header
+        KEY_RING.load().verify(token)
     }
 }
 
-/// Custom extractor to get `RefreshClaims` from cookie refresh token.
-impl<S> FromRequestParts<S> for RefreshClaims
-where
-    S: Send + Sync,
-{
-    type Rejection = AuthError;
-    async fn from_request_parts(
-        parts: &mut Parts,
-        state: &S,
-    ) -> Result<Self, Self::Rejection> {
-        let jar = CookieJar::from_request_parts(parts, state).await.unwrap();
-
-        let refresh_token_cookie =
-            jar.get("refresh-token").ok_or(AuthError::InvalidToken)?;
-        let refresh_token = refresh_token_cookie.value();
-
-        // Decode token with HS512 Algorithm
-        let mut validation = Validation::default();
-        validation.algorithms = vec![Algorithm::HS512];
-
-        let token_data =
-            decode::<RefreshClaims>(refresh_token, &KEYS.decoding, &validation)
-                .map_err(|_| AuthError::InvalidToken)?;
-
-        Ok(token_data.claims)
+impl Claims {
+    /// The user the token was issued for, usernames can change so `sub` holds the id.
+    pub fn user_id(&self) -> Result<i32, AuthError> {
+        self.sub.parse().map_err(|_| AuthError::InvalidToken)
     }
 }
 
-/// Create jwt token for a given user ID and role (access token)
+/// Create jwt token for a given user ID, role and session (access token).
+/// `token_version` is the user's current one, bumping it invalidates the token.
 pub fn create_access_jwt(
-    user_id: String,
+    user_id: i32,
     role: String,
+    session_id: i64,
+    token_version: i32,
 ) -> Result<String, AuthError> {
     let now = Utc::now();
     let iat = now.timestamp() as usize;
@@ -116,31 +340,98 @@ pub fn create_access_jwt(
     let exp = (now + Duration::minutes(30)).timestamp() as usize;
 
     let claims = Claims {
-        sub: user_id,
+        sub: user_id.to_string(),
         role,
+        ver: token_version,
+        sid: session_id,
+        iss: ISSUER.clone(),
         exp,
         iat,
     };
 
-    // Specify HS512 algorithm in the header
-    encode(&Header::new(Algorithm::HS512), &claims, &KEYS.encoding)
-        .map_err(|_| AuthError::TokenCreation)
+    // Signed with the current key, its `kid` goes into the header
+    KEY_RING.load().sign(&claims)
 }
 
-/// Create refresh jwt token for a given user ID (refresh token)
-pub fn create_refresh_jwt(user_id: String) -> Result<String, AuthError> {
-    let now = Utc::now();
-    let iat = now.timestamp() as usize;
+/// Refresh tokens are valid for 7 days, every rotation starts a new period.
+pub const REFRESH_TOKEN_TTL_DAYS: i64 = 7;
 
-    // Refresh token valid for 7 days
-    let exp = (now + Duration::days(7)).timestamp() as usize;
+/// A session ends 30 days after the login at the latest, however often it is refreshed.
+pub const SESSION_MAX_LIFETIME_DAYS: i64 = 30;
 
-    let claims = RefreshClaims {
-        sub: user_id,
-        exp,
-        iat,
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_claims() -> Claims {
+        Claims {
+            sub: "42".to_string(),
+            role: "user".to_string(),
+            ver: 0,
+            sid: 1,
+            iss: ISSUER.clone(),
+            exp: (Utc::now() + Duration::minutes(5)).timestamp() as usize,
+            iat: Utc::now().timestamp() as usize,
+        }
+    }
+
+    #[test]
+    fn test_key_rotation_keeps_old_tokens_valid() {
+        let now = Utc::now();
+        let old_key = generate_signing_key(
+            now - Duration::days(30),
+            now + Duration::days(1),
+        )
+        .unwrap();
+        let new_key =
+            generate_signing_key(now, now + Duration::days(31)).unwrap();
+        let pending_key = generate_signing_key(
+            now + Duration::hours(1),
+            now + Duration::days(32),
+        )
+        .unwrap();
+
+        let old_ring = KeyRing::from_keys(std::slice::from_ref(&old_key), now);
+        let old_token = old_ring.sign(&test_claims()).ok().unwrap();
 
-    encode(&Header::new(Algorithm::HS512), &claims, &KEYS.encoding)
-        .map_err(|_| AuthError::TokenCreation)
+        let ring = KeyRing::from_keys(&[old_key, new_key, pending_key], now);
+        let new_token = ring.sign(&test_claims()).ok().unwrap();
+
+        // The newest active key signs, pending keys are only published
+        assert_eq!(ring.signing.as_ref().unwrap().0, ring.jwks[1]["kid"]);
+        assert_eq!(ring.jwks.len(), 3);
+        assert_eq!(
+            ring.verify(&old_token).ok().unwrap().user_id().ok(),
+            Some(42)
+        );
+        let claims = ring.verify(&new_token).ok().unwrap();
+        assert_eq!(claims.user_id().ok(), Some(42));
+        assert_eq!(claims.role, "user");
+
+        // Tokens of unknown keys are rejected
+        assert!(old_ring.verify(&new_token).is_err());
+    }
+
+    #[test]
+    fn test_private_keys_are_encrypted_at_rest() {
+        let kek = KeyEncryptionKey::new(&[7; 32]).unwrap();
+        let now = Utc::now();
+        let key = generate_signing_key(now, now + Duration::days(1)).unwrap();
+
+        let stored = kek.encrypt(&key.kid, &key.private_key).unwrap();
+        assert!(
+            !stored
+                .windows(key.private_key.len())
+                .any(|window| window == key.private_key)
+        );
+        assert_eq!(kek.decrypt(&key.kid, &stored).unwrap(), key.private_key);
+
+        // Bound to its row and to the configured key
+        assert!(kek.decrypt("other-kid", &stored).is_err());
+        let other_kek = KeyEncryptionKey::new(&[8; 32]).unwrap();
+        assert!(other_kek.decrypt(&key.kid, &stored).is_err());
+        assert!(kek.decrypt(&key.kid, &stored[..8]).is_err());
+
+        assert!(KeyEncryptionKey::new(&[7; 16]).is_err());
+    }
 }
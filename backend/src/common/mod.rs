@@ -2,5 +2,12 @@ pub mod dynamic_proxy;
 pub mod email_service;
 pub mod error;
 pub mod hashing;
+pub mod image_urls;
 pub mod jwt;
+pub mod oidc;
+pub mod permissions;
+pub mod rate_limit;
+pub mod totp;
+pub mod upload;
+pub mod user_cache;
 pub mod utils;
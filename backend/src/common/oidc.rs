@@ -0,0 +1,611 @@
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use rand::RngCore;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OnceCell, RwLock};
+use url::Url;
+
+const DEFAULT_SCOPES: &str = "openid email profile";
+
+/// Endpoints of a provider, from its discovery document or configured by hand.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: Option<String>,
+    pub jwks_uri: Option<String>,
+    /// Algorithms the provider signs ID tokens with, RS256 when not listed (OIDC Discovery 1.0).
+    #[serde(default)]
+    pub id_token_signing_alg_values_supported: Vec<String>,
+}
+
+/// The account at the provider a login resolved to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderIdentity {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+}
+
+impl ProviderIdentity {
+    // Standard OIDC claims, with the field names of plain OAuth2 userinfo APIs (Discord) as fallback
+    fn from_claims(claims: &Map<String, Value>) -> Result<Self> {
+        let subject = match claims.get("sub").or_else(|| claims.get("id")) {
+            Some(Value::String(subject)) if !subject.is_empty() => {
+                subject.clone()
+            }
+            Some(Value::Number(subject)) => subject.to_string(),
+            _ => return Err(anyhow!("Provider returned no subject")),
+        };
+
+        let string_claim = |name: &str| {
+            claims
+                .get(name)
+                .and_then(Value::as_str)
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+        };
+        let email_verified =
+            ["email_verified", "verified"].iter().any(|name| {
+                match claims.get(*name) {
+                    Some(Value::Bool(verified)) => *verified,
+                    Some(Value::String(verified)) => verified == "true",
+                    _ => false,
+                }
+            });
+
+        Ok(Self {
+            subject,
+            email: string_claim("email"),
+            email_verified,
+            preferred_username: string_claim("preferred_username")
+                .or_else(|| string_claim("username"))
+                .or_else(|| string_claim("name")),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    id_token: Option<String>,
+}
+
+/// One configured login provider.
+pub struct OidcProvider {
+    pub name: String,
+    pub display_name: String,
+    client_id: String,
+    client_secret: String,
+    scopes: String,
+    redirect_uri: String,
+    issuer: Option<String>,
+    metadata: OnceCell<ProviderMetadata>,
+    jwks: RwLock<Option<JwkSet>>,
+    http_client: reqwest::Client,
+}
+
+impl OidcProvider {
+    /// Provider using OIDC discovery at `{issuer}/.well-known/openid-configuration`.
+    pub fn new(
+        name: &str,
+        client_id: &str,
+        client_secret: &str,
+        issuer: &str,
+        redirect_uri: &str,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            scopes: DEFAULT_SCOPES.to_string(),
+            redirect_uri: redirect_uri.to_string(),
+            issuer: Some(issuer.trim_end_matches('/').to_string()),
+            metadata: OnceCell::new(),
+            jwks: RwLock::new(None),
+            http_client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Reads `OIDC_<NAME>_*` for the provider `name`:
+    /// - `CLIENT_ID`, `CLIENT_SECRET`: Required.
+    /// - `ISSUER`: Issuer URL for discovery, or instead for plain OAuth2 providers
+    ///   `AUTHORIZATION_ENDPOINT`, `TOKEN_ENDPOINT` and `USERINFO_ENDPOINT`.
+    /// - `SCOPES`: Requested scopes (default `openid email profile`).
+    /// - `DISPLAY_NAME`: Name shown on the login button.
+    fn from_env(name: &str, redirect_base_url: &str) -> Result<Self> {
+        let prefix = format!("OIDC_{}_", name.to_uppercase().replace('-', "_"));
+        let var = |key: &str| {
+            env::var(format!("{}{}", prefix, key))
+                .ok()
+                .filter(|value| !value.is_empty())
+        };
+        let required = |key: &str| {
+            var(key).ok_or_else(|| anyhow!("{}{} must be set", prefix, key))
+        };
+
+        let redirect_uri = format!(
+            "{}/api/auth/oidc/{}/callback",
+            redirect_base_url.trim_end_matches('/'),
+            name
+        );
+        let issuer = var("ISSUER");
+        let mut provider = Self::new(
+            name,
+            &required("CLIENT_ID")?,
+            &required("CLIENT_SECRET")?,
+            issuer.as_deref().unwrap_or_default(),
+            &redirect_uri,
+        );
+
+        if let Some(display_name) = var("DISPLAY_NAME") {
+            provider.display_name = display_name;
+        }
+        if let Some(scopes) = var("SCOPES") {
+            provider.scopes = scopes;
+        }
+        if issuer.is_none() {
+            // Without discovery there is no ID token to check, the identity comes from userinfo
+            provider.issuer = None;
+            provider.metadata = OnceCell::new_with(Some(ProviderMetadata {
+                issuer: String::new(),
+                authorization_endpoint: required("AUTHORIZATION_ENDPOINT")?,
+                token_endpoint: required("TOKEN_ENDPOINT")?,
+                userinfo_endpoint: Some(required("USERINFO_ENDPOINT")?),
+                jwks_uri: None,
+                id_token_signing_alg_values_supported: Vec::new(),
+            }));
+        }
+
+        Ok(provider)
+    }
+
+    async fn metadata(&self) -> Result<&ProviderMetadata> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let issuer = self
+                    .issuer
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("Provider has no issuer"))?;
+                let metadata: ProviderMetadata = self
+                    .get_json(
+                        &format!("{}/.well-known/openid-configuration", issuer),
+                        None,
+                    )
+                    .await
+                    .context("Failed to fetch OIDC discovery document")?;
+
+                if metadata.issuer.trim_end_matches('/') != issuer {
+                    return Err(anyhow!(
+                        "Discovery document issuer '{}' does not match '{}'",
+                        metadata.issuer,
+                        issuer
+                    ));
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+
+    /// URL the browser is sent to, with the PKCE challenge of `code_verifier`.
+    pub async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<String> {
+        let metadata = self.metadata().await?;
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("scope", self.scopes.as_str()),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", &pkce_challenge(code_verifier)),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .context("Invalid authorization endpoint")?;
+
+        Ok(url.to_string())
+    }
+
+    /// Redeems the authorization code and returns the verified identity.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<ProviderIdentity> {
+        let metadata = self.metadata().await?;
+
+        let response = self
+            .http_client
+            .post(&metadata.token_endpoint)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("code_verifier", code_verifier),
+            ])
+            .send()
+            .await
+            .context("Failed to send token request")?;
+        let tokens: TokenResponse = parse_json_response(response)
+            .await
+            .context("Token request failed")?;
+
+        match (&tokens.id_token, &metadata.userinfo_endpoint) {
+            (Some(id_token), _) if self.issuer.is_some() => {
+                let claims = self.verify_id_token(id_token, nonce).await?;
+                ProviderIdentity::from_claims(&claims)
+            }
+            (_, Some(userinfo_endpoint)) => {
+                let claims: Map<String, Value> = self
+                    .get_json(userinfo_endpoint, Some(&tokens.access_token))
+                    .await
+                    .context("Failed to fetch userinfo")?;
+                ProviderIdentity::from_claims(&claims)
+            }
+            _ => Err(anyhow!("Provider returned no ID token")),
+        }
+    }
+
+    async fn verify_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<Map<String, Value>> {
+        let metadata = self.metadata().await?;
+        let header = decode_header(id_token).context("Malformed ID token")?;
+
+        // The token must not pick its own algorithm, e.g. HS256 with a public RSA key
+        if !allowed_id_token_algorithms(metadata).contains(&header.alg) {
+            return Err(anyhow!(
+                "ID token algorithm {:?} is not used by the provider",
+                header.alg
+            ));
+        }
+
+        let key = match header.alg {
+            // Signed with the client secret (allowed by OIDC for confidential clients)
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                DecodingKey::from_secret(self.client_secret.as_bytes())
+            }
+            _ => self.jwk_decoding_key(header.kid.as_deref()).await?,
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims = decode::<Map<String, Value>>(id_token, &key, &validation)
+            .context("Invalid ID token")?
+            .claims;
+
+        if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+            return Err(anyhow!("ID token nonce does not match"));
+        }
+
+        Ok(claims)
+    }
+
+    // Keys are cached, an unknown key id refetches them once (key rotation)
+    async fn jwk_decoding_key(&self, kid: Option<&str>) -> Result<DecodingKey> {
+        let find_key = |jwks: &JwkSet| match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None => jwks.keys.first().cloned(),
+        };
+
+        if let Some(jwk) = self.jwks.read().await.as_ref().and_then(find_key) {
+            return DecodingKey::from_jwk(&jwk).context("Unsupported JWK");
+        }
+
+        let jwks_uri = self
+            .metadata()
+            .await?
+            .jwks_uri
+            .as_deref()
+            .ok_or_else(|| anyhow!("Provider has no jwks_uri"))?;
+        let jwks: JwkSet = self
+            .get_json(jwks_uri, None)
+            .await
+            .context("Failed to fetch JWKS")?;
+
+        let jwk = find_key(&jwks)
+            .ok_or_else(|| anyhow!("No JWK for key id {:?}", kid))?;
+        *self.jwks.write().await = Some(jwks);
+
+        DecodingKey::from_jwk(&jwk).context("Unsupported JWK")
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        bearer_token: Option<&str>,
+    ) -> Result<T> {
+        let mut request = self
+            .http_client
+            .get(url)
+            .header(reqwest::header::ACCEPT, "application/json");
+        if let Some(token) = bearer_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await.context("Request failed")?;
+        parse_json_response(response).await
+    }
+}
+
+// Unknown names (and `none`) are skipped, they can never be accepted
+fn allowed_id_token_algorithms(metadata: &ProviderMetadata) -> Vec<Algorithm> {
+    if metadata.id_token_signing_alg_values_supported.is_empty() {
+        return vec![Algorithm::RS256];
+    }
+
+    metadata
+        .id_token_signing_alg_values_supported
+        .iter()
+        .filter_map(|name| name.parse::<Algorithm>().ok())
+        .collect()
+}
+
+async fn parse_json_response<T: DeserializeOwned>(
+    response: reqwest::Response,
+) -> Result<T> {
+    let status = response.status();
+    let body = response.bytes().await.context("Failed to read response")?;
+    if !status.is_success() {
+        return Err(anyhow!(
+            "Provider responded with {}: {}",
+            status,
+            String::from_utf8_lossy(&body)
+        ));
+    }
+
+    serde_json::from_slice(&body).context("Failed to parse provider response")
+}
+
+/// The configured providers, keyed by the name used in the routes.
+#[derive(Clone, Default)]
+pub struct OidcProviders {
+    providers: Arc<BTreeMap<String, Arc<OidcProvider>>>,
+    frontend_url: String,
+}
+
+impl OidcProviders {
+    /// Environment variables:
+    /// - `OIDC_PROVIDERS`: Comma separated provider names (e.g. `google,discord`), none by default.
+    /// - `OIDC_REDIRECT_BASE_URL`: Public URL of this backend (default `http://localhost:8000`).
+    /// - `FRONTEND_URL`: Where the browser is sent after the login.
+    ///
+    /// Every provider is configured by its own `OIDC_<NAME>_*` variables.
+    pub fn new_from_env() -> Result<Self> {
+        let redirect_base_url = env::var("OIDC_REDIRECT_BASE_URL")
+            .unwrap_or_else(|_| "http://localhost:8000".to_string());
+        let frontend_url = env::var("FRONTEND_URL")
+            .unwrap_or_else(|_| "http://localhost:1998".to_string());
+
+        let mut providers = BTreeMap::new();
+        for name in env::var("OIDC_PROVIDERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|name| name.trim().to_lowercase())
+            .filter(|name| !name.is_empty())
+        {
+            let provider = OidcProvider::from_env(&name, &redirect_base_url)?;
+            providers.insert(name, Arc::new(provider));
+        }
+
+        Ok(Self {
+            providers: Arc::new(providers),
+            frontend_url: frontend_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<OidcProvider>> {
+        self.providers.get(name).cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &OidcProvider> {
+        self.providers.values().map(Arc::as_ref)
+    }
+
+    /// Frontend URL of `path`, the end of every browser redirect.
+    pub fn frontend_url(&self, path: &str) -> String {
+        format!("{}{}", self.frontend_url, path)
+    }
+}
+
+/// Random value for `state`, `nonce` and the PKCE code verifier.
+pub fn generate_oidc_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// S256 code challenge of a PKCE code verifier (RFC 7636).
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::routing::{get, post};
+    use axum::{Form, Json, Router};
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_pkce_challenge() {
+        // RFC 7636 appendix B
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        assert_eq!(generate_oidc_token().len(), 43);
+    }
+
+    // Minimal issuer: discovery listing `signing_algs` and a token endpoint issuing HS256 ID tokens
+    async fn start_mock_issuer(
+        signing_algs: &'static [&'static str],
+    ) -> String {
+        let listener =
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+        let app = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(move |State(issuer): State<String>| async move {
+                    Json(serde_json::json!({
+                        "issuer": issuer,
+                        "authorization_endpoint": format!("{}/authorize", issuer),
+                        "token_endpoint": format!("{}/token", issuer),
+                        "id_token_signing_alg_values_supported": signing_algs,
+                    }))
+                }),
+            )
+            .route(
+                "/token",
+                post(
+                    |State(issuer): State<String>,
+                     Form(form): Form<HashMap<String, String>>| async move {
+                        // The code is the nonce, the mock has no storage
+                        let verifier = &form["code_verifier"];
+                        assert_eq!(pkce_challenge(verifier).len(), 43);
+                        let claims = serde_json::json!({
+                            "iss": issuer,
+                            "aud": "client-id",
+                            "sub": "mock-user-1",
+                            "exp": chrono::Utc::now().timestamp() + 60,
+                            "nonce": form["code"],
+                            "email": "reader@example.com",
+                            "email_verified": true,
+                        });
+                        let id_token = encode(
+                            &Header::new(Algorithm::HS256),
+                            &claims,
+                            &EncodingKey::from_secret(b"client-secret"),
+                        )
+                        .unwrap();
+                        Json(serde_json::json!({
+                            "access_token": "access",
+                            "token_type": "Bearer",
+                            "id_token": id_token,
+                        }))
+                    },
+                ),
+            )
+            .with_state(issuer.clone());
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        issuer
+    }
+
+    #[tokio::test]
+    async fn test_login_against_mock_issuer() {
+        let issuer = start_mock_issuer(&["HS256"]).await;
+        let provider = OidcProvider::new(
+            "mock",
+            "client-id",
+            "client-secret",
+            &issuer,
+            "http://localhost:8000/api/auth/oidc/mock/callback",
+        );
+
+        let authorization_url = provider
+            .authorization_url("state-1", "nonce-1", "verifier")
+            .await
+            .unwrap();
+        assert!(
+            authorization_url.starts_with(&format!("{}/authorize?", issuer))
+        );
+        assert!(authorization_url.contains("code_challenge_method=S256"));
+
+        let identity = provider
+            .exchange_code("nonce-1", "verifier", "nonce-1")
+            .await
+            .unwrap();
+        assert_eq!(identity.subject, "mock-user-1");
+        assert_eq!(identity.email.as_deref(), Some("reader@example.com"));
+        assert!(identity.email_verified);
+
+        // A token issued for another login attempt is rejected
+        assert!(
+            provider
+                .exchange_code("nonce-2", "verifier", "nonce-1")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_id_token_algorithm_is_set_by_the_provider() {
+        // Signs with RS256 only, an HS256 token is forged with public data
+        let issuer = start_mock_issuer(&["RS256"]).await;
+        let provider = OidcProvider::new(
+            "mock",
+            "client-id",
+            "client-secret",
+            &issuer,
+            "http://localhost:8000/api/auth/oidc/mock/callback",
+        );
+
+        let error = provider
+            .exchange_code("nonce-1", "verifier", "nonce-1")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("not used by the provider"));
+    }
+
+    #[test]
+    fn test_allowed_id_token_algorithms() {
+        let metadata = |algs: &[&str]| ProviderMetadata {
+            issuer: String::new(),
+            authorization_endpoint: String::new(),
+            token_endpoint: String::new(),
+            userinfo_endpoint: None,
+            jwks_uri: None,
+            id_token_signing_alg_values_supported: algs
+                .iter()
+                .map(|alg| alg.to_string())
+                .collect(),
+        };
+
+        assert_eq!(
+            allowed_id_token_algorithms(&metadata(&[])),
+            vec![Algorithm::RS256]
+        );
+        assert_eq!(
+            allowed_id_token_algorithms(&metadata(&["none", "ES256", "RS256"])),
+            vec![Algorithm::ES256, Algorithm::RS256]
+        );
+    }
+}
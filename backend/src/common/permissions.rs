@@ -0,0 +1,54 @@
+/// A permission of the `permissions` table, granted to roles through `role_permissions`.
+/// Used as the parameter of the `RequirePermission` extractor.
+pub trait Permission: Send + Sync {
+    const NAME: &'static str;
+}
+
+/// Create and edit series, chapters and category tags.
+pub struct SeriesEdit;
+
+impl Permission for SeriesEdit {
+    const NAME: &'static str = "series.edit";
+}
+
+/// Archive, restore and delete series.
+pub struct SeriesDelete;
+
+impl Permission for SeriesDelete {
+    const NAME: &'static str = "series.delete";
+}
+
+/// Remove comments of any user.
+pub struct CommentsModerate;
+
+impl Permission for CommentsModerate {
+    const NAME: &'static str = "comments.moderate";
+}
+
+/// List user accounts.
+pub struct UsersView;
+
+impl Permission for UsersView {
+    const NAME: &'static str = "users.view";
+}
+
+/// Suspend and ban user accounts.
+pub struct UsersBan;
+
+impl Permission for UsersBan {
+    const NAME: &'static str = "users.ban";
+}
+
+/// Change roles, force password resets and delete accounts.
+pub struct UsersManage;
+
+impl Permission for UsersManage {
+    const NAME: &'static str = "users.manage";
+}
+
+/// Storage maintenance and metrics.
+pub struct SystemManage;
+
+impl Permission for SystemManage {
+    const NAME: &'static str = "system.manage";
+}
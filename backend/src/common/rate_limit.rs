@@ -0,0 +1,295 @@
+use crate::api::extractor::ClientInfo;
+use crate::common::error::AuthError;
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum_core::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Idle keys are dropped once this many are tracked
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// At most `max_requests` within `window`, refilled continuously.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitPolicy {
+    pub max_requests: u32,
+    pub window: Duration,
+}
+
+impl RateLimitPolicy {
+    pub const fn new(max_requests: u32, window_secs: u64) -> Self {
+        Self {
+            max_requests,
+            window: Duration::from_secs(window_secs),
+        }
+    }
+
+    // `<requests>/<seconds>`, `off` disables the limit
+    fn parse(value: &str) -> Option<Option<Self>> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("off") {
+            return Some(None);
+        }
+
+        let (max_requests, window_secs) = value.split_once('/')?;
+        let max_requests = max_requests.trim().parse::<u32>().ok()?;
+        let window_secs = window_secs.trim().parse::<u64>().ok()?;
+        if max_requests == 0 || window_secs == 0 {
+            return None;
+        }
+
+        Some(Some(Self::new(max_requests, window_secs)))
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.max_requests as f64 / self.window.as_secs_f64()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+struct Buckets {
+    by_key: HashMap<String, Bucket>,
+    prune_at: usize,
+}
+
+/// In-memory token buckets per key (client address, account).
+/// Every instance of the backend counts on its own.
+#[derive(Clone)]
+pub struct RateLimiter {
+    policy: Option<RateLimitPolicy>,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    pub fn new(policy: Option<RateLimitPolicy>) -> Self {
+        Self {
+            policy,
+            buckets: Arc::new(Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                prune_at: PRUNE_THRESHOLD,
+            })),
+        }
+    }
+
+    /// Reads `RATE_LIMIT_<group>` as `<requests>/<seconds>` (e.g. `10/60`) or `off`.
+    /// Invalid values fall back to the default.
+    pub fn from_env(group: &str, default: RateLimitPolicy) -> Self {
+        let name = format!("RATE_LIMIT_{}", group);
+        let policy = match std::env::var(&name) {
+            Ok(value) => RateLimitPolicy::parse(&value).unwrap_or_else(|| {
+                eprintln!(
+                    "[RATE-LIMIT] Invalid {} '{}', using {}/{}",
+                    name,
+                    value,
+                    default.max_requests,
+                    default.window.as_secs()
+                );
+                Some(default)
+            }),
+            Err(_) => Some(default),
+        };
+
+        Self::new(policy)
+    }
+
+    /// Takes one request from the key's budget.
+    /// `Err` holds the seconds until the next request is allowed.
+    pub fn check(&self, key: &str) -> Result<(), u64> {
+        self.check_at(key, Instant::now())
+    }
+
+    /// Like `check`, without taking from the budget.
+    /// For limits on failures, counted with `check` once one happens.
+    pub fn peek(&self, key: &str) -> Result<(), u64> {
+        self.take_at(key, Instant::now(), false)
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), u64> {
+        self.take_at(key, now, true)
+    }
+
+    fn take_at(
+        &self,
+        key: &str,
+        now: Instant,
+        consume: bool,
+    ) -> Result<(), u64> {
+        let Some(policy) = self.policy else {
+            return Ok(());
+        };
+        let capacity = policy.max_requests as f64;
+        let refill_per_sec = policy.refill_per_sec();
+
+        // A poisoned lock only means another request panicked, the counters are still usable
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if buckets.by_key.len() >= buckets.prune_at {
+            buckets.by_key.retain(|_, bucket| {
+                let elapsed = now.duration_since(bucket.updated_at);
+                bucket.tokens + elapsed.as_secs_f64() * refill_per_sec
+                    < capacity
+            });
+            buckets.prune_at = (buckets.by_key.len() * 2).max(PRUNE_THRESHOLD);
+        }
+
+        let bucket = buckets.by_key.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at);
+        bucket.tokens = (bucket.tokens
+            + elapsed.as_secs_f64() * refill_per_sec)
+            .min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            if consume {
+                bucket.tokens -= 1.0;
+            }
+            Ok(())
+        } else {
+            let retry_after = (1.0 - bucket.tokens) / refill_per_sec;
+            Err(retry_after.ceil().max(1.0) as u64)
+        }
+    }
+}
+
+/// Middleware limiting requests per client address,
+/// layered with `axum::middleware::from_fn_with_state(limiter, enforce_rate_limit)`.
+pub async fn enforce_rate_limit(
+    State(limiter): State<RateLimiter>,
+    client: ClientInfo,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = client.ip_address.as_deref().unwrap_or("unknown");
+
+    match limiter.check(key) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after_secs) => {
+            AuthError::TooManyRequests { retry_after_secs }.into_response()
+        }
+    }
+}
+
+/// Failed logins per account: short delays at first, then a temporary lockout.
+/// Complements the per-address limit, spreading guesses over many addresses does not help.
+#[derive(Debug, Clone, Copy)]
+pub struct LoginLockoutPolicy {
+    // Failures before the first delay
+    pub delay_after_failures: u32,
+    // Failures before the full lockout
+    pub lockout_after_failures: u32,
+    pub lockout: Duration,
+}
+
+impl LoginLockoutPolicy {
+    /// Optional environment variables:
+    /// - `LOGIN_LOCKOUT_THRESHOLD`: Failed logins before an account is locked (default 10).
+    /// - `LOGIN_LOCKOUT_MINUTES`: How long it stays locked (default 15).
+    ///   Failures older than this are forgotten.
+    pub fn from_env() -> Self {
+        let read_env = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(default)
+        };
+
+        let lockout_after_failures =
+            read_env("LOGIN_LOCKOUT_THRESHOLD", 10) as u32;
+
+        Self {
+            delay_after_failures: 3.min(lockout_after_failures),
+            lockout_after_failures,
+            lockout: Duration::from_secs(
+                read_env("LOGIN_LOCKOUT_MINUTES", 15) * 60,
+            ),
+        }
+    }
+
+    /// How long the account is locked after its `failures`th failed login in a row.
+    /// The delay doubles with every failure until the full lockout.
+    pub fn lock_duration(&self, failures: u32) -> Option<Duration> {
+        if failures >= self.lockout_after_failures {
+            return Some(self.lockout);
+        }
+        if failures < self.delay_after_failures {
+            return None;
+        }
+
+        let exponent = (failures - self.delay_after_failures).min(20);
+        Some(Duration::from_secs(1 << exponent).min(self.lockout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_refill() {
+        let limiter = RateLimiter::new(Some(RateLimitPolicy::new(2, 60)));
+        let start = Instant::now();
+
+        assert_eq!(limiter.check_at("1.2.3.4", start), Ok(()));
+        assert_eq!(limiter.check_at("1.2.3.4", start), Ok(()));
+        assert_eq!(limiter.check_at("1.2.3.4", start), Err(30));
+        // Other clients have their own budget
+        assert_eq!(limiter.check_at("5.6.7.8", start), Ok(()));
+
+        let later = start + Duration::from_secs(30);
+        assert_eq!(limiter.check_at("1.2.3.4", later), Ok(()));
+        assert!(limiter.check_at("1.2.3.4", later).is_err());
+
+        let disabled = RateLimiter::new(None);
+        assert!((0..100).all(|_| disabled.check_at("1.2.3.4", start).is_ok()));
+    }
+
+    #[test]
+    fn test_rate_limiter_peek_does_not_count() {
+        let limiter = RateLimiter::new(Some(RateLimitPolicy::new(1, 60)));
+        let start = Instant::now();
+
+        assert_eq!(limiter.take_at("1.2.3.4", start, false), Ok(()));
+        assert_eq!(limiter.take_at("1.2.3.4", start, false), Ok(()));
+        assert_eq!(limiter.check_at("1.2.3.4", start), Ok(()));
+        assert_eq!(limiter.take_at("1.2.3.4", start, false), Err(60));
+    }
+
+    #[test]
+    fn test_rate_limit_policy_parse() {
+        assert_eq!(
+            RateLimitPolicy::parse("10/60"),
+            Some(Some(RateLimitPolicy::new(10, 60)))
+        );
+        assert_eq!(RateLimitPolicy::parse("off"), Some(None));
+        assert_eq!(RateLimitPolicy::parse("0/60"), None);
+        assert_eq!(RateLimitPolicy::parse("10"), None);
+    }
+
+    #[test]
+    fn test_login_lock_duration() {
+        let policy = LoginLockoutPolicy {
+            delay_after_failures: 3,
+            lockout_after_failures: 10,
+            lockout: Duration::from_secs(15 * 60),
+        };
+
+        assert_eq!(policy.lock_duration(2), None);
+        assert_eq!(policy.lock_duration(3), Some(Duration::from_secs(1)));
+        assert_eq!(policy.lock_duration(5), Some(Duration::from_secs(4)));
+        assert_eq!(policy.lock_duration(10), Some(policy.lockout));
+        assert_eq!(policy.lock_duration(50), Some(policy.lockout));
+    }
+}
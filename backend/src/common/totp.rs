@@ -0,0 +1,196 @@
+use hmac::{Hmac, Mac};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use rand::RngCore;
+use sha1::Sha1;
+
+type HmacSha1 = Hmac<Sha1>;
+
+// RFC 6238 defaults, the only parameters authenticator apps reliably support
+const TOTP_DIGITS: u32 = 6;
+const TOTP_PERIOD_SECS: i64 = 30;
+// Codes of the previous and next period are accepted for clock drift
+const TOTP_ALLOWED_DRIFT_STEPS: i64 = 1;
+const SECRET_BYTES: usize = 20;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+// Without 0/O and 1/I/L, recovery codes are typed from paper
+const RECOVERY_CODE_ALPHABET: &[u8; 31] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+
+/// New random shared secret, base32 encoded as authenticator apps expect it.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_BYTES];
+    rand::rng().fill_bytes(&mut bytes);
+    base32_encode(&bytes)
+}
+
+/// `otpauth://` URI for the QR code scanned by authenticator apps.
+pub fn provisioning_uri(
+    secret: &str,
+    account_name: &str,
+    issuer: &str,
+) -> String {
+    let issuer = utf8_percent_encode(issuer, NON_ALPHANUMERIC);
+    let account_name = utf8_percent_encode(account_name, NON_ALPHANUMERIC);
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        issuer, account_name, secret, issuer, TOTP_DIGITS, TOTP_PERIOD_SECS
+    )
+}
+
+/// Checks a code at `unix_time` and returns its time step.
+/// Steps up to `last_used_step` are rejected, a code works only once.
+pub fn verify_code(
+    secret: &str,
+    code: &str,
+    unix_time: i64,
+    last_used_step: Option<i64>,
+) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize
+        || !code.bytes().all(|byte| byte.is_ascii_digit())
+    {
+        return None;
+    }
+    let key = base32_decode(secret)?;
+    let current_step = unix_time.div_euclid(TOTP_PERIOD_SECS);
+
+    (-TOTP_ALLOWED_DRIFT_STEPS..=TOTP_ALLOWED_DRIFT_STEPS)
+        .map(|drift| current_step + drift)
+        .filter(|step| {
+            *step >= 0 && last_used_step.is_none_or(|last| *step > last)
+        })
+        .find(|step| format_code(hotp(&key, *step as u64)) == code)
+}
+
+/// One-time codes for when the authenticator is lost, shown to the user once.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..10)
+                .map(|_| {
+                    let index =
+                        rng.next_u32() as usize % RECOVERY_CODE_ALPHABET.len();
+                    RECOVERY_CODE_ALPHABET[index] as char
+                })
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Recovery codes are compared without case and separators.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+// RFC 4226 HOTP value before it is reduced to digits
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let mut mac =
+        HmacSha1::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ])
+}
+
+fn format_code(value: u32) -> String {
+    format!(
+        "{:0width$}",
+        value % 10u32.pow(TOTP_DIGITS),
+        width = TOTP_DIGITS as usize
+    )
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(
+                BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char,
+            );
+        }
+    }
+    if bits > 0 {
+        output.push(
+            BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char,
+        );
+    }
+
+    output
+}
+
+fn base32_decode(value: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in value.chars().filter(|c| *c != '=' && !c.is_whitespace()) {
+        let index = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a as char == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | index as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        // RFC 6238 SHA-1 secret "12345678901234567890", last 6 digits of the 8-digit values
+        let secret = base32_encode(b"12345678901234567890");
+        assert_eq!(secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(base32_decode(&secret).unwrap(), b"12345678901234567890");
+
+        assert_eq!(verify_code(&secret, "287082", 59, None), Some(1));
+        assert_eq!(
+            verify_code(&secret, "081804", 1111111109, None),
+            Some(37037036)
+        );
+        assert_eq!(
+            verify_code(&secret, "005924", 1234567890, None),
+            Some(41152263)
+        );
+
+        // Neighbouring periods are accepted, used or malformed codes are not
+        assert_eq!(verify_code(&secret, "287082", 89, None), Some(1));
+        assert_eq!(verify_code(&secret, "287082", 59, Some(1)), None);
+        assert_eq!(verify_code(&secret, "287083", 59, None), None);
+        assert_eq!(verify_code(&secret, "28708", 59, None), None);
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes();
+
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(codes[0].len(), 11);
+        assert_eq!(normalize_recovery_code(&codes[0].to_lowercase()).len(), 10);
+    }
+}
@@ -0,0 +1,317 @@
+use crate::common::error::UploadError;
+use crate::database::storage::local::read_file_chunks;
+use anyhow::Result;
+use axum_extra::extract::multipart::Field;
+use bytes::Bytes;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use image::ImageFormat;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+const MIB: usize = 1024 * 1024;
+
+// Per-route upload limits, enforced while the file is being received
+pub const COVER_UPLOAD_MAX_BYTES: usize = 20 * MIB;
+pub const AVATAR_UPLOAD_MAX_BYTES: usize = 5 * MIB;
+pub const COMMENT_ATTACHMENT_MAX_BYTES: usize = 5 * MIB;
+pub const MAX_COMMENT_ATTACHMENTS: usize = 4;
+pub const CHAPTER_PAGE_MAX_BYTES: usize = 64 * MIB;
+pub const CHAPTER_ARCHIVE_MAX_BYTES: usize = 256 * MIB;
+
+// Room for the other form fields and the multipart boundaries
+const MULTIPART_OVERHEAD_BYTES: usize = MIB;
+
+// Enough to recognize every accepted image format
+const SNIFF_BYTES: usize = 32;
+
+const ACCEPTED_IMAGE_FORMATS: [ImageFormat; 5] = [
+    ImageFormat::Jpeg,
+    ImageFormat::Png,
+    ImageFormat::WebP,
+    ImageFormat::Gif,
+    ImageFormat::Avif,
+];
+
+/// Request body limit of a route receiving `max_files` files of up to `max_file_bytes`.
+/// Bodies above it are rejected before the handler reads them.
+pub const fn multipart_body_limit(
+    max_file_bytes: usize,
+    max_files: usize,
+) -> usize {
+    max_file_bytes * max_files + MULTIPART_OVERHEAD_BYTES
+}
+
+/// Image type detected from the file content, the client's content type is ignored.
+pub fn sniff_image_format(bytes: &[u8]) -> Option<ImageFormat> {
+    image::guess_format(bytes)
+        .ok()
+        .filter(|format| ACCEPTED_IMAGE_FORMATS.contains(format))
+}
+
+/// File extension used for the object key of a detected image.
+pub fn image_extension(format: ImageFormat) -> &'static str {
+    format.extensions_str().first().copied().unwrap_or("bin")
+}
+
+/// An image field whose type is known, the rest is received while streaming it.
+pub struct ImageFieldStream {
+    pub format: ImageFormat,
+    head: Bytes,
+    field: Field,
+    max_bytes: usize,
+}
+
+impl ImageFieldStream {
+    /// Reads the first bytes of the field to detect the image type.
+    pub async fn open(
+        mut field: Field,
+        max_bytes: usize,
+    ) -> Result<Self, UploadError> {
+        let mut head = Vec::new();
+        while head.len() < SNIFF_BYTES {
+            match field.chunk().await? {
+                Some(chunk) => head.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+
+        if head.len() > max_bytes {
+            return Err(UploadError::TooLarge { max_bytes });
+        }
+        let format =
+            sniff_image_format(&head).ok_or(UploadError::UnsupportedType)?;
+
+        Ok(Self {
+            format,
+            head: Bytes::from(head),
+            field,
+            max_bytes,
+        })
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        self.format.to_mime_type()
+    }
+
+    /// Chunks of the whole file, failing with `UploadError::TooLarge`
+    /// as soon as the limit is crossed.
+    pub fn into_chunks(self) -> BoxStream<'static, Result<Bytes>> {
+        field_chunks(self.field, self.head, self.max_bytes)
+    }
+
+    /// Receives the whole file into memory.
+    pub async fn read_to_end(self) -> Result<Vec<u8>, UploadError> {
+        let mut chunks = self.into_chunks();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = chunks.next().await {
+            bytes.extend_from_slice(&chunk?);
+        }
+        Ok(bytes)
+    }
+}
+
+/// Receives a whole image field into memory and detects its type.
+pub async fn read_image_field(
+    field: Field,
+    max_bytes: usize,
+) -> Result<(Vec<u8>, ImageFormat), UploadError> {
+    let stream = ImageFieldStream::open(field, max_bytes).await?;
+    let format = stream.format;
+    Ok((stream.read_to_end().await?, format))
+}
+
+// Chunks of a field after the already received `head`, limited to `max_bytes` in total
+fn field_chunks(
+    field: Field,
+    head: Bytes,
+    max_bytes: usize,
+) -> BoxStream<'static, Result<Bytes>> {
+    let received = head.len();
+    let rest = futures::stream::try_unfold(
+        (field, received),
+        move |(mut field, received)| async move {
+            let Some(chunk) = field.chunk().await.map_err(UploadError::from)?
+            else {
+                return Ok(None);
+            };
+
+            let received = received + chunk.len();
+            if received > max_bytes {
+                return Err(UploadError::TooLarge { max_bytes }.into());
+            }
+            Ok(Some((chunk, (field, received))))
+        },
+    );
+
+    futures::stream::iter((!head.is_empty()).then_some(Ok(head)))
+        .chain(rest)
+        .boxed()
+}
+
+/// An uploaded file spooled to disk, removed again when dropped.
+pub struct TempUploadFile {
+    path: PathBuf,
+}
+
+impl TempUploadFile {
+    /// Writes the field to a temporary file chunk by chunk,
+    /// large archives never have to fit in memory.
+    pub async fn from_field(
+        field: Field,
+        max_bytes: usize,
+    ) -> Result<Self, UploadError> {
+        Self::from_chunks(field_chunks(field, Bytes::new(), max_bytes)).await
+    }
+
+    /// Writes a stream of chunks to a temporary file,
+    /// errors of the stream are returned as `UploadError`.
+    pub async fn from_chunks(
+        mut chunks: BoxStream<'_, Result<Bytes>>,
+    ) -> Result<Self, UploadError> {
+        let temp_file = Self {
+            path: std::env::temp_dir()
+                .join(format!("web-rs-upload-{}", uuid::Uuid::new_v4())),
+        };
+
+        let mut file = tokio::fs::File::create(&temp_file.path)
+            .await
+            .map_err(|e| UploadError::Storage(e.into()))?;
+        while let Some(chunk) = chunks.next().await {
+            file.write_all(&chunk?)
+                .await
+                .map_err(|e| UploadError::Storage(e.into()))?;
+        }
+        file.flush()
+            .await
+            .map_err(|e| UploadError::Storage(e.into()))?;
+
+        Ok(temp_file)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the file back chunk by chunk, e.g. to upload it.
+    pub async fn chunks(&self) -> Result<BoxStream<'static, Result<Bytes>>> {
+        let file = tokio::fs::File::open(&self.path).await?;
+        Ok(read_file_chunks(file))
+    }
+}
+
+impl Drop for TempUploadFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_image_format() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let jpeg = b"\xFF\xD8\xFF\xE0\0\x10JFIF\0";
+        let avif = b"\0\0\0\x1cftypavif\0\0\0\0";
+
+        assert_eq!(sniff_image_format(png), Some(ImageFormat::Png));
+        assert_eq!(sniff_image_format(jpeg), Some(ImageFormat::Jpeg));
+        assert_eq!(sniff_image_format(avif), Some(ImageFormat::Avif));
+        assert_eq!(image_extension(ImageFormat::Jpeg), "jpg");
+
+        // Renamed files and other formats are rejected by content
+        assert_eq!(sniff_image_format(b"<svg xmlns=\"\"></svg>"), None);
+        assert_eq!(sniff_image_format(b"BM\0\0\0\0\0\0\0\0"), None);
+        assert_eq!(sniff_image_format(b""), None);
+    }
+
+    async fn multipart_field(file_bytes: &[u8]) -> Field {
+        use axum::body::Body;
+        use axum::extract::FromRequest;
+        use axum::http::{Request, header};
+        use axum_extra::extract::Multipart;
+
+        let mut body = b"--BOUNDARY\r\nContent-Disposition: form-data; \
+            name=\"file\"; filename=\"upload\"\r\n\r\n"
+            .to_vec();
+        body.extend_from_slice(file_bytes);
+        body.extend_from_slice(b"\r\n--BOUNDARY--\r\n");
+
+        let request = Request::builder()
+            .header(
+                header::CONTENT_TYPE,
+                "multipart/form-data; boundary=BOUNDARY",
+            )
+            .body(Body::from(body))
+            .unwrap();
+        let mut multipart =
+            Multipart::from_request(request, &()).await.unwrap();
+        multipart.next_field().await.unwrap().unwrap()
+    }
+
+    fn png_file(size: usize) -> Vec<u8> {
+        let mut file = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        file.resize(size, 7);
+        file
+    }
+
+    #[tokio::test]
+    async fn test_image_field_upload_limit() {
+        let file = png_file(4096);
+
+        // Spooled to disk unchanged while below the limit
+        let field = multipart_field(&file).await;
+        let upload = ImageFieldStream::open(field, 4096).await.unwrap();
+        assert_eq!(upload.content_type(), "image/png");
+        let spooled = TempUploadFile::from_chunks(upload.into_chunks())
+            .await
+            .unwrap();
+        assert_eq!(tokio::fs::read(spooled.path()).await.unwrap(), file);
+
+        let chunks: Vec<Bytes> =
+            futures::TryStreamExt::try_collect(spooled.chunks().await.unwrap())
+                .await
+                .unwrap();
+        assert_eq!(chunks.concat(), file);
+
+        let path = spooled.path().to_path_buf();
+        drop(spooled);
+        assert!(!path.exists());
+
+        // One byte over the limit is rejected
+        let field = multipart_field(&file).await;
+        let result = match ImageFieldStream::open(field, 4095).await {
+            Ok(upload) => TempUploadFile::from_chunks(upload.into_chunks())
+                .await
+                .map(|_| ()),
+            Err(e) => Err(e),
+        };
+        assert!(matches!(
+            result,
+            Err(UploadError::TooLarge { max_bytes: 4095 })
+        ));
+
+        let field = multipart_field(b"GIF").await;
+        assert!(matches!(
+            ImageFieldStream::open(field, 4096).await,
+            Err(UploadError::UnsupportedType)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_temp_upload_file_limit() {
+        let archive = vec![1; 10_000];
+
+        let field = multipart_field(&archive).await;
+        let spooled = TempUploadFile::from_field(field, 10_000).await.unwrap();
+        assert_eq!(tokio::fs::read(spooled.path()).await.unwrap(), archive);
+
+        let field = multipart_field(&archive).await;
+        assert!(matches!(
+            TempUploadFile::from_field(field, 9_999).await,
+            Err(UploadError::TooLarge { max_bytes: 9_999 })
+        ));
+    }
+}
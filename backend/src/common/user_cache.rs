@@ -0,0 +1,222 @@
+use crate::database::UserAuthState;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Expired entries are dropped once this many users are cached
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct CachedUsers {
+    by_id: HashMap<i32, (Instant, UserAuthState)>,
+    // Sessions found active, by id with the user they belong to
+    sessions: HashMap<i64, (Instant, i32)>,
+    prune_at: usize,
+}
+
+/// Users and sessions recently seen by the authentication extractors, by id.
+/// Every instance caches on its own, a change made elsewhere
+/// (role, token version, a revoked session) shows up here after at most the TTL.
+#[derive(Clone)]
+pub struct UserCache {
+    ttl: Option<Duration>,
+    users: Arc<Mutex<CachedUsers>>,
+}
+
+impl UserCache {
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            ttl,
+            users: Arc::new(Mutex::new(CachedUsers {
+                by_id: HashMap::new(),
+                sessions: HashMap::new(),
+                prune_at: PRUNE_THRESHOLD,
+            })),
+        }
+    }
+
+    /// Reads `USER_CACHE_TTL_SECONDS`, unset or `0` disables the cache
+    /// and every request loads its user from the database.
+    pub fn from_env() -> Self {
+        let ttl = std::env::var("USER_CACHE_TTL_SECONDS").ok().and_then(|value| {
+            match value.trim().parse::<u64>() {
+                Ok(secs) => Some(Duration::from_secs(secs)),
+                Err(_) => {
+                    eprintln!(
+                        "[USER-CACHE] Invalid USER_CACHE_TTL_SECONDS '{}', cache disabled",
+                        value
+                    );
+                    None
+                }
+            }
+        });
+
+        Self::new(ttl.filter(|ttl| !ttl.is_zero()))
+    }
+
+    pub fn get(&self, user_id: i32) -> Option<UserAuthState> {
+        self.get_at(user_id, Instant::now())
+    }
+
+    fn get_at(&self, user_id: i32, now: Instant) -> Option<UserAuthState> {
+        let ttl = self.ttl?;
+        let users = self.lock();
+
+        users
+            .by_id
+            .get(&user_id)
+            .filter(|(cached_at, _)| now.duration_since(*cached_at) < ttl)
+            .map(|(_, user)| user.clone())
+    }
+
+    pub fn insert(&self, user: UserAuthState) {
+        self.insert_at(user, Instant::now());
+    }
+
+    fn insert_at(&self, user: UserAuthState, now: Instant) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+        let mut users = self.lock();
+
+        if users.by_id.len() >= users.prune_at {
+            users.by_id.retain(|_, (cached_at, _)| {
+                now.duration_since(*cached_at) < ttl
+            });
+            users.prune_at = (users.by_id.len() * 2).max(PRUNE_THRESHOLD);
+        }
+
+        users.by_id.insert(user.id, (now, user));
+    }
+
+    /// Whether the session was found active within the TTL,
+    /// otherwise the extractor checks it in the database.
+    pub fn is_session_active(&self, session_id: i64, user_id: i32) -> bool {
+        self.is_session_active_at(session_id, user_id, Instant::now())
+    }
+
+    fn is_session_active_at(
+        &self,
+        session_id: i64,
+        user_id: i32,
+        now: Instant,
+    ) -> bool {
+        let Some(ttl) = self.ttl else {
+            return false;
+        };
+
+        self.lock().sessions.get(&session_id).is_some_and(
+            |(cached_at, owner)| {
+                *owner == user_id && now.duration_since(*cached_at) < ttl
+            },
+        )
+    }
+
+    pub fn insert_session(&self, session_id: i64, user_id: i32) {
+        self.insert_session_at(session_id, user_id, Instant::now());
+    }
+
+    fn insert_session_at(&self, session_id: i64, user_id: i32, now: Instant) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+        let mut users = self.lock();
+
+        if users.sessions.len() >= users.prune_at {
+            users.sessions.retain(|_, (cached_at, _)| {
+                now.duration_since(*cached_at) < ttl
+            });
+            users.prune_at = (users.sessions.len() * 2).max(PRUNE_THRESHOLD);
+        }
+
+        users.sessions.insert(session_id, (now, user_id));
+    }
+
+    /// Drops a session revoked on this instance, its access tokens stop working at once.
+    pub fn invalidate_session(&self, session_id: i64) {
+        self.lock().sessions.remove(&session_id);
+    }
+
+    /// Drops the user and its sessions after a change on this instance,
+    /// the next request reloads them.
+    pub fn invalidate(&self, user_id: i32) {
+        let mut users = self.lock();
+        users.by_id.remove(&user_id);
+        users.sessions.retain(|_, (_, owner)| *owner != user_id);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CachedUsers> {
+        // A poisoned lock only means another request panicked, the entries are still usable
+        self.users
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: i32) -> UserAuthState {
+        UserAuthState {
+            id,
+            username: format!("user{}", id),
+            role_name: "user".to_string(),
+            permissions: Vec::new(),
+            email_verified: true,
+            two_factor_enabled: false,
+            token_version: 0,
+            is_active: true,
+            suspended_until: None,
+            suspension_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_entries_expire_and_invalidate() {
+        let cache = UserCache::new(Some(Duration::from_secs(30)));
+        let now = Instant::now();
+
+        cache.insert_at(user(1), now);
+        cache.insert_at(user(2), now);
+        assert_eq!(cache.get_at(1, now).unwrap().username, "user1");
+        assert!(cache.get_at(1, now + Duration::from_secs(30)).is_none());
+
+        cache.invalidate(2);
+        assert!(cache.get_at(2, now).is_none());
+    }
+
+    #[test]
+    fn test_sessions_expire_and_invalidate() {
+        let cache = UserCache::new(Some(Duration::from_secs(30)));
+        let now = Instant::now();
+
+        cache.insert_session_at(10, 1, now);
+        cache.insert_session_at(11, 1, now);
+        cache.insert_session_at(20, 2, now);
+        assert!(cache.is_session_active_at(10, 1, now));
+        assert!(!cache.is_session_active_at(10, 2, now));
+        assert!(!cache.is_session_active_at(
+            10,
+            1,
+            now + Duration::from_secs(30)
+        ));
+
+        cache.invalidate_session(10);
+        assert!(!cache.is_session_active_at(10, 1, now));
+        assert!(cache.is_session_active_at(11, 1, now));
+
+        // Revoking all sessions of a user goes through `invalidate`
+        cache.invalidate(1);
+        assert!(!cache.is_session_active_at(11, 1, now));
+        assert!(cache.is_session_active_at(20, 2, now));
+    }
+
+    #[test]
+    fn test_disabled_cache_keeps_nothing() {
+        let cache = UserCache::new(None);
+        cache.insert(user(1));
+        cache.insert_session(10, 1);
+        assert!(cache.get(1).is_none());
+        assert!(!cache.is_session_active(10, 1));
+    }
+}
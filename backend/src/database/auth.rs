@@ -75,4 +75,192 @@ impl DatabaseService {
 
         Ok(())
     }
+
+    pub async fn create_email_verification_token(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        email: &str,
+        expires_at: DateTime<Utc>,
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            "INSERT INTO email_verification_tokens (user_id, token_hash, email, expires_at) VALUES ($1, $2, $3, $4)",
+            user_id,
+            token_hash,
+            email,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create email verification token")?;
+
+        Ok(())
+    }
+
+    /// When the last verification email was sent and how many were sent in the last hour.
+    pub async fn get_email_verification_send_stats(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<(Option<DateTime<Utc>>, i64)> {
+        let stats = sqlx::query!(
+            r#"
+            SELECT
+                MAX(created_at) AS last_sent_at,
+                COUNT(*) FILTER (WHERE created_at > NOW() - interval '1 hour') AS "sent_last_hour!"
+            FROM email_verification_tokens
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to get email verification stats")?;
+
+        Ok((stats.last_sent_at, stats.sent_last_hour))
+    }
+
+    /// Marks the email as verified, returns the user id.
+    /// A link sent to an address the user has since changed is rejected.
+    pub async fn verify_email_with_token(
+        &self,
+        token_hash: &str,
+    ) -> AnyhowResult<Option<i32>> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        let user_id = sqlx::query_scalar!(
+            r#"
+            UPDATE users u
+            SET email_verified_at = COALESCE(u.email_verified_at, NOW()),
+                updated_at = NOW()
+            FROM email_verification_tokens t
+            WHERE t.token_hash = $1
+                AND t.expires_at > NOW()
+                AND t.user_id = u.id
+                AND t.email = u.email
+            RETURNING u.id
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .context("Failed to verify email")?;
+
+        if let Some(user_id) = user_id {
+            sqlx::query!(
+                "DELETE FROM email_verification_tokens WHERE user_id = $1",
+                user_id
+            )
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete email verification tokens")?;
+        }
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(user_id)
+    }
+
+    /// End of the current login lockout of an account or identifier, if it is locked.
+    pub async fn get_login_locked_until(
+        &self,
+        failure_key: &str,
+    ) -> AnyhowResult<Option<DateTime<Utc>>> {
+        let locked_until = sqlx::query_scalar!(
+            r#"
+            SELECT locked_until
+            FROM login_failures
+            WHERE failure_key = $1 AND locked_until > NOW()
+            "#,
+            failure_key
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get login lockout with sqlx")?
+        .flatten();
+
+        Ok(locked_until)
+    }
+
+    /// Counts a failed login and returns the failures in a row.
+    /// The count starts over when the previous failure is older than `reset_after`.
+    pub async fn record_failed_login(
+        &self,
+        failure_key: &str,
+        reset_after: std::time::Duration,
+    ) -> AnyhowResult<i32> {
+        let failures = sqlx::query_scalar!(
+            r#"
+            INSERT INTO login_failures (failure_key, failed_count, last_failed_at)
+            VALUES ($1, 1, NOW())
+            ON CONFLICT (failure_key) DO UPDATE
+            SET failed_count = CASE
+                    WHEN login_failures.last_failed_at > NOW() - make_interval(secs => $2)
+                        THEN login_failures.failed_count + 1
+                    ELSE 1
+                END,
+                last_failed_at = NOW()
+            RETURNING failed_count
+            "#,
+            failure_key,
+            reset_after.as_secs_f64()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to record failed login with sqlx")?;
+
+        Ok(failures)
+    }
+
+    pub async fn lock_login(
+        &self,
+        failure_key: &str,
+        locked_until: DateTime<Utc>,
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            "UPDATE login_failures SET locked_until = $2 WHERE failure_key = $1",
+            failure_key,
+            locked_until
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to lock login with sqlx")?;
+
+        Ok(())
+    }
+
+    /// Clears the failure count and lockout after a successful login.
+    pub async fn reset_failed_logins(
+        &self,
+        failure_key: &str,
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            "DELETE FROM login_failures WHERE failure_key = $1",
+            failure_key
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to reset failed logins with sqlx")?;
+
+        Ok(())
+    }
+
+    /// Failure counts idle for a day and no longer locked, mostly mistyped or guessed names.
+    pub async fn cleanup_stale_login_failures(&self) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM login_failures
+            WHERE last_failed_at < NOW() - interval '1 day'
+                AND (locked_until IS NULL OR locked_until < NOW())
+            "#
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to clean up login failures with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
 }
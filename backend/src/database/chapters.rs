@@ -38,22 +38,364 @@ impl DatabaseService {
         Ok(new_id)
     }
 
+    /// Records one stored page (or page tile) of a chapter.
+    /// `image_order` is the reading order across all tiles, while
+    /// `source_image_order` and `tile_index` locate the tile in the source image.
     pub async fn add_chapter_images(
         &self,
         chapter_id: i32,
         image_order: i32,
         image_url: &str, // This will be the R2/CDN Url
+        source_image_order: i32,
+        tile_index: i32,
     ) -> AnyhowResult<i32> {
         let new_id = sqlx::query_scalar!(
-                "INSERT INTO chapter_images (chapter_id, image_order, image_url) VALUES ($1, $2, $3) RETURNING id",
+            "INSERT INTO chapter_images (chapter_id, image_order, image_url, source_image_order, tile_index)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id",
             chapter_id,
             image_order,
             image_url,
-            ).fetch_one(&self.pool).await.context("Failed to add chapter image with sqlx")?;
+            source_image_order,
+            tile_index,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to add chapter image with sqlx")?;
 
         Ok(new_id)
     }
 
+    /// Drops the page failures of a previous processing run of the chapter.
+    pub async fn clear_chapter_image_failures(
+        &self,
+        chapter_id: i32,
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            "DELETE FROM chapter_image_failures WHERE chapter_id = $1",
+            chapter_id
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to clear chapter image failures with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn add_chapter_image_failure(
+        &self,
+        chapter_id: i32,
+        source_image_order: i32,
+        source_url: &str,
+        reason: &str,
+        detail: Option<&str>,
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            "INSERT INTO chapter_image_failures (chapter_id, source_image_order, source_url, reason, detail)
+            VALUES ($1, $2, $3, $4, $5)",
+            chapter_id,
+            source_image_order,
+            source_url,
+            reason,
+            detail,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to add chapter image failure with sqlx")?;
+
+        Ok(())
+    }
+
+    pub async fn get_chapter_image_failures(
+        &self,
+        chapter_id: i32,
+    ) -> AnyhowResult<Vec<ChapterImageFailure>> {
+        let failures = sqlx::query_as!(
+            ChapterImageFailure,
+            r#"
+            SELECT source_image_order, source_url, reason, detail, created_at
+            FROM chapter_image_failures
+            WHERE chapter_id = $1
+            ORDER BY source_image_order ASC
+            "#,
+            chapter_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get chapter image failures with sqlx")?;
+
+        Ok(failures)
+    }
+
+    pub async fn get_chapter_by_id(
+        &self,
+        chapter_id: i32,
+    ) -> AnyhowResult<Option<SeriesChapter>> {
+        let chapter = sqlx::query_as!(
+            SeriesChapter,
+            r#"
+            SELECT id, series_id, chapter_number, status AS "status: _", status_reason, title, source_url, created_at
+            FROM series_chapters
+            WHERE id = $1
+            "#,
+            chapter_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get chapter by ID with sqlx")?;
+
+        Ok(chapter)
+    }
+
+    pub async fn get_chapter_id_by_number(
+        &self,
+        series_id: i32,
+        chapter_number: f32,
+    ) -> AnyhowResult<Option<i32>> {
+        let chapter_id = sqlx::query_scalar!(
+            "SELECT id FROM series_chapters WHERE series_id = $1 AND chapter_number = $2",
+            series_id,
+            chapter_number,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get chapter ID by number with sqlx")?;
+
+        Ok(chapter_id)
+    }
+
+    /// Updates the number and/or title of a chapter, `None` keeps the current value.
+    pub async fn update_chapter_details(
+        &self,
+        chapter_id: i32,
+        chapter_number: Option<f32>,
+        title: Option<&str>,
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            "UPDATE series_chapters
+            SET chapter_number = COALESCE($1, chapter_number),
+                title = COALESCE($2, title),
+                updated_at = NOW()
+            WHERE id = $3",
+            chapter_number,
+            title,
+            chapter_id,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to update chapter details with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Points the images of a chapter and their variants at moved objects.
+    /// `moves` lists `(old_key, new_key)` for the images, variants and placeholders.
+    pub async fn move_chapter_image_keys(
+        &self,
+        chapter_id: i32,
+        moves: &[(String, String)],
+    ) -> AnyhowResult<()> {
+        let (old_keys, new_keys): (Vec<String>, Vec<String>) =
+            moves.iter().cloned().unzip();
+
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to start transaction")?;
+
+        // Variants reference their set by key, so the sets are copied first
+        sqlx::query!(
+            "INSERT INTO image_variant_sets (source_key, width, height, placeholder_key, created_at)
+            SELECT m.new_key, s.width, s.height, COALESCE(p.new_key, s.placeholder_key), s.created_at
+            FROM image_variant_sets s
+            JOIN UNNEST($1::TEXT[], $2::TEXT[]) AS m(old_key, new_key) ON s.source_key = m.old_key
+            LEFT JOIN UNNEST($1::TEXT[], $2::TEXT[]) AS p(old_key, new_key) ON s.placeholder_key = p.old_key",
+            &old_keys,
+            &new_keys,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to copy image variant sets")?;
+
+        sqlx::query!(
+            "UPDATE image_variants v
+            SET source_key = m.new_key,
+                object_key = COALESCE(
+                    (SELECT o.new_key FROM UNNEST($1::TEXT[], $2::TEXT[]) AS o(old_key, new_key)
+                    WHERE o.old_key = v.object_key),
+                    v.object_key
+                )
+            FROM UNNEST($1::TEXT[], $2::TEXT[]) AS m(old_key, new_key)
+            WHERE v.source_key = m.old_key",
+            &old_keys,
+            &new_keys,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to move image variants")?;
+
+        sqlx::query!(
+            "DELETE FROM image_variant_sets WHERE source_key = ANY($1)",
+            &old_keys,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete old image variant sets")?;
+
+        sqlx::query!(
+            "UPDATE chapter_images ci
+            SET image_url = m.new_key
+            FROM UNNEST($2::TEXT[], $3::TEXT[]) AS m(old_key, new_key)
+            WHERE ci.chapter_id = $1 AND ci.image_url = m.old_key",
+            chapter_id,
+            &old_keys,
+            &new_keys,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to move chapter images")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(())
+    }
+
+    /// All stored images of a chapter in reading order.
+    pub async fn get_chapter_images(
+        &self,
+        chapter_id: i32,
+    ) -> AnyhowResult<Vec<ChapterImage>> {
+        let images = sqlx::query_as!(
+            ChapterImage,
+            r#"
+            SELECT id, image_order, image_url,
+                COALESCE(source_image_order, image_order) AS "page_number!",
+                tile_index
+            FROM chapter_images
+            WHERE chapter_id = $1
+            ORDER BY image_order ASC
+            "#,
+            chapter_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get chapter images with sqlx")?;
+
+        Ok(images)
+    }
+
+    /// Swaps the images of one page for newly uploaded tiles and returns
+    /// the keys of the replaced images. Passing no keys deletes the page.
+    /// Images are renumbered afterwards so pages stay consecutive.
+    pub async fn replace_chapter_page_images(
+        &self,
+        chapter_id: i32,
+        page_number: i32,
+        new_image_keys: &[String],
+    ) -> AnyhowResult<Vec<String>> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to start transaction")?;
+
+        let old_image_keys = sqlx::query_scalar!(
+            "DELETE FROM chapter_images
+            WHERE chapter_id = $1 AND COALESCE(source_image_order, image_order) = $2
+            RETURNING image_url",
+            chapter_id,
+            page_number,
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .context("Failed to delete chapter page images")?;
+
+        // Placeholder image_order, fixed by the renumbering below
+        sqlx::query!(
+            "INSERT INTO chapter_images (chapter_id, image_order, image_url, source_image_order, tile_index)
+            SELECT $1, 0, tile.image_url, $2, (tile.position - 1)::INT
+            FROM UNNEST($3::TEXT[]) WITH ORDINALITY AS tile(image_url, position)",
+            chapter_id,
+            page_number,
+            new_image_keys,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to insert replacement page images")?;
+
+        Self::renumber_chapter_images(&mut tx, chapter_id).await?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(old_image_keys)
+    }
+
+    /// Moves pages into a new order. `page_order` lists every current
+    /// page number of the chapter, in the order they should be read.
+    pub async fn reorder_chapter_pages(
+        &self,
+        chapter_id: i32,
+        page_order: &[i32],
+    ) -> AnyhowResult<u64> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to start transaction")?;
+
+        let result = sqlx::query!(
+            "UPDATE chapter_images ci
+            SET source_image_order = new_order.position::INT
+            FROM UNNEST($2::INT[]) WITH ORDINALITY AS new_order(page_number, position)
+            WHERE ci.chapter_id = $1
+                AND COALESCE(ci.source_image_order, ci.image_order) = new_order.page_number",
+            chapter_id,
+            page_order,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to reorder chapter pages")?;
+
+        Self::renumber_chapter_images(&mut tx, chapter_id).await?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Rewrites image_order and page numbers as 1..n following the current page
+    // and tile order, closing the gaps left by deleted or moved pages.
+    async fn renumber_chapter_images(
+        tx: &mut Transaction<'_, Postgres>,
+        chapter_id: i32,
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            "WITH ranked AS (
+                SELECT id,
+                    ROW_NUMBER() OVER (
+                        ORDER BY COALESCE(source_image_order, image_order), tile_index, id
+                    ) AS new_image_order,
+                    DENSE_RANK() OVER (
+                        ORDER BY COALESCE(source_image_order, image_order)
+                    ) AS new_page_number
+                FROM chapter_images
+                WHERE chapter_id = $1
+            )
+            UPDATE chapter_images ci
+            SET image_order = ranked.new_image_order::INT,
+                source_image_order = ranked.new_page_number::INT
+            FROM ranked
+            WHERE ci.id = ranked.id",
+            chapter_id,
+        )
+        .execute(&mut **tx)
+        .await
+        .context("Failed to renumber chapter images")?;
+
+        Ok(())
+    }
+
     pub async fn delete_chapter_and_images_for_chapter(
         &self,
         series_id: i32,
@@ -105,10 +447,12 @@ impl DatabaseService {
         &self,
         chapter_id: i32,
         new_status: ChapterStatus,
+        status_reason: Option<&str>,
     ) -> AnyhowResult<u64> {
         let result = sqlx::query!(
-            "UPDATE series_chapters SET status = $1 WHERE id = $2",
+            "UPDATE series_chapters SET status = $1, status_reason = $2 WHERE id = $3",
             new_status as _,
+            status_reason,
             chapter_id,
         )
         .execute(&self.pool)
@@ -152,6 +496,38 @@ impl DatabaseService {
         Ok(urls)
     }
 
+    /// Available chapters between two chapter numbers (inclusive)
+    /// with their image keys in reading order.
+    pub async fn get_chapter_pages_in_range(
+        &self,
+        series_id: i32,
+        from_chapter: f32,
+        to_chapter: f32,
+    ) -> AnyhowResult<Vec<ChapterPages>> {
+        let chapters = sqlx::query_as!(
+            ChapterPages,
+            r#"
+            SELECT sc.chapter_number, sc.title,
+                ARRAY_AGG(ci.image_url ORDER BY ci.image_order) AS "image_keys!"
+            FROM series_chapters sc
+            JOIN chapter_images ci ON ci.chapter_id = sc.id
+            WHERE sc.series_id = $1
+                AND sc.chapter_number BETWEEN $2 AND $3
+                AND sc.status = 'Available'
+            GROUP BY sc.id
+            ORDER BY sc.chapter_number ASC
+            "#,
+            series_id,
+            from_chapter,
+            to_chapter,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get chapter pages in range with sqlx")?;
+
+        Ok(chapters)
+    }
+
     // Get chapters for a sepecific series
     pub async fn get_chapters_by_series_id(
         &self,
@@ -160,7 +536,7 @@ impl DatabaseService {
         let chapters = sqlx::query_as!(
             SeriesChapter,
             r#"
-            SELECT id, series_id, chapter_number, status AS "status: _",title, source_url, created_at
+            SELECT id, series_id, chapter_number, status AS "status: _", status_reason, title, source_url, created_at
             FROM series_chapters
             WHERE series_id = $1
             ORDER BY chapter_number
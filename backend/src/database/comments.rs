@@ -316,6 +316,19 @@ impl DatabaseService {
         Ok(updated_html)
     }
 
+    /// Hides a comment of any user together with its replies, returns the rows affected.
+    pub async fn remove_comment(&self, comment_id: i64) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            "UPDATE comments SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL",
+            comment_id
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to remove comment")?;
+
+        Ok(result.rows_affected())
+    }
+
     pub async fn vote_on_comment(
         &self,
         comment_id: i64,
@@ -0,0 +1,268 @@
+use super::*;
+
+/// A provider account linked to a user, as shown on the profile page.
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserIdentity {
+    pub provider: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+/// Authorization request data needed again in the callback.
+#[derive(Debug, FromRow)]
+pub struct OidcLoginState {
+    pub code_verifier: String,
+    pub nonce: String,
+    pub link_user_id: Option<i32>,
+}
+
+/// Social login: provider identities and pending authorization requests.
+impl DatabaseService {
+    pub async fn create_oidc_login_state(
+        &self,
+        state_hash: &str,
+        provider: &str,
+        code_verifier: &str,
+        nonce: &str,
+        link_user_id: Option<i32>,
+        expires_at: DateTime<Utc>,
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO oidc_login_states
+                (state_hash, provider, code_verifier, nonce, link_user_id, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            state_hash,
+            provider,
+            code_verifier,
+            nonce,
+            link_user_id,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create oidc login state with sqlx")?;
+
+        Ok(())
+    }
+
+    /// Removes and returns an unexpired authorization request, a state works only once.
+    pub async fn take_oidc_login_state(
+        &self,
+        state_hash: &str,
+        provider: &str,
+    ) -> AnyhowResult<Option<OidcLoginState>> {
+        let login_state = sqlx::query_as!(
+            OidcLoginState,
+            r#"
+            DELETE FROM oidc_login_states
+            WHERE state_hash = $1 AND provider = $2 AND expires_at > NOW()
+            RETURNING code_verifier, nonce, link_user_id
+            "#,
+            state_hash,
+            provider
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to take oidc login state with sqlx")?;
+
+        Ok(login_state)
+    }
+
+    pub async fn cleanup_expired_oidc_login_states(&self) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            "DELETE FROM oidc_login_states WHERE expires_at < NOW()"
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to cleanup oidc login states with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// User linked to a provider account, recording the login.
+    pub async fn get_user_id_by_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> AnyhowResult<Option<i32>> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+            UPDATE user_identities
+            SET last_login_at = NOW()
+            WHERE provider = $1 AND subject = $2
+            RETURNING user_id
+            "#,
+            provider,
+            subject
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get user by identity with sqlx")?;
+
+        Ok(user_id)
+    }
+
+    /// Links a provider account to a user.
+    /// False if the account is linked to someone else or the user already has one of this provider.
+    pub async fn link_user_identity(
+        &self,
+        user_id: i32,
+        provider: &str,
+        subject: &str,
+        email: Option<&str>,
+    ) -> AnyhowResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_identities (user_id, provider, subject, email)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT DO NOTHING
+            "#,
+            user_id,
+            provider,
+            subject,
+            email
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to link user identity with sqlx")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Creates an account without password for a new provider account.
+    /// None if the username or email was taken in the meantime.
+    pub async fn create_user_with_identity(
+        &self,
+        username: &str,
+        email: &str,
+        email_verified: bool,
+        role_id: i32,
+        provider: &str,
+        subject: &str,
+    ) -> AnyhowResult<Option<i32>> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        // An empty hash never verifies, a password can be set later from the profile
+        let user_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO users (username, email, password_hash, role_id, email_verified_at)
+            VALUES ($1, $2, '', $3, CASE WHEN $4 THEN NOW() END)
+            ON CONFLICT DO NOTHING
+            RETURNING id
+            "#,
+            username,
+            email,
+            role_id,
+            email_verified
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .context("Failed to create user with sqlx")?;
+
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_identities (user_id, provider, subject, email, last_login_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            "#,
+            user_id,
+            provider,
+            subject,
+            email
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to link user identity with sqlx")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(Some(user_id))
+    }
+
+    pub async fn get_user_identities(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<Vec<UserIdentity>> {
+        let identities = sqlx::query_as!(
+            UserIdentity,
+            r#"
+            SELECT provider, email, created_at, last_login_at
+            FROM user_identities
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get user identities with sqlx")?;
+
+        Ok(identities)
+    }
+
+    /// Unlinks a provider, unless it is the only way left to log in.
+    /// Returns None if the provider is not linked, Some(false) if it is the last login method.
+    pub async fn unlink_user_identity(
+        &self,
+        user_id: i32,
+        provider: &str,
+    ) -> AnyhowResult<Option<bool>> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        // Locks the user row, two concurrent unlinks can not remove both providers
+        let login_methods = sqlx::query!(
+            r#"
+            SELECT
+                u.password_hash <> '' AS "has_password!",
+                (SELECT COUNT(*) FROM user_identities i WHERE i.user_id = u.id) AS "identities!",
+                EXISTS (
+                    SELECT 1 FROM user_identities i
+                    WHERE i.user_id = u.id AND i.provider = $2
+                ) AS "is_linked!"
+            FROM users u
+            WHERE u.id = $1
+            FOR UPDATE
+            "#,
+            user_id,
+            provider
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .context("Failed to get user login methods with sqlx")?;
+
+        let Some(login_methods) = login_methods.filter(|m| m.is_linked) else {
+            return Ok(None);
+        };
+        if !login_methods.has_password && login_methods.identities <= 1 {
+            return Ok(Some(false));
+        }
+
+        sqlx::query!(
+            "DELETE FROM user_identities WHERE user_id = $1 AND provider = $2",
+            user_id,
+            provider
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to unlink user identity with sqlx")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(Some(true))
+    }
+}
@@ -0,0 +1,156 @@
+use super::*;
+use std::collections::HashMap;
+
+/// Macros `sqlx::query!`
+/// For DML operations (INSERT, UPDATE, DELETE) or SELECTs,
+/// where you're manually processing generic `sqlx::Row`s (anonymous struct).
+///
+/// Macros `sqlx::query_as!`
+/// For mapping SELECT results directly to a defined rust struct (`#[derive(FromRow)]`),
+/// recommended for structured data retrieval.
+///
+/// Macros `sqlx::query_scalar!`
+/// For queries returning a single value (one row, one column).
+/// Highly efficient for this purpose.
+impl DatabaseService {
+    /// Records (or replaces) the responsive variants of a full-size image.
+    /// `variants` is a list of `(width, object_key)`.
+    pub async fn upsert_image_variant_set(
+        &self,
+        source_key: &str,
+        width: i32,
+        height: i32,
+        placeholder_key: Option<&str>,
+        variants: &[(i32, String)],
+    ) -> AnyhowResult<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to start transaction")?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO image_variant_sets (source_key, width, height, placeholder_key)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (source_key) DO UPDATE
+            SET width = EXCLUDED.width,
+                height = EXCLUDED.height,
+                placeholder_key = EXCLUDED.placeholder_key
+            "#,
+            source_key,
+            width,
+            height,
+            placeholder_key,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to upsert image variant set with sqlx")?;
+
+        // Variants of a previous encode are replaced as a whole
+        sqlx::query!(
+            "DELETE FROM image_variants WHERE source_key = $1",
+            source_key
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete old image variants with sqlx")?;
+
+        let (widths, object_keys): (Vec<i32>, Vec<String>) =
+            variants.iter().cloned().unzip();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO image_variants (source_key, width, object_key)
+            SELECT $1, * FROM UNNEST($2::int[], $3::text[])
+            "#,
+            source_key,
+            &widths,
+            &object_keys,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to insert image variants with sqlx")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(())
+    }
+
+    /// Get the variant sets for the given source keys.
+    /// Keys without recorded variants are simply missing from the map.
+    pub async fn get_image_variant_sets(
+        &self,
+        source_keys: &[String],
+    ) -> AnyhowResult<HashMap<String, ImageVariantSet>> {
+        if source_keys.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let set_rows = sqlx::query!(
+            r#"
+            SELECT source_key, width, height, placeholder_key
+            FROM image_variant_sets
+            WHERE source_key = ANY($1)
+            "#,
+            source_keys
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get image variant sets with sqlx")?;
+
+        let variant_rows = sqlx::query!(
+            r#"
+            SELECT source_key, width, object_key
+            FROM image_variants
+            WHERE source_key = ANY($1)
+            ORDER BY width ASC
+            "#,
+            source_keys
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get image variants with sqlx")?;
+
+        let mut sets: HashMap<String, ImageVariantSet> = set_rows
+            .into_iter()
+            .map(|row| {
+                let set = ImageVariantSet {
+                    width: row.width,
+                    height: row.height,
+                    placeholder_key: row.placeholder_key,
+                    variants: Vec::new(),
+                };
+                (row.source_key, set)
+            })
+            .collect();
+
+        for row in variant_rows {
+            if let Some(set) = sets.get_mut(&row.source_key) {
+                set.variants.push(ImageVariant {
+                    width: row.width,
+                    object_key: row.object_key,
+                });
+            }
+        }
+
+        Ok(sets)
+    }
+
+    /// Removes the variant records of the given source images.
+    /// The objects themselves must be deleted from storage by the caller.
+    pub async fn delete_image_variant_sets(
+        &self,
+        source_keys: &[String],
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            "DELETE FROM image_variant_sets WHERE source_key = ANY($1)",
+            source_keys
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to delete image variant sets with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+}
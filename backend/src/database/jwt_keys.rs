@@ -0,0 +1,105 @@
+use super::*;
+
+// Serializes key rotation between backend instances
+const JWT_KEY_ROTATION_LOCK_ID: i64 = 0x6a77_746b_6579;
+
+/// A stored access token signing key.
+#[derive(Debug, FromRow)]
+pub struct JwtSigningKey {
+    pub kid: String,
+    pub algorithm: String,
+    pub private_key: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub activates_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Signing keys of the access tokens.
+impl DatabaseService {
+    /// Every key that can still verify tokens, including ones not signing yet.
+    pub async fn get_jwt_signing_keys(
+        &self,
+    ) -> AnyhowResult<Vec<JwtSigningKey>> {
+        let keys = sqlx::query_as!(
+            JwtSigningKey,
+            r#"
+            SELECT kid, algorithm, private_key, public_key, activates_at, expires_at
+            FROM jwt_signing_keys
+            WHERE expires_at > NOW()
+            ORDER BY activates_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get jwt signing keys with sqlx")?;
+
+        Ok(keys)
+    }
+
+    /// Latest activation time of the unexpired keys, read under the rotation lock.
+    /// `insert` decides from it whether a new key is due and returns it.
+    pub async fn rotate_jwt_signing_key<F>(
+        &self,
+        insert: F,
+    ) -> AnyhowResult<Option<String>>
+    where
+        F: FnOnce(Option<DateTime<Utc>>) -> AnyhowResult<Option<JwtSigningKey>>,
+    {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock($1)",
+            JWT_KEY_ROTATION_LOCK_ID
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to lock jwt key rotation with sqlx")?;
+
+        let latest_activation = sqlx::query_scalar!(
+            "SELECT MAX(activates_at) FROM jwt_signing_keys WHERE expires_at > NOW()"
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .context("Failed to get latest jwt signing key with sqlx")?;
+
+        let Some(key) = insert(latest_activation)? else {
+            return Ok(None);
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO jwt_signing_keys
+                (kid, algorithm, private_key, public_key, activates_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            key.kid,
+            key.algorithm,
+            key.private_key,
+            key.public_key,
+            key.activates_at,
+            key.expires_at
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to store jwt signing key with sqlx")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(Some(key.kid))
+    }
+
+    pub async fn delete_expired_jwt_signing_keys(&self) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            "DELETE FROM jwt_signing_keys WHERE expires_at < NOW()"
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to delete expired jwt signing keys with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -2,16 +2,25 @@ use anyhow::{Context, Result as AnyhowResult};
 use chrono::{DateTime, Utc};
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool, Type};
+use sqlx::{FromRow, PgPool, Postgres, Transaction, Type};
 use std::fmt;
 use url::Url;
 
 pub mod auth;
 pub mod chapters;
 pub mod comments;
+pub mod identities;
+pub mod image_variants;
+pub mod jwt_keys;
+pub mod moderation;
 pub mod series;
+pub mod series_deletion;
 pub mod series_user_actions;
+pub mod sessions;
 pub mod storage;
+pub mod storage_references;
+pub mod storage_usage;
+pub mod two_factor;
 pub mod users;
 
 // Type alias for database connection pool
@@ -45,6 +54,45 @@ pub enum SeriesStatus {
     Deleting,
     #[sqlx(rename = "Deletion Failed")]
     DeletionFailed,
+    #[sqlx(rename = "Pending Archive")]
+    PendingArchive,
+    Archiving,
+    Archived,
+    #[sqlx(rename = "Archive Failed")]
+    ArchiveFailed,
+    #[sqlx(rename = "Pending Restore")]
+    PendingRestore,
+    Restoring,
+}
+
+impl SeriesStatus {
+    /// Archived or scheduled for deletion, hidden from public endpoints.
+    pub fn is_hidden(&self) -> bool {
+        self.is_archived() || self.is_deleted()
+    }
+
+    /// Waiting for, or being processed by, the deletion worker.
+    pub fn is_deleted(&self) -> bool {
+        matches!(
+            self,
+            SeriesStatus::PendingDeletion
+                | SeriesStatus::Deleting
+                | SeriesStatus::DeletionFailed
+        )
+    }
+
+    /// Archived or on the way in or out of the archive.
+    pub fn is_archived(&self) -> bool {
+        matches!(
+            self,
+            SeriesStatus::PendingArchive
+                | SeriesStatus::Archiving
+                | SeriesStatus::Archived
+                | SeriesStatus::ArchiveFailed
+                | SeriesStatus::PendingRestore
+                | SeriesStatus::Restoring
+        )
+    }
 }
 
 impl fmt::Display for SeriesStatus {
@@ -61,6 +109,12 @@ impl fmt::Display for SeriesStatus {
             SeriesStatus::PendingDeletion => "PendingDeletion",
             SeriesStatus::Deleting => "Deleting",
             SeriesStatus::DeletionFailed => "DeletionFailed",
+            SeriesStatus::PendingArchive => "PendingArchive",
+            SeriesStatus::Archiving => "Archiving",
+            SeriesStatus::Archived => "Archived",
+            SeriesStatus::ArchiveFailed => "ArchiveFailed",
+            SeriesStatus::PendingRestore => "PendingRestore",
+            SeriesStatus::Restoring => "Restoring",
         };
         write!(f, "{}", status_str)
     }
@@ -106,7 +160,74 @@ pub struct SeriesChapter {
     pub chapter_number: f32,
     pub title: Option<String>,
     pub status: ChapterStatus,
+    // Why the chapter is incomplete, e.g. "2 of 40 pages failed: truncated (2)"
+    pub status_reason: Option<String>,
+    pub source_url: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A stored page (or page tile) of a chapter.
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterImage {
+    pub id: i32,
+    pub image_order: i32,
+    pub image_url: String,
+    // Source page the tile belongs to, starting at 1
+    pub page_number: i32,
+    pub tile_index: i32,
+}
+
+/// A chapter with the object keys of its images, in reading order.
+#[derive(Debug, FromRow)]
+pub struct ChapterPages {
+    pub chapter_number: f32,
+    pub title: Option<String>,
+    pub image_keys: Vec<String>,
+}
+
+/// Stored bytes of one group of objects (a prefix, series, chapter or uploader).
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageUsage {
+    pub id: Option<i32>,
+    pub name: String,
+    pub object_count: i64,
+    pub total_bytes: i64,
+}
+
+/// Stored bytes at the end of a day.
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageUsagePoint {
+    pub day: DateTime<Utc>,
+    pub object_count: i64,
+    pub total_bytes: i64,
+}
+
+/// A series waiting for the deletion worker, still cancellable before `deletion_scheduled_at`.
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingSeriesDeletion {
+    pub id: i32,
+    pub title: String,
+    pub processing_status: SeriesStatus,
+    pub status_before_deletion: Option<SeriesStatus>,
+    pub deletion_scheduled_at: DateTime<Utc>,
+    // Set once the deletion worker has recorded the objects to delete
+    pub deletion_objects_total: Option<i32>,
+    pub deletion_objects_deleted: i32,
+    pub deletion_error: Option<String>,
+}
+
+/// A page that could not be stored while processing a chapter.
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterImageFailure {
+    pub source_image_order: i32,
     pub source_url: String,
+    pub reason: String,
+    pub detail: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -118,6 +239,24 @@ pub struct Users {
     pub email: String,
     pub password_hash: String,
     pub role_id: i32,
+    pub email_verified: bool,
+    pub token_version: i32,
+}
+
+/// What authenticating a request needs to know about its user,
+/// kept in the short-lived user cache.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserAuthState {
+    pub id: i32,
+    pub username: String,
+    pub role_name: String,
+    pub permissions: Vec<String>,
+    pub email_verified: bool,
+    pub two_factor_enabled: bool,
+    pub token_version: i32,
+    pub is_active: bool,
+    pub suspended_until: Option<DateTime<Utc>>,
+    pub suspension_reason: Option<String>,
 }
 
 #[derive(Debug, FromRow, Serialize)]
@@ -135,6 +274,8 @@ pub struct UserWithRole {
     pub username: String,
     pub email: String,
     pub role_name: String,
+    pub is_active: bool,
+    pub suspended_until: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug)]
@@ -192,6 +333,32 @@ impl SeriesDeletionImagekeys {
     }
 }
 
+// Responsive variants stored for one full-size image (page tile or cover).
+#[derive(Debug, Clone)]
+pub struct ImageVariantSet {
+    pub width: i32,
+    pub height: i32,
+    pub placeholder_key: Option<String>,
+    // Ordered from smallest to largest width
+    pub variants: Vec<ImageVariant>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct ImageVariant {
+    pub width: i32,
+    pub object_key: String,
+}
+
+impl ImageVariantSet {
+    // Every object key belonging to this set, excluding the source image itself
+    pub fn object_keys(&self) -> impl Iterator<Item = &str> {
+        self.placeholder_key
+            .as_deref()
+            .into_iter()
+            .chain(self.variants.iter().map(|v| v.object_key.as_str()))
+    }
+}
+
 // Order by field for fetching series list.
 #[derive(Debug, Clone)]
 pub enum SeriesOrderBy {
@@ -334,6 +501,7 @@ pub enum CommentEntityType {
     SeriesChapters,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct NewCommentPayload {
     pub content_markdown: String,
@@ -0,0 +1,158 @@
+use super::*;
+
+/// Whether an account may log in, checked on every login and token refresh.
+#[derive(Debug)]
+pub struct AccountStatus {
+    pub is_active: bool,
+    pub suspended_until: Option<DateTime<Utc>>,
+    pub suspension_reason: Option<String>,
+    pub password_reset_required: bool,
+}
+
+/// Account management by staff (roles, bans, suspensions, deletion).
+/// Every change bumps the token version, access tokens issued before stop working.
+impl DatabaseService {
+    pub async fn get_account_status(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<Option<AccountStatus>> {
+        let status = sqlx::query_as!(
+            AccountStatus,
+            r#"
+            SELECT is_active, suspended_until, suspension_reason, password_reset_required
+            FROM users
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get account status with sqlx")?;
+
+        Ok(status)
+    }
+
+    /// Returns false when the user does not exist.
+    pub async fn update_user_role(
+        &self,
+        user_id: i32,
+        role_id: i32,
+    ) -> AnyhowResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET role_id = $2, token_version = token_version + 1, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            user_id,
+            role_id
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to update user role with sqlx")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Blocks logins until `suspended_until`, returns false when the user does not exist.
+    pub async fn suspend_user(
+        &self,
+        user_id: i32,
+        suspended_until: DateTime<Utc>,
+        reason: Option<&str>,
+    ) -> AnyhowResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET suspended_until = $2, suspension_reason = $3,
+                token_version = token_version + 1, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            user_id,
+            suspended_until,
+            reason
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to suspend user with sqlx")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Blocks logins permanently, returns false when the user does not exist.
+    pub async fn ban_user(
+        &self,
+        user_id: i32,
+        reason: Option<&str>,
+    ) -> AnyhowResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET is_active = FALSE, suspension_reason = $2,
+                token_version = token_version + 1, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            user_id,
+            reason
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to ban user with sqlx")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Lifts a ban or suspension, returns false when the user does not exist.
+    pub async fn lift_user_suspension(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET is_active = TRUE, suspended_until = NULL, suspension_reason = NULL,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to lift user suspension with sqlx")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Refuses password logins until the password is reset,
+    /// returns false when the user does not exist.
+    pub async fn require_password_reset(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_reset_required = TRUE,
+                token_version = token_version + 1, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to require password reset with sqlx")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Deletes the account with its sessions, profile, comments and bookmarks.
+    /// Uploaded files are left to the orphaned storage object worker.
+    pub async fn delete_user(&self, user_id: i32) -> AnyhowResult<bool> {
+        let result = sqlx::query!("DELETE FROM users WHERE id = $1", user_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete user with sqlx")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
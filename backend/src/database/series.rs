@@ -1,5 +1,6 @@
 use super::*;
 use anyhow::{Context, anyhow};
+use std::time::Duration;
 
 /// Macros `sqlx::query!`
 /// For DML operations (INSERT, UPDATE, DELETE) or SELECTs,
@@ -219,6 +220,7 @@ impl DatabaseService {
         Ok(series)
     }
 
+    #[allow(dead_code)]
     pub async fn get_series_by_title(
         &self,
         title: &str,
@@ -433,8 +435,10 @@ impl DatabaseService {
 
         let final_status = new_status.unwrap_or(series.processing_status);
 
+        // A deletion scheduled during the check keeps its status
         let result = sqlx::query!(
-            "UPDATE series SET processing_status = $1, last_checked_at = NOW(), next_checked_at = $2 WHERE id = $3",
+            "UPDATE series SET processing_status = CASE WHEN deletion_scheduled_at IS NULL THEN $1 ELSE processing_status END,
+                last_checked_at = NOW(), next_checked_at = $2 WHERE id = $3",
             final_status as _,
             final_next_checked_at,
             series_id,
@@ -571,25 +575,236 @@ impl DatabaseService {
         Ok(result.rows_affected())
     }
 
+    /// Hides the series and schedules its deletion after `delay`.
+    /// A failed deletion is rescheduled right away, its objects are already partly gone.
     pub async fn mark_series_for_deletion(
         &self,
         series_id: i32,
+        delay: Duration,
+    ) -> AnyhowResult<u64> {
+        let delay_seconds = delay.as_secs_f64();
+
+        // Objects are being moved while archiving or restoring
+        let result = sqlx::query!(
+            r#"
+            UPDATE series
+            SET processing_status = $1,
+                status_before_deletion = CASE
+                    WHEN processing_status = $2 THEN status_before_deletion
+                    -- The running check ends before the deletion window does
+                    WHEN processing_status = $3 THEN $4
+                    ELSE processing_status
+                END,
+                deletion_scheduled_at = CASE
+                    WHEN processing_status = $2 THEN NOW()
+                    ELSE NOW() + make_interval(secs => $5)
+                END,
+                updated_at = NOW()
+            WHERE id = $6 AND processing_status NOT IN ($1, $7, $8, $9)
+            "#,
+            SeriesStatus::PendingDeletion as _,
+            SeriesStatus::DeletionFailed as _,
+            SeriesStatus::Processing as _,
+            SeriesStatus::Ongoing as _,
+            delay_seconds,
+            series_id,
+            SeriesStatus::Deleting as _,
+            SeriesStatus::Archiving as _,
+            SeriesStatus::Restoring as _,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark series for deletion with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Brings back a series whose deletion window has not ended yet.
+    pub async fn cancel_series_deletion(
+        &self,
+        series_id: i32,
     ) -> AnyhowResult<u64> {
         let result = sqlx::query!(
-            "UPDATE series SET processing_status = $1,
-                  updated_at = NOW() WHERE id = $2 AND processing_status NOT IN ($3, $4)",
+            r#"
+            UPDATE series
+            SET processing_status = COALESCE(status_before_deletion, $1),
+                status_before_deletion = NULL,
+                deletion_scheduled_at = NULL,
+                updated_at = NOW()
+            WHERE id = $2
+                AND processing_status = $3
+                AND deletion_scheduled_at > NOW()
+            "#,
+            SeriesStatus::Ongoing as _,
+            series_id,
             SeriesStatus::PendingDeletion as _,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to cancel series deletion with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_pending_series_deletions(
+        &self,
+    ) -> AnyhowResult<Vec<PendingSeriesDeletion>> {
+        let deletions = sqlx::query_as!(
+            PendingSeriesDeletion,
+            r#"
+            SELECT
+                id, title,
+                processing_status as "processing_status: SeriesStatus",
+                status_before_deletion as "status_before_deletion: SeriesStatus",
+                deletion_scheduled_at as "deletion_scheduled_at!",
+                deletion_objects_total, deletion_objects_deleted, deletion_error
+            FROM series
+            WHERE deletion_scheduled_at IS NOT NULL
+            ORDER BY deletion_scheduled_at ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get pending series deletions with sqlx")?;
+
+        Ok(deletions)
+    }
+
+    /// Hides the series and queues it for the archive worker.
+    /// A failed archive can be retried, the status to restore is kept.
+    pub async fn mark_series_for_archive(
+        &self,
+        series_id: i32,
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE series
+            SET processing_status = $1,
+                status_before_archive = CASE
+                    WHEN processing_status = $2 THEN status_before_archive
+                    ELSE processing_status
+                END,
+                archived_at = COALESCE(archived_at, NOW()),
+                updated_at = NOW()
+            WHERE id = $3
+                AND (processing_status = $2 OR processing_status NOT IN ($4, $5, $6, $7, $8, $9, $10, $11, $12))
+            "#,
+            SeriesStatus::PendingArchive as _,
+            SeriesStatus::ArchiveFailed as _,
             series_id,
+            // A running check would keep uploading into the live prefix
+            SeriesStatus::Processing as _,
             SeriesStatus::PendingDeletion as _,
             SeriesStatus::Deleting as _,
+            SeriesStatus::DeletionFailed as _,
+            SeriesStatus::PendingArchive as _,
+            SeriesStatus::Archiving as _,
+            SeriesStatus::Archived as _,
+            SeriesStatus::PendingRestore as _,
+            SeriesStatus::Restoring as _,
         )
-            .execute(&self.pool)
-            .await
-            .context("Failed to mark series for deletion with sqlx")?;
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark series for archive with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Queues an archived (or partially archived) series for restore.
+    pub async fn mark_series_for_restore(
+        &self,
+        series_id: i32,
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            "UPDATE series SET processing_status = $1, updated_at = NOW()
+                WHERE id = $2 AND processing_status IN ($3, $4)",
+            SeriesStatus::PendingRestore as _,
+            series_id,
+            SeriesStatus::Archived as _,
+            SeriesStatus::ArchiveFailed as _,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark series for restore with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Takes the next pending archive or restore job,
+    /// returns the series id and its new `Archiving` or `Restoring` status.
+    pub async fn find_and_lock_series_for_archival(
+        &self,
+    ) -> AnyhowResult<Option<(i32, SeriesStatus)>> {
+        let job = sqlx::query!(
+            r#"
+            WITH candidate AS (
+                SELECT id, processing_status FROM series
+                WHERE processing_status IN ($1, $2)
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            UPDATE series s
+            SET processing_status = CASE
+                WHEN c.processing_status = $1 THEN $3::series_status
+                ELSE $4::series_status
+            END
+            FROM candidate c
+            WHERE s.id = c.id
+            RETURNING s.id, s.processing_status as "processing_status: SeriesStatus"
+            "#,
+            SeriesStatus::PendingArchive as _,
+            SeriesStatus::PendingRestore as _,
+            SeriesStatus::Archiving as _,
+            SeriesStatus::Restoring as _,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to find and lock series for archival with sqlx")?;
+
+        Ok(job.map(|row| (row.id, row.processing_status)))
+    }
+
+    /// Makes a restored series public again and resumes its checks.
+    pub async fn complete_series_restore(
+        &self,
+        series_id: i32,
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE series
+            SET processing_status = COALESCE(status_before_archive, $1),
+                status_before_archive = NULL,
+                archived_at = NULL,
+                next_checked_at = NOW(),
+                updated_at = NOW()
+            WHERE id = $2
+            "#,
+            SeriesStatus::Ongoing as _,
+            series_id,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to complete series restore with sqlx")?;
 
         Ok(result.rows_affected())
     }
 
+    /// Set while any of the series' objects may be in the archive.
+    pub async fn get_series_archived_at(
+        &self,
+        series_id: i32,
+    ) -> AnyhowResult<Option<DateTime<Utc>>> {
+        let archived_at = sqlx::query_scalar!(
+            "SELECT archived_at FROM series WHERE id = $1",
+            series_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get series archived_at with sqlx")?;
+
+        Ok(archived_at.flatten())
+    }
+
     pub async fn find_and_lock_series_for_check(
         &self,
     ) -> AnyhowResult<Option<Series>> {
@@ -634,12 +849,17 @@ impl DatabaseService {
             r#"
             WITH candidate AS (
                 SELECT id FROM series
-                WHERE processing_status = $1
+                WHERE (processing_status = $1 AND deletion_scheduled_at <= NOW())
+                    -- Deletions interrupted by a restart (or without any recorded progress) resume where they stopped
+                    OR (processing_status = $2 AND (deletion_progress_at IS NULL OR deletion_progress_at < NOW() - interval '30 minutes'))
+                ORDER BY deletion_scheduled_at ASC
                 LIMIT 1
                 FOR UPDATE SKIP LOCKED
             )
             UPDATE series
-            SET processing_status = $2
+            SET processing_status = $2,
+                deletion_progress_at = NOW(),
+                deletion_error = NULL
             WHERE id = (SELECT id FROM candidate)
             RETURNING
                 id, title, original_title, description, cover_image_url, current_source_url,
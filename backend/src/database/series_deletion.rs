@@ -0,0 +1,150 @@
+use super::*;
+
+/// Progress of the deletion worker, kept in `series_deletion_objects`
+/// and the `deletion_*` columns of the series.
+impl DatabaseService {
+    /// `None` until the objects of the series have been recorded.
+    pub async fn get_series_deletion_objects_total(
+        &self,
+        series_id: i32,
+    ) -> AnyhowResult<Option<i32>> {
+        let total = sqlx::query_scalar!(
+            "SELECT deletion_objects_total FROM series WHERE id = $1",
+            series_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get series deletion total with sqlx")?;
+
+        Ok(total.flatten())
+    }
+
+    /// Records every object to delete, once per deletion.
+    pub async fn record_series_deletion_objects(
+        &self,
+        series_id: i32,
+        object_keys: &[String],
+    ) -> AnyhowResult<u64> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO series_deletion_objects (series_id, object_key)
+            SELECT $1, key FROM UNNEST($2::text[]) AS key
+            ON CONFLICT (series_id, object_key) DO NOTHING
+            "#,
+            series_id,
+            object_keys
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to record series deletion objects with sqlx")?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE series
+            SET deletion_objects_total = (
+                    SELECT COUNT(*) FROM series_deletion_objects WHERE series_id = $1
+                ),
+                deletion_objects_deleted = (
+                    SELECT COUNT(*) FROM series_deletion_objects
+                    WHERE series_id = $1 AND deleted_at IS NOT NULL
+                ),
+                deletion_progress_at = NOW()
+            WHERE id = $1
+            "#,
+            series_id
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to update series deletion total with sqlx")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// The next objects still waiting to be deleted.
+    pub async fn get_pending_series_deletion_objects(
+        &self,
+        series_id: i32,
+        limit: i64,
+    ) -> AnyhowResult<Vec<String>> {
+        let object_keys = sqlx::query_scalar!(
+            r#"
+            SELECT object_key FROM series_deletion_objects
+            WHERE series_id = $1 AND deleted_at IS NULL
+            ORDER BY object_key
+            LIMIT $2
+            "#,
+            series_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get pending series deletion objects with sqlx")?;
+
+        Ok(object_keys)
+    }
+
+    /// Marks a deleted batch and advances the progress of the series.
+    pub async fn mark_series_deletion_objects_deleted(
+        &self,
+        series_id: i32,
+        object_keys: &[String],
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            WITH deleted AS (
+                UPDATE series_deletion_objects
+                SET deleted_at = NOW()
+                WHERE series_id = $1
+                    AND object_key = ANY($2)
+                    AND deleted_at IS NULL
+                RETURNING 1
+            )
+            UPDATE series
+            SET deletion_objects_deleted = deletion_objects_deleted + (SELECT COUNT(*) FROM deleted),
+                deletion_progress_at = NOW()
+            WHERE id = $1
+            "#,
+            series_id,
+            object_keys
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark series deletion objects with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Gives up on a deletion, the error is shown in the pending deletions list.
+    /// The recorded progress is kept for the next attempt.
+    pub async fn fail_series_deletion(
+        &self,
+        series_id: i32,
+        error: &str,
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE series
+            SET processing_status = $1,
+                deletion_error = $2,
+                updated_at = NOW()
+            WHERE id = $3
+            "#,
+            SeriesStatus::DeletionFailed as _,
+            error,
+            series_id
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark series deletion as failed with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+}
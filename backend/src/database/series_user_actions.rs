@@ -81,6 +81,7 @@ impl DatabaseService {
                 series_view_log svl ON s.id = svl.series_id
             WHERE
                 svl.viewed_at >= NOW() - $1::interval
+                AND s.archived_at IS NULL AND s.deletion_scheduled_at IS NULL
             GROUP BY
                 s.id
             ORDER BY
@@ -210,6 +211,7 @@ impl DatabaseService {
                 AND s.last_chapter_found_in_storage = sc.chapter_number
             WHERE
                 ub.user_id = $1
+                AND s.archived_at IS NULL AND s.deletion_scheduled_at IS NULL
             ORDER BY
                 s.updated_at DESC
             "#,
@@ -381,6 +383,7 @@ impl DatabaseService {
                 AND s.last_chapter_found_in_storage = sc.chapter_number
             WHERE
                 s.updated_at >= NOW() - interval '7 days'
+                AND s.archived_at IS NULL AND s.deletion_scheduled_at IS NULL
             ORDER BY
                 s.updated_at DESC
             LIMIT $1
@@ -497,6 +500,7 @@ impl DatabaseService {
                         LEFT JOIN authors a ON sa.author_id = a.id
                         LEFT JOIN series_categories sc ON s.id = sc.series_id
                         LEFT JOIN categories c ON sc.category_id = c.id
+                    WHERE s.archived_at IS NULL AND s.deletion_scheduled_at IS NULL
                     GROUP BY s.id, s.title, s.original_title, s.description, s.cover_image_url,
                              s.updated_at, s.last_chapter_found_in_storage, s.views_count, s.total_rating_score, s.created_at
                     ORDER BY {}
@@ -546,6 +550,7 @@ impl DatabaseService {
                         LEFT JOIN authors a ON sa.author_id = a.id
                         LEFT JOIN series_categories sc ON s.id = sc.series_id
                         LEFT JOIN categories c ON sc.category_id = c.id
+                    WHERE s.archived_at IS NULL AND s.deletion_scheduled_at IS NULL
                     GROUP BY s.id, s.title, s.original_title, s.description, s.cover_image_url,
                              s.updated_at, s.last_chapter_found_in_storage, s.views_count, s.total_rating_score, s.created_at
                     ORDER BY {}
@@ -586,7 +591,7 @@ impl DatabaseService {
                         LEFT JOIN authors a ON sa.author_id = a.id
                         LEFT JOIN series_categories sc ON s.id = sc.series_id
                         LEFT JOIN categories c ON sc.category_id = c.id
-                    WHERE NOT EXISTS (
+                    WHERE s.archived_at IS NULL AND s.deletion_scheduled_at IS NULL AND NOT EXISTS (
                         SELECT 1
                         FROM series_categories sc_exclude
                         WHERE sc_exclude.series_id = s.id
@@ -631,6 +636,7 @@ impl DatabaseService {
                         LEFT JOIN authors a ON sa.author_id = a.id
                         LEFT JOIN series_categories sc ON s.id = sc.series_id
                         LEFT JOIN categories c ON sc.category_id = c.id
+                    WHERE s.archived_at IS NULL AND s.deletion_scheduled_at IS NULL
                     GROUP BY s.id, s.title, s.original_title, s.description, s.cover_image_url,
                              s.updated_at, s.last_chapter_found_in_storage, s.views_count, s.total_rating_score, s.created_at
                     ORDER BY {}
@@ -0,0 +1,317 @@
+use super::*;
+
+// A rotated token presented again this soon is a concurrent refresh
+// (two tabs), not a stolen copy. It is rejected without revoking the session.
+const REFRESH_REUSE_GRACE_SECONDS: f64 = 10.0;
+
+/// A login as shown to its user.
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSession {
+    pub id: i64,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Outcome of presenting a refresh token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshTokenRotation {
+    /// The token was valid and has been replaced by the new one.
+    Rotated { user_id: i32, session_id: i64 },
+    /// An already rotated token was replayed, the whole session has been revoked.
+    Reused { user_id: i32, session_id: i64 },
+    /// Unknown, expired or revoked token.
+    Invalid,
+}
+
+/// Login sessions and their refresh tokens, stored as hashes.
+impl DatabaseService {
+    /// Starts a session with its first refresh token.
+    /// Refreshing never extends the session past `max_expires_at`.
+    pub async fn create_user_session(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+        max_expires_at: DateTime<Utc>,
+        user_agent: Option<&str>,
+        ip_address: Option<&str>,
+    ) -> AnyhowResult<i64> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        let session_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO user_sessions (user_id, expires_at, max_expires_at, user_agent, ip_address)
+            VALUES ($1, LEAST($2::TIMESTAMPTZ, $3::TIMESTAMPTZ), $3, $4, $5)
+            RETURNING id
+            "#,
+            user_id,
+            expires_at,
+            max_expires_at,
+            user_agent,
+            ip_address
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .context("Failed to create user session with sqlx")?;
+
+        sqlx::query!(
+            "INSERT INTO refresh_tokens (token_hash, session_id) VALUES ($1, $2)",
+            token_hash,
+            session_id
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to store refresh token with sqlx")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(session_id)
+    }
+
+    /// Exchanges a refresh token for a new one in the same session.
+    /// Replaying a rotated token revokes the session, both the thief
+    /// and the legitimate user have to log in again.
+    pub async fn rotate_refresh_token(
+        &self,
+        token_hash: &str,
+        new_token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> AnyhowResult<RefreshTokenRotation> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        // Locks the token, a concurrent rotation of it waits and then sees it used
+        let token = sqlx::query!(
+            r#"
+            SELECT
+                rt.session_id,
+                s.user_id,
+                rt.used_at > NOW() - make_interval(secs => $2) AS "recently_used",
+                rt.used_at IS NOT NULL AS "used!",
+                (s.revoked_at IS NULL AND s.expires_at > NOW()) AS "active!"
+            FROM refresh_tokens rt
+            JOIN user_sessions s ON s.id = rt.session_id
+            WHERE rt.token_hash = $1
+            FOR UPDATE OF rt, s
+            "#,
+            token_hash,
+            REFRESH_REUSE_GRACE_SECONDS
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .context("Failed to get refresh token with sqlx")?;
+
+        let Some(token) = token else {
+            return Ok(RefreshTokenRotation::Invalid);
+        };
+        if !token.active || token.recently_used == Some(true) {
+            return Ok(RefreshTokenRotation::Invalid);
+        }
+
+        if token.used {
+            sqlx::query!(
+                "UPDATE user_sessions SET revoked_at = NOW(), revoked_reason = $1 WHERE id = $2",
+                "refresh token reuse",
+                token.session_id
+            )
+            .execute(&mut *tx)
+            .await
+            .context("Failed to revoke user session with sqlx")?;
+            tx.commit().await.context("Failed to commit transaction")?;
+
+            return Ok(RefreshTokenRotation::Reused {
+                user_id: token.user_id,
+                session_id: token.session_id,
+            });
+        }
+
+        sqlx::query!(
+            "UPDATE refresh_tokens SET used_at = NOW() WHERE token_hash = $1",
+            token_hash
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to mark refresh token used with sqlx")?;
+
+        sqlx::query!(
+            "INSERT INTO refresh_tokens (token_hash, session_id) VALUES ($1, $2)",
+            new_token_hash,
+            token.session_id
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to store refresh token with sqlx")?;
+
+        sqlx::query!(
+            "UPDATE user_sessions SET expires_at = LEAST($1, max_expires_at), last_seen_at = NOW() WHERE id = $2",
+            expires_at,
+            token.session_id
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to extend user session with sqlx")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(RefreshTokenRotation::Rotated {
+            user_id: token.user_id,
+            session_id: token.session_id,
+        })
+    }
+
+    /// Revokes the session a refresh token (current or rotated) belongs to,
+    /// returns its id when it was still active.
+    pub async fn revoke_session_by_refresh_token(
+        &self,
+        token_hash: &str,
+        reason: &str,
+    ) -> AnyhowResult<Option<i64>> {
+        let session_id = sqlx::query_scalar!(
+            r#"
+            UPDATE user_sessions
+            SET revoked_at = NOW(), revoked_reason = $1
+            WHERE revoked_at IS NULL
+                AND id = (SELECT session_id FROM refresh_tokens WHERE token_hash = $2)
+            RETURNING id
+            "#,
+            reason,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to revoke user session with sqlx")?;
+
+        Ok(session_id)
+    }
+
+    /// Checks that an access token's session is still active and records it as seen.
+    /// `last_seen_at` is written at most once a minute per session,
+    /// less often while the session is in the user cache.
+    pub async fn touch_user_session(
+        &self,
+        session_id: i64,
+        user_id: i32,
+    ) -> AnyhowResult<bool> {
+        let is_active = sqlx::query_scalar!(
+            r#"
+            WITH active AS (
+                SELECT id, last_seen_at FROM user_sessions
+                WHERE id = $1 AND user_id = $2
+                    AND revoked_at IS NULL AND expires_at > NOW()
+            ),
+            touched AS (
+                UPDATE user_sessions s SET last_seen_at = NOW()
+                FROM active a
+                WHERE s.id = a.id AND a.last_seen_at < NOW() - interval '1 minute'
+                RETURNING s.id
+            )
+            SELECT EXISTS (SELECT 1 FROM active) AS "is_active!"
+            "#,
+            session_id,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to check user session with sqlx")?;
+
+        Ok(is_active)
+    }
+
+    pub async fn get_active_user_sessions(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<Vec<UserSession>> {
+        let sessions = sqlx::query_as!(
+            UserSession,
+            r#"
+            SELECT id, user_agent, ip_address, created_at, last_seen_at, expires_at
+            FROM user_sessions
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            ORDER BY last_seen_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get active user sessions with sqlx")?;
+
+        Ok(sessions)
+    }
+
+    /// Revokes one session, only if it belongs to `user_id`.
+    pub async fn revoke_user_session(
+        &self,
+        user_id: i32,
+        session_id: i64,
+        reason: &str,
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_sessions
+            SET revoked_at = NOW(), revoked_reason = $1
+            WHERE id = $2 AND user_id = $3 AND revoked_at IS NULL
+            "#,
+            reason,
+            session_id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to revoke user session with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Revokes every session of a user except `keep_session_id`, e.g. after a password change.
+    pub async fn revoke_all_user_sessions(
+        &self,
+        user_id: i32,
+        keep_session_id: Option<i64>,
+        reason: &str,
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_sessions
+            SET revoked_at = NOW(), revoked_reason = $1
+            WHERE user_id = $2 AND revoked_at IS NULL
+                AND id IS DISTINCT FROM $3
+            "#,
+            reason,
+            user_id,
+            keep_session_id
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to revoke user sessions with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Removes sessions that ended more than a week ago, their tokens are removed with them.
+    /// Revoked sessions are kept that long so a replayed token is still recognized.
+    pub async fn cleanup_expired_user_sessions(&self) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM user_sessions
+            WHERE expires_at < NOW() - interval '7 days'
+                OR revoked_at < NOW() - interval '7 days'
+            "#
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to cleanup expired user sessions with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,365 @@
+use super::{ObjectListing, StorageBackend, StoredObject};
+use anyhow::{Context, Result, anyhow};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+// Size of the chunks a stored file is served in
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+// Extension of files still being written, renamed to the object once complete
+const TEMP_FILE_EXTENSION: &str = "tmp";
+
+/// Reads an opened file as a stream of chunks.
+pub fn read_file_chunks(file: fs::File) -> BoxStream<'static, Result<Bytes>> {
+    futures::stream::try_unfold(file, |mut file| async move {
+        let mut chunk = vec![0; READ_CHUNK_SIZE];
+        let read = file.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        chunk.truncate(read);
+        Ok(Some((Bytes::from(chunk), file)))
+    })
+    .boxed()
+}
+
+/// Backend that keeps objects as plain files under a root directory.
+/// Object keys map to relative paths, the files are served by the static storage route.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub async fn new(root: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&root).await.with_context(|| {
+            format!("Failed to create storage directory {}", root.display())
+        })?;
+
+        println!("[STORAGE] Local storage initialized at: {}", root.display());
+
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Keys come from our own code, but never let one escape the storage root
+    fn object_path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        let is_safe = !key.is_empty()
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+
+        if !is_safe {
+            return Err(anyhow!("Invalid object key '{}'", key));
+        }
+
+        Ok(self.root.join(relative))
+    }
+
+    // Write next to the target and rename, readers never see a partial file
+    async fn write_object(
+        &self,
+        key: &str,
+        mut body: BoxStream<'_, Result<Bytes>>,
+    ) -> Result<u64> {
+        let path = self.object_path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await.with_context(|| {
+                format!("Failed to create directory for object {}", key)
+            })?;
+        }
+
+        let temp_path = path.with_extension(format!(
+            "{}.{}",
+            uuid::Uuid::new_v4().simple(),
+            TEMP_FILE_EXTENSION
+        ));
+        let result = async {
+            let mut file = fs::File::create(&temp_path).await?;
+            let mut size = 0;
+            while let Some(chunk) = body.try_next().await? {
+                file.write_all(&chunk).await?;
+                size += chunk.len() as u64;
+            }
+            file.flush().await?;
+            fs::rename(&temp_path, &path).await?;
+            Ok::<_, anyhow::Error>(size)
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path).await;
+        }
+        result
+    }
+}
+
+impl StorageBackend for LocalStorage {
+    fn put_object<'a>(
+        &'a self,
+        key: &'a str,
+        data: Vec<u8>,
+        _content_type: &'a str,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let body = futures::stream::once(async { Ok(Bytes::from(data)) });
+            self.write_object(key, Box::pin(body))
+                .await
+                .with_context(|| format!("Failed to store object {}", key))?;
+            Ok(())
+        })
+    }
+
+    fn put_object_stream<'a>(
+        &'a self,
+        key: &'a str,
+        body: BoxStream<'a, Result<Bytes>>,
+        _content_type: &'a str,
+    ) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(self.write_object(key, body))
+    }
+
+    fn get_object<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            let path = self.object_path(key)?;
+            fs::read(&path)
+                .await
+                .with_context(|| format!("Failed to get object {}", key))
+        })
+    }
+
+    fn get_object_stream<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<Bytes>>>> {
+        Box::pin(async move {
+            let path = self.object_path(key)?;
+            let file = fs::File::open(&path)
+                .await
+                .with_context(|| format!("Failed to get object {}", key))?;
+
+            let key = key.to_string();
+            Ok(read_file_chunks(file)
+                .map_err(move |e| {
+                    e.context(format!("Failed to read object {}", key))
+                })
+                .boxed())
+        })
+    }
+
+    // Everything is returned in one page, local installs stay small
+    fn list_objects<'a>(
+        &'a self,
+        prefix: &'a str,
+        _continuation_token: Option<String>,
+    ) -> BoxFuture<'a, Result<ObjectListing>> {
+        Box::pin(async move {
+            // Only walk the directory the prefix points into
+            let prefix_dir = prefix.rsplit_once('/').map_or("", |(dir, _)| dir);
+            let mut pending_dirs = vec![self.root.join(prefix_dir)];
+            let mut objects = Vec::new();
+
+            while let Some(dir) = pending_dirs.pop() {
+                let mut entries = match fs::read_dir(&dir).await {
+                    Ok(entries) => entries,
+                    Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                    Err(e) => {
+                        return Err(e).with_context(|| {
+                            format!(
+                                "Failed to list directory {}",
+                                dir.display()
+                            )
+                        });
+                    }
+                };
+
+                while let Some(entry) = entries.next_entry().await? {
+                    let path = entry.path();
+                    let metadata = entry.metadata().await?;
+                    if metadata.is_dir() {
+                        pending_dirs.push(path);
+                        continue;
+                    }
+                    // Uploads in progress are not objects yet,
+                    // the orphan cleanup must not delete them
+                    if path.extension().is_some_and(|extension| {
+                        extension == TEMP_FILE_EXTENSION
+                    }) {
+                        continue;
+                    }
+
+                    let Ok(relative) = path.strip_prefix(&self.root) else {
+                        continue;
+                    };
+                    let key = relative
+                        .components()
+                        .map(|component| {
+                            component.as_os_str().to_string_lossy()
+                        })
+                        .collect::<Vec<_>>()
+                        .join("/");
+
+                    if key.starts_with(prefix) {
+                        objects.push(StoredObject {
+                            key,
+                            size: metadata.len(),
+                            last_modified: metadata
+                                .modified()
+                                .map(DateTime::<Utc>::from)
+                                .unwrap_or_else(|_| Utc::now()),
+                        });
+                    }
+                }
+            }
+
+            Ok(ObjectListing {
+                objects,
+                next_token: None,
+            })
+        })
+    }
+
+    fn copy_object<'a>(
+        &'a self,
+        from_key: &'a str,
+        to_key: &'a str,
+    ) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(async move {
+            let data = match fs::read(self.object_path(from_key)?).await {
+                Ok(data) => data,
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to read object {}", from_key)
+                    });
+                }
+            };
+
+            self.put_object(to_key, data, "").await?;
+            Ok(true)
+        })
+    }
+
+    fn delete_objects<'a>(
+        &'a self,
+        keys: &'a [String],
+    ) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move {
+            let mut deleted = 0;
+            for key in keys {
+                let path = self.object_path(key)?;
+                match fs::remove_file(&path).await {
+                    Ok(()) => deleted += 1,
+                    // Same as "NoSuchKey" on S3, the object is already gone
+                    Err(e) if e.kind() == ErrorKind::NotFound => deleted += 1,
+                    Err(e) => {
+                        return Err(e).with_context(|| {
+                            format!("Failed to delete object {}", key)
+                        });
+                    }
+                }
+            }
+
+            Ok(deleted)
+        })
+    }
+}
+
+/// Storage root under the system temp directory for tests,
+/// removed when dropped, also when the test fails.
+#[cfg(test)]
+pub struct TempStorageRoot(PathBuf);
+
+#[cfg(test)]
+impl TempStorageRoot {
+    pub async fn new() -> (Self, LocalStorage) {
+        let root = std::env::temp_dir()
+            .join(format!("web-rs-storage-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(root.clone()).await.unwrap();
+        (Self(root), storage)
+    }
+
+    /// The local storage behind a client serving from `https://cdn.example/`.
+    pub async fn client() -> (Self, super::StorageClient) {
+        let (root, storage) = Self::new().await;
+        let client = super::StorageClient::new(
+            std::sync::Arc::new(storage),
+            "https://cdn.example/",
+            None,
+        );
+        (root, client)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempStorageRoot {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_storage_round_trip() {
+        let (_root, storage) = TempStorageRoot::new().await;
+
+        let key = "series/some-title/ch-1/001-abcd.avif";
+        storage
+            .put_object(key, b"avif".to_vec(), "image/avif")
+            .await
+            .unwrap();
+        assert_eq!(storage.get_object(key).await.unwrap(), b"avif");
+
+        let listing = storage.list_objects("series/", None).await.unwrap();
+        assert_eq!(listing.objects.len(), 1);
+        assert_eq!(listing.objects[0].key, key);
+        assert_eq!(listing.objects[0].size, 4);
+        let listing = storage.list_objects("avatars/", None).await.unwrap();
+        assert!(listing.objects.is_empty());
+
+        let keys = vec![key.to_string(), "series/missing.avif".to_string()];
+        assert_eq!(storage.delete_objects(&keys).await.unwrap(), 2);
+        assert!(storage.get_object(key).await.is_err());
+
+        for key in ["../outside.avif", "/etc/passwd", "series/../../x", ""] {
+            assert!(storage.put_object(key, vec![], "").await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listing_skips_uploads_in_progress() {
+        let (root, storage) = TempStorageRoot::new().await;
+
+        let key = "series/title/ch-1/001.avif";
+        storage
+            .put_object(key, b"avif".to_vec(), "image/avif")
+            .await
+            .unwrap();
+        let temp_path = root.path().join("series/title/ch-1/002.0123abcd.tmp");
+        fs::write(&temp_path, b"partial").await.unwrap();
+
+        let listing = storage.list_objects("series/", None).await.unwrap();
+        let keys: Vec<_> = listing.objects.iter().map(|o| &o.key).collect();
+        assert_eq!(keys, vec![key]);
+    }
+}
@@ -0,0 +1,501 @@
+use crate::database::DatabaseService;
+use anyhow::{Context, Result, anyhow};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub mod local;
+pub mod s3;
+
+use local::LocalStorage;
+use s3::S3Storage;
+
+/// Path of the static route serving the local storage backend.
+pub const LOCAL_STORAGE_ROUTE: &str = "/storage";
+
+// Objects copied at the same time while moving a batch
+const MOVE_CONCURRENCY: usize = 16;
+/// S3 deletes at most 1000 keys per request, larger sets are sent in batches of this size.
+pub const OBJECT_BATCH_SIZE: usize = 1000;
+
+/// An object found while listing the storage.
+#[derive(Debug, Clone)]
+pub struct StoredObject {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// One page of a listing, `next_token` continues it when more objects remain.
+#[derive(Debug, Default)]
+pub struct ObjectListing {
+    pub objects: Vec<StoredObject>,
+    pub next_token: Option<String>,
+}
+
+/// Object operations every storage backend has to provide.
+/// Futures are boxed so the backend can be picked at runtime.
+pub trait StorageBackend: Send + Sync {
+    fn put_object<'a>(
+        &'a self,
+        key: &'a str,
+        data: Vec<u8>,
+        content_type: &'a str,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// Uploads an object from a stream of chunks and returns its size.
+    /// The default buffers the whole body, backends override it to stream.
+    fn put_object_stream<'a>(
+        &'a self,
+        key: &'a str,
+        body: BoxStream<'a, Result<Bytes>>,
+        content_type: &'a str,
+    ) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(async move {
+            let data: Vec<u8> = body
+                .try_fold(Vec::new(), |mut data, chunk| async move {
+                    data.extend_from_slice(&chunk);
+                    Ok(data)
+                })
+                .await?;
+            let size = data.len() as u64;
+            self.put_object(key, data, content_type).await?;
+            Ok(size)
+        })
+    }
+
+    fn get_object<'a>(&'a self, key: &'a str)
+    -> BoxFuture<'a, Result<Vec<u8>>>;
+
+    /// Downloads an object as a stream of chunks.
+    /// The default buffers the whole object, backends override it to stream.
+    fn get_object_stream<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<Bytes>>>> {
+        Box::pin(async move {
+            let data = self.get_object(key).await?;
+            Ok(futures::stream::once(async { Ok(Bytes::from(data)) }).boxed())
+        })
+    }
+
+    /// Lists objects whose key starts with `prefix`, one page at a time.
+    fn list_objects<'a>(
+        &'a self,
+        prefix: &'a str,
+        continuation_token: Option<String>,
+    ) -> BoxFuture<'a, Result<ObjectListing>>;
+
+    /// Deletes the objects and returns how many are confirmed gone.
+    /// Missing objects are not an error.
+    fn delete_objects<'a>(
+        &'a self,
+        keys: &'a [String],
+    ) -> BoxFuture<'a, Result<usize>>;
+
+    /// Copies an object inside the storage without downloading it.
+    /// Returns `false` when the source does not exist.
+    fn copy_object<'a>(
+        &'a self,
+        from_key: &'a str,
+        to_key: &'a str,
+    ) -> BoxFuture<'a, Result<bool>>;
+
+    /// Temporary download URL for a private object, when the backend supports it.
+    fn presign_get_object<'a>(
+        &'a self,
+        _key: &'a str,
+        _expires_in: Duration,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async { Err(anyhow!("Storage backend cannot presign URLs")) })
+    }
+
+    fn supports_presigning(&self) -> bool {
+        false
+    }
+}
+
+/// A client for the configured object storage, R2 or the local filesystem.
+#[derive(Clone)]
+pub struct StorageClient {
+    backend: Arc<dyn StorageBackend>,
+    domain_cdn_url: String,
+    // Set when files are served by this server instead of a CDN
+    local_root: Option<PathBuf>,
+    // Object sizes are recorded for usage accounting when set
+    usage_db: Option<DatabaseService>,
+    // Cold prefix archived series are moved under
+    archive_prefix: String,
+}
+
+impl StorageClient {
+    /// Creates a new StorageClient from environment variables.
+    /// `STORAGE_BACKEND` selects the backend: `r2` (default) or `local`.
+    /// `ARCHIVE_STORAGE_PREFIX` is where archived series are kept (default `archive/`).
+    ///
+    /// For `r2`, see `S3Storage::new_from_env` and:
+    /// - `R2_DOMAIN_CDN_URL`: The public URL of your bucket (https://pub-xxxxxxxx.r2.dev or your custom domain).
+    ///
+    /// For `local`:
+    /// - `LOCAL_STORAGE_PATH`: Directory for the objects (default `storage`).
+    /// - `LOCAL_STORAGE_PUBLIC_URL`: Public base URL of the static route (default `http://localhost:8000/storage`).
+    pub async fn new_from_env() -> Result<Self> {
+        let client = Self::backend_from_env().await?;
+
+        match env::var("ARCHIVE_STORAGE_PREFIX") {
+            Ok(prefix) => client.with_archive_prefix(&prefix),
+            Err(_) => Ok(client),
+        }
+    }
+
+    async fn backend_from_env() -> Result<Self> {
+        let backend_name =
+            env::var("STORAGE_BACKEND").unwrap_or_else(|_| "r2".to_string());
+
+        match backend_name.to_lowercase().as_str() {
+            "r2" | "s3" => {
+                let domain_cdn_url = env::var("R2_DOMAIN_CDN_URL").context(
+                    "Environment variable R2_DOMAIN_CDN_URL is not set",
+                )?;
+                let backend = S3Storage::new_from_env().await?;
+
+                Ok(Self::new(Arc::new(backend), &domain_cdn_url, None))
+            }
+            "local" => {
+                let root = env::var("LOCAL_STORAGE_PATH")
+                    .unwrap_or_else(|_| "storage".to_string());
+                let public_url = env::var("LOCAL_STORAGE_PUBLIC_URL")
+                    .unwrap_or_else(|_| {
+                        format!("http://localhost:8000{}", LOCAL_STORAGE_ROUTE)
+                    });
+                let backend = LocalStorage::new(PathBuf::from(root)).await?;
+                let local_root = backend.root().to_path_buf();
+
+                Ok(Self::new(Arc::new(backend), &public_url, Some(local_root)))
+            }
+            other => Err(anyhow!(
+                "Unknown STORAGE_BACKEND '{}', expected 'r2' or 'local'",
+                other
+            )),
+        }
+    }
+
+    pub fn new(
+        backend: Arc<dyn StorageBackend>,
+        domain_cdn_url: &str,
+        local_root: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            backend,
+            domain_cdn_url: domain_cdn_url.trim_end_matches('/').to_string(),
+            local_root,
+            usage_db: None,
+            archive_prefix: "archive/".to_string(),
+        }
+    }
+
+    /// Moves archived objects under another prefix.
+    pub fn with_archive_prefix(mut self, prefix: &str) -> Result<Self> {
+        let prefix = format!("{}/", prefix.trim_matches('/'));
+        if prefix == "/" {
+            return Err(anyhow!("ARCHIVE_STORAGE_PREFIX cannot be empty"));
+        }
+
+        self.archive_prefix = prefix;
+        Ok(self)
+    }
+
+    /// Record the size of every uploaded and deleted object in `storage_objects`.
+    pub fn with_usage_tracking(mut self, db_service: DatabaseService) -> Self {
+        self.usage_db = Some(db_service);
+        self
+    }
+
+    /// Directory to serve on `LOCAL_STORAGE_ROUTE`, only for the local backend.
+    pub fn local_root(&self) -> Option<&Path> {
+        self.local_root.as_deref()
+    }
+
+    /* Uploads an object (an image) to the storage.
+     * `key` - The full path and filename for the object in the bucket ("series-title/chapter-1/01.avif").
+     * `data` - The raw bytes of the object to upload.
+     * `content_type` - The MIME type of the object (e.g., "image/avif").
+     * The full public CDN URL to the uploaded object.
+     */
+    pub async fn upload_image_series_objects(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<String> {
+        let size = data.len();
+        self.backend.put_object(key, data, content_type).await?;
+        self.record_upload(key, size, None).await;
+
+        let public_url = self.public_url(key);
+        println!("[STORAGE] Successfully uploaded object to: {}", public_url);
+        Ok(public_url)
+    }
+
+    /* Deletes multiple objects from the storage.
+     * `keys` - A vector of object keys to delete, sent in batches of `OBJECT_BATCH_SIZE`.
+     * It will only fail on persistent network/permission errors,
+     * the batches before the failing one stay deleted.
+     */
+    pub async fn delete_image_objects(&self, keys: &[String]) -> Result<()> {
+        // If there are no keys to delete, do nothing.
+        if keys.is_empty() {
+            println!("[STORAGE] No objects to delete");
+            return Ok(());
+        }
+
+        let mut deleted = 0;
+        for batch in keys.chunks(OBJECT_BATCH_SIZE) {
+            deleted += self.backend.delete_objects(batch).await?;
+
+            if let Some(db_service) = &self.usage_db
+                && let Err(e) =
+                    db_service.mark_storage_objects_deleted(batch).await
+            {
+                eprintln!("[STORAGE] Failed to record deleted objects: {}", e);
+            }
+        }
+
+        println!(
+            "[STORAGE] Successfully deleted or confirmed deletion for {} objects.",
+            deleted
+        );
+
+        Ok(())
+    }
+
+    /// Uploads a user provided file, `uploaded_by` is the user it is accounted to.
+    pub async fn upload_image_file(
+        &self,
+        file_bytes: Vec<u8>,
+        object_key: &str,
+        content_type: &str,
+        uploaded_by: Option<i32>,
+    ) -> Result<String> {
+        let size = file_bytes.len();
+        self.backend
+            .put_object(object_key, file_bytes, content_type)
+            .await
+            .map_err(|e| anyhow!("Failed to upload file: {:?}", e))?;
+        self.record_upload(object_key, size, uploaded_by).await;
+
+        Ok(object_key.to_string())
+    }
+
+    /// Uploads a user provided file chunk by chunk, large files are sent in parts.
+    /// Errors of the body stream are returned unchanged.
+    pub async fn upload_stream(
+        &self,
+        object_key: &str,
+        body: BoxStream<'_, Result<Bytes>>,
+        content_type: &str,
+        uploaded_by: Option<i32>,
+    ) -> Result<String> {
+        let size = self
+            .backend
+            .put_object_stream(object_key, body, content_type)
+            .await?;
+        self.record_upload(object_key, size as usize, uploaded_by)
+            .await;
+
+        Ok(object_key.to_string())
+    }
+
+    /// Downloads a whole object from the storage, used when repackaging stored pages.
+    pub async fn get_object_bytes(&self, object_key: &str) -> Result<Vec<u8>> {
+        self.backend.get_object(object_key).await
+    }
+
+    /// Downloads an object chunk by chunk, used to serve it without buffering.
+    pub async fn get_object_stream(
+        &self,
+        object_key: &str,
+    ) -> Result<BoxStream<'static, Result<Bytes>>> {
+        self.backend.get_object_stream(object_key).await
+    }
+
+    /// Lists one page of objects under `prefix`, pass the previous `next_token` to continue.
+    pub async fn list_objects(
+        &self,
+        prefix: &str,
+        continuation_token: Option<String>,
+    ) -> Result<ObjectListing> {
+        self.backend.list_objects(prefix, continuation_token).await
+    }
+
+    /// Key of an object while its series is archived.
+    pub fn archive_object_key(&self, object_key: &str) -> String {
+        format!("{}{}", self.archive_prefix, object_key)
+    }
+
+    /// Moves objects to new keys: copy, then delete the source.
+    /// A missing source counts as already moved, so an interrupted move can simply be repeated.
+    pub async fn move_objects(&self, moves: &[(String, String)]) -> Result<()> {
+        for batch in moves.chunks(OBJECT_BATCH_SIZE) {
+            let copies: Vec<_> = batch
+                .iter()
+                .map(|(from_key, to_key)| {
+                    self.backend.copy_object(from_key, to_key)
+                })
+                .collect();
+            futures::stream::iter(copies)
+                .buffer_unordered(MOVE_CONCURRENCY)
+                .try_collect::<Vec<bool>>()
+                .await?;
+
+            let (from_keys, to_keys): (Vec<String>, Vec<String>) =
+                batch.iter().cloned().unzip();
+            if let Some(db_service) = &self.usage_db
+                && let Err(e) =
+                    db_service.move_storage_objects(&from_keys, &to_keys).await
+            {
+                eprintln!("[STORAGE] Failed to record moved objects: {}", e);
+            }
+
+            // The usage records were already moved, not marked as deleted
+            self.backend.delete_objects(&from_keys).await?;
+        }
+
+        println!("[STORAGE] Successfully moved {} objects.", moves.len());
+
+        Ok(())
+    }
+
+    pub fn supports_presigning(&self) -> bool {
+        self.backend.supports_presigning()
+    }
+
+    /// Presigned download URL of an object, valid for `expires_in`.
+    pub async fn presigned_url(
+        &self,
+        object_key: &str,
+        expires_in: Duration,
+    ) -> Result<String> {
+        self.backend
+            .presign_get_object(object_key, expires_in)
+            .await
+    }
+
+    // The object is already stored, a failed record only skews the usage numbers
+    async fn record_upload(
+        &self,
+        object_key: &str,
+        size: usize,
+        uploaded_by: Option<i32>,
+    ) {
+        if let Some(db_service) = &self.usage_db
+            && let Err(e) = db_service
+                .record_storage_object(object_key, size as i64, uploaded_by)
+                .await
+        {
+            eprintln!(
+                "[STORAGE] Failed to record size of object {}: {}",
+                object_key, e
+            );
+        }
+    }
+
+    /// Base URL objects are publicly served from, without a trailing slash.
+    pub fn domain_cdn_url(&self) -> &str {
+        &self.domain_cdn_url
+    }
+
+    /// Public URL of a stored value. The database keeps object keys,
+    /// external images (e.g. a cover linked from another site) are kept as absolute URLs.
+    pub fn public_url(&self, stored_value: &str) -> String {
+        if is_absolute_url(stored_value) {
+            return stored_value.to_string();
+        }
+        format!("{}/{}", self.domain_cdn_url, stored_value)
+    }
+
+    /// Object key of a stored value or of a URL pointing into our storage.
+    /// `None` for external URLs, which are not ours to delete.
+    pub fn object_key(&self, value: &str) -> Option<String> {
+        if !is_absolute_url(value) {
+            return Some(value.to_string());
+        }
+
+        value
+            .strip_prefix(&self.domain_cdn_url)
+            .and_then(|remaining| remaining.strip_prefix('/'))
+            .map(String::from)
+    }
+
+    /// Value to store for an image URL sent by a client, the key when it points into our storage.
+    pub fn to_stored_value(&self, value: &str) -> String {
+        self.object_key(value).unwrap_or_else(|| value.to_string())
+    }
+}
+
+fn is_absolute_url(value: &str) -> bool {
+    value.starts_with("https://") || value.starts_with("http://")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use local::TempStorageRoot;
+
+    #[tokio::test]
+    async fn test_object_keys_and_public_urls() {
+        let (_root, storage) = TempStorageRoot::client().await;
+
+        let key = "cover-manga/abcd.jpg";
+        assert_eq!(
+            storage.public_url(key),
+            "https://cdn.example/cover-manga/abcd.jpg"
+        );
+        assert_eq!(
+            storage.object_key("https://cdn.example/cover-manga/abcd.jpg"),
+            Some(key.to_string())
+        );
+        assert_eq!(storage.object_key(key), Some(key.to_string()));
+
+        // External images are stored and served unchanged
+        let external = "https://other.example/cover.jpg";
+        assert_eq!(storage.object_key(external), None);
+        assert_eq!(storage.to_stored_value(external), external);
+        assert_eq!(storage.public_url(external), external);
+    }
+
+    #[tokio::test]
+    async fn test_get_object_stream_returns_whole_object() {
+        let (_root, storage) = TempStorageRoot::client().await;
+
+        // Larger than one read chunk
+        let data: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        let key = "series/title/ch-1/001.avif";
+        storage
+            .upload_image_file(data.clone(), key, "image/avif", None)
+            .await
+            .unwrap();
+
+        let chunks: Vec<Bytes> = storage
+            .get_object_stream(key)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), data);
+        assert!(
+            storage
+                .get_object_stream("series/missing.avif")
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,642 @@
+use super::{ObjectListing, StorageBackend, StoredObject};
+use anyhow::{Context, Result};
+use aws_sdk_s3::Client;
+use aws_sdk_s3::config::{Credentials, Region};
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{
+    CompletedMultipartUpload, CompletedPart, Delete, Error as S3Error,
+    ObjectIdentifier,
+};
+use bytes::Bytes;
+use chrono::DateTime;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use std::env;
+use std::time::Duration;
+
+// Parts must be at least 5 MiB, except the last one.
+// Bodies smaller than one part are sent with a single put.
+const MULTIPART_PART_SIZE: usize = 8 * 1024 * 1024;
+
+// Everything except unreserved characters and the path separator
+const COPY_SOURCE_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// Backend for an S3-compatible object storage like Cloudflare R2.
+pub struct S3Storage {
+    client: Client,
+    bucket_name: String,
+}
+
+impl S3Storage {
+    /// Required environment variables:
+    /// - `R2_BUCKET_NAME`: The name of your R2 bucket.
+    /// - `R2_ACCOUNT_ID`: Your Cloudflare account ID.
+    /// - `R2_ACCESS_KEY_ID`: Your R2 access key ID.
+    /// - `R2_SECRET_ACCESS_KEY`: Your R2 secret access key.
+    pub async fn new_from_env() -> Result<Self> {
+        let bucket_name = env::var("R2_BUCKET_NAME")
+            .context("Environment variable R2_BUCKET_NAME is not set")?;
+        let account_id = env::var("R2_ACCOUNT_ID")
+            .context("Environment variable R2_ACCOUNT_ID is not set")?;
+        let access_key_id = env::var("R2_ACCESS_KEY_ID")
+            .context("Environment variable R2_ACCESS_KEY_ID is not set")?;
+        let secret_access_key = env::var("R2_SECRET_ACCESS_KEY")
+            .context("Environment variable R2_SECRET_ACCESS_KEY is not set")?;
+
+        // Construct the S3 endpoint URL for Cloudflare R2
+        let endpoint_url =
+            format!("https://{account_id}.r2.cloudflarestorage.com");
+
+        // Create a static credentials provider
+        let credentials = Credentials::new(
+            access_key_id,
+            secret_access_key,
+            None,                            // session_token
+            None,                            // expiry
+            "cloudflare-r2-static-provider", // provider_name
+        );
+
+        // Load AWS configuration, overriding the endpoint and credentials
+        let config = aws_config::from_env()
+            .endpoint_url(endpoint_url)
+            .credentials_provider(credentials)
+            // A region is often required, even for R2. 'auto' is a safe default
+            .region(Region::new("auto"))
+            .load()
+            .await;
+
+        let client = Client::new(&config);
+
+        println!(
+            "[STORAGE] R2 Storage client initialized for bucket: {}",
+            bucket_name
+        );
+
+        Ok(Self {
+            client,
+            bucket_name,
+        })
+    }
+}
+
+impl S3Storage {
+    // Fill `buffer` from the body until a part is complete, `false` once the body ended
+    async fn fill_part(
+        buffer: &mut Vec<u8>,
+        body: &mut BoxStream<'_, Result<Bytes>>,
+    ) -> Result<bool> {
+        while buffer.len() < MULTIPART_PART_SIZE {
+            match body.try_next().await? {
+                Some(chunk) => buffer.extend_from_slice(&chunk),
+                None => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    async fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        mut buffer: Vec<u8>,
+        mut body: BoxStream<'_, Result<Bytes>>,
+    ) -> Result<(Vec<CompletedPart>, u64)> {
+        let mut parts = Vec::new();
+        let mut size = 0;
+
+        loop {
+            let has_more = Self::fill_part(&mut buffer, &mut body).await?;
+            if !buffer.is_empty() {
+                let part_number = parts.len() as i32 + 1;
+                size += buffer.len() as u64;
+
+                let output = self
+                    .client
+                    .upload_part()
+                    .bucket(&self.bucket_name)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .part_number(part_number)
+                    .body(ByteStream::from(std::mem::replace(
+                        &mut buffer,
+                        Vec::with_capacity(MULTIPART_PART_SIZE),
+                    )))
+                    .send()
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Failed to upload part {} of object '{}'",
+                            part_number, key
+                        )
+                    })?;
+
+                parts.push(
+                    CompletedPart::builder()
+                        .set_e_tag(output.e_tag)
+                        .part_number(part_number)
+                        .build(),
+                );
+            }
+
+            if !has_more {
+                return Ok((parts, size));
+            }
+        }
+    }
+}
+
+impl StorageBackend for S3Storage {
+    fn put_object<'a>(
+        &'a self,
+        key: &'a str,
+        data: Vec<u8>,
+        content_type: &'a str,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.client
+                .put_object()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .body(ByteStream::from(data))
+                .content_type(content_type)
+                .send()
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to upload object with key '{}' to R2 bucket",
+                        key
+                    )
+                })?;
+
+            Ok(())
+        })
+    }
+
+    fn put_object_stream<'a>(
+        &'a self,
+        key: &'a str,
+        mut body: BoxStream<'a, Result<Bytes>>,
+        content_type: &'a str,
+    ) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(async move {
+            let mut buffer = Vec::with_capacity(MULTIPART_PART_SIZE);
+            if !Self::fill_part(&mut buffer, &mut body).await? {
+                let size = buffer.len() as u64;
+                self.put_object(key, buffer, content_type).await?;
+                return Ok(size);
+            }
+
+            let upload_id = self
+                .client
+                .create_multipart_upload()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .content_type(content_type)
+                .send()
+                .await
+                .with_context(|| {
+                    format!("Failed to start multipart upload of '{}'", key)
+                })?
+                .upload_id
+                .with_context(|| {
+                    format!("No upload id for multipart upload of '{}'", key)
+                })?;
+
+            let result =
+                match self.upload_parts(key, &upload_id, buffer, body).await {
+                    Ok((parts, size)) => self
+                        .client
+                        .complete_multipart_upload()
+                        .bucket(&self.bucket_name)
+                        .key(key)
+                        .upload_id(&upload_id)
+                        .multipart_upload(
+                            CompletedMultipartUpload::builder()
+                                .set_parts(Some(parts))
+                                .build(),
+                        )
+                        .send()
+                        .await
+                        .with_context(|| {
+                            format!(
+                                "Failed to complete multipart upload of '{}'",
+                                key
+                            )
+                        })
+                        .map(|_| size),
+                    Err(e) => Err(e),
+                };
+
+            // Uploaded parts are billed until the upload is aborted
+            if result.is_err()
+                && let Err(e) = self
+                    .client
+                    .abort_multipart_upload()
+                    .bucket(&self.bucket_name)
+                    .key(key)
+                    .upload_id(&upload_id)
+                    .send()
+                    .await
+            {
+                eprintln!(
+                    "[STORAGE] Failed to abort multipart upload of {}: {}",
+                    key, e
+                );
+            }
+
+            result
+        })
+    }
+
+    fn get_object<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            let output = self
+                .client
+                .get_object()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .send()
+                .await
+                .with_context(|| format!("Failed to get object {}", key))?;
+
+            let data =
+                output.body.collect().await.with_context(|| {
+                    format!("Failed to read object {}", key)
+                })?;
+
+            Ok(data.into_bytes().to_vec())
+        })
+    }
+
+    fn get_object_stream<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<Bytes>>>> {
+        Box::pin(async move {
+            let output = self
+                .client
+                .get_object()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .send()
+                .await
+                .with_context(|| format!("Failed to get object {}", key))?;
+
+            let key = key.to_string();
+            let chunks =
+                futures::stream::unfold(output.body, move |mut body| {
+                    let key = key.clone();
+                    async move {
+                        let chunk = body.next().await?.with_context(|| {
+                            format!("Failed to read object {}", key)
+                        });
+                        Some((chunk, body))
+                    }
+                });
+
+            Ok(chunks.boxed())
+        })
+    }
+
+    fn list_objects<'a>(
+        &'a self,
+        prefix: &'a str,
+        continuation_token: Option<String>,
+    ) -> BoxFuture<'a, Result<ObjectListing>> {
+        Box::pin(async move {
+            let output = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket_name)
+                .prefix(prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await
+                .with_context(|| {
+                    format!("Failed to list objects with prefix '{}'", prefix)
+                })?;
+
+            let objects = output
+                .contents
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|object| {
+                    let last_modified = object.last_modified?;
+                    Some(StoredObject {
+                        key: object.key?,
+                        size: object.size.unwrap_or(0).max(0) as u64,
+                        last_modified: DateTime::from_timestamp(
+                            last_modified.secs(),
+                            last_modified.subsec_nanos(),
+                        )?,
+                    })
+                })
+                .collect();
+
+            Ok(ObjectListing {
+                objects,
+                next_token: output
+                    .is_truncated
+                    .unwrap_or(false)
+                    .then_some(output.next_continuation_token)
+                    .flatten(),
+            })
+        })
+    }
+
+    fn delete_objects<'a>(
+        &'a self,
+        keys: &'a [String],
+    ) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move {
+            // Convert the list of key strings into a list of S3 ObjectIdentifiers
+            let objects_to_delete: Vec<ObjectIdentifier> = keys
+                .iter()
+                .map(|key| ObjectIdentifier::builder().key(key).build())
+                .map(Result::unwrap)
+                .collect();
+
+            let delete_payload = Delete::builder()
+                .set_objects(Some(objects_to_delete))
+                .build()
+                .map_err(|e| {
+                    anyhow::anyhow!("Failed to build Delete payload: {}", e)
+                })?;
+
+            // Send the delete_objects request.
+            let result = self
+                .client
+                .delete_objects()
+                .bucket(&self.bucket_name)
+                .delete(delete_payload)
+                .send()
+                .await
+                .with_context(
+                    || "Failed to send delete_objects request to R2 bucket",
+                )?;
+
+            // Check for "real" errors. Ignore "NoSuchKey" as it means the object is already gone.
+            if let Some(errors) = result.errors {
+                let persistent_errors: Vec<&S3Error> = errors
+                    .iter()
+                    .filter(|e| !matches!(e.code.as_deref(), Some("NoSuchKey")))
+                    .collect();
+
+                if !persistent_errors.is_empty() {
+                    // Log and return the first persistent error
+                    for error in persistent_errors {
+                        eprintln!(
+                            "[STORAGE] Persistent error while deleting object: Key: {}, Code: {}, Message: {}",
+                            error.key.as_deref().unwrap_or("Unknown"),
+                            error.code.as_deref().unwrap_or("Unknown"),
+                            error.message.as_deref().unwrap_or("Unknown")
+                        );
+                    }
+                    return Err(anyhow::anyhow!(
+                        "Encountered persistent errors during object deletion from Storage."
+                    ));
+                }
+            }
+
+            Ok(result.deleted.map_or(0, |d| d.len()))
+        })
+    }
+
+    fn copy_object<'a>(
+        &'a self,
+        from_key: &'a str,
+        to_key: &'a str,
+    ) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(async move {
+            // The copy source is "bucket/key" and has to be URL-encoded
+            let encoded_key =
+                utf8_percent_encode(from_key, COPY_SOURCE_ENCODE_SET);
+
+            let result = self
+                .client
+                .copy_object()
+                .bucket(&self.bucket_name)
+                .copy_source(format!("{}/{}", self.bucket_name, encoded_key))
+                .key(to_key)
+                .send()
+                .await;
+
+            match result {
+                Ok(_) => Ok(true),
+                Err(e) if e.code() == Some("NoSuchKey") => Ok(false),
+                Err(e) => Err(e).with_context(|| {
+                    format!(
+                        "Failed to copy object '{}' to '{}'",
+                        from_key, to_key
+                    )
+                }),
+            }
+        })
+    }
+
+    fn presign_get_object<'a>(
+        &'a self,
+        key: &'a str,
+        expires_in: Duration,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let presigning_config = PresigningConfig::expires_in(expires_in)
+                .context("Invalid presigned URL expiry")?;
+
+            // Signing is done locally, no request is sent to the bucket
+            let request = self
+                .client
+                .get_object()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .presigned(presigning_config)
+                .await
+                .with_context(|| format!("Failed to presign object {}", key))?;
+
+            Ok(request.uri().to_string())
+        })
+    }
+
+    fn supports_presigning(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::error::UploadError;
+    use aws_sdk_s3::config::BehaviorVersion;
+    use axum::body::Bytes as BodyBytes;
+    use axum::extract::DefaultBodyLimit;
+    use axum::http::{Method, StatusCode, Uri, header};
+    use axum::response::IntoResponse;
+    use std::sync::{Arc, Mutex};
+
+    type Requests = Arc<Mutex<Vec<(Method, String, usize)>>>;
+
+    // Minimal S3 endpoint answering the multipart calls, records every request
+    async fn fake_s3(requests: Requests) -> S3Storage {
+        let app = axum::Router::new()
+            .fallback(
+                move |method: Method, uri: Uri, body: BodyBytes| async move {
+                    let query = uri.query().unwrap_or_default().to_string();
+                    requests.lock().unwrap().push((
+                        method.clone(),
+                        query.clone(),
+                        body.len(),
+                    ));
+
+                    let has = |name: &str| {
+                        query
+                            .split('&')
+                            .any(|param| param.split('=').next() == Some(name))
+                    };
+                    if method == Method::POST && has("uploads") {
+                        "<InitiateMultipartUploadResult><Bucket>bucket</Bucket>\
+                     <Key>key</Key><UploadId>upload-1</UploadId>\
+                     </InitiateMultipartUploadResult>"
+                            .into_response()
+                    } else if method == Method::POST {
+                        "<CompleteMultipartUploadResult><Bucket>bucket</Bucket>\
+                     <Key>key</Key><ETag>\"etag\"</ETag>\
+                     </CompleteMultipartUploadResult>"
+                            .into_response()
+                    } else if method == Method::DELETE {
+                        StatusCode::NO_CONTENT.into_response()
+                    } else {
+                        [(header::ETAG, "\"etag\"")].into_response()
+                    }
+                },
+            )
+            .layer(DefaultBodyLimit::disable());
+
+        let listener =
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .endpoint_url(format!("http://{}", address))
+            .region(Region::new("auto"))
+            .credentials_provider(Credentials::new(
+                "id", "secret", None, None, "test",
+            ))
+            .force_path_style(true)
+            .build();
+
+        S3Storage {
+            client: Client::from_conf(config),
+            bucket_name: "bucket".to_string(),
+        }
+    }
+
+    fn body(chunks: Vec<Result<Bytes>>) -> BoxStream<'static, Result<Bytes>> {
+        futures::stream::iter(chunks).boxed()
+    }
+
+    // S3 operation of every recorded request with its body size
+    fn upload_calls(requests: &Requests) -> Vec<(&'static str, usize)> {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(method, query, size)| {
+                let operation = match (method.clone(), query) {
+                    (Method::POST, query) if query.contains("uploads") => {
+                        "create"
+                    }
+                    (Method::POST, _) => "complete",
+                    (Method::PUT, query) if query.contains("partNumber") => {
+                        "part"
+                    }
+                    (Method::PUT, _) => "put",
+                    (Method::DELETE, _) => "abort",
+                    _ => "other",
+                };
+                (operation, *size)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_small_body_is_a_single_put() {
+        let requests = Requests::default();
+        let storage = fake_s3(requests.clone()).await;
+
+        let chunks = (0..3).map(|_| Ok(Bytes::from(vec![1; 1000]))).collect();
+        let size = storage
+            .put_object_stream("key", body(chunks), "image/png")
+            .await
+            .unwrap();
+
+        assert_eq!(size, 3000);
+        assert_eq!(upload_calls(&requests), vec![("put", 3000)]);
+    }
+
+    #[tokio::test]
+    async fn test_large_body_is_uploaded_in_parts() {
+        let requests = Requests::default();
+        let storage = fake_s3(requests.clone()).await;
+
+        let chunk = Bytes::from(vec![1; MULTIPART_PART_SIZE / 2 + 1]);
+        let chunks = vec![Ok(chunk.clone()), Ok(chunk.clone()), Ok(chunk)];
+        let size = storage
+            .put_object_stream("key", body(chunks), "image/png")
+            .await
+            .unwrap();
+
+        let total = 3 * (MULTIPART_PART_SIZE / 2 + 1);
+        assert_eq!(size, total as u64);
+
+        // Parts are filled up to the part size, the rest goes into the last one
+        let calls = upload_calls(&requests);
+        let operations: Vec<&str> =
+            calls.iter().map(|(operation, _)| *operation).collect();
+        assert_eq!(operations, vec!["create", "part", "part", "complete"]);
+        assert_eq!(calls[1].1, MULTIPART_PART_SIZE + 2);
+        assert_eq!(calls[2].1, total - MULTIPART_PART_SIZE - 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_body_aborts_the_multipart_upload() {
+        let requests = Requests::default();
+        let storage = fake_s3(requests.clone()).await;
+
+        let max_bytes = MULTIPART_PART_SIZE + 1;
+        let chunks = vec![
+            Ok(Bytes::from(vec![1; MULTIPART_PART_SIZE])),
+            Err(UploadError::TooLarge { max_bytes }.into()),
+        ];
+        let error = storage
+            .put_object_stream("key", body(chunks), "image/png")
+            .await
+            .unwrap_err();
+
+        // The body error reaches the handler unchanged
+        assert!(matches!(
+            UploadError::from(error),
+            UploadError::TooLarge { max_bytes: limit } if limit == max_bytes
+        ));
+
+        // The full first part was sent, the upload must not stay open
+        let operations: Vec<&str> = upload_calls(&requests)
+            .into_iter()
+            .map(|(operation, _)| operation)
+            .collect();
+        assert_eq!(operations, vec!["create", "part", "abort"]);
+    }
+}
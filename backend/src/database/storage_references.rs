@@ -0,0 +1,114 @@
+use super::*;
+
+/// Macros `sqlx::query!`
+/// For DML operations (INSERT, UPDATE, DELETE) or SELECTs,
+/// where you're manually processing generic `sqlx::Row`s (anonymous struct).
+///
+/// Macros `sqlx::query_as!`
+/// For mapping SELECT results directly to a defined rust struct (`#[derive(FromRow)]`),
+/// recommended for structured data retrieval.
+///
+/// Macros `sqlx::query_scalar!`
+/// For queries returning a single value (one row, one column).
+/// Highly efficient for this purpose.
+impl DatabaseService {
+    /// Returns the subset of `object_keys` that is still referenced by any table.
+    pub async fn get_referenced_object_keys(
+        &self,
+        object_keys: &[String],
+    ) -> AnyhowResult<Vec<String>> {
+        let referenced_keys = sqlx::query_scalar!(
+            r#"
+            SELECT c.key AS "key!"
+            FROM UNNEST($1::TEXT[]) AS c(key)
+            WHERE EXISTS (SELECT 1 FROM chapter_images WHERE image_url = c.key)
+               OR EXISTS (SELECT 1 FROM series WHERE cover_image_url = c.key)
+               OR EXISTS (SELECT 1 FROM user_profiles WHERE avatar_url = c.key)
+               OR EXISTS (SELECT 1 FROM comment_attachments WHERE file_url = c.key)
+               OR EXISTS (SELECT 1 FROM image_variant_sets WHERE source_key = c.key OR placeholder_key = c.key)
+               OR EXISTS (SELECT 1 FROM image_variants WHERE object_key = c.key)
+            "#,
+            object_keys,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get referenced object keys with sqlx")?;
+
+        Ok(referenced_keys)
+    }
+
+    /// Rewrites absolute URLs on our CDN into object keys, once per database.
+    /// Public URLs are built from the configured CDN domain when responding,
+    /// URLs on any other host are external images and stay absolute.
+    /// Returns the number of rewritten rows, `None` when it already ran.
+    pub async fn migrate_stored_urls_to_object_keys(
+        &self,
+        domain_cdn_url: &str,
+    ) -> AnyhowResult<Option<u64>> {
+        let mut tx = self.pool.begin().await?;
+
+        let is_new = sqlx::query!(
+            "INSERT INTO data_migrations (name) VALUES ('store_object_keys')
+             ON CONFLICT (name) DO NOTHING",
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to record data migration with sqlx")?
+        .rows_affected()
+            > 0;
+        if !is_new {
+            return Ok(None);
+        }
+
+        let url_prefix = format!("{}/", domain_cdn_url.trim_end_matches('/'));
+        let mut rewritten = 0;
+
+        rewritten += sqlx::query!(
+            "UPDATE series
+             SET cover_image_url = SUBSTRING(cover_image_url FROM LENGTH($1) + 1)
+             WHERE STARTS_WITH(cover_image_url, $1)",
+            url_prefix,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to rewrite series covers with sqlx")?
+        .rows_affected();
+
+        rewritten += sqlx::query!(
+            "UPDATE chapter_images
+             SET image_url = SUBSTRING(image_url FROM LENGTH($1) + 1)
+             WHERE STARTS_WITH(image_url, $1)",
+            url_prefix,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to rewrite chapter images with sqlx")?
+        .rows_affected();
+
+        rewritten += sqlx::query!(
+            "UPDATE user_profiles
+             SET avatar_url = SUBSTRING(avatar_url FROM LENGTH($1) + 1)
+             WHERE STARTS_WITH(avatar_url, $1)",
+            url_prefix,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to rewrite avatars with sqlx")?
+        .rows_affected();
+
+        rewritten += sqlx::query!(
+            "UPDATE comment_attachments
+             SET file_url = SUBSTRING(file_url FROM LENGTH($1) + 1)
+             WHERE STARTS_WITH(file_url, $1)",
+            url_prefix,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to rewrite comment attachments with sqlx")?
+        .rows_affected();
+
+        tx.commit().await?;
+
+        Ok(Some(rewritten))
+    }
+}
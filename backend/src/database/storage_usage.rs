@@ -0,0 +1,414 @@
+use super::*;
+
+/// Macros `sqlx::query!`
+/// For DML operations (INSERT, UPDATE, DELETE) or SELECTs,
+/// where you're manually processing generic `sqlx::Row`s (anonymous struct).
+///
+/// Macros `sqlx::query_as!`
+/// For mapping SELECT results directly to a defined rust struct (`#[derive(FromRow)]`),
+/// recommended for structured data retrieval.
+///
+/// Macros `sqlx::query_scalar!`
+/// For queries returning a single value (one row, one column).
+/// Highly efficient for this purpose.
+impl DatabaseService {
+    /// Records the size of an uploaded object.
+    /// Uploading to an existing key replaces the size, the object keeps its
+    /// original `created_at` and the change is recorded as a new event.
+    pub async fn record_storage_object(
+        &self,
+        object_key: &str,
+        size_bytes: i64,
+        uploaded_by: Option<i32>,
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            r#"
+            WITH previous AS (
+                SELECT size_bytes, deleted_at IS NULL AS live
+                FROM storage_objects
+                WHERE object_key = $1
+                FOR UPDATE
+            ),
+            upserted AS (
+                INSERT INTO storage_objects (object_key, size_bytes, uploaded_by)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (object_key) DO UPDATE
+                SET size_bytes = EXCLUDED.size_bytes,
+                    uploaded_by = EXCLUDED.uploaded_by,
+                    deleted_at = NULL
+                RETURNING object_key
+            )
+            INSERT INTO storage_object_events (object_key, object_delta, size_delta)
+            SELECT u.object_key,
+                CASE WHEN p.live THEN 0 ELSE 1 END,
+                $2 - CASE WHEN p.live THEN p.size_bytes ELSE 0 END
+            FROM upserted u
+            LEFT JOIN previous p ON TRUE
+            "#,
+            object_key,
+            size_bytes,
+            uploaded_by,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to record storage object with sqlx")?;
+
+        Ok(())
+    }
+
+    /// Adds objects found in storage that have no record yet,
+    /// e.g. uploaded before usage accounting existed.
+    pub async fn record_listed_storage_objects(
+        &self,
+        object_keys: &[String],
+        sizes_bytes: &[i64],
+        created_at: &[DateTime<Utc>],
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            WITH listed AS (
+                INSERT INTO storage_objects (object_key, size_bytes, created_at)
+                SELECT * FROM UNNEST($1::TEXT[], $2::BIGINT[], $3::TIMESTAMPTZ[])
+                ON CONFLICT (object_key) DO NOTHING
+                RETURNING object_key, size_bytes, created_at
+            )
+            INSERT INTO storage_object_events (object_key, object_delta, size_delta, created_at)
+            SELECT object_key, 1, size_bytes, created_at FROM listed
+            "#,
+            object_keys,
+            sizes_bytes,
+            created_at,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to record listed storage objects with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn mark_storage_objects_deleted(
+        &self,
+        object_keys: &[String],
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            WITH deleted AS (
+                UPDATE storage_objects
+                SET deleted_at = NOW()
+                WHERE object_key = ANY($1) AND deleted_at IS NULL
+                RETURNING object_key, size_bytes
+            )
+            INSERT INTO storage_object_events (object_key, object_delta, size_delta)
+            SELECT object_key, -1, -size_bytes FROM deleted
+            "#,
+            object_keys,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark storage objects deleted with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Moves the records of objects copied to a new key, e.g. into the archive.
+    /// `object_keys` and `new_keys` are matched by position.
+    /// The moved record keeps the `created_at` of the original upload.
+    pub async fn move_storage_objects(
+        &self,
+        object_keys: &[String],
+        new_keys: &[String],
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            r#"
+            WITH moved AS (
+                UPDATE storage_objects so
+                SET deleted_at = NOW()
+                FROM UNNEST($1::TEXT[], $2::TEXT[]) AS m(old_key, new_key)
+                WHERE so.object_key = m.old_key AND so.deleted_at IS NULL
+                RETURNING so.object_key AS old_key, m.new_key, so.size_bytes,
+                    so.uploaded_by, so.created_at
+            ),
+            -- Live objects already stored under a new key are overwritten
+            previous AS (
+                SELECT so.object_key, so.size_bytes
+                FROM storage_objects so
+                JOIN moved ON so.object_key = moved.new_key
+                WHERE so.deleted_at IS NULL
+            ),
+            inserted AS (
+                INSERT INTO storage_objects (object_key, size_bytes, uploaded_by, created_at)
+                SELECT new_key, size_bytes, uploaded_by, created_at FROM moved
+                ON CONFLICT (object_key) DO UPDATE
+                SET size_bytes = EXCLUDED.size_bytes,
+                    uploaded_by = EXCLUDED.uploaded_by,
+                    deleted_at = NULL
+                RETURNING object_key, size_bytes
+            )
+            INSERT INTO storage_object_events (object_key, object_delta, size_delta)
+            SELECT old_key, -1, -size_bytes FROM moved
+            UNION ALL
+            SELECT i.object_key,
+                CASE WHEN p.object_key IS NULL THEN 1 ELSE 0 END,
+                i.size_bytes - COALESCE(p.size_bytes, 0)
+            FROM inserted i
+            LEFT JOIN previous p ON p.object_key = i.object_key
+            "#,
+            object_keys,
+            new_keys,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to move storage objects with sqlx")?;
+
+        Ok(())
+    }
+
+    /// Current usage grouped by the first segment of the object key
+    /// (`series`, `cover-manga`, `avatars`, `comments`).
+    pub async fn get_storage_usage_by_prefix(
+        &self,
+    ) -> AnyhowResult<Vec<StorageUsage>> {
+        let usage = sqlx::query_as!(
+            StorageUsage,
+            r#"
+            SELECT
+                NULL::INTEGER AS id,
+                split_part(object_key, '/', 1) AS "name!",
+                COUNT(*) AS "object_count!",
+                SUM(size_bytes)::BIGINT AS "total_bytes!"
+            FROM storage_objects
+            WHERE deleted_at IS NULL
+            GROUP BY 2
+            ORDER BY 4 DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get storage usage by prefix with sqlx")?;
+
+        Ok(usage)
+    }
+
+    /// Series using the most storage: chapter images, cover and their variants.
+    pub async fn get_series_storage_usage(
+        &self,
+        limit: i64,
+    ) -> AnyhowResult<Vec<StorageUsage>> {
+        let usage = sqlx::query_as!(
+            StorageUsage,
+            r#"
+            WITH sources AS (
+                SELECT sc.series_id, ci.image_url AS source_key
+                FROM chapter_images ci
+                JOIN series_chapters sc ON sc.id = ci.chapter_id
+                UNION
+                SELECT s.id, s.cover_image_url
+                FROM series s
+            ),
+            series_keys AS (
+                SELECT series_id, source_key AS object_key FROM sources
+                UNION
+                SELECT src.series_id, iv.object_key
+                FROM sources src
+                JOIN image_variants iv ON iv.source_key = src.source_key
+                UNION
+                SELECT src.series_id, vs.placeholder_key
+                FROM sources src
+                JOIN image_variant_sets vs ON vs.source_key = src.source_key
+                WHERE vs.placeholder_key IS NOT NULL
+            )
+            SELECT
+                s.id AS "id?",
+                s.title AS name,
+                COUNT(so.object_key) AS "object_count!",
+                SUM(so.size_bytes)::BIGINT AS "total_bytes!"
+            FROM series_keys k
+            JOIN storage_objects so ON so.object_key = k.object_key AND so.deleted_at IS NULL
+            JOIN series s ON s.id = k.series_id
+            GROUP BY s.id, s.title
+            ORDER BY 4 DESC
+            LIMIT $1
+            "#,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get series storage usage with sqlx")?;
+
+        Ok(usage)
+    }
+
+    /// Storage used by every chapter of a series, images and their variants.
+    pub async fn get_chapter_storage_usage(
+        &self,
+        series_id: i32,
+    ) -> AnyhowResult<Vec<StorageUsage>> {
+        let usage = sqlx::query_as!(
+            StorageUsage,
+            r#"
+            WITH sources AS (
+                SELECT ci.chapter_id, ci.image_url AS source_key
+                FROM chapter_images ci
+                JOIN series_chapters sc ON sc.id = ci.chapter_id
+                WHERE sc.series_id = $1
+            ),
+            chapter_keys AS (
+                SELECT chapter_id, source_key AS object_key FROM sources
+                UNION
+                SELECT src.chapter_id, iv.object_key
+                FROM sources src
+                JOIN image_variants iv ON iv.source_key = src.source_key
+                UNION
+                SELECT src.chapter_id, vs.placeholder_key
+                FROM sources src
+                JOIN image_variant_sets vs ON vs.source_key = src.source_key
+                WHERE vs.placeholder_key IS NOT NULL
+            )
+            SELECT
+                sc.id AS "id?",
+                'Chapter ' || sc.chapter_number AS "name!",
+                COUNT(so.object_key) AS "object_count!",
+                SUM(so.size_bytes)::BIGINT AS "total_bytes!"
+            FROM chapter_keys k
+            JOIN storage_objects so ON so.object_key = k.object_key AND so.deleted_at IS NULL
+            JOIN series_chapters sc ON sc.id = k.chapter_id
+            GROUP BY sc.id, sc.chapter_number
+            ORDER BY sc.chapter_number
+            "#,
+            series_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get chapter storage usage with sqlx")?;
+
+        Ok(usage)
+    }
+
+    /// Users with the most uploaded bytes (avatars, comment attachments, covers).
+    pub async fn get_user_storage_usage(
+        &self,
+        limit: i64,
+    ) -> AnyhowResult<Vec<StorageUsage>> {
+        let usage = sqlx::query_as!(
+            StorageUsage,
+            r#"
+            SELECT
+                u.id AS "id?",
+                u.username AS name,
+                COUNT(*) AS "object_count!",
+                SUM(so.size_bytes)::BIGINT AS "total_bytes!"
+            FROM storage_objects so
+            JOIN users u ON u.id = so.uploaded_by
+            WHERE so.deleted_at IS NULL
+            GROUP BY u.id, u.username
+            ORDER BY 4 DESC
+            LIMIT $1
+            "#,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get user storage usage with sqlx")?;
+
+        Ok(usage)
+    }
+
+    /// Total stored objects and bytes at the end of each of the last `days` days.
+    pub async fn get_storage_usage_history(
+        &self,
+        days: i32,
+    ) -> AnyhowResult<Vec<StorageUsagePoint>> {
+        // Net change per day over the whole history, the totals are running sums
+        let daily_changes = sqlx::query_as!(
+            StorageUsagePoint,
+            r#"
+            SELECT
+                date_trunc('day', created_at) AS "day!",
+                SUM(object_delta)::BIGINT AS "object_count!",
+                SUM(size_delta)::BIGINT AS "total_bytes!"
+            FROM storage_object_events
+            GROUP BY 1
+            ORDER BY 1
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get storage usage history with sqlx")?;
+
+        let today = Utc::now()
+            .date_naive()
+            .and_time(chrono::NaiveTime::MIN)
+            .and_utc();
+        Ok(accumulate_usage_history(&daily_changes, today, days))
+    }
+}
+
+/// Totals at the end of each of the `days` days up to `last_day`, from the
+/// net change of every day with events (ordered by day).
+pub fn accumulate_usage_history(
+    daily_changes: &[StorageUsagePoint],
+    last_day: DateTime<Utc>,
+    days: i32,
+) -> Vec<StorageUsagePoint> {
+    let mut changes = daily_changes.iter().peekable();
+    let (mut object_count, mut total_bytes) = (0, 0);
+
+    (0..days.max(1))
+        .rev()
+        .map(|days_back| {
+            let day = last_day - chrono::Duration::days(days_back.into());
+            while let Some(change) = changes.next_if(|change| change.day <= day)
+            {
+                object_count += change.object_count;
+                total_bytes += change.total_bytes;
+            }
+            StorageUsagePoint {
+                day,
+                object_count,
+                total_bytes,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, day, 0, 0, 0).unwrap()
+    }
+
+    fn change(
+        on: u32,
+        object_count: i64,
+        total_bytes: i64,
+    ) -> StorageUsagePoint {
+        StorageUsagePoint {
+            day: day(on),
+            object_count,
+            total_bytes,
+        }
+    }
+
+    #[test]
+    fn test_usage_history_keeps_past_days() {
+        let changes = vec![
+            change(1, 2, 300),
+            // Upload on the 10th, overwritten with a larger file on the 12th
+            change(10, 1, 100),
+            change(12, 0, 50),
+            change(13, -1, -100),
+        ];
+
+        let history = accumulate_usage_history(&changes, day(13), 4);
+        let totals: Vec<(i64, i64)> = history
+            .iter()
+            .map(|point| (point.object_count, point.total_bytes))
+            .collect();
+        assert_eq!(totals, vec![(3, 400), (3, 400), (3, 450), (2, 350)]);
+        assert_eq!(history[0].day, day(10));
+        assert_eq!(history[3].day, day(13));
+    }
+}
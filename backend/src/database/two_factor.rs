@@ -0,0 +1,337 @@
+use super::*;
+
+/// TOTP enrolment of a user.
+#[derive(Debug, FromRow)]
+pub struct UserTotp {
+    pub secret: String,
+    pub enabled: bool,
+    pub last_used_step: Option<i64>,
+}
+
+/// TOTP secrets, recovery codes and pending second login steps.
+impl DatabaseService {
+    pub async fn get_user_totp(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<Option<UserTotp>> {
+        let totp = sqlx::query_as!(
+            UserTotp,
+            r#"
+            SELECT secret, enabled_at IS NOT NULL AS "enabled!", last_used_step
+            FROM user_totp
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get user totp with sqlx")?;
+
+        Ok(totp)
+    }
+
+    pub async fn is_two_factor_enabled(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<bool> {
+        let is_enabled = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM user_totp
+                WHERE user_id = $1 AND enabled_at IS NOT NULL
+            ) AS "is_enabled!"
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to check two-factor status with sqlx")?;
+
+        Ok(is_enabled)
+    }
+
+    /// Stores a new secret waiting for confirmation.
+    /// Returns false if two-factor authentication is already enabled.
+    pub async fn start_totp_enrolment(
+        &self,
+        user_id: i32,
+        secret: &str,
+    ) -> AnyhowResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_totp (user_id, secret)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = NOW()
+            WHERE user_totp.enabled_at IS NULL
+            "#,
+            user_id,
+            secret
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to store totp secret with sqlx")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Confirms the pending enrolment with the step of the first valid code
+    /// and stores the recovery codes.
+    pub async fn enable_totp(
+        &self,
+        user_id: i32,
+        used_step: i64,
+        recovery_code_hashes: &[String],
+    ) -> AnyhowResult<bool> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_totp
+            SET enabled_at = NOW(), last_used_step = $2
+            WHERE user_id = $1 AND enabled_at IS NULL
+            "#,
+            user_id,
+            used_step
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to enable totp with sqlx")?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        Self::replace_recovery_codes_in_tx(
+            &mut tx,
+            user_id,
+            recovery_code_hashes,
+        )
+        .await?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(true)
+    }
+
+    /// Removes the secret and the recovery codes.
+    pub async fn disable_totp(&self, user_id: i32) -> AnyhowResult<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        sqlx::query!("DELETE FROM user_totp WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete totp with sqlx")?;
+
+        sqlx::query!(
+            "DELETE FROM user_recovery_codes WHERE user_id = $1",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete recovery codes with sqlx")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(())
+    }
+
+    /// Accepts a code's time step only if it is newer than the last accepted one,
+    /// two concurrent logins with the same code can not both succeed.
+    pub async fn record_totp_step(
+        &self,
+        user_id: i32,
+        step: i64,
+    ) -> AnyhowResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_totp
+            SET last_used_step = $2
+            WHERE user_id = $1 AND enabled_at IS NOT NULL
+                AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            user_id,
+            step
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to record totp step with sqlx")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Invalidates all previous recovery codes.
+    pub async fn replace_recovery_codes(
+        &self,
+        user_id: i32,
+        recovery_code_hashes: &[String],
+    ) -> AnyhowResult<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        Self::replace_recovery_codes_in_tx(
+            &mut tx,
+            user_id,
+            recovery_code_hashes,
+        )
+        .await?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        Ok(())
+    }
+
+    async fn replace_recovery_codes_in_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: i32,
+        recovery_code_hashes: &[String],
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            "DELETE FROM user_recovery_codes WHERE user_id = $1",
+            user_id
+        )
+        .execute(&mut **tx)
+        .await
+        .context("Failed to delete recovery codes with sqlx")?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_recovery_codes (user_id, code_hash)
+            SELECT $1, * FROM UNNEST($2::text[])
+            "#,
+            user_id,
+            recovery_code_hashes
+        )
+        .execute(&mut **tx)
+        .await
+        .context("Failed to store recovery codes with sqlx")?;
+
+        Ok(())
+    }
+
+    /// Marks an unused recovery code as used, false if there is none.
+    pub async fn use_recovery_code(
+        &self,
+        user_id: i32,
+        code_hash: &str,
+    ) -> AnyhowResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_recovery_codes
+            SET used_at = NOW()
+            WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+            user_id,
+            code_hash
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to use recovery code with sqlx")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn count_unused_recovery_codes(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM user_recovery_codes
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to count recovery codes with sqlx")?;
+
+        Ok(count)
+    }
+
+    pub async fn create_two_factor_challenge(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO two_factor_challenges (token_hash, user_id, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            token_hash,
+            user_id,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create two-factor challenge with sqlx")?;
+
+        Ok(())
+    }
+
+    /// Counts an attempt against an open challenge and returns its user.
+    /// None once the challenge expired or ran out of attempts.
+    pub async fn attempt_two_factor_challenge(
+        &self,
+        token_hash: &str,
+        max_attempts: i32,
+    ) -> AnyhowResult<Option<i32>> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+            UPDATE two_factor_challenges
+            SET attempts = attempts + 1
+            WHERE token_hash = $1 AND expires_at > NOW() AND attempts < $2
+            RETURNING user_id
+            "#,
+            token_hash,
+            max_attempts
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to update two-factor challenge with sqlx")?;
+
+        Ok(user_id)
+    }
+
+    pub async fn delete_two_factor_challenge(
+        &self,
+        token_hash: &str,
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            "DELETE FROM two_factor_challenges WHERE token_hash = $1",
+            token_hash
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to delete two-factor challenge with sqlx")?;
+
+        Ok(())
+    }
+
+    pub async fn cleanup_expired_two_factor_challenges(
+        &self,
+    ) -> AnyhowResult<u64> {
+        let result = sqlx::query!(
+            "DELETE FROM two_factor_challenges WHERE expires_at < NOW()"
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to cleanup two-factor challenges with sqlx")?;
+
+        Ok(result.rows_affected())
+    }
+}
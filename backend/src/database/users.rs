@@ -19,12 +19,80 @@ impl DatabaseService {
         let user = sqlx::query_as!(
             Users,
                 // Check both column email and username
-                "SELECT id, username, email, password_hash, role_id FROM users WHERE email = $1 OR username = $1",
+                r#"SELECT id, username, email, password_hash, role_id, email_verified_at IS NOT NULL AS "email_verified!", token_version
+                FROM users WHERE email = $1 OR username = $1"#,
                 identifier,
             ).fetch_optional(&self.pool).await.context("Failed to get user by identifier")?;
         Ok(user)
     }
 
+    pub async fn get_user_by_id(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<Option<Users>> {
+        let user = sqlx::query_as!(
+            Users,
+            r#"SELECT id, username, email, password_hash, role_id, email_verified_at IS NOT NULL AS "email_verified!", token_version
+            FROM users WHERE id = $1"#,
+            user_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get user by id")?;
+
+        Ok(user)
+    }
+
+    pub async fn get_user_auth_state(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<Option<UserAuthState>> {
+        let user = sqlx::query_as!(
+            UserAuthState,
+            r#"
+            SELECT u.id, u.username, r.role_name,
+                ARRAY(
+                    SELECT p.permission_name
+                    FROM role_permissions rp
+                    JOIN permissions p ON p.id = rp.permission_id
+                    WHERE rp.role_id = u.role_id
+                ) AS "permissions!",
+                u.email_verified_at IS NOT NULL AS "email_verified!",
+                EXISTS (
+                    SELECT 1 FROM user_totp t
+                    WHERE t.user_id = u.id AND t.enabled_at IS NOT NULL
+                ) AS "two_factor_enabled!",
+                u.token_version, u.is_active, u.suspended_until, u.suspension_reason
+            FROM users u
+            JOIN roles r ON u.role_id = r.id
+            WHERE u.id = $1
+            "#,
+            user_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get user auth state")?;
+
+        Ok(user)
+    }
+
+    /// Access tokens issued before are rejected from now on,
+    /// the user keeps the sessions and gets new tokens on refresh.
+    pub async fn bump_user_token_version(
+        &self,
+        user_id: i32,
+    ) -> AnyhowResult<()> {
+        sqlx::query!(
+            "UPDATE users SET token_version = token_version + 1, updated_at = NOW() WHERE id = $1",
+            user_id
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to bump user token version")?;
+
+        Ok(())
+    }
+
     pub async fn create_user(
         &self,
         username: &str,
@@ -53,7 +121,7 @@ impl DatabaseService {
         new_password_hash: &str,
     ) -> AnyhowResult<()> {
         sqlx::query!(
-            "UPDATE users SET password_hash = $1, updated_at = NOW() WHERE id = $2",
+            "UPDATE users SET password_hash = $1, password_reset_required = FALSE, updated_at = NOW() WHERE id = $2",
             new_password_hash,
             user_id
         )
@@ -136,8 +204,9 @@ impl DatabaseService {
         }
 
         if let Some(mail) = email {
+            // A new address has to be verified again
             sqlx::query!(
-                "UPDATE users SET email = $1, updated_at = NOW() WHERE id = $2",
+                "UPDATE users SET email = $1, email_verified_at = NULL, updated_at = NOW() WHERE id = $2 AND email <> $1",
                 mail,
                 user_id
             )
@@ -219,6 +288,8 @@ impl DatabaseService {
                             u.username,
                             u.email,
                             r.role_name,
+                            u.is_active,
+                            u.suspended_until,
                             u.user_tsv
                         FROM users u
                         JOIN roles r ON u.role_id = r.id
@@ -251,6 +322,8 @@ impl DatabaseService {
                         rr.username,
                         rr.email,
                         rr.role_name,
+                        rr.is_active,
+                        rr.suspended_until,
                         tc.total as total_items
                     FROM ranked_results rr
                     CROSS JOIN total_count tc
@@ -278,6 +351,8 @@ impl DatabaseService {
                         username: row.username,
                         email: row.email,
                         role_name: row.role_name,
+                        is_active: row.is_active,
+                        suspended_until: row.suspended_until,
                     })
                     .collect();
 
@@ -295,6 +370,8 @@ impl DatabaseService {
                     u.username,
                     u.email,
                     r.role_name,
+                    u.is_active,
+                    u.suspended_until,
                     COUNT(*) OVER() as total_items
                 FROM users u
                 JOIN roles r ON u.role_id = r.id
@@ -319,6 +396,8 @@ impl DatabaseService {
                         username: row.username,
                         email: row.email,
                         role_name: row.role_name,
+                        is_active: row.is_active,
+                        suspended_until: row.suspended_until,
                     })
                     .collect();
 
@@ -0,0 +1,259 @@
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+type EncodeJob = Box<dyn FnOnce() + Send + 'static>;
+
+struct QueuedJob {
+    enqueued_at: Instant,
+    run: EncodeJob,
+}
+
+/// Process-wide pool of dedicated OS threads for CPU-bound image encoding.
+/// Jobs go through a bounded queue: when it is full, `run` waits,
+/// which slows producers (scraping workers) down instead of piling work
+/// onto tokio's blocking pool and starving HTTP handlers.
+#[derive(Clone)]
+pub struct EncodingPool {
+    sender: async_channel::Sender<QueuedJob>,
+    metrics: Arc<PoolMetrics>,
+    thread_count: usize,
+}
+
+#[derive(Default)]
+struct PoolMetrics {
+    jobs_submitted: AtomicU64,
+    jobs_completed: AtomicU64,
+    jobs_panicked: AtomicU64,
+    total_queue_wait_micros: AtomicU64,
+    max_queue_wait_micros: AtomicU64,
+    total_encode_micros: AtomicU64,
+    max_encode_micros: AtomicU64,
+}
+
+/// Point-in-time view of the encoding pool metrics.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodingPoolSnapshot {
+    pub thread_count: usize,
+    pub queue_capacity: usize,
+    pub queued_jobs: usize,
+    pub jobs_submitted: u64,
+    pub jobs_completed: u64,
+    pub jobs_panicked: u64,
+    pub avg_queue_wait_ms: f64,
+    pub max_queue_wait_ms: f64,
+    pub avg_encode_ms: f64,
+    pub max_encode_ms: f64,
+}
+
+impl PoolMetrics {
+    fn record(total: &AtomicU64, max: &AtomicU64, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        total.fetch_add(micros, Ordering::Relaxed);
+        max.fetch_max(micros, Ordering::Relaxed);
+    }
+}
+
+impl EncodingPool {
+    /// Starts `thread_count` encoding threads sharing a queue of `queue_capacity` jobs.
+    pub fn new(thread_count: usize, queue_capacity: usize) -> Self {
+        let thread_count = thread_count.max(1);
+        let (sender, receiver) =
+            async_channel::bounded::<QueuedJob>(queue_capacity.max(1));
+        let metrics = Arc::new(PoolMetrics::default());
+
+        for i in 0..thread_count {
+            let receiver = receiver.clone();
+            let metrics = metrics.clone();
+
+            thread::Builder::new()
+                .name(format!("encoding-worker-{}", i))
+                .spawn(move || {
+                    while let Ok(job) = receiver.recv_blocking() {
+                        PoolMetrics::record(
+                            &metrics.total_queue_wait_micros,
+                            &metrics.max_queue_wait_micros,
+                            job.enqueued_at.elapsed(),
+                        );
+
+                        let started_at = Instant::now();
+                        (job.run)();
+                        PoolMetrics::record(
+                            &metrics.total_encode_micros,
+                            &metrics.max_encode_micros,
+                            started_at.elapsed(),
+                        );
+                        metrics.jobs_completed.fetch_add(1, Ordering::Relaxed);
+                    }
+                })
+                .expect("Failed to spawn encoding worker thread");
+        }
+
+        println!(
+            "[ENCODING-POOL] Started {} encoding threads (queue capacity {})",
+            thread_count,
+            queue_capacity.max(1)
+        );
+
+        Self {
+            sender,
+            metrics,
+            thread_count,
+        }
+    }
+
+    /// Creates the pool from environment variables.
+    /// - `ENCODING_POOL_THREADS`: number of encoding threads
+    ///   (default: available cores minus one, so the async runtime keeps a core).
+    /// - `ENCODING_POOL_QUEUE_SIZE`: jobs allowed to wait for a thread (default: 2x threads).
+    pub fn new_from_env() -> Self {
+        let default_threads = thread::available_parallelism()
+            .map(|n| n.get().saturating_sub(1).max(1))
+            .unwrap_or(2);
+
+        let thread_count =
+            read_env_usize("ENCODING_POOL_THREADS").unwrap_or(default_threads);
+        let queue_capacity = read_env_usize("ENCODING_POOL_QUEUE_SIZE")
+            .unwrap_or(thread_count * 2);
+
+        Self::new(thread_count, queue_capacity)
+    }
+
+    /// Runs a CPU-bound closure on the pool and waits for its result.
+    /// Waits for a free queue slot first when the pool is saturated.
+    pub async fn run<T, F>(&self, job: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result_tx, result_rx) = oneshot::channel();
+        let metrics = self.metrics.clone();
+
+        let run: EncodeJob = Box::new(move || {
+            // Keep the worker thread alive if an encoder panics
+            let result =
+                catch_unwind(AssertUnwindSafe(job)).unwrap_or_else(|_| {
+                    metrics.jobs_panicked.fetch_add(1, Ordering::Relaxed);
+                    Err(anyhow!("Encoding job panicked"))
+                });
+            // The caller may have gone away, nothing to do then
+            let _ = result_tx.send(result);
+        });
+
+        self.metrics.jobs_submitted.fetch_add(1, Ordering::Relaxed);
+        self.sender
+            .send(QueuedJob {
+                enqueued_at: Instant::now(),
+                run,
+            })
+            .await
+            .map_err(|_| anyhow!("Encoding pool is shut down"))?;
+
+        result_rx
+            .await
+            .map_err(|_| anyhow!("Encoding job was dropped before finishing"))?
+    }
+
+    pub fn snapshot(&self) -> EncodingPoolSnapshot {
+        let m = &self.metrics;
+        let completed = m.jobs_completed.load(Ordering::Relaxed);
+        let avg_ms = |total: &AtomicU64| {
+            if completed == 0 {
+                0.0
+            } else {
+                total.load(Ordering::Relaxed) as f64 / completed as f64 / 1000.0
+            }
+        };
+        let max_ms =
+            |max: &AtomicU64| max.load(Ordering::Relaxed) as f64 / 1000.0;
+
+        EncodingPoolSnapshot {
+            thread_count: self.thread_count,
+            queue_capacity: self.sender.capacity().unwrap_or_default(),
+            queued_jobs: self.sender.len(),
+            jobs_submitted: m.jobs_submitted.load(Ordering::Relaxed),
+            jobs_completed: completed,
+            jobs_panicked: m.jobs_panicked.load(Ordering::Relaxed),
+            avg_queue_wait_ms: avg_ms(&m.total_queue_wait_micros),
+            max_queue_wait_ms: max_ms(&m.max_queue_wait_micros),
+            avg_encode_ms: avg_ms(&m.total_encode_micros),
+            max_encode_ms: max_ms(&m.max_encode_micros),
+        }
+    }
+}
+
+fn read_env_usize(name: &str) -> Option<usize> {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|value| *value > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_encoding_pool_queues_jobs() {
+        let pool = EncodingPool::new(1, 1);
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (started_tx, started_rx) = oneshot::channel::<()>();
+
+        // Occupies the only thread until released
+        let blocking = tokio::spawn({
+            let pool = pool.clone();
+            async move {
+                pool.run(move || {
+                    let _ = started_tx.send(());
+                    release_rx.recv().unwrap();
+                    Ok(1)
+                })
+                .await
+            }
+        });
+        started_rx.await.unwrap();
+
+        // Waits in the queue behind the running job
+        let queued = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.run(|| Ok(2)).await }
+        });
+        while pool.snapshot().queued_jobs == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(pool.snapshot().queued_jobs, 1);
+        assert!(!queued.is_finished());
+
+        release_tx.send(()).unwrap();
+        assert_eq!(blocking.await.unwrap().unwrap(), 1);
+        assert_eq!(queued.await.unwrap().unwrap(), 2);
+
+        let snapshot = pool.snapshot();
+        assert_eq!(snapshot.jobs_submitted, 2);
+        assert_eq!(snapshot.jobs_completed, 2);
+        assert_eq!(snapshot.queued_jobs, 0);
+    }
+
+    #[tokio::test]
+    async fn test_encoding_pool_survives_panics() {
+        let pool = EncodingPool::new(1, 1);
+
+        let panicked: Result<()> = pool.run(|| panic!("encoder bug")).await;
+        assert!(panicked.is_err());
+        assert!(
+            pool.run(|| Err::<(), _>(anyhow!("bad image")))
+                .await
+                .is_err()
+        );
+        // The worker thread is still there for the next job
+        assert_eq!(pool.run(|| Ok("encoded")).await.unwrap(), "encoded");
+        assert_eq!(pool.snapshot().jobs_panicked, 1);
+    }
+}
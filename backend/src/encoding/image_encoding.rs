@@ -1,16 +1,157 @@
 use anyhow::{Context, Result};
-use image::{GenericImageView, load_from_memory};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{
+    DynamicImage, GenericImageView, ImageFormat, load_from_memory,
+    load_from_memory_with_format,
+};
 use ravif::{Encoder, Img};
 use rgb::FromSlice;
 
-/// This function is CPU-intensive and is designed to be run in a blocking thread or parallel iwth rayon
-/// [NOTE]: Using `tokio::task::spawn_blocking` to avoid blocking the async runtime?
-pub fn covert_image_bytes_to_avif(image_bytes: &[u8]) -> Result<Vec<u8>> {
-    // Decode the image from memory
+/// Default maximum height (in pixels) of a single stored page tile.
+/// Long webtoon strips above this height are split into several tiles.
+pub const DEFAULT_MAX_TILE_HEIGHT: u32 = 4000;
+
+/// Maximum per-channel difference for a row to still count as a "clean" row
+/// (a gutter of nearly uniform colour between panels).
+const CLEAN_ROW_TOLERANCE: u8 = 8;
+
+/// Widths (in pixels) of the downscaled variants served through `srcset`.
+/// Only widths smaller than the source image are generated.
+pub const VARIANT_WIDTHS: [u32; 3] = [360, 720, 1080];
+
+/// Width of the tiny blurred preview shown while the real image loads.
+const PLACEHOLDER_WIDTH: u32 = 24;
+
+/// A full-size AVIF page tile together with its responsive variants.
+pub struct EncodedTile {
+    pub avif: Vec<u8>,
+    pub variants: ResponsiveVariants,
+}
+
+/// Downscaled AVIF variants and blurred placeholder of a single image.
+pub struct ResponsiveVariants {
+    /// Dimensions of the full-size source image
+    pub width: u32,
+    pub height: u32,
+    /// `(width, avif bytes)` ordered from smallest to largest
+    pub sized: Vec<(u32, Vec<u8>)>,
+    pub placeholder: Vec<u8>,
+}
+
+/// Reads the maximum tile height from `IMAGE_MAX_TILE_HEIGHT`,
+/// falling back to [`DEFAULT_MAX_TILE_HEIGHT`] when unset or invalid.
+pub fn max_tile_height_from_env() -> u32 {
+    std::env::var("IMAGE_MAX_TILE_HEIGHT")
+        .ok()
+        .and_then(|value| value.parse::<u32>().ok())
+        .filter(|height| *height > 0)
+        .unwrap_or(DEFAULT_MAX_TILE_HEIGHT)
+}
+
+/// Encode an already decoded (and validated) image to AVIF, splitting very
+/// tall strips into ordered tiles no taller than `max_tile_height`.
+/// Images that already fit are returned as a single tile.
+/// Every tile also gets its responsive width variants and placeholder.
+/// This function is CPU-intensive and is designed to be run in a blocking thread.
+pub fn convert_image_to_avif_tiles(
+    img: &DynamicImage,
+    max_tile_height: u32,
+) -> Result<Vec<EncodedTile>> {
+    let (width, height) = img.dimensions();
+    let boundaries = find_tile_boundaries(img, max_tile_height);
+
+    if boundaries.len() > 1 {
+        println!(
+            "[IMAGE ENCODING] Splitting tall image ({}x{}) into {} tiles",
+            width,
+            height,
+            boundaries.len()
+        );
+    }
+
+    boundaries
+        .into_iter()
+        .map(|(top, tile_height)| {
+            let encode_tile = |tile: &DynamicImage| {
+                Ok(EncodedTile {
+                    avif: encode_image_to_avif(tile)?,
+                    variants: encode_responsive_variants(tile)?,
+                })
+            };
+
+            if top == 0 && tile_height == height {
+                encode_tile(img)
+            } else {
+                encode_tile(&img.crop_imm(0, top, width, tile_height))
+            }
+        })
+        .collect()
+}
+
+/// Decode an uploaded image (e.g. a series cover) and only produce its
+/// responsive variants, the original bytes are stored as uploaded.
+pub fn convert_image_bytes_to_variants(
+    image_bytes: &[u8],
+) -> Result<ResponsiveVariants> {
     let img = load_from_memory(image_bytes).with_context(
         || "Failed to decode image from memory. The format may be unsupported or data corrupted",
     )?;
 
+    encode_responsive_variants(&img)
+}
+
+/// Encode the smaller width variants and the blurred placeholder of an image.
+pub fn encode_responsive_variants(
+    img: &DynamicImage,
+) -> Result<ResponsiveVariants> {
+    let (width, height) = img.dimensions();
+
+    let sized = VARIANT_WIDTHS
+        .iter()
+        .filter(|variant_width| **variant_width < width)
+        .map(|variant_width| {
+            let resized =
+                img.resize(*variant_width, u32::MAX, FilterType::Lanczos3);
+            Ok((*variant_width, encode_image_to_avif(&resized)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Tiny, heavily blurred preview. The quality barely matters at this size
+    let placeholder = img
+        .resize(PLACEHOLDER_WIDTH, u32::MAX, FilterType::Triangle)
+        .blur(2.0);
+    let placeholder = encode_image_to_avif(&placeholder)?;
+
+    Ok(ResponsiveVariants {
+        width,
+        height,
+        sized,
+        placeholder,
+    })
+}
+
+/// Object key of a width variant, stored next to its source image.
+/// `series/a/ch-1/000.avif` -> `series/a/ch-1/000-w720.avif`
+pub fn variant_object_key(source_key: &str, width: u32) -> String {
+    format!("{}-w{}.avif", strip_key_extension(source_key), width)
+}
+
+/// Object key of the blurred placeholder, stored next to its source image.
+/// `series/a/ch-1/000.avif` -> `series/a/ch-1/000-placeholder.avif`
+pub fn placeholder_object_key(source_key: &str) -> String {
+    format!("{}-placeholder.avif", strip_key_extension(source_key))
+}
+
+fn strip_key_extension(key: &str) -> &str {
+    match key.rsplit_once('.') {
+        Some((stem, ext)) if !ext.contains('/') => stem,
+        _ => key,
+    }
+}
+
+/// Encode an already decoded image to AVIF.
+pub fn encode_image_to_avif(img: &DynamicImage) -> Result<Vec<u8>> {
     let (width_u32, height_u32) = img.dimensions();
 
     // Convert dimensions to usize, which is required by ravif's Img::new()
@@ -48,3 +189,117 @@ pub fn covert_image_bytes_to_avif(image_bytes: &[u8]) -> Result<Vec<u8>> {
 
     Ok(avif_data.avif_file)
 }
+
+/// Quality of JPEG pages generated for exports that cannot embed AVIF.
+const EXPORT_JPEG_QUALITY: u8 = 85;
+
+/// Whether stored AVIF pages can be decoded for EPUB and PDF exports, which needs
+/// a build with the `avif-decoding` feature (AVIF decoding links against dav1d).
+pub fn avif_decoding_enabled() -> bool {
+    ImageFormat::Avif.reading_enabled()
+}
+
+/// Decode a stored AVIF page and re-encode it as baseline JPEG.
+/// Returns the JPEG bytes with the image width and height.
+/// This function is CPU-intensive and is designed to be run in a blocking thread.
+pub fn convert_avif_to_jpeg(avif_bytes: &[u8]) -> Result<(Vec<u8>, u32, u32)> {
+    let img = load_from_memory_with_format(avif_bytes, ImageFormat::Avif)
+        .with_context(|| "Failed to decode AVIF image")?;
+    let rgb_image = img.to_rgb8();
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, EXPORT_JPEG_QUALITY)
+        .encode_image(&rgb_image)
+        .with_context(|| "Failed to encode image to JPEG")?;
+
+    Ok((jpeg, rgb_image.width(), rgb_image.height()))
+}
+
+/// Compute `(top, height)` of every tile for the image.
+/// Each cut is placed on the clean row closest to the maximum tile height,
+/// searching back up to half a tile, so panels are not sliced through.
+/// When no clean row is found the tile is cut at exactly `max_tile_height`.
+fn find_tile_boundaries(
+    img: &DynamicImage,
+    max_tile_height: u32,
+) -> Vec<(u32, u32)> {
+    let (_, height) = img.dimensions();
+    let max_tile_height = max_tile_height.max(1);
+
+    if height <= max_tile_height {
+        return vec![(0, height)];
+    }
+
+    let luma = img.to_luma8();
+    let row_width = luma.width() as usize;
+    let is_clean_row = |y: u32| {
+        let start = y as usize * row_width;
+        let row = &luma.as_raw()[start..start + row_width];
+        row.first().is_none_or(|first| {
+            row.iter()
+                .all(|p| p.abs_diff(*first) <= CLEAN_ROW_TOLERANCE)
+        })
+    };
+
+    let mut boundaries = Vec::new();
+    let mut top = 0;
+
+    while height - top > max_tile_height {
+        let hard_cut = top + max_tile_height;
+        let search_floor = top + max_tile_height / 2;
+
+        let cut = (search_floor..hard_cut)
+            .rev()
+            .find(|y| is_clean_row(*y))
+            .unwrap_or(hard_cut);
+
+        boundaries.push((top, cut - top));
+        top = cut;
+    }
+
+    boundaries.push((top, height - top));
+    boundaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    /// Builds a grayscale strip with noisy "panels" and flat gutter rows.
+    fn strip_with_gutters(height: u32, gutters: &[u32]) -> DynamicImage {
+        let img = GrayImage::from_fn(4, height, |x, y| {
+            if gutters.contains(&y) {
+                Luma([255])
+            } else {
+                Luma([((x * 97 + y * 31) % 200) as u8])
+            }
+        });
+        DynamicImage::ImageLuma8(img)
+    }
+
+    #[test]
+    fn test_short_image_is_single_tile() {
+        let img = strip_with_gutters(100, &[]);
+        assert_eq!(find_tile_boundaries(&img, 100), vec![(0, 100)]);
+    }
+
+    #[test]
+    fn test_cuts_on_clean_row_and_covers_whole_image() {
+        let img = strip_with_gutters(250, &[80]);
+        let tiles = find_tile_boundaries(&img, 100);
+
+        assert_eq!(tiles[0], (0, 80));
+        assert_eq!(tiles.iter().map(|(_, h)| h).sum::<u32>(), 250);
+        assert!(tiles.iter().all(|(_, h)| *h <= 100));
+    }
+
+    #[test]
+    fn test_hard_cut_without_clean_rows() {
+        let img = strip_with_gutters(250, &[]);
+        assert_eq!(
+            find_tile_boundaries(&img, 100),
+            vec![(0, 100), (100, 100), (200, 50)]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader};
+use std::fmt;
+use std::io::Cursor;
+
+/// Default minimum width (in pixels) of an accepted page image.
+pub const DEFAULT_MIN_IMAGE_WIDTH: u32 = 100;
+
+/// Default minimum height (in pixels) of an accepted page image.
+/// Webtoon sites often slice strips into short pieces, so this stays low.
+pub const DEFAULT_MIN_IMAGE_HEIGHT: u32 = 16;
+
+/// Maximum hamming distance between two difference hashes
+/// for an image to be considered the same as a known placeholder.
+const PLACEHOLDER_HASH_DISTANCE: u32 = 6;
+
+/// Why a fetched image was rejected before encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageRejection {
+    // Server answered with something that is not an image (e.g. text/html)
+    UnexpectedContentType(String),
+    // An HTML/text error page served with an image URL
+    HtmlPage,
+    // Magic bytes don't match any supported image format
+    UnrecognizedFormat,
+    // The file ends before the format's end marker
+    Truncated,
+    // The header looked fine, but decoding failed
+    Corrupted(String),
+    TooSmall { width: u32, height: u32 },
+    // Matches a known "image not available" placeholder
+    Placeholder,
+}
+
+impl ImageRejection {
+    /// Stable short code stored alongside the page failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ImageRejection::UnexpectedContentType(_) => "invalid_content_type",
+            ImageRejection::HtmlPage => "html_page",
+            ImageRejection::UnrecognizedFormat => "unrecognized_format",
+            ImageRejection::Truncated => "truncated",
+            ImageRejection::Corrupted(_) => "corrupted",
+            ImageRejection::TooSmall { .. } => "too_small",
+            ImageRejection::Placeholder => "placeholder",
+        }
+    }
+}
+
+impl fmt::Display for ImageRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageRejection::UnexpectedContentType(content_type) => {
+                write!(f, "Unexpected content type '{}'", content_type)
+            }
+            ImageRejection::HtmlPage => {
+                write!(f, "Received an HTML page instead of an image")
+            }
+            ImageRejection::UnrecognizedFormat => {
+                write!(f, "Unrecognized or unsupported image format")
+            }
+            ImageRejection::Truncated => write!(f, "Image data is truncated"),
+            ImageRejection::Corrupted(e) => {
+                write!(f, "Image data is corrupted: {}", e)
+            }
+            ImageRejection::TooSmall { width, height } => {
+                write!(f, "Image is too small ({}x{})", width, height)
+            }
+            ImageRejection::Placeholder => {
+                write!(f, "Image is an 'image not available' placeholder")
+            }
+        }
+    }
+}
+
+/// Limits and fingerprints used by [`validate_image_bytes`].
+#[derive(Debug, Clone)]
+pub struct ImageValidationRules {
+    pub min_width: u32,
+    pub min_height: u32,
+    /// Difference hashes of known placeholder images
+    pub placeholder_hashes: Vec<u64>,
+}
+
+impl ImageValidationRules {
+    /// Reads `IMAGE_MIN_WIDTH` / `IMAGE_MIN_HEIGHT` from the environment
+    /// and parses the hex placeholder hashes of the site configuration.
+    pub fn from_env(placeholder_hashes: &[String]) -> Self {
+        let read_env = |name: &str, default: u32| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<u32>().ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            min_width: read_env("IMAGE_MIN_WIDTH", DEFAULT_MIN_IMAGE_WIDTH),
+            min_height: read_env("IMAGE_MIN_HEIGHT", DEFAULT_MIN_IMAGE_HEIGHT),
+            placeholder_hashes: placeholder_hashes
+                .iter()
+                .filter_map(|hash| u64::from_str_radix(hash.trim(), 16).ok())
+                .collect(),
+        }
+    }
+}
+
+/// Validate fetched bytes and decode them.
+/// Checks run from cheapest to most expensive: the header checks of
+/// [`check_image_header`], full decode and placeholder fingerprint.
+/// This function is CPU-intensive and is designed to be run in a blocking thread.
+pub fn validate_image_bytes(
+    bytes: &[u8],
+    content_type: Option<&str>,
+    rules: &ImageValidationRules,
+) -> Result<DynamicImage, ImageRejection> {
+    let format = check_image_header(bytes, content_type, rules)?;
+
+    let img = image::load_from_memory_with_format(bytes, format)
+        .map_err(|e| ImageRejection::Corrupted(e.to_string()))?;
+
+    if !rules.placeholder_hashes.is_empty() {
+        let hash = difference_hash(&img);
+        if rules.placeholder_hashes.iter().any(|known| {
+            (known ^ hash).count_ones() <= PLACEHOLDER_HASH_DISTANCE
+        }) {
+            return Err(ImageRejection::Placeholder);
+        }
+    }
+
+    Ok(img)
+}
+
+/// Checks that don't decode the pixels: content type, magic bytes,
+/// end-of-file markers and the dimensions read from the image header.
+pub fn check_image_header(
+    bytes: &[u8],
+    content_type: Option<&str>,
+    rules: &ImageValidationRules,
+) -> Result<ImageFormat, ImageRejection> {
+    if let Some(content_type) = content_type {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        // Some CDNs serve images as octet-stream, let the magic bytes decide
+        if !mime.starts_with("image/")
+            && mime != "application/octet-stream"
+            && !mime.is_empty()
+        {
+            return Err(if mime.starts_with("text/") {
+                ImageRejection::HtmlPage
+            } else {
+                ImageRejection::UnexpectedContentType(mime)
+            });
+        }
+    }
+
+    let format = detect_format(bytes)?;
+
+    if is_truncated(bytes, format) {
+        return Err(ImageRejection::Truncated);
+    }
+
+    let (width, height) = ImageReader::with_format(Cursor::new(bytes), format)
+        .into_dimensions()
+        .map_err(|e| ImageRejection::Corrupted(e.to_string()))?;
+    if width < rules.min_width || height < rules.min_height {
+        return Err(ImageRejection::TooSmall { width, height });
+    }
+
+    Ok(format)
+}
+
+/// 64-bit difference hash (dHash) of an image, used to fingerprint placeholders.
+/// Formatted as 16 hex characters in `config_sites.toml`.
+pub fn difference_hash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y).0[0];
+            let right = small.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+fn detect_format(bytes: &[u8]) -> Result<ImageFormat, ImageRejection> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Ok(ImageFormat::Jpeg)
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Ok(ImageFormat::Png)
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Ok(ImageFormat::Gif)
+    } else if bytes.len() >= 12
+        && bytes.starts_with(b"RIFF")
+        && &bytes[8..12] == b"WEBP"
+    {
+        Ok(ImageFormat::WebP)
+    } else {
+        let head = String::from_utf8_lossy(&bytes[..bytes.len().min(512)])
+            .trim_start()
+            .to_ascii_lowercase();
+        if head.starts_with('<') || head.contains("<html") {
+            Err(ImageRejection::HtmlPage)
+        } else {
+            Err(ImageRejection::UnrecognizedFormat)
+        }
+    }
+}
+
+// How far from the end of a JPEG the EOI marker may be
+const JPEG_EOI_SEARCH_WINDOW: usize = 64 * 1024;
+
+// Look for the end marker of each format. Decoders are often lenient
+// and happily return a half-grey page for a cut-off download.
+fn is_truncated(bytes: &[u8], format: ImageFormat) -> bool {
+    match format {
+        ImageFormat::Jpeg => {
+            // Cameras and editors often append padding or trailer data after
+            // the EOI marker. Only the tail is searched, so the EOI of an
+            // embedded EXIF thumbnail near the start doesn't count.
+            let window = JPEG_EOI_SEARCH_WINDOW.min(bytes.len() / 2);
+            !bytes[bytes.len() - window..]
+                .windows(2)
+                .any(|pair| pair == [0xFF, 0xD9])
+        }
+        ImageFormat::Png => {
+            let iend = [b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82];
+            !trim_trailing_padding(bytes).ends_with(&iend)
+        }
+        ImageFormat::Gif => trim_trailing_padding(bytes).last() != Some(&0x3B),
+        ImageFormat::WebP => {
+            // RIFF chunk size excludes the 8 byte "RIFF" + size header
+            let declared =
+                u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+            bytes.len() < declared as usize + 8
+        }
+        _ => false,
+    }
+}
+
+fn trim_trailing_padding(bytes: &[u8]) -> &[u8] {
+    let end = bytes
+        .iter()
+        .rposition(|b| !matches!(b, 0x00 | b'\r' | b'\n' | b' '))
+        .map_or(0, |i| i + 1);
+    &bytes[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn rules() -> ImageValidationRules {
+        ImageValidationRules {
+            min_width: 100,
+            min_height: 16,
+            placeholder_hashes: Vec::new(),
+        }
+    }
+
+    fn encode(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+        let img = ImageBuffer::from_fn(width, height, |x, y| {
+            Rgb([(x % 255) as u8, (y % 255) as u8, 128])
+        });
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_accepts_valid_images() {
+        for format in [ImageFormat::Jpeg, ImageFormat::Png] {
+            let bytes = encode(200, 50, format);
+            assert!(
+                validate_image_bytes(&bytes, Some("image/jpeg"), &rules())
+                    .is_ok()
+            );
+            assert_eq!(check_image_header(&bytes, None, &rules()), Ok(format));
+        }
+    }
+
+    #[test]
+    fn test_rejects_html_error_page() {
+        let html = b"<!DOCTYPE html><html><body>403 Forbidden</body></html>";
+        assert_eq!(
+            validate_image_bytes(html, Some("text/html"), &rules()).err(),
+            Some(ImageRejection::HtmlPage)
+        );
+        assert_eq!(
+            validate_image_bytes(html, None, &rules()).err(),
+            Some(ImageRejection::HtmlPage)
+        );
+    }
+
+    #[test]
+    fn test_rejects_truncated_and_small_images() {
+        let bytes = encode(200, 50, ImageFormat::Jpeg);
+        let cut = &bytes[..bytes.len() / 2];
+        assert_eq!(
+            validate_image_bytes(cut, None, &rules()).err(),
+            Some(ImageRejection::Truncated)
+        );
+
+        let mut padded = bytes.clone();
+        padded.extend_from_slice(b"\x00\x00trailer data from an editor");
+        assert!(validate_image_bytes(&padded, None, &rules()).is_ok());
+
+        let small = encode(20, 20, ImageFormat::Png);
+        assert_eq!(
+            validate_image_bytes(&small, None, &rules()).err(),
+            Some(ImageRejection::TooSmall {
+                width: 20,
+                height: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_known_placeholder() {
+        let bytes = encode(200, 50, ImageFormat::Png);
+        let hash = difference_hash(&image::load_from_memory(&bytes).unwrap());
+        let rules = ImageValidationRules {
+            placeholder_hashes: vec![hash],
+            ..rules()
+        };
+        assert_eq!(
+            validate_image_bytes(&bytes, None, &rules).err(),
+            Some(ImageRejection::Placeholder)
+        );
+    }
+}
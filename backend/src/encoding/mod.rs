@@ -1 +1,4 @@
+pub mod encoding_pool;
 pub mod image_encoding;
+pub mod image_validation;
+pub mod pdf_writer;
@@ -0,0 +1,202 @@
+use std::io::{self, Write};
+
+// Object numbers reserved for the document structure, pages start after them
+const CATALOG_ID: u32 = 1;
+const PAGES_ID: u32 = 2;
+const INFO_ID: u32 = 3;
+const FIRST_FREE_ID: u32 = 4;
+
+/// Minimal streaming PDF writer for image-only documents.
+/// Every page is a single JPEG sized to the image, written as soon as it is added,
+/// so the whole document never has to be kept in memory.
+/// The page tree, catalog and cross-reference table are written by `finish`.
+pub struct PdfWriter<W: Write> {
+    inner: W,
+    // Bytes written so far, used for the cross-reference table
+    position: usize,
+    // Byte offset of every object, indexed by object number - 1
+    object_offsets: Vec<usize>,
+    page_ids: Vec<u32>,
+}
+
+impl<W: Write> PdfWriter<W> {
+    pub fn new(inner: W) -> io::Result<Self> {
+        let mut writer = Self {
+            inner,
+            position: 0,
+            object_offsets: vec![0; (FIRST_FREE_ID - 1) as usize],
+            page_ids: Vec::new(),
+        };
+        // The binary comment tells transfer tools the file is not plain text
+        writer.write_raw(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n")?;
+        Ok(writer)
+    }
+
+    /// Append a page showing a baseline RGB JPEG at its pixel size.
+    pub fn add_jpeg_page(
+        &mut self,
+        jpeg: &[u8],
+        width: u32,
+        height: u32,
+    ) -> io::Result<()> {
+        let image_id = self.reserve_id();
+        let content_id = self.reserve_id();
+        let page_id = self.reserve_id();
+
+        self.write_stream_object(
+            image_id,
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode",
+                width, height
+            ),
+            jpeg,
+        )?;
+
+        let content = format!("q {} 0 0 {} 0 0 cm /Im0 Do Q", width, height);
+        self.write_stream_object(content_id, "", content.as_bytes())?;
+
+        self.write_object(
+            page_id,
+            &format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+                PAGES_ID, width, height, image_id, content_id
+            ),
+        )?;
+
+        self.page_ids.push(page_id);
+        Ok(())
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.page_ids.len()
+    }
+
+    /// Write the page tree, catalog, document info and cross-reference table.
+    pub fn finish(mut self, title: &str) -> io::Result<W> {
+        let kids = self
+            .page_ids
+            .iter()
+            .map(|id| format!("{} 0 R", id))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        self.write_object(
+            PAGES_ID,
+            &format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids,
+                self.page_ids.len()
+            ),
+        )?;
+        self.write_object(
+            CATALOG_ID,
+            &format!("<< /Type /Catalog /Pages {} 0 R >>", PAGES_ID),
+        )?;
+        self.write_object(
+            INFO_ID,
+            &format!(
+                "<< /Title {} /Producer (web-rs) >>",
+                encode_text_string(title)
+            ),
+        )?;
+
+        let xref_position = self.position;
+        let mut xref = format!(
+            "xref\n0 {}\n0000000000 65535 f \n",
+            self.object_offsets.len() + 1
+        );
+        for offset in &self.object_offsets {
+            xref.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        xref.push_str(&format!(
+            "trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.object_offsets.len() + 1,
+            CATALOG_ID,
+            INFO_ID,
+            xref_position
+        ));
+        self.write_raw(xref.as_bytes())?;
+
+        Ok(self.inner)
+    }
+
+    fn reserve_id(&mut self) -> u32 {
+        self.object_offsets.push(0);
+        self.object_offsets.len() as u32
+    }
+
+    fn write_object(&mut self, id: u32, body: &str) -> io::Result<()> {
+        self.object_offsets[(id - 1) as usize] = self.position;
+        self.write_raw(format!("{} 0 obj\n{}\nendobj\n", id, body).as_bytes())
+    }
+
+    fn write_stream_object(
+        &mut self,
+        id: u32,
+        dictionary: &str,
+        data: &[u8],
+    ) -> io::Result<()> {
+        self.object_offsets[(id - 1) as usize] = self.position;
+        self.write_raw(
+            format!(
+                "{} 0 obj\n<< {} /Length {} >>\nstream\n",
+                id,
+                dictionary,
+                data.len()
+            )
+            .as_bytes(),
+        )?;
+        self.write_raw(data)?;
+        self.write_raw(b"\nendstream\nendobj\n")
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.inner.write_all(bytes)?;
+        self.position += bytes.len();
+        Ok(())
+    }
+}
+
+// UTF-16BE hex string, so titles outside of Latin-1 survive
+fn encode_text_string(text: &str) -> String {
+    let mut encoded = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        encoded.push_str(&format!("{:04X}", unit));
+    }
+    encoded.push('>');
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xref_offsets_point_at_objects() {
+        let mut writer = PdfWriter::new(Vec::new()).unwrap();
+        writer.add_jpeg_page(b"fake-jpeg", 10, 20).unwrap();
+        writer.add_jpeg_page(b"fake-jpeg", 30, 40).unwrap();
+        let pdf = writer.finish("Title").unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.starts_with(b"%PDF-1.7"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 2"));
+
+        let startxref: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|tail| tail.lines().next())
+            .and_then(|line| line.parse().ok())
+            .unwrap();
+        assert!(pdf[startxref..].starts_with(b"xref"));
+
+        // Entries follow the free entry of object 0
+        let xref = std::str::from_utf8(&pdf[startxref..]).unwrap();
+        for (index, line) in xref.lines().skip(3).take(9).enumerate() {
+            let offset: usize = line[..10].parse().unwrap();
+            let header = format!("{} 0 obj", index + 1);
+            assert!(pdf[offset..].starts_with(header.as_bytes()));
+        }
+    }
+}
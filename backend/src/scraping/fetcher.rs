@@ -114,15 +114,33 @@ pub async fn fetch_html(client: &Client, url: &str) -> Result<String> {
     .await
 }
 
-/// PUBLIC API: Fetch binary data (bytes) of a resource (images, files, etc.) from a URL
-pub async fn fetch_image_bytes(client: &Client, url: &str) -> Result<Bytes> {
+/// Raw image bytes together with the `Content-Type` the server reported.
+pub struct FetchedImage {
+    pub bytes: Bytes,
+    pub content_type: Option<String>,
+}
+
+/// PUBLIC API: Fetch binary data (bytes) of an image from a URL
+pub async fn fetch_image(client: &Client, url: &str) -> Result<FetchedImage> {
     println!("[FETCHER] Fetching image bytes from {}", url);
 
     // Call generic fetch function with binary-specific processor
     fetch_with_retry(client, url, |response| async {
+        // Keep the header, so image validation can reject HTML error pages early
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+
         // This preserves the exact binary data without any text conversion
-        response.bytes().await.with_context(|| {
+        let bytes = response.bytes().await.with_context(|| {
             format!("Failed to read bytes from response of {}", url)
+        })?;
+
+        Ok(FetchedImage {
+            bytes,
+            content_type,
         })
     })
     .await
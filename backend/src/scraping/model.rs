@@ -15,6 +15,9 @@ pub struct SiteScrapingConfig {
     pub image_url_attribute: String, // Primary attribute to get image URL ("src", "data-src")
     pub image_url_fallback_attributes: Vec<String>, // Fallback attributes if primary fails
     pub chapter_order: String,
+    // Hex dHash fingerprints of the site's "image not available" placeholders
+    #[serde(default)]
+    pub placeholder_image_hashes: Vec<String>,
     // Consider adding delay configurations here:
     // pub delay_after_chapter_page_fetch_ms: Option<u64>,
     // pub delay_after_image_download_ms: Option<u64>,
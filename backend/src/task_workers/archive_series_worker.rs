@@ -0,0 +1,214 @@
+use crate::database::storage::StorageClient;
+use crate::database::{DatabaseService, SeriesStatus};
+use anyhow::Context;
+use backon::{BackoffBuilder, Retryable};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveDirection {
+    Archive,
+    Restore,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchiveJob {
+    series_id: i32,
+    direction: ArchiveDirection,
+}
+
+// Scheduler to pool database for archive and restore jobs
+pub async fn run_archive_scheduler(
+    db_service: DatabaseService,
+    job_sender: mpsc::Sender<ArchiveJob>,
+) {
+    println!("[ARCHIVE-WORKER] Archive scheduler started");
+
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    // Skip first tick
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        // Take jobs until none are pending
+        loop {
+            match db_service.find_and_lock_series_for_archival().await {
+                Ok(Some((series_id, status))) => {
+                    let direction = if status == SeriesStatus::Restoring {
+                        ArchiveDirection::Restore
+                    } else {
+                        ArchiveDirection::Archive
+                    };
+                    println!(
+                        "[ARCHIVE-WORKER] Found {:?} job for series {}, send to worker",
+                        direction, series_id
+                    );
+
+                    let job = ArchiveJob {
+                        series_id,
+                        direction,
+                    };
+                    if job_sender.send(job).await.is_err() {
+                        eprintln!(
+                            "[ARCHIVE-WORKER] CRITICAL: Receiver channel closed. Shutting down."
+                        );
+                        return;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!(
+                        "[ARCHIVE-WORKER] Error finding job: {}. Retrying later.",
+                        e
+                    );
+                    break;
+                }
+            }
+        }
+    }
+}
+
+pub async fn run_archive_worker(
+    db_service: DatabaseService,
+    storage_client: Arc<StorageClient>,
+    mut job_receiver: mpsc::Receiver<ArchiveJob>,
+) {
+    println!("[ARCHIVE-WORKER] Archive worker started");
+
+    while let Some(job) = job_receiver.recv().await {
+        let series_id = job.series_id;
+        println!(
+            "[ARCHIVE-WORKER] Processing {:?} job for series {}",
+            job.direction, series_id
+        );
+
+        let retry_strategy = backon::ConstantBuilder::default()
+            .with_delay(Duration::from_millis(1000))
+            .with_jitter()
+            .with_max_times(5)
+            .build();
+
+        // Moving is idempotent, a retry continues with the objects left behind
+        let retry_operation = || {
+            let db_attempt = db_service.clone();
+            let storage_attempt = storage_client.clone();
+            let job_attempt = job.clone();
+            async move {
+                execute_archive_job(job_attempt, &db_attempt, &storage_attempt)
+                    .await
+            }
+        };
+
+        let result = retry_operation
+            .retry(retry_strategy)
+            .notify(|e, dur| {
+                eprintln!(
+                    "[ARCHIVE-WORKER] Retrying for series {} after {:?}. Error {:?}",
+                    series_id, dur, e
+                );
+            })
+            .await;
+
+        match result {
+            Ok(()) => println!(
+                "[ARCHIVE-WORKER] Successfully finished {:?} job for series {}.",
+                job.direction, series_id
+            ),
+            // Objects can be split between both prefixes, archive or restore can be requested again
+            Err(e) => {
+                eprintln!(
+                    "[ARCHIVE-WORKER] {:?} job for series {} failed after all retry attempts: {}. Moving to 'archive_failed'",
+                    job.direction, series_id, e
+                );
+
+                if let Err(e_update) = db_service
+                    .update_series_processing_status(
+                        series_id,
+                        SeriesStatus::ArchiveFailed,
+                    )
+                    .await
+                {
+                    eprintln!(
+                        "[ARCHIVE-WORKER] CRITICAL: Failed to mark series {} as 'archive_failed'. Error: {}",
+                        series_id, e_update
+                    );
+                }
+            }
+        }
+    }
+
+    println!("[ARCHIVE-WORKER] Channel closed. Shutting down.");
+}
+
+async fn execute_archive_job(
+    job: ArchiveJob,
+    db_service: &DatabaseService,
+    storage_client: &StorageClient,
+) -> anyhow::Result<()> {
+    let object_keys = series_chapter_object_keys(job.series_id, db_service)
+        .await
+        .context("Failed to get chapter image keys")?;
+
+    let moves: Vec<(String, String)> = object_keys
+        .into_iter()
+        .map(|key| {
+            let archive_key = storage_client.archive_object_key(&key);
+            match job.direction {
+                ArchiveDirection::Archive => (key, archive_key),
+                ArchiveDirection::Restore => (archive_key, key),
+            }
+        })
+        .collect();
+
+    storage_client
+        .move_objects(&moves)
+        .await
+        .context("Failed to move chapter images")?;
+
+    match job.direction {
+        ArchiveDirection::Archive => {
+            db_service
+                .update_series_processing_status(
+                    job.series_id,
+                    SeriesStatus::Archived,
+                )
+                .await
+        }
+        ArchiveDirection::Restore => {
+            db_service.complete_series_restore(job.series_id).await
+        }
+    }
+    .context("Failed to update series status")?;
+
+    Ok(())
+}
+
+// Chapter images and their responsive variants.
+// The database keeps the live keys, the archive keys are derived from them.
+// The cover stays in place, it is small and shown in the admin list.
+async fn series_chapter_object_keys(
+    series_id: i32,
+    db_service: &DatabaseService,
+) -> anyhow::Result<Vec<String>> {
+    let chapter_image_keys = db_service
+        .get_image_keys_for_series_deletion(series_id)
+        .await?
+        .map(|keys| keys.chapter_image_urls)
+        .unwrap_or_default();
+
+    let variant_sets = db_service
+        .get_image_variant_sets(&chapter_image_keys)
+        .await?;
+
+    let mut object_keys = chapter_image_keys;
+    object_keys.extend(
+        variant_sets
+            .values()
+            .flat_map(|set| set.object_keys())
+            .map(String::from),
+    );
+
+    Ok(object_keys)
+}
@@ -1,10 +1,19 @@
+use crate::common::jwt::JwtKeyRotationPolicy;
 use crate::database::DatabaseService;
 use crate::database::storage::StorageClient;
+use crate::encoding::encoding_pool::EncodingPool;
 use crate::scraping::model::SitesConfig;
+use crate::task_workers::archive_series_worker::{
+    run_archive_scheduler, run_archive_worker,
+};
 use crate::task_workers::delete_series_worker::{
-    run_deletion_scheduler, run_deletion_worker,
+    DeletionJob, run_deletion_scheduler, run_deletion_worker,
 };
+use crate::task_workers::jwt_key_worker::run_jwt_key_worker;
 use crate::task_workers::log_view_cleanup_worker::run_log_view_cleanup_worker;
+use crate::task_workers::orphan_gc_worker::{
+    OrphanGcConfig, run_orphan_gc_worker,
+};
 use crate::task_workers::repair_chapter_worker::{
     RepairChapterMsg, run_repair_chapter_worker,
 };
@@ -16,6 +25,9 @@ use reqwest::Client;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 
+// Series deletions run in parallel, each one deletes its objects in batches
+const DELETION_WORKER_COUNT: usize = 2;
+
 #[derive(Clone)]
 pub struct OnDemandChannels {
     pub repair_tx: mpsc::Sender<RepairChapterMsg>,
@@ -27,6 +39,7 @@ pub fn setup_worker_channels(
     storage_client: Arc<StorageClient>,
     http_client: Client,
     sites_config: Arc<ArcSwap<SitesConfig>>,
+    encoding_pool: EncodingPool,
 ) -> OnDemandChannels {
     // Check series worker channels
     let (series_check_tx, series_check_rx) =
@@ -46,19 +59,33 @@ pub fn setup_worker_channels(
             storage_client.clone(),
             http_client.clone(),
             sites_config.clone(),
+            encoding_pool.clone(),
             rx_clone,
         ));
     }
 
     // Deletion worker channels
-    let (deletion_tx, deletion_rx) = mpsc::channel(16);
+    let (deletion_tx, deletion_rx) = async_channel::bounded::<DeletionJob>(16);
 
     tokio::spawn(run_deletion_scheduler(db_service.clone(), deletion_tx));
-    tokio::spawn(run_deletion_worker(
-        1,
+
+    for i in 0..DELETION_WORKER_COUNT {
+        tokio::spawn(run_deletion_worker(
+            i,
+            db_service.clone(),
+            storage_client.clone(),
+            deletion_rx.clone(),
+        ));
+    }
+
+    // Archive and restore worker channels
+    let (archive_tx, archive_rx) = mpsc::channel(16);
+
+    tokio::spawn(run_archive_scheduler(db_service.clone(), archive_tx));
+    tokio::spawn(run_archive_worker(
         db_service.clone(),
         storage_client.clone(),
-        deletion_rx,
+        archive_rx,
     ));
 
     // Repair worker channels
@@ -69,11 +96,25 @@ pub fn setup_worker_channels(
         storage_client.clone(),
         http_client.clone(),
         sites_config.clone(),
+        encoding_pool,
     ));
 
     // Log View Cleanup worker
     tokio::spawn(run_log_view_cleanup_worker(db_service.clone()));
 
+    // Access token signing key rotation
+    tokio::spawn(run_jwt_key_worker(
+        db_service.clone(),
+        JwtKeyRotationPolicy::from_env(),
+    ));
+
+    // Orphaned storage object worker
+    tokio::spawn(run_orphan_gc_worker(
+        db_service.clone(),
+        storage_client.clone(),
+        OrphanGcConfig::from_env(),
+    ));
+
     OnDemandChannels {
         repair_tx,
         series_check_tx,
@@ -1,10 +1,19 @@
-use crate::database::storage::StorageClient;
-use crate::database::{DatabaseService, Series, SeriesStatus};
+use crate::database::storage::{OBJECT_BATCH_SIZE, StorageClient};
+use crate::database::{DatabaseService, Series};
 use anyhow::Context;
 use backon::{BackoffBuilder, Retryable};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc;
+
+/// How long a deleted series stays recoverable.
+/// Optional environment variable `SERIES_DELETION_DELAY_HOURS` (default 168, `0` deletes right away).
+pub fn series_deletion_delay_from_env() -> Duration {
+    let hours = std::env::var("SERIES_DELETION_DELAY_HOURS")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(7 * 24);
+    Duration::from_secs(hours * 60 * 60)
+}
 
 #[derive(Debug, Clone)]
 pub struct DeletionJob {
@@ -14,7 +23,7 @@ pub struct DeletionJob {
 // Scheduler to pool database for deletion jobs
 pub async fn run_deletion_scheduler(
     db_service: DatabaseService,
-    job_sender: mpsc::Sender<DeletionJob>,
+    job_sender: async_channel::Sender<DeletionJob>,
 ) {
     println!("[WORKER] Deletion worker started");
 
@@ -62,12 +71,11 @@ pub async fn run_deletion_worker(
     worker_id: usize,
     db_service: DatabaseService,
     storage_client: Arc<StorageClient>,
-    // Use async-channel in the future for more than 1 worker
-    mut job_receiver: mpsc::Receiver<DeletionJob>,
+    job_receiver: async_channel::Receiver<DeletionJob>,
 ) {
     println!("[DELETION-WORKER] Deletion worker {} started", worker_id);
 
-    while let Some(job) = job_receiver.recv().await {
+    while let Ok(job) = job_receiver.recv().await {
         println!(
             "[DELETION-WORKER] Processing job for series {}",
             job.series.id
@@ -112,10 +120,7 @@ pub async fn run_deletion_worker(
             );
 
             if let Err(e_update) = db_service
-                .update_series_processing_status(
-                    series_id,
-                    SeriesStatus::DeletionFailed,
-                )
+                .fail_series_deletion(series_id, &format!("{:#}", e))
                 .await
             {
                 eprintln!(
@@ -137,38 +142,51 @@ pub async fn run_deletion_worker(
     );
 }
 
-// Execute the full deletion process
+// Execute the full deletion process.
+// Objects are deleted in batches and each batch is recorded,
+// a retry (or a later attempt) only handles what is left.
 async fn execute_full_deletion(
     series_id: i32,
     db_service: &DatabaseService,
     storage_client: Arc<StorageClient>,
 ) -> anyhow::Result<()> {
-    // Get all image keys
-    let image_keys = db_service
-        .get_image_keys_for_series_deletion(series_id)
+    if db_service
+        .get_series_deletion_objects_total(series_id)
         .await
-        .context("Failed to get image keys")
-        // If no series found, assume no images
-        .unwrap_or_default();
-
-    let keys_to_delete: Vec<String> = image_keys
-        .iter()
-        .flat_map(|keys| keys.all_urls())
-        .filter_map(|url| storage_client.extract_object_key_from_url(url))
-        .collect();
-
-    if !keys_to_delete.is_empty() {
-        storage_client
-            .delete_image_objects(&keys_to_delete)
+        .context("Failed to get deletion progress")?
+        .is_none()
+    {
+        let keys_to_delete =
+            collect_series_object_keys(series_id, db_service, &storage_client)
+                .await?;
+        db_service
+            .record_series_deletion_objects(series_id, &keys_to_delete)
             .await
-            .context("Failed to delete image objects")?;
+            .context("Failed to record objects to delete")?;
     }
 
+    let progress = SeriesDeletionProgress {
+        series_id,
+        db_service,
+    };
+    delete_pending_objects(&progress, &storage_client, OBJECT_BATCH_SIZE)
+        .await?;
+
+    // Responsive variants are recorded against the object key of their source image
+    let variant_source_keys =
+        series_image_object_keys(series_id, db_service, &storage_client)
+            .await?;
+
     db_service
         .delete_series_by_id(series_id)
         .await
         .context("Failed to delete series")?;
 
+    db_service
+        .delete_image_variant_sets(&variant_source_keys)
+        .await
+        .context("Failed to delete image variant records")?;
+
     println!(
         "[WORKER] Successfully processed and deleted series {}",
         series_id
@@ -176,3 +194,216 @@ async fn execute_full_deletion(
 
     Ok(())
 }
+
+/// Keeps track of the objects of a deletion that are left to delete.
+trait DeletionProgress {
+    async fn pending_batch(&self, limit: usize) -> anyhow::Result<Vec<String>>;
+
+    async fn mark_deleted(&self, object_keys: &[String]) -> anyhow::Result<()>;
+}
+
+struct SeriesDeletionProgress<'a> {
+    series_id: i32,
+    db_service: &'a DatabaseService,
+}
+
+impl DeletionProgress for SeriesDeletionProgress<'_> {
+    async fn pending_batch(&self, limit: usize) -> anyhow::Result<Vec<String>> {
+        self.db_service
+            .get_pending_series_deletion_objects(self.series_id, limit as i64)
+            .await
+            .context("Failed to get objects to delete")
+    }
+
+    async fn mark_deleted(&self, object_keys: &[String]) -> anyhow::Result<()> {
+        self.db_service
+            .mark_series_deletion_objects_deleted(self.series_id, object_keys)
+            .await
+            .context("Failed to record deleted objects")?;
+        Ok(())
+    }
+}
+
+// Deletes the pending objects batch by batch, recording each deleted batch.
+// Deleting a missing object is not an error, so a batch deleted right before
+// an interruption is simply deleted again by the next attempt.
+async fn delete_pending_objects(
+    progress: &impl DeletionProgress,
+    storage_client: &StorageClient,
+    batch_size: usize,
+) -> anyhow::Result<()> {
+    loop {
+        let batch = progress.pending_batch(batch_size).await?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        storage_client
+            .delete_image_objects(&batch)
+            .await
+            .context("Failed to delete image objects")?;
+        progress.mark_deleted(&batch).await?;
+    }
+}
+
+// Cover and chapter images of the series
+async fn series_image_object_keys(
+    series_id: i32,
+    db_service: &DatabaseService,
+    storage_client: &StorageClient,
+) -> anyhow::Result<Vec<String>> {
+    let image_keys = db_service
+        .get_image_keys_for_series_deletion(series_id)
+        .await
+        .context("Failed to get image keys")?;
+
+    Ok(image_keys
+        .iter()
+        .flat_map(|keys| keys.all_urls())
+        .filter_map(|value| storage_client.object_key(value))
+        .collect())
+}
+
+// Every object of the series: images, their variants and archived copies
+async fn collect_series_object_keys(
+    series_id: i32,
+    db_service: &DatabaseService,
+    storage_client: &StorageClient,
+) -> anyhow::Result<Vec<String>> {
+    let mut keys_to_delete =
+        series_image_object_keys(series_id, db_service, storage_client).await?;
+
+    let variant_sets = db_service
+        .get_image_variant_sets(&keys_to_delete)
+        .await
+        .context("Failed to get image variant keys")?;
+
+    keys_to_delete.extend(
+        variant_sets
+            .values()
+            .flat_map(|set| set.object_keys())
+            .map(String::from),
+    );
+
+    // Chapter images of a (partially) archived series live under the archive prefix
+    let is_archived = db_service
+        .get_series_archived_at(series_id)
+        .await
+        .context("Failed to get series archive state")?
+        .is_some();
+    if is_archived {
+        let archive_keys: Vec<String> = keys_to_delete
+            .iter()
+            .map(|key| storage_client.archive_object_key(key))
+            .collect();
+        keys_to_delete.extend(archive_keys);
+    }
+
+    Ok(keys_to_delete)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::storage::local::TempStorageRoot;
+    use std::sync::Mutex;
+
+    // Progress kept in memory, `fail_after_marks` simulates an interruption
+    #[derive(Default)]
+    struct MemoryProgress {
+        objects: Mutex<Vec<(String, bool)>>,
+        batches: Mutex<Vec<Vec<String>>>,
+        fail_after_marks: Mutex<Option<usize>>,
+    }
+
+    impl DeletionProgress for MemoryProgress {
+        async fn pending_batch(
+            &self,
+            limit: usize,
+        ) -> anyhow::Result<Vec<String>> {
+            let batch: Vec<String> = self
+                .objects
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(_, deleted)| !deleted)
+                .take(limit)
+                .map(|(key, _)| key.clone())
+                .collect();
+            self.batches.lock().unwrap().push(batch.clone());
+            Ok(batch)
+        }
+
+        async fn mark_deleted(
+            &self,
+            object_keys: &[String],
+        ) -> anyhow::Result<()> {
+            let mut fail_after_marks = self.fail_after_marks.lock().unwrap();
+            match fail_after_marks.as_mut() {
+                Some(0) => return Err(anyhow::anyhow!("Connection lost")),
+                Some(remaining) => *remaining -= 1,
+                None => {}
+            }
+
+            for (key, deleted) in self.objects.lock().unwrap().iter_mut() {
+                if object_keys.contains(key) {
+                    *deleted = true;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_deletion_resumes_with_the_pending_objects() {
+        let (root, storage) = TempStorageRoot::client().await;
+
+        let keys: Vec<String> = (1..=5)
+            .map(|page| format!("series/title/{}.avif", page))
+            .collect();
+        for key in &keys {
+            storage
+                .upload_image_file(vec![1; 16], key, "image/avif", None)
+                .await
+                .unwrap();
+        }
+
+        let progress = MemoryProgress {
+            objects: Mutex::new(
+                keys.iter().map(|key| (key.clone(), false)).collect(),
+            ),
+            fail_after_marks: Mutex::new(Some(1)),
+            ..Default::default()
+        };
+
+        // The second batch is deleted, but recording it fails
+        assert!(
+            delete_pending_objects(&progress, &storage, 2)
+                .await
+                .is_err()
+        );
+        assert!(!root.path().join(&keys[3]).exists());
+        assert!(root.path().join(&keys[4]).exists());
+
+        // The next attempt starts at the unrecorded batch, not from the beginning
+        progress.batches.lock().unwrap().clear();
+        *progress.fail_after_marks.lock().unwrap() = None;
+        delete_pending_objects(&progress, &storage, 2)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *progress.batches.lock().unwrap(),
+            vec![keys[2..4].to_vec(), keys[4..].to_vec(), Vec::new()]
+        );
+        assert!(
+            progress
+                .objects
+                .lock()
+                .unwrap()
+                .iter()
+                .all(|(_, deleted)| *deleted)
+        );
+        assert!(keys.iter().all(|key| !root.path().join(key).exists()));
+    }
+}
@@ -0,0 +1,39 @@
+use crate::common::jwt::{
+    JwtKeyRotationPolicy, reload_signing_keys, rotate_signing_keys,
+};
+use crate::database::DatabaseService;
+use std::time::Duration;
+
+// Well below the publish delay of new keys, every instance knows a key before it signs
+const KEY_RELOAD_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+pub async fn run_jwt_key_worker(
+    db_service: DatabaseService,
+    policy: JwtKeyRotationPolicy,
+) {
+    println!("[JWT-KEYS] Signing key worker started");
+
+    let mut interval = tokio::time::interval(KEY_RELOAD_INTERVAL);
+    // Keys were loaded during startup
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        match rotate_signing_keys(&db_service, policy).await {
+            Ok(Some(kid)) => {
+                println!("[JWT-KEYS] Published new signing key {}", kid)
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("[JWT-KEYS] Error rotating signing key: {}", e),
+        }
+
+        if let Err(e) = reload_signing_keys(&db_service).await {
+            eprintln!("[JWT-KEYS] Error reloading signing keys: {}", e);
+        }
+
+        if let Err(e) = db_service.delete_expired_jwt_signing_keys().await {
+            eprintln!("[JWT-KEYS] Error deleting expired signing keys: {}", e);
+        }
+    }
+}
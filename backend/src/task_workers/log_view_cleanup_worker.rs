@@ -29,5 +29,38 @@ pub async fn run_log_view_cleanup_worker(db_service: DatabaseService) {
                 eprintln!("[WORKER] Error cleaning up log view entries: {}", e);
             }
         }
+
+        // Ended sessions and their refresh tokens
+        match db_service.cleanup_expired_user_sessions().await {
+            Ok(deleted_rows) if deleted_rows > 0 => {
+                println!(
+                    "[WORKER] Cleaned up {} expired user sessions",
+                    deleted_rows
+                );
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("[WORKER] Error cleaning up user sessions: {}", e);
+            }
+        }
+
+        // Second login steps that were never completed
+        if let Err(e) = db_service.cleanup_expired_two_factor_challenges().await
+        {
+            eprintln!(
+                "[WORKER] Error cleaning up two-factor challenges: {}",
+                e
+            );
+        }
+
+        // Failed login counts that no longer lock anything
+        if let Err(e) = db_service.cleanup_stale_login_failures().await {
+            eprintln!("[WORKER] Error cleaning up login failures: {}", e);
+        }
+
+        // Social logins that never came back from the provider
+        if let Err(e) = db_service.cleanup_expired_oidc_login_states().await {
+            eprintln!("[WORKER] Error cleaning up oidc login states: {}", e);
+        }
     }
 }
@@ -1,5 +1,8 @@
+pub mod archive_series_worker;
 pub mod channels;
 pub mod delete_series_worker;
+pub mod jwt_key_worker;
 pub mod log_view_cleanup_worker;
+pub mod orphan_gc_worker;
 pub mod repair_chapter_worker;
 pub mod series_check_worker;
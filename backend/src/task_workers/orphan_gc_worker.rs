@@ -0,0 +1,198 @@
+use crate::database::DatabaseService;
+use crate::database::storage::StorageClient;
+use anyhow::Result;
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Prefixes of every object the backend writes
+const ORPHAN_GC_PREFIXES: [&str; 4] =
+    ["series/", "cover-manga/", "avatars/", "comments/"];
+
+// Keys listed in a report, the counters always cover everything
+const MAX_REPORTED_KEYS: usize = 1000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct OrphanGcConfig {
+    interval: Duration,
+    // Objects younger than this may not be referenced yet (upload in progress)
+    grace_period: Duration,
+    dry_run: bool,
+}
+
+impl OrphanGcConfig {
+    /// Optional environment variables:
+    /// - `ORPHAN_GC_INTERVAL_HOURS`: Time between runs (default 24).
+    /// - `ORPHAN_GC_GRACE_HOURS`: Minimum age of an object before it can be deleted (default 48).
+    /// - `ORPHAN_GC_DRY_RUN`: Only report orphaned objects, never delete them (default false).
+    pub fn from_env() -> Self {
+        let hours = |name: &str, default: u64| {
+            let hours = std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(default);
+            Duration::from_secs(hours * 60 * 60)
+        };
+
+        let dry_run = std::env::var("ORPHAN_GC_DRY_RUN")
+            .is_ok_and(|value| matches!(value.as_str(), "1" | "true"));
+
+        Self {
+            interval: hours("ORPHAN_GC_INTERVAL_HOURS", 24),
+            grace_period: hours("ORPHAN_GC_GRACE_HOURS", 48),
+            dry_run,
+        }
+    }
+
+    /// Same grace period, but nothing is deleted.
+    pub fn as_dry_run(self) -> Self {
+        Self {
+            dry_run: true,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanGcReport {
+    pub dry_run: bool,
+    pub scanned_objects: usize,
+    // Skipped because they are inside the grace period
+    pub recent_objects: usize,
+    pub orphaned_objects: usize,
+    pub orphaned_bytes: u64,
+    pub deleted_objects: usize,
+    pub orphaned_keys: Vec<String>,
+}
+
+pub async fn run_orphan_gc_worker(
+    db_service: DatabaseService,
+    storage_client: Arc<StorageClient>,
+    config: OrphanGcConfig,
+) {
+    println!(
+        "[ORPHAN-GC] Orphaned object worker started (dry run: {})",
+        config.dry_run
+    );
+
+    let mut interval = tokio::time::interval(config.interval);
+    // Skip the first tick, a restart should not trigger a full bucket scan
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        match collect_orphaned_objects(&db_service, &storage_client, config)
+            .await
+        {
+            Ok(report) => println!(
+                "[ORPHAN-GC] Scanned {} objects, {} orphaned ({} bytes), {} deleted, {} within grace period",
+                report.scanned_objects,
+                report.orphaned_objects,
+                report.orphaned_bytes,
+                report.deleted_objects,
+                report.recent_objects
+            ),
+            Err(e) => {
+                eprintln!(
+                    "[ORPHAN-GC] Error collecting orphaned objects: {}",
+                    e
+                )
+            }
+        }
+    }
+}
+
+/// Lists every known prefix, compares the objects with the database references
+/// and deletes the unreferenced ones that are older than the grace period.
+pub async fn collect_orphaned_objects(
+    db_service: &DatabaseService,
+    storage_client: &StorageClient,
+    config: OrphanGcConfig,
+) -> Result<OrphanGcReport> {
+    let mut report = OrphanGcReport {
+        dry_run: config.dry_run,
+        ..Default::default()
+    };
+    let cutoff = Utc::now()
+        - chrono::Duration::from_std(config.grace_period)
+            .unwrap_or(chrono::Duration::MAX);
+
+    for prefix in ORPHAN_GC_PREFIXES {
+        let mut continuation_token = None;
+
+        loop {
+            let listing = storage_client
+                .list_objects(prefix, continuation_token)
+                .await?;
+            report.scanned_objects += listing.objects.len();
+
+            // Objects from before usage accounting existed get their record here
+            if !config.dry_run && !listing.objects.is_empty() {
+                let (keys, (sizes, created_at)): (Vec<_>, (Vec<_>, Vec<_>)) =
+                    listing
+                        .objects
+                        .iter()
+                        .map(|object| {
+                            (
+                                object.key.clone(),
+                                (object.size as i64, object.last_modified),
+                            )
+                        })
+                        .unzip();
+                db_service
+                    .record_listed_storage_objects(&keys, &sizes, &created_at)
+                    .await?;
+            }
+
+            let (candidates, recent): (Vec<_>, Vec<_>) = listing
+                .objects
+                .into_iter()
+                .partition(|object| object.last_modified < cutoff);
+            report.recent_objects += recent.len();
+
+            if !candidates.is_empty() {
+                let candidate_keys: Vec<String> = candidates
+                    .iter()
+                    .map(|object| object.key.clone())
+                    .collect();
+                let referenced: HashSet<String> = db_service
+                    .get_referenced_object_keys(&candidate_keys)
+                    .await?
+                    .into_iter()
+                    .collect();
+
+                let orphans: Vec<_> = candidates
+                    .into_iter()
+                    .filter(|object| !referenced.contains(&object.key))
+                    .collect();
+
+                report.orphaned_objects += orphans.len();
+                for object in &orphans {
+                    report.orphaned_bytes += object.size;
+                    if report.orphaned_keys.len() < MAX_REPORTED_KEYS {
+                        report.orphaned_keys.push(object.key.clone());
+                    }
+                }
+
+                if !config.dry_run && !orphans.is_empty() {
+                    let orphan_keys: Vec<String> =
+                        orphans.into_iter().map(|object| object.key).collect();
+                    storage_client.delete_image_objects(&orphan_keys).await?;
+                    report.deleted_objects += orphan_keys.len();
+                }
+            }
+
+            continuation_token = listing.next_token;
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+    }
+
+    Ok(report)
+}
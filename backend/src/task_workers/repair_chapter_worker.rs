@@ -1,6 +1,7 @@
 use crate::app::orchestrator::repair_specific_chapter_series;
 use crate::database::DatabaseService;
 use crate::database::storage::StorageClient;
+use crate::encoding::encoding_pool::EncodingPool;
 use crate::scraping::model::SitesConfig;
 use arc_swap::ArcSwap;
 use reqwest::Client;
@@ -20,6 +21,7 @@ pub async fn run_repair_chapter_worker(
     storage_client: Arc<StorageClient>,
     http_client: Client,
     sites_config: Arc<ArcSwap<SitesConfig>>,
+    encoding_pool: EncodingPool,
 ) {
     println!("[WORKER] Repair worker started.");
 
@@ -33,6 +35,7 @@ pub async fn run_repair_chapter_worker(
         let storage_clone = storage_client.clone();
         let http_clone = http_client.clone();
         let sites_config_clone = sites_config.clone();
+        let encoding_pool = encoding_pool.clone();
 
         tokio::spawn(async move {
             let sites_config_snapshot = sites_config_clone.load();
@@ -43,6 +46,7 @@ pub async fn run_repair_chapter_worker(
                 storage_clone,
                 http_clone,
                 sites_config_snapshot.clone(),
+                &encoding_pool,
             )
             .await
             {
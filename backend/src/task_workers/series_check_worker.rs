@@ -1,6 +1,7 @@
 use crate::app::orchestrator;
 use crate::database::storage::StorageClient;
 use crate::database::{DatabaseService, Series, SeriesStatus};
+use crate::encoding::encoding_pool::EncodingPool;
 use crate::scraping::model::SitesConfig;
 use arc_swap::ArcSwap;
 use reqwest::Client;
@@ -64,6 +65,7 @@ pub async fn run_series_check_worker(
     storage_client: Arc<StorageClient>,
     http_client: Client,
     sites_config: Arc<ArcSwap<SitesConfig>>,
+    encoding_pool: EncodingPool,
     job_receiver: async_channel::Receiver<SeriesCheckJob>,
 ) {
     println!("[SERIES-WORKER {}] Starting...", worker_id);
@@ -81,6 +83,7 @@ pub async fn run_series_check_worker(
             &db_service,
             sites_config.load().clone(),
             storage_client.clone(),
+            &encoding_pool,
         )
         .await;
 
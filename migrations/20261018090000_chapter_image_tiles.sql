@@ -0,0 +1,6 @@
+-- Very tall webtoon strips are split into several tiles before upload.
+-- Every tile is stored as its own chapter image row, with `image_order` running
+-- sequentially over all tiles so the reader renders them in the correct order.
+ALTER TABLE chapter_images
+    ADD COLUMN IF NOT EXISTS source_image_order INTEGER,           -- 1-based position of the original source image in the chapter
+    ADD COLUMN IF NOT EXISTS tile_index         INTEGER NOT NULL DEFAULT 0; -- 0-based position of this tile within the source image
@@ -0,0 +1,20 @@
+-- Responsive variants generated for every stored page tile and series cover.
+-- Rows are keyed by the object key of the full-size source image.
+CREATE TABLE IF NOT EXISTS image_variant_sets
+(
+    source_key      TEXT PRIMARY KEY,                  -- Object key of the full-size image
+    width           INTEGER     NOT NULL,              -- Width of the full-size image in pixels
+    height          INTEGER     NOT NULL,              -- Height of the full-size image in pixels
+    placeholder_key TEXT,                              -- Object key of the small blurred placeholder
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS image_variants
+(
+    id         SERIAL PRIMARY KEY,
+    source_key TEXT    NOT NULL REFERENCES image_variant_sets (source_key) ON DELETE CASCADE,
+    width      INTEGER NOT NULL,        -- Width of this downscaled variant in pixels
+    object_key TEXT    NOT NULL UNIQUE, -- Object key of the variant in storage
+    UNIQUE (source_key, width)
+);
+CREATE INDEX IF NOT EXISTS idx_image_variants_source_key ON image_variants (source_key);
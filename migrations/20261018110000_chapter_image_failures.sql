@@ -0,0 +1,15 @@
+-- Pages that could not be stored, so an incomplete chapter says why.
+ALTER TABLE series_chapters
+    ADD COLUMN IF NOT EXISTS status_reason TEXT; -- Human readable summary of the current status
+
+CREATE TABLE IF NOT EXISTS chapter_image_failures
+(
+    id                 SERIAL PRIMARY KEY,
+    chapter_id         INTEGER     NOT NULL REFERENCES series_chapters (id) ON DELETE CASCADE,
+    source_image_order INTEGER     NOT NULL, -- 1-based position of the source image in the chapter
+    source_url         TEXT        NOT NULL, -- Original image URL on the scraped site
+    reason             TEXT        NOT NULL, -- Short code, e.g. 'truncated', 'placeholder', 'fetch_failed'
+    detail             TEXT,                 -- Full error message
+    created_at         TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_chapter_image_failures_chapter_id ON chapter_image_failures (chapter_id);
@@ -0,0 +1,4 @@
+-- Lookups by stored object key, used by the orphaned object garbage collection.
+CREATE INDEX IF NOT EXISTS idx_chapter_images_image_url ON chapter_images (image_url);
+CREATE INDEX IF NOT EXISTS idx_series_cover_image_url ON series (cover_image_url);
+CREATE INDEX IF NOT EXISTS idx_user_profiles_avatar_url ON user_profiles (avatar_url);
@@ -0,0 +1,12 @@
+-- Size of every object written to storage, for usage accounting.
+-- Deleted objects keep their row with `deleted_at` set, so usage over time can be computed.
+CREATE TABLE IF NOT EXISTS storage_objects
+(
+    object_key  TEXT PRIMARY KEY,
+    size_bytes  BIGINT      NOT NULL,
+    uploaded_by INTEGER     REFERENCES users (id) ON DELETE SET NULL, -- NULL for scraped images
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deleted_at  TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS idx_storage_objects_uploaded_by ON storage_objects (uploaded_by);
+CREATE INDEX IF NOT EXISTS idx_storage_objects_created_at ON storage_objects (created_at);
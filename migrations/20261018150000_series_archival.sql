@@ -0,0 +1,13 @@
+-- Archive lifecycle: chapter images are moved to a cold prefix and the series is hidden.
+-- `archived_at` is set from the archive request until a restore completes,
+-- `status_before_archive` is the status a restore brings back.
+ALTER TYPE series_status ADD VALUE IF NOT EXISTS 'Pending Archive';
+ALTER TYPE series_status ADD VALUE IF NOT EXISTS 'Archiving';
+ALTER TYPE series_status ADD VALUE IF NOT EXISTS 'Archived';
+ALTER TYPE series_status ADD VALUE IF NOT EXISTS 'Archive Failed';
+ALTER TYPE series_status ADD VALUE IF NOT EXISTS 'Pending Restore';
+ALTER TYPE series_status ADD VALUE IF NOT EXISTS 'Restoring';
+
+ALTER TABLE series
+    ADD COLUMN IF NOT EXISTS archived_at           TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS status_before_archive series_status;
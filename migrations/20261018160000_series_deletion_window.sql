@@ -0,0 +1,10 @@
+-- Deletion is delayed so it can still be cancelled.
+-- `deletion_scheduled_at` is when the deletion worker may start, the series is hidden until then,
+-- `status_before_deletion` is the status a cancellation brings back.
+ALTER TABLE series
+    ADD COLUMN IF NOT EXISTS deletion_scheduled_at  TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS status_before_deletion series_status;
+
+CREATE INDEX IF NOT EXISTS idx_series_deletion_scheduled_at
+    ON series (deletion_scheduled_at)
+    WHERE deletion_scheduled_at IS NOT NULL;
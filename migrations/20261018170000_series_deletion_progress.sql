@@ -0,0 +1,21 @@
+-- Objects of a series being deleted, recorded once when the deletion starts.
+-- Batches are marked as they are deleted, so a failed or interrupted deletion resumes
+-- where it stopped instead of starting over.
+CREATE TABLE IF NOT EXISTS series_deletion_objects
+(
+    series_id  INT         NOT NULL REFERENCES series (id) ON DELETE CASCADE,
+    object_key TEXT        NOT NULL,
+    deleted_at TIMESTAMPTZ,
+    PRIMARY KEY (series_id, object_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_series_deletion_objects_pending
+    ON series_deletion_objects (series_id)
+    WHERE deleted_at IS NULL;
+
+-- Progress shown to admins, `deletion_progress_at` also tells a stalled deletion from a running one
+ALTER TABLE series
+    ADD COLUMN IF NOT EXISTS deletion_objects_total   INT,
+    ADD COLUMN IF NOT EXISTS deletion_objects_deleted INT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS deletion_progress_at     TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS deletion_error           TEXT;
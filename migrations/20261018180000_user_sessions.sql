@@ -0,0 +1,25 @@
+-- One row per login. Every refresh token rotated from that login belongs to it (the token family),
+-- revoking the session invalidates all of them at once.
+CREATE TABLE IF NOT EXISTS user_sessions
+(
+    id             BIGSERIAL PRIMARY KEY,
+    user_id        INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at     TIMESTAMPTZ NOT NULL,
+    revoked_at     TIMESTAMPTZ,
+    revoked_reason TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_sessions_user_id ON user_sessions (user_id);
+
+-- Refresh tokens are stored as SHA-256 hashes, never in plain text
+CREATE TABLE IF NOT EXISTS refresh_tokens
+(
+    token_hash TEXT PRIMARY KEY,
+    session_id BIGINT      NOT NULL REFERENCES user_sessions (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Set when the token is rotated, presenting it again means it was copied
+    used_at    TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_session_id ON refresh_tokens (session_id);
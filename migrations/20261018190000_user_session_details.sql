@@ -0,0 +1,5 @@
+-- Device details shown in the active sessions list
+ALTER TABLE user_sessions
+    ADD COLUMN IF NOT EXISTS user_agent   TEXT,
+    ADD COLUMN IF NOT EXISTS ip_address   TEXT,
+    ADD COLUMN IF NOT EXISTS last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
@@ -0,0 +1,33 @@
+-- TOTP shared secrets. A row without `enabled_at` is an enrolment waiting for its first code.
+CREATE TABLE IF NOT EXISTS user_totp
+(
+    user_id        INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    secret         TEXT        NOT NULL,
+    enabled_at     TIMESTAMPTZ,
+    -- Time step of the last accepted code, a code can not be replayed
+    last_used_step BIGINT,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One-time recovery codes, stored as SHA-256 hashes
+CREATE TABLE IF NOT EXISTS user_recovery_codes
+(
+    id         SERIAL PRIMARY KEY,
+    user_id    INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    code_hash  TEXT    NOT NULL,
+    used_at    TIMESTAMPTZ,
+    UNIQUE (user_id, code_hash)
+);
+
+-- Password was correct, the second factor is still missing.
+-- The token is handed to the client instead of session cookies.
+CREATE TABLE IF NOT EXISTS two_factor_challenges
+(
+    token_hash TEXT PRIMARY KEY,
+    user_id    INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    attempts   INTEGER     NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_two_factor_challenges_user_id ON two_factor_challenges (user_id);
@@ -0,0 +1,5 @@
+-- Failed logins in a row per account, repeated failures lock the account for a while
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS failed_login_count   INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS last_failed_login_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS login_locked_until   TIMESTAMPTZ;
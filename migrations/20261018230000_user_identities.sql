@@ -0,0 +1,27 @@
+-- Accounts at external login providers (Google, Discord, ...) linked to local users
+CREATE TABLE IF NOT EXISTS user_identities
+(
+    id            SERIAL PRIMARY KEY,
+    user_id       INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    provider      TEXT        NOT NULL,
+    -- The provider's stable account id (`sub`), emails can change
+    subject       TEXT        NOT NULL,
+    email         TEXT,
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_login_at TIMESTAMPTZ,
+    UNIQUE (provider, subject),
+    UNIQUE (user_id, provider)
+);
+
+-- Authorization requests waiting for the provider's callback.
+-- `state` is stored as a hash, `link_user_id` is set when linking from the profile page.
+CREATE TABLE IF NOT EXISTS oidc_login_states
+(
+    state_hash    TEXT PRIMARY KEY,
+    provider      TEXT        NOT NULL,
+    code_verifier TEXT        NOT NULL,
+    nonce         TEXT        NOT NULL,
+    link_user_id  INTEGER REFERENCES users (id) ON DELETE CASCADE,
+    expires_at    TIMESTAMPTZ NOT NULL,
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
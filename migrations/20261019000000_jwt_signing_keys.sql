@@ -0,0 +1,14 @@
+-- Ed25519 keys signing the access tokens, shared by every backend instance.
+-- A new key is published (JWKS, verification) before it signs anything,
+-- a retired key keeps verifying until the tokens it signed have expired.
+CREATE TABLE IF NOT EXISTS jwt_signing_keys
+(
+    kid          TEXT PRIMARY KEY,
+    algorithm    TEXT        NOT NULL,
+    -- PKCS#8 document of the private key
+    private_key  BYTEA       NOT NULL,
+    public_key   BYTEA       NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    activates_at TIMESTAMPTZ NOT NULL,
+    expires_at   TIMESTAMPTZ NOT NULL
+);
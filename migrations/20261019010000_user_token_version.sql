@@ -0,0 +1,4 @@
+-- Access tokens carry the version they were issued for,
+-- bumping it invalidates every token of the user (role change, ban)
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS token_version INTEGER NOT NULL DEFAULT 0;
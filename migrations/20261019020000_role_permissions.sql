@@ -0,0 +1,41 @@
+-- Capabilities granted to roles, routes are guarded by permission instead of role name
+CREATE TABLE IF NOT EXISTS permissions
+(
+    id              SERIAL PRIMARY KEY,
+    permission_name TEXT NOT NULL UNIQUE,
+    description     TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions
+(
+    role_id       INTEGER NOT NULL REFERENCES roles (id) ON DELETE CASCADE,
+    permission_id INTEGER NOT NULL REFERENCES permissions (id) ON DELETE CASCADE,
+    PRIMARY KEY (role_id, permission_id)
+);
+
+INSERT INTO permissions (permission_name, description)
+VALUES ('series.edit', 'Create and edit series, chapters and category tags'),
+       ('series.delete', 'Archive, restore and delete series'),
+       ('comments.moderate', 'Remove comments of any user'),
+       ('users.view', 'List user accounts'),
+       ('users.ban', 'Suspend and ban user accounts'),
+       ('users.manage', 'Change roles, force password resets and delete accounts'),
+       ('system.manage', 'Storage maintenance and metrics')
+ON CONFLICT (permission_name) DO NOTHING;
+
+-- Admins can do everything
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         CROSS JOIN permissions p
+WHERE r.role_name = 'admin'
+ON CONFLICT DO NOTHING;
+
+-- Moderators look after the community, not the catalogue or the system
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p
+              ON p.permission_name IN ('comments.moderate', 'users.view', 'users.ban')
+WHERE r.role_name = 'moderator'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,12 @@
+-- Staff actions on accounts: `is_active = FALSE` is a permanent ban,
+-- `suspended_until` a temporary one, both with an optional reason shown to the user
+UPDATE users
+SET is_active = TRUE
+WHERE is_active IS NULL;
+
+ALTER TABLE users
+    ALTER COLUMN is_active SET NOT NULL,
+    ADD COLUMN IF NOT EXISTS suspended_until         TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS suspension_reason       TEXT,
+    -- Password logins are refused until the account went through a password reset
+    ADD COLUMN IF NOT EXISTS password_reset_required BOOLEAN NOT NULL DEFAULT FALSE;
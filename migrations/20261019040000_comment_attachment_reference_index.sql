@@ -0,0 +1,4 @@
+-- Lookups by stored object key for the orphaned object garbage collection,
+-- completes the indexes of 20261018120000_object_reference_indexes.
+CREATE INDEX IF NOT EXISTS idx_comment_attachments_file_url ON comment_attachments (file_url);
+CREATE INDEX IF NOT EXISTS idx_image_variant_sets_placeholder_key ON image_variant_sets (placeholder_key);
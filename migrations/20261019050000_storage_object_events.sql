@@ -0,0 +1,20 @@
+-- Every change of the stored objects, so the usage history is not rewritten
+-- when an object is uploaded again or moved. Summed per day for the history.
+CREATE TABLE IF NOT EXISTS storage_object_events
+(
+    id           BIGSERIAL PRIMARY KEY,
+    object_key   TEXT        NOT NULL,
+    object_delta INTEGER     NOT NULL, -- 1 for an upload, -1 for a deletion, 0 for an overwrite
+    size_delta   BIGINT      NOT NULL, -- Change of the stored bytes
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_storage_object_events_created_at ON storage_object_events (created_at);
+
+-- Events of the objects recorded so far
+INSERT INTO storage_object_events (object_key, object_delta, size_delta, created_at)
+SELECT object_key, 1, size_bytes, created_at
+FROM storage_objects
+UNION ALL
+SELECT object_key, -1, -size_bytes, deleted_at
+FROM storage_objects
+WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,7 @@
+-- Data migrations that need the runtime configuration and run once at startup,
+-- e.g. rewriting stored CDN URLs into object keys for the configured CDN domain.
+CREATE TABLE IF NOT EXISTS data_migrations
+(
+    name       TEXT PRIMARY KEY,
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
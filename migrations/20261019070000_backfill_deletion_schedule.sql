@@ -0,0 +1,6 @@
+-- Series marked for deletion before deletions were delayed have no schedule yet,
+-- neither the deletion worker nor a cancellation would pick them up.
+UPDATE series
+SET deletion_scheduled_at = NOW()
+WHERE processing_status = 'Pending Deletion'
+  AND deletion_scheduled_at IS NULL;
@@ -0,0 +1,11 @@
+-- Absolute end of a session, refreshing extends `expires_at` but never past it.
+-- Existing sessions get the default lifetime counted from their login.
+ALTER TABLE user_sessions
+    ADD COLUMN IF NOT EXISTS max_expires_at TIMESTAMPTZ;
+
+UPDATE user_sessions
+SET max_expires_at = created_at + interval '30 days'
+WHERE max_expires_at IS NULL;
+
+ALTER TABLE user_sessions
+    ALTER COLUMN max_expires_at SET NOT NULL;
@@ -0,0 +1,8 @@
+-- Private keys are now stored encrypted with `JWT_KEY_ENCRYPTION_KEY`.
+-- The plaintext keys are dropped instead of converted, they may already be in backups.
+-- A new key is created on startup, access tokens of the old keys fail verification
+-- and clients renew them with their refresh token.
+DELETE FROM jwt_signing_keys;
+
+COMMENT ON COLUMN jwt_signing_keys.private_key IS
+    'AES-256-GCM encrypted PKCS#8 document: 12 byte nonce, ciphertext and tag';
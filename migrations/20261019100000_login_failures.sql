@@ -0,0 +1,22 @@
+-- Failed logins in a row per account, or per identifier that matches no account.
+-- Unknown names are throttled like real ones, a lockout does not reveal which accounts exist.
+CREATE TABLE IF NOT EXISTS login_failures (
+    failure_key    TEXT PRIMARY KEY,
+    failed_count   INTEGER NOT NULL DEFAULT 0,
+    last_failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    locked_until   TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_failures_last_failed_at
+    ON login_failures (last_failed_at);
+
+INSERT INTO login_failures (failure_key, failed_count, last_failed_at, locked_until)
+SELECT 'user:' || id, failed_login_count, last_failed_login_at, login_locked_until
+FROM users
+WHERE last_failed_login_at IS NOT NULL
+ON CONFLICT (failure_key) DO NOTHING;
+
+ALTER TABLE users
+    DROP COLUMN IF EXISTS failed_login_count,
+    DROP COLUMN IF EXISTS last_failed_login_at,
+    DROP COLUMN IF EXISTS login_locked_until;